@@ -93,6 +93,30 @@ impl Responder for Balance {
     }
 }
 
+/// AccountStats holds the performance statistics of an account's balance history, sampled at the
+/// last balance recorded each day. `stats` is null until at least two days have been recorded, or
+/// when a recorded balance isn't positive, as returns can't be calculated from it.
+#[derive(Serialize)]
+pub struct AccountStats {
+    pub account_id: i32,
+    pub days: usize,
+    pub stats: Option<ta::stats::Summary>,
+}
+
+/// Responder is an actix_web Trait that generates responses
+/// https://docs.rs/actix-web/0.4.5/actix_web/trait.Responder.html
+impl Responder for AccountStats {
+    type Error = Error;
+    type Future = Ready<Result<HttpResponse, Error>>;
+
+    fn respond_to(self, _req: &HttpRequest) -> Self::Future {
+        let body = serde_json::to_string(&self).unwrap();
+        ready(Ok(HttpResponse::Ok()
+            .content_type("application/json")
+            .body(body)))
+    }
+}
+
 // Payload Structs
 /// NewAccountPayload structures what NewAccount request JSON should look like.
 /// A valid request for this object would look like:
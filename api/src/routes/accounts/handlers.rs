@@ -46,6 +46,19 @@ pub async fn get_balance_all() -> impl Responder {
     get_balances()
 }
 
+// READ STATS ID
+// curl http://localhost:8000/accounts/stats/1
+pub async fn get_stats(req: HttpRequest) -> impl Responder {
+    let id: i32 = req
+        .match_info()
+        .get("id")
+        .unwrap()
+        .to_string()
+        .parse()
+        .unwrap();
+    get_stats_by_id(id)
+}
+
 // READ ACCOUNT ALL
 // curl http://localhost:8000/accounts/
 pub async fn get_summary_all() -> impl Responder {
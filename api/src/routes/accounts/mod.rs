@@ -37,6 +37,11 @@ pub fn accounts_factory(app: &mut web::ServiceConfig) {
         &router.new_route(String::from("/summary/")),
         web::get().to(handlers::get_summary_all),
     );
+    // R Stats 1
+    app.route(
+        &router.new_route(String::from("/stats/{id}")),
+        web::get().to(handlers::get_stats),
+    );
     // U Account 1
     app.route(
         &router.new_route(String::from("/")),
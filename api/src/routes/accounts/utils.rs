@@ -93,6 +93,39 @@ pub fn get_balance_by_id(target_account_id: i32) -> Balances {
     }
 }
 
+// READ Stats ID
+/// Returns performance statistics of the balance history associated with the given id
+/// Return serializes to:
+/// {"account_id":1,"days":0,"stats":null}
+pub fn get_stats_by_id(target_account_id: i32) -> AccountStats {
+    use crate::schema::account_histories::dsl::*;
+
+    let connection = establish_connection();
+
+    let results = account_histories
+        .filter(account_id.eq(target_account_id))
+        .order(updated.asc())
+        .load::<Balance>(&connection)
+        .unwrap_or_default();
+
+    let points: Vec<(usize, f64)> = results
+        .iter()
+        .map(|b| (b.updated.timestamp_millis().max(0) as usize, b.balance))
+        .collect();
+
+    // the collector records a balance on every poll, so keep the last one of each day
+    let daily: Vec<f64> = ta::stats::period_closes(&points, ta::resample::Timeframe::Days(1))
+        .into_iter()
+        .map(|(_, value)| value)
+        .collect();
+
+    AccountStats {
+        account_id: target_account_id,
+        days: daily.len(),
+        stats: ta::stats::summarize(&daily, ta::stats::CALENDAR_DAYS).ok(),
+    }
+}
+
 // READ Account ALL
 /// Returns all accounts stored in the database
 pub fn get_accounts() -> Accounts {
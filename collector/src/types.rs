@@ -8,6 +8,7 @@ use std::{env, fs::create_dir, path::Path};
 use tda_sdk::responses::{Candle, SecuritiesAccount};
 
 use accounts::kraken::KrakenAccount;
+use accounts::paper::PaperAccount;
 use accounts::tdameritrade::TDAmeritradeAccount;
 use accounts::types::AccountType;
 
@@ -207,6 +208,26 @@ impl Collector {
                     info!("Collecting crypto pairs");
                     self.poll_kraken(account);
                 }
+                AccountType::PaperAccount(account) => {
+                    info!("Collecting paper balance data");
+
+                    // paper accounts are priced by mate as it trades, so there's only a balance to post
+                    if let Some(api_host) = &self.conf.api_host {
+                        let db_id = account.database_id.unwrap_or(0);
+
+                        let payload = self.poll_paper_balance(account, db_id);
+                        let api_client = Client {
+                            api_host: get_api_host(api_host.to_string()),
+                        };
+
+                        match api_client.submit_account_balances(payload) {
+                            Ok(_) => (),
+                            Err(e) => {
+                                error!("account balance to API failed: {}", e.to_string());
+                            }
+                        };
+                    }
+                }
             }
         }
     }
@@ -449,6 +470,21 @@ impl Collector {
             }],
         }
     }
+
+    fn poll_paper_balance(
+        &self,
+        account: &PaperAccount,
+        mate_account_id: i32,
+    ) -> NewAccountBalancesPayload {
+        let balance = &account.get_account_balance();
+
+        NewAccountBalancesPayload {
+            balances: vec![NewAccountBalancePayload {
+                account_id: mate_account_id,
+                balance: balance.to_f64().unwrap(),
+            }],
+        }
+    }
 }
 
 /// TODO:
@@ -476,6 +512,19 @@ impl MateCandle {
     }
 }
 
+impl From<MateCandle> for ta::Candle {
+    fn from(candle: MateCandle) -> ta::Candle {
+        ta::Candle {
+            close: candle.close,
+            datetime: candle.datetime,
+            high: candle.high,
+            low: candle.low,
+            open: candle.open,
+            volume: candle.volume as f64,
+        }
+    }
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 pub struct NewAccountBalancePayload {
     pub account_id: i32,
@@ -1,5 +1,7 @@
 pub mod kraken;
 use kraken::KrakenAccount;
+pub mod paper;
+use paper::PaperAccount;
 pub mod tdameritrade;
 use tdameritrade::TDAmeritradeAccount;
 pub mod traits;
@@ -9,6 +11,10 @@ use types::AccountType;
 use std::env;
 
 /// new_account is an account factory
+///
+/// A `paper` account takes no credentials: its key is the file its state is saved to, which
+/// defaults to `paper/{name}.json`, and its secret is the cash it starts with, which defaults to
+/// `paper::DEFAULT_CASH`.
 pub fn new_account(
     name: &str,
     vendor: &str,
@@ -52,6 +58,27 @@ pub fn new_account(
                 db_id,
             )))
         }
+        "paper" => {
+            let path = match key {
+                "" => PaperAccount::path_for(name).to_string_lossy().to_string(),
+                path => path.to_string(),
+            };
+            let cash = match secret {
+                "" => paper::DEFAULT_CASH,
+                cash => match cash.parse::<f64>() {
+                    Ok(cash) if cash.is_finite() && cash >= 0.0 => cash,
+                    _ => return Err("invalid paper account starting cash"),
+                },
+            };
+
+            Ok(AccountType::PaperAccount(PaperAccount::new(
+                name,
+                id,
+                path.as_str(),
+                cash,
+                database_id,
+            )))
+        }
         _ => Err("unsupported account type"),
     }
 }
@@ -0,0 +1,426 @@
+use super::traits::get::Get;
+use super::types::*;
+use log::{info, warn};
+use rust_decimal::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// DEFAULT_CASH is what a paper account starts with when it isn't given a balance
+pub const DEFAULT_CASH: f64 = 10_000.0;
+
+/// # PaperAccount
+///  PaperAccount simulates a brokerage account, trading against whatever prices it's marked with,
+///  live or replayed. Its cash, positions and order book are saved to `path` after every change,
+///  so the account carries on where it left off when restarted.
+/// ```no_run
+/// use accounts::paper::{OrderKind, PaperAccount};
+///
+/// let account = PaperAccount::new("paper", "paper", "paper/paper.json", 10_000.0, None);
+/// account.place_order("MSFT", 10.0, OrderKind::Market).unwrap();
+/// account.mark("MSFT", 300.0, 1_640_000_000_000).unwrap();
+/// println!("{}", account.get_account_balance());
+/// ```
+#[derive(Clone)]
+pub struct PaperAccount {
+    pub account_id: String,
+    pub account: Account,
+    pub database_id: Option<i32>,
+    pub path: PathBuf,
+    pub starting_cash: f64,
+    pub active: bool,
+}
+
+/// OrderKind is when a paper order fills: straight away, or once the price reaches `price`
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum OrderKind {
+    /// fills at the next price the account is marked with
+    Market,
+    /// fills once the price is at `price` or better
+    Limit { price: f64 },
+    /// fills once the price moves through `price`, against the position
+    Stop { price: f64 },
+}
+
+/// PaperOrder is an order waiting in the book. A positive quantity buys, a negative one sells.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct PaperOrder {
+    pub id: u64,
+    pub symbol: String,
+    pub quantity: f64,
+    pub kind: OrderKind,
+}
+
+/// PaperFill is an order that was filled, at `price` on the mark at `datetime`
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct PaperFill {
+    pub order_id: u64,
+    pub symbol: String,
+    pub quantity: f64,
+    pub price: f64,
+    pub datetime: usize,
+}
+
+/// PaperPosition is a holding, short when `quantity` is negative, entered at an average of
+/// `entry_price`
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct PaperPosition {
+    pub quantity: f64,
+    pub entry_price: f64,
+}
+
+/// Mark is the last price a symbol was marked at
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Mark {
+    pub price: f64,
+    pub datetime: usize,
+}
+
+/// PaperState is everything a paper account saves between restarts
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct PaperState {
+    pub cash: f64,
+    pub positions: BTreeMap<String, PaperPosition>,
+    pub orders: Vec<PaperOrder>,
+    pub fills: Vec<PaperFill>,
+    pub marks: BTreeMap<String, Mark>,
+    pub next_order_id: u64,
+}
+
+impl PaperState {
+    pub fn new(cash: f64) -> PaperState {
+        PaperState {
+            cash,
+            next_order_id: 1,
+            ..Default::default()
+        }
+    }
+
+    /// equity is the cash plus every position at the price it was last marked at
+    pub fn equity(&self) -> f64 {
+        self.positions
+            .iter()
+            .fold(self.cash, |equity, (symbol, p)| {
+                let price = self.marks.get(symbol).map_or(p.entry_price, |m| m.price);
+                equity + p.quantity * price
+            })
+    }
+
+    /// fill books `order` at `price`, returning false if there isn't the cash for a buy
+    fn fill(&mut self, order: &PaperOrder, price: f64, datetime: usize) -> bool {
+        let cost = order.quantity * price;
+        if cost > self.cash {
+            return false;
+        }
+        self.cash -= cost;
+
+        let position = self.positions.entry(order.symbol.clone()).or_default();
+        let quantity = position.quantity + order.quantity;
+        if position.quantity * order.quantity >= 0.0 {
+            // adding to a position averages its entry price
+            position.entry_price = (position.quantity * position.entry_price + cost) / quantity;
+        } else if position.quantity * quantity < 0.0 {
+            // the order flipped the position, so what's left was entered at this price
+            position.entry_price = price;
+        }
+        position.quantity = quantity;
+        if quantity == 0.0 {
+            self.positions.remove(&order.symbol);
+        }
+
+        self.fills.push(PaperFill {
+            order_id: order.id,
+            symbol: order.symbol.clone(),
+            quantity: order.quantity,
+            price,
+            datetime,
+        });
+        true
+    }
+}
+
+impl PaperOrder {
+    /// fills_at is whether the order fills when its symbol is marked at `price`
+    fn fills_at(&self, price: f64) -> bool {
+        let buy = self.quantity > 0.0;
+        match self.kind {
+            OrderKind::Market => true,
+            OrderKind::Limit { price: limit } => {
+                (buy && price <= limit) || (!buy && price >= limit)
+            }
+            OrderKind::Stop { price: stop } => (buy && price >= stop) || (!buy && price <= stop),
+        }
+    }
+}
+
+impl PaperAccount {
+    pub fn new(
+        name: &str,
+        account_id: &str,
+        path: &str,
+        starting_cash: f64,
+        database_id: Option<i32>,
+    ) -> PaperAccount {
+        PaperAccount {
+            account_id: account_id.to_string(),
+            account: Account::new(name),
+            database_id,
+            path: PathBuf::from(path),
+            starting_cash,
+            active: true,
+        }
+    }
+
+    /// state reads the account's saved state, or starts it afresh with `starting_cash`
+    pub fn state(&self) -> Result<PaperState, String> {
+        if !self.path.exists() {
+            return Ok(PaperState::new(self.starting_cash));
+        }
+
+        let contents = fs::read_to_string(&self.path)
+            .map_err(|e| format!("could not read {}: {}", self.path.display(), e))?;
+        serde_json::from_str(&contents)
+            .map_err(|e| format!("could not parse {}: {}", self.path.display(), e))
+    }
+
+    /// save writes `state` to a temporary file before moving it over the last one, so a crash
+    /// mid-write doesn't lose the account
+    fn save(&self, state: &PaperState) -> Result<(), String> {
+        if let Some(dir) = self.path.parent().filter(|d| !d.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)
+                .map_err(|e| format!("could not create {}: {}", dir.display(), e))?;
+        }
+
+        let contents = serde_json::to_string_pretty(state).map_err(|e| e.to_string())?;
+        let tmp = self.path.with_extension("tmp");
+        fs::write(&tmp, contents)
+            .map_err(|e| format!("could not write {}: {}", tmp.display(), e))?;
+        fs::rename(&tmp, &self.path)
+            .map_err(|e| format!("could not write {}: {}", self.path.display(), e))
+    }
+
+    /// update loads the state, applies `change` and saves it again
+    fn update<T, F: FnOnce(&mut PaperState) -> T>(&self, change: F) -> Result<T, String> {
+        let mut state = self.state()?;
+        let result = change(&mut state);
+        self.save(&state)?;
+        Ok(result)
+    }
+
+    /// place_order adds an order to the book, buying a positive `quantity` of `symbol` or selling
+    /// a negative one, and returns its id. Nothing fills until the symbol is next marked.
+    pub fn place_order(&self, symbol: &str, quantity: f64, kind: OrderKind) -> Result<u64, String> {
+        if quantity == 0.0 || !quantity.is_finite() {
+            return Err(format!("can't order {} {}", quantity, symbol));
+        }
+
+        self.update(|state| {
+            let id = state.next_order_id;
+            state.next_order_id += 1;
+            state.orders.push(PaperOrder {
+                id,
+                symbol: symbol.to_string(),
+                quantity,
+                kind,
+            });
+            id
+        })
+    }
+
+    /// cancel_order removes an order from the book, returning whether it was still there
+    pub fn cancel_order(&self, id: u64) -> Result<bool, String> {
+        self.update(|state| {
+            let before = state.orders.len();
+            state.orders.retain(|o| o.id != id);
+            state.orders.len() != before
+        })
+    }
+
+    /// mark prices `symbol` at `price` as of `datetime`, filling any orders for it that price
+    /// reaches. A buy there isn't the cash for is dropped from the book.
+    pub fn mark(
+        &self,
+        symbol: &str,
+        price: f64,
+        datetime: usize,
+    ) -> Result<Vec<PaperFill>, String> {
+        if !price.is_finite() || price <= 0.0 {
+            return Err(format!("can't mark {} at {}", symbol, price));
+        }
+
+        self.update(|state| {
+            state
+                .marks
+                .insert(symbol.to_string(), Mark { price, datetime });
+
+            let fills = state.fills.len();
+            let orders = std::mem::take(&mut state.orders);
+            for order in orders {
+                if order.symbol != symbol || !order.fills_at(price) {
+                    state.orders.push(order);
+                } else if !state.fill(&order, price, datetime) {
+                    warn!(
+                        "{}: dropped order {} for {}, there isn't the cash to fill it at {}",
+                        self.account.name, order.id, order.quantity, price
+                    );
+                }
+            }
+
+            let filled = state.fills[fills..].to_vec();
+            for fill in &filled {
+                info!(
+                    "{}: filled {} {} at {}",
+                    self.account.name, fill.quantity, fill.symbol, fill.price
+                );
+            }
+            filled
+        })
+    }
+
+    /// marked returns when `symbol` was last marked, if ever
+    pub fn marked(&self, symbol: &str) -> Option<usize> {
+        self.state().ok()?.marks.get(symbol).map(|m| m.datetime)
+    }
+
+    /// get_account_balance returns the account's equity: its cash plus every position at the
+    /// price it was last marked at
+    pub fn get_account_balance(&self) -> Decimal {
+        match self.state() {
+            Ok(state) => Decimal::from_f64(state.equity()).unwrap_or_default(),
+            Err(e) => {
+                warn!("{}", e);
+                Decimal::from_f64(self.starting_cash).unwrap_or_default()
+            }
+        }
+    }
+
+    /// path_for returns where an account named `name` is saved when no path is given
+    pub fn path_for(name: &str) -> PathBuf {
+        Path::new("paper").join(format!("{}.json", name))
+    }
+}
+
+impl Get for PaperAccount {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn account(name: &str) -> PaperAccount {
+        let path = std::env::temp_dir().join(format!("mate-paper-{}.json", name));
+        let _ = fs::remove_file(&path);
+        PaperAccount::new(name, name, path.to_str().unwrap(), 1_000.0, None)
+    }
+
+    #[test]
+    fn market_test() {
+        let account = account("market");
+        account.place_order("MSFT", 5.0, OrderKind::Market).unwrap();
+        assert_eq!(account.mark("AAPL", 50.0, 1).unwrap(), vec![]);
+
+        let fills = account.mark("MSFT", 100.0, 2).unwrap();
+        assert_eq!(fills.len(), 1);
+        assert_eq!(fills[0].price, 100.0);
+
+        let state = account.state().unwrap();
+        assert_eq!(state.cash, 500.0);
+        assert_eq!(state.positions["MSFT"].quantity, 5.0);
+        assert!(state.orders.is_empty());
+
+        account.mark("MSFT", 120.0, 3).unwrap();
+        assert_eq!(account.get_account_balance(), Decimal::from(1_100));
+
+        // selling half keeps the entry price, and selling past flat flips it
+        account
+            .place_order("MSFT", -2.0, OrderKind::Market)
+            .unwrap();
+        account.mark("MSFT", 110.0, 4).unwrap();
+        assert_eq!(
+            account.state().unwrap().positions["MSFT"].entry_price,
+            100.0
+        );
+        account
+            .place_order("MSFT", -5.0, OrderKind::Market)
+            .unwrap();
+        account.mark("MSFT", 90.0, 5).unwrap();
+        let state = account.state().unwrap();
+        assert_eq!(
+            state.positions["MSFT"],
+            PaperPosition {
+                quantity: -2.0,
+                entry_price: 90.0
+            }
+        );
+        assert_eq!(state.cash, 1_170.0);
+        assert_eq!(state.equity(), 990.0);
+    }
+
+    #[test]
+    fn limit_stop_test() {
+        let account = account("limit-stop");
+        let limit = account
+            .place_order("MSFT", 2.0, OrderKind::Limit { price: 95.0 })
+            .unwrap();
+        account
+            .place_order("MSFT", 1.0, OrderKind::Stop { price: 105.0 })
+            .unwrap();
+
+        assert!(account.mark("MSFT", 100.0, 1).unwrap().is_empty());
+        let fills = account.mark("MSFT", 94.0, 2).unwrap();
+        assert_eq!(fills.len(), 1);
+        assert_eq!(fills[0].order_id, limit);
+
+        let fills = account.mark("MSFT", 106.0, 3).unwrap();
+        assert_eq!(fills.len(), 1);
+        assert_eq!(fills[0].quantity, 1.0);
+
+        let sell = account
+            .place_order("MSFT", -3.0, OrderKind::Stop { price: 90.0 })
+            .unwrap();
+        assert!(account.cancel_order(sell).unwrap());
+        assert!(!account.cancel_order(sell).unwrap());
+        assert!(account.state().unwrap().orders.is_empty());
+    }
+
+    #[test]
+    fn cash_test() {
+        let account = account("cash");
+        account
+            .place_order("MSFT", 20.0, OrderKind::Market)
+            .unwrap();
+        assert!(account.mark("MSFT", 100.0, 1).unwrap().is_empty());
+
+        let state = account.state().unwrap();
+        assert_eq!(state.cash, 1_000.0);
+        assert!(state.orders.is_empty());
+        assert!(account.place_order("MSFT", 0.0, OrderKind::Market).is_err());
+        assert!(account.mark("MSFT", f64::NAN, 2).is_err());
+    }
+
+    #[test]
+    fn restart_test() {
+        let account = account("restart");
+        account.place_order("MSFT", 1.0, OrderKind::Market).unwrap();
+        account
+            .place_order("MSFT", 1.0, OrderKind::Limit { price: 50.0 })
+            .unwrap();
+        account.mark("MSFT", 100.0, 1).unwrap();
+
+        // a new account at the same path picks up where the last left off
+        let restarted = PaperAccount::new(
+            "restart",
+            "restart",
+            account.path.to_str().unwrap(),
+            0.0,
+            None,
+        );
+        assert_eq!(restarted.state().unwrap(), account.state().unwrap());
+        assert_eq!(restarted.marked("MSFT"), Some(1));
+        assert_eq!(restarted.marked("AAPL"), None);
+        assert_eq!(restarted.place_order("MSFT", 1.0, OrderKind::Market), Ok(3));
+    }
+}
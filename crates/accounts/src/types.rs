@@ -1,4 +1,5 @@
 use super::kraken::KrakenAccount;
+use super::paper::PaperAccount;
 use super::tdameritrade::TDAmeritradeAccount;
 use super::traits::get::Get;
 
@@ -6,6 +7,7 @@ use super::traits::get::Get;
 #[derive(Clone)]
 pub enum AccountType {
     KrakenAccount(KrakenAccount),
+    PaperAccount(PaperAccount),
     TDAmeritradeAccount(TDAmeritradeAccount),
 }
 
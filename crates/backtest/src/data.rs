@@ -0,0 +1,275 @@
+use crate::BacktestError;
+use serde_json::{Map, Value};
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+use ta::resample::{resample_ticks, ticks_from_map, Tick, Timeframe};
+use ta::{Candle, Series};
+
+/// equity_path is where the collector stores daily candles for a stock, under its `--filepath`
+pub fn equity_path(filepath: &Path, symbol: &str) -> PathBuf {
+    filepath
+        .join("equity")
+        .join("daily")
+        .join(format!("{}.json", symbol))
+}
+
+/// crypto_path is the directory the collector stores a crypto pair's ticks in, one file per day
+pub fn crypto_path(filepath: &Path, pair: &str) -> PathBuf {
+    filepath.join("crypto").join("tick").join(pair)
+}
+
+/// load reads the history the collector stored for `symbol`: its daily candles if it's a stock, or
+/// its ticks resampled to `timeframe` if it's a crypto pair. Daily candles are used as they are,
+/// whatever `timeframe` is.
+pub fn load(filepath: &Path, symbol: &str, timeframe: Timeframe) -> Result<Series, BacktestError> {
+    if equity_path(filepath, symbol).is_file() {
+        load_equity_daily(filepath, symbol)
+    } else if crypto_path(filepath, symbol).is_dir() {
+        load_crypto(filepath, symbol, timeframe)
+    } else {
+        Err(BacktestError::NoData(symbol.to_string()))
+    }
+}
+
+/// load_equity_daily reads the daily candles the collector stored for a stock in
+/// `equity/daily/<symbol>.json`
+pub fn load_equity_daily(filepath: &Path, symbol: &str) -> Result<Series, BacktestError> {
+    let path = equity_path(filepath, symbol);
+    let contents = read(&path)?;
+    let candles: Vec<Candle> =
+        serde_json::from_str(&contents).map_err(|e| BacktestError::Parse {
+            path: path.clone(),
+            message: e.to_string(),
+        })?;
+
+    let mut series = Series::new(symbol, candles);
+    series.candles.dedup_by_key(|c| c.datetime);
+    Ok(series)
+}
+
+/// load_crypto reads every tick the collector stored for a crypto pair and resamples them into
+/// candles of `timeframe`. Periods the collector wasn't running for have no candle.
+pub fn load_crypto(
+    filepath: &Path,
+    pair: &str,
+    timeframe: Timeframe,
+) -> Result<Series, BacktestError> {
+    let ticks = load_crypto_ticks(filepath, pair)?;
+    let candles = resample_ticks(&ticks, timeframe).map_err(BacktestError::Ta)?;
+    Ok(Series::new(pair, candles))
+}
+
+/// load_crypto_ticks reads the ticks in every `crypto/tick/<pair>/<yyyymmdd>.json` file, sorted by
+/// datetime
+pub fn load_crypto_ticks(filepath: &Path, pair: &str) -> Result<Vec<Tick>, BacktestError> {
+    let mut ticks = Vec::new();
+    for path in tick_files(filepath, pair)? {
+        let map: Map<String, Value> =
+            serde_json::from_str(&read(&path)?).map_err(|e| BacktestError::Parse {
+                path: path.clone(),
+                message: e.to_string(),
+            })?;
+        ticks.extend(ticks_from_map(&map).map_err(|e| BacktestError::Parse {
+            path: path.clone(),
+            message: e.to_string(),
+        })?);
+    }
+
+    if ticks.is_empty() {
+        return Err(BacktestError::NoData(pair.to_string()));
+    }
+
+    ticks.sort_by_key(|tick| tick.datetime);
+    ticks.dedup_by_key(|tick| tick.datetime);
+    Ok(ticks)
+}
+
+/// Spreads are the average bid-ask spread quoted during each candle, as a fraction of the price,
+/// keyed by the candle's datetime
+pub type Spreads = HashMap<usize, f64>;
+
+/// load_spreads reads the ask and bid of every tick the collector stored for `symbol`, and
+/// averages their spread over each period of `timeframe`. The collector only stores quotes for
+/// crypto pairs, so stocks have no spreads.
+pub fn load_spreads(
+    filepath: &Path,
+    symbol: &str,
+    timeframe: Timeframe,
+) -> Result<Spreads, BacktestError> {
+    let mut totals: HashMap<usize, (f64, usize)> = HashMap::new();
+    if !crypto_path(filepath, symbol).is_dir() {
+        return Ok(Spreads::new());
+    }
+
+    for path in tick_files(filepath, symbol)? {
+        let map: Map<String, Value> =
+            serde_json::from_str(&read(&path)?).map_err(|e| BacktestError::Parse {
+                path: path.clone(),
+                message: e.to_string(),
+            })?;
+
+        for (epoch, tick) in &map {
+            let quote = |field: &str| {
+                tick.get(field)?
+                    .get(0)?
+                    .as_str()?
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|price| price.is_finite() && *price > 0.0)
+            };
+            let (seconds, ask, bid) = match (epoch.parse::<usize>(), quote("a"), quote("b")) {
+                (Ok(seconds), Some(ask), Some(bid)) => (seconds, ask, bid),
+                _ => continue,
+            };
+
+            let start = timeframe.start(seconds.saturating_mul(1000));
+            let total = totals.entry(start).or_insert((0.0, 0));
+            total.0 += (ask - bid).max(0.0) / ((ask + bid) / 2.0);
+            total.1 += 1;
+        }
+    }
+
+    Ok(totals
+        .into_iter()
+        .map(|(start, (sum, count))| (start, sum / count as f64))
+        .collect())
+}
+
+/// tick_files lists the JSON files in a pair's tick directory, sorted by name, and so by day
+pub(crate) fn tick_files(filepath: &Path, pair: &str) -> Result<Vec<PathBuf>, BacktestError> {
+    let dir = crypto_path(filepath, pair);
+    let entries = fs::read_dir(&dir).map_err(|error| BacktestError::Io {
+        path: dir.clone(),
+        error,
+    })?;
+
+    let mut paths = Vec::new();
+    for entry in entries {
+        let path = entry
+            .map_err(|error| BacktestError::Io {
+                path: dir.clone(),
+                error,
+            })?
+            .path();
+        if path.extension().is_some_and(|e| e == "json") {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+
+    Ok(paths)
+}
+
+fn read(path: &Path) -> Result<String, BacktestError> {
+    fs::read_to_string(path).map_err(|error| BacktestError::Io {
+        path: path.to_path_buf(),
+        error,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ta::resample::HOUR_MILLIS;
+
+    /// collected writes a directory laid out like the collector's `--filepath`, unique to `name`
+    fn collected(name: &str) -> PathBuf {
+        let filepath = std::env::temp_dir().join(format!("mate-backtest-{}", name));
+        let _ = fs::remove_dir_all(&filepath);
+
+        fs::create_dir_all(filepath.join("equity/daily")).unwrap();
+        fs::write(
+            equity_path(&filepath, "MSFT"),
+            r#"[
+                {"close": 11.0, "datetime": 172800000, "high": 12.0, "low": 10.0, "open": 10.5, "volume": 900},
+                {"close": 10.0, "datetime": 86400000, "high": 11.0, "low": 9.0, "open": 9.5, "volume": 1000}
+            ]"#,
+        )
+        .unwrap();
+
+        let ticks = crypto_path(&filepath, "XXBTZUSD");
+        fs::create_dir_all(&ticks).unwrap();
+        let tick = |price: &str| {
+            format!(
+                r#"{{"a": ["{0}", "1", "1.000"], "b": ["{0}", "1", "1.000"], "c": ["{0}", "0.5"]}}"#,
+                price
+            )
+        };
+        fs::write(
+            ticks.join("20220101.json"),
+            format!(
+                r#"{{"0": {}, "1800": {}, "3600": {}}}"#,
+                tick("100.0"),
+                tick("110.0"),
+                tick("105.0")
+            ),
+        )
+        .unwrap();
+        fs::write(
+            ticks.join("20220102.json"),
+            format!(r#"{{"7200": {}}}"#, tick("120.0")),
+        )
+        .unwrap();
+        fs::write(ticks.join("notes.txt"), "not ticks").unwrap();
+        fs::write(
+            ticks.join("20220103.json"),
+            r#"{"10800": {"a": ["101.0"], "b": ["99.0"], "c": ["100.0", "1"]}, "12600": {"a": ["100.5"], "b": ["99.5"], "c": ["100.0", "1"]}}"#,
+        )
+        .unwrap();
+
+        filepath
+    }
+
+    #[test]
+    fn load_test() {
+        let filepath = collected("load");
+        let hourly = Timeframe::Hours(1);
+
+        let msft = load(&filepath, "MSFT", hourly).unwrap();
+        assert_eq!(msft.symbol, "MSFT");
+        assert_eq!(
+            msft.candles,
+            vec![
+                Candle::new(86400000, 9.5, 11.0, 9.0, 10.0, 1000.0),
+                Candle::new(172800000, 10.5, 12.0, 10.0, 11.0, 900.0),
+            ]
+        );
+
+        let ticks = load_crypto_ticks(&filepath, "XXBTZUSD").unwrap();
+        assert_eq!(ticks.len(), 6);
+        assert_eq!(ticks[3].datetime, 2 * HOUR_MILLIS);
+
+        let btc = load(&filepath, "XXBTZUSD", hourly).unwrap();
+        assert_eq!(
+            btc.candles,
+            vec![
+                Candle::new(0, 100.0, 110.0, 100.0, 110.0, 1.0),
+                Candle::new(HOUR_MILLIS, 105.0, 105.0, 105.0, 105.0, 0.5),
+                Candle::new(2 * HOUR_MILLIS, 120.0, 120.0, 120.0, 120.0, 0.5),
+                Candle::new(3 * HOUR_MILLIS, 100.0, 100.0, 100.0, 100.0, 2.0),
+            ]
+        );
+
+        let spreads = load_spreads(&filepath, "XXBTZUSD", hourly).unwrap();
+        assert_eq!(spreads.len(), 4);
+        assert_eq!(spreads[&0], 0.0);
+        assert_eq!(spreads[&(3 * HOUR_MILLIS)], 0.015);
+        assert!(load_spreads(&filepath, "MSFT", hourly).unwrap().is_empty());
+
+        assert!(matches!(
+            load(&filepath, "AAPL", hourly),
+            Err(BacktestError::NoData(_))
+        ));
+
+        fs::write(equity_path(&filepath, "QQQ"), "{").unwrap();
+        assert!(matches!(
+            load(&filepath, "QQQ", hourly),
+            Err(BacktestError::Parse { .. })
+        ));
+
+        fs::remove_dir_all(&filepath).unwrap();
+    }
+}
@@ -0,0 +1,42 @@
+use std::{fmt, io, path::PathBuf};
+use ta::error::TaError;
+
+/// BacktestError describes why a backtest couldn't be set up
+#[derive(Debug)]
+pub enum BacktestError {
+    /// a data file or directory couldn't be read
+    Io { path: PathBuf, error: io::Error },
+    /// a data file isn't in the format the collector writes
+    Parse { path: PathBuf, message: String },
+    /// the collector hasn't stored any data for the symbol
+    NoData(String),
+    /// a backtest parameter is out of range, eg: a negative commission
+    InvalidParameter { name: &'static str },
+    /// an optimized parameter isn't a field of the strategy's config
+    UnknownParameter(String),
+    /// the collected data couldn't be resampled, eg: a tick with a non-finite price
+    Ta(TaError),
+}
+
+impl fmt::Display for BacktestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BacktestError::Io { path, error } => {
+                write!(f, "couldn't read {}: {}", path.display(), error)
+            }
+            BacktestError::Parse { path, message } => {
+                write!(f, "couldn't parse {}: {}", path.display(), message)
+            }
+            BacktestError::NoData(symbol) => write!(f, "no data collected for {}", symbol),
+            BacktestError::InvalidParameter { name } => {
+                write!(f, "invalid backtest parameter {}", name)
+            }
+            BacktestError::UnknownParameter(name) => {
+                write!(f, "the strategy has no parameter {}", name)
+            }
+            BacktestError::Ta(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for BacktestError {}
@@ -0,0 +1,456 @@
+use crate::BacktestError;
+use serde::{Deserialize, Serialize};
+use ta::Candle;
+
+/// Order is an order to buy or sell at market, or at a limit or stop price
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Order {
+    Market,
+    Limit(f64),
+    Stop(f64),
+}
+
+/// Liquidity is whether a fill added liquidity to the order book, as a resting limit order does,
+/// or took it, as a market order does. Exchanges such as Kraken charge each a different fee.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Liquidity {
+    Maker,
+    Taker,
+}
+
+impl Order {
+    /// fill returns the price an order placed before `candle` fills at during it, or `None` if the
+    /// price never reached the order. Only the candle's open, high and low are known, so:
+    ///
+    /// - a market order fills at the open
+    /// - a limit order fills at its price once the price reaches it, or at the open if the candle
+    ///   gapped through it, in which case it fills immediately and takes liquidity
+    /// - a stop order becomes a market order once the price reaches it, filling at its price, or at
+    ///   the open if the candle gapped through it
+    pub fn fill(&self, buy: bool, candle: &Candle) -> Option<(f64, Liquidity)> {
+        match *self {
+            Order::Market => Some((candle.open, Liquidity::Taker)),
+            Order::Limit(price) => {
+                let (gapped, touched) = if buy {
+                    (candle.open <= price, candle.low <= price)
+                } else {
+                    (candle.open >= price, candle.high >= price)
+                };
+                if gapped {
+                    Some((candle.open, Liquidity::Taker))
+                } else if touched {
+                    Some((price, Liquidity::Maker))
+                } else {
+                    None
+                }
+            }
+            Order::Stop(price) => {
+                let (gapped, touched) = if buy {
+                    (candle.open >= price, candle.high >= price)
+                } else {
+                    (candle.open <= price, candle.low <= price)
+                };
+                if gapped {
+                    Some((candle.open, Liquidity::Taker))
+                } else if touched {
+                    Some((price, Liquidity::Taker))
+                } else {
+                    None
+                }
+            }
+        }
+    }
+}
+
+/// OrderType is how signals are placed as orders. Limit and stop orders are priced `offset`, a
+/// fraction, away from the close the signal was given on: a limit below it to buy and above it to
+/// sell, and a stop above it to buy and below it to sell. Exits are always placed at market, so a
+/// position can always be closed.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OrderType {
+    #[default]
+    Market,
+    Limit {
+        offset: f64,
+    },
+    Stop {
+        offset: f64,
+    },
+}
+
+impl OrderType {
+    /// order places an order to buy or sell, priced from `price`
+    pub fn order(&self, buy: bool, price: f64) -> Order {
+        let direction = if buy { 1.0 } else { -1.0 };
+        match *self {
+            OrderType::Market => Order::Market,
+            OrderType::Limit { offset } => Order::Limit(price * (1.0 - direction * offset)),
+            OrderType::Stop { offset } => Order::Stop(price * (1.0 + direction * offset)),
+        }
+    }
+}
+
+/// FeeTier is the maker and taker fee, as fractions of a fill's value, charged once the value
+/// traded over the last 30 days reaches `volume`
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub struct FeeTier {
+    pub volume: f64,
+    pub maker: f64,
+    pub taker: f64,
+}
+
+/// Commission is what a brokerage or exchange charges for each fill
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Commission {
+    #[default]
+    None,
+    /// a fee for each share or unit, but no less than `minimum` a fill
+    PerShare { rate: f64, minimum: f64 },
+    /// a fraction of the value of each fill, eg: 0.001 for 0.1%
+    Percentage(f64),
+    /// maker and taker fees which fall as more is traded, see `Commission::kraken`
+    MakerTaker(Vec<FeeTier>),
+}
+
+impl Commission {
+    /// kraken is Kraken's spot fee schedule, by 30 day volume in USD
+    pub fn kraken() -> Commission {
+        let tier = |volume, maker, taker| FeeTier {
+            volume,
+            maker,
+            taker,
+        };
+        Commission::MakerTaker(vec![
+            tier(0.0, 0.0016, 0.0026),
+            tier(50_000.0, 0.0014, 0.0024),
+            tier(100_000.0, 0.0012, 0.0022),
+            tier(250_000.0, 0.001, 0.002),
+            tier(500_000.0, 0.0008, 0.0018),
+            tier(1_000_000.0, 0.0006, 0.0016),
+            tier(2_500_000.0, 0.0004, 0.0014),
+            tier(5_000_000.0, 0.0002, 0.0012),
+            tier(10_000_000.0, 0.0, 0.001),
+        ])
+    }
+
+    /// fee is the commission on a fill of `quantity` at `price`, where `volume` is the value
+    /// traded over the 30 days before it
+    pub fn fee(&self, quantity: f64, price: f64, liquidity: Liquidity, volume: f64) -> f64 {
+        let quantity = quantity.abs();
+        match self {
+            Commission::None => 0.0,
+            Commission::PerShare { rate, minimum } => (quantity * rate).max(*minimum),
+            Commission::Percentage(rate) => quantity * price * rate,
+            Commission::MakerTaker(tiers) => {
+                let tier = tiers
+                    .iter()
+                    .filter(|tier| tier.volume <= volume)
+                    .max_by(|a, b| a.volume.total_cmp(&b.volume));
+                let rate = match (tier, liquidity) {
+                    (Some(tier), Liquidity::Maker) => tier.maker,
+                    (Some(tier), Liquidity::Taker) => tier.taker,
+                    (None, _) => 0.0,
+                };
+                quantity * price * rate
+            }
+        }
+    }
+
+    fn check(&self) -> Result<(), BacktestError> {
+        let rates: Vec<f64> = match self {
+            Commission::None => Vec::new(),
+            Commission::PerShare { rate, minimum } => vec![*rate, *minimum],
+            Commission::Percentage(rate) => vec![*rate],
+            Commission::MakerTaker(tiers) => tiers
+                .iter()
+                .flat_map(|tier| vec![tier.volume, tier.maker, tier.taker])
+                .collect(),
+        };
+        check_rates("commission", &rates)
+    }
+}
+
+/// Slippage is how much worse than the quoted price a fill which takes liquidity gets
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Slippage {
+    #[default]
+    None,
+    /// a fixed number of basis points, eg: 5.0 for 0.05%
+    Fixed(f64),
+    /// `impact` times the square of the fraction of the candle's volume the fill is, so small
+    /// orders barely move the price but large ones move it a lot. Candles without volume don't
+    /// slip, and crypto volumes built from ticks are only an approximation.
+    VolumeShare { impact: f64 },
+    /// half the bid-ask spread quoted around the fill, or `fallback` basis points when there's no
+    /// quote, as for equities, which the collector only stores candles for
+    Spread { fallback: f64 },
+}
+
+impl Slippage {
+    /// fraction is how much the price moves against a fill of `quantity` during `candle`, where
+    /// `spread` is the bid-ask spread at the time as a fraction of the price
+    pub fn fraction(&self, quantity: f64, candle: &Candle, spread: Option<f64>) -> f64 {
+        match *self {
+            Slippage::None => 0.0,
+            Slippage::Fixed(bps) => bps / 10_000.0,
+            Slippage::VolumeShare { impact } => {
+                if candle.volume <= 0.0 {
+                    return 0.0;
+                }
+                let share = (quantity.abs() / candle.volume).min(1.0);
+                impact * share * share
+            }
+            Slippage::Spread { fallback } => spread.map_or(fallback / 10_000.0, |s| s / 2.0),
+        }
+    }
+
+    /// check rejects negative rates, and rates that could move the price by 100% or more, which
+    /// would fill sells at or below 0
+    fn check(&self) -> Result<(), BacktestError> {
+        let most = match *self {
+            Slippage::None => 0.0,
+            Slippage::Fixed(bps) => bps / 10_000.0,
+            Slippage::VolumeShare { impact } => impact,
+            Slippage::Spread { fallback } => fallback / 10_000.0,
+        };
+        check_rates("slippage", &[most])?;
+        if most >= 1.0 {
+            return Err(BacktestError::InvalidParameter { name: "slippage" });
+        }
+        Ok(())
+    }
+}
+
+/// Execution is how simulated orders are placed, filled and charged for, eg: in TOML
+///
+/// ```toml
+/// orders = { limit = { offset = 0.001 } }
+/// commission = { percentage = 0.001 }
+/// slippage = { fixed = 5.0 }
+/// ```
+///
+/// The default fills every signal at market, with no commission or slippage.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(default)]
+pub struct Execution {
+    pub orders: OrderType,
+    pub commission: Commission,
+    pub slippage: Slippage,
+}
+
+impl Execution {
+    /// execute fills an order to buy or sell `quantity` during `candle`, returning the price it
+    /// fills at after slippage and whether it made or took liquidity, or `None` if it didn't fill.
+    /// Slippage never takes a limit order past its limit.
+    pub fn execute(
+        &self,
+        order: &Order,
+        quantity: f64,
+        candle: &Candle,
+        spread: Option<f64>,
+    ) -> Option<(f64, Liquidity)> {
+        let buy = quantity > 0.0;
+        let (price, liquidity) = order.fill(buy, candle)?;
+        if liquidity == Liquidity::Maker {
+            return Some((price, liquidity));
+        }
+
+        let slipped = self.slippage.fraction(quantity, candle, spread);
+        let price = if buy {
+            price * (1.0 + slipped)
+        } else {
+            price * (1.0 - slipped)
+        };
+        let price = match *order {
+            Order::Limit(limit) if buy => price.min(limit),
+            Order::Limit(limit) => price.max(limit),
+            _ => price,
+        };
+
+        Some((price, liquidity))
+    }
+
+    /// check returns `BacktestError::InvalidParameter` if a rate or offset is negative or not
+    /// finite, or an offset or slippage could move the price by 100% or more
+    pub fn check(&self) -> Result<(), BacktestError> {
+        match self.orders {
+            OrderType::Market => (),
+            OrderType::Limit { offset } | OrderType::Stop { offset } => {
+                check_rates("order offset", &[offset])?;
+                if offset >= 1.0 {
+                    return Err(BacktestError::InvalidParameter {
+                        name: "order offset",
+                    });
+                }
+            }
+        }
+        self.commission.check()?;
+        self.slippage.check()
+    }
+}
+
+fn check_rates(name: &'static str, rates: &[f64]) -> Result<(), BacktestError> {
+    if rates.iter().any(|rate| !rate.is_finite() || *rate < 0.0) {
+        return Err(BacktestError::InvalidParameter { name });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candle() -> Candle {
+        Candle::new(0, 100.0, 110.0, 90.0, 105.0, 1000.0)
+    }
+
+    #[test]
+    fn fill_test() {
+        let candle = candle();
+        let maker = |price| Some((price, Liquidity::Maker));
+        let taker = |price| Some((price, Liquidity::Taker));
+
+        assert_eq!(Order::Market.fill(true, &candle), taker(100.0));
+
+        assert_eq!(Order::Limit(95.0).fill(true, &candle), maker(95.0));
+        assert_eq!(Order::Limit(102.0).fill(true, &candle), taker(100.0));
+        assert_eq!(Order::Limit(85.0).fill(true, &candle), None);
+        assert_eq!(Order::Limit(108.0).fill(false, &candle), maker(108.0));
+        assert_eq!(Order::Limit(98.0).fill(false, &candle), taker(100.0));
+        assert_eq!(Order::Limit(115.0).fill(false, &candle), None);
+
+        assert_eq!(Order::Stop(108.0).fill(true, &candle), taker(108.0));
+        assert_eq!(Order::Stop(98.0).fill(true, &candle), taker(100.0));
+        assert_eq!(Order::Stop(115.0).fill(true, &candle), None);
+        assert_eq!(Order::Stop(95.0).fill(false, &candle), taker(95.0));
+        assert_eq!(Order::Stop(102.0).fill(false, &candle), taker(100.0));
+        assert_eq!(Order::Stop(85.0).fill(false, &candle), None);
+
+        assert_eq!(OrderType::Market.order(true, 100.0), Order::Market);
+        assert_eq!(
+            OrderType::Limit { offset: 0.5 }.order(true, 100.0),
+            Order::Limit(50.0)
+        );
+        assert_eq!(
+            OrderType::Stop { offset: 0.5 }.order(false, 100.0),
+            Order::Stop(50.0)
+        );
+    }
+
+    #[test]
+    fn commission_test() {
+        let taker = Liquidity::Taker;
+
+        assert_eq!(Commission::None.fee(10.0, 100.0, taker, 0.0), 0.0);
+        let per_share = Commission::PerShare {
+            rate: 0.01,
+            minimum: 1.0,
+        };
+        assert_eq!(per_share.fee(10.0, 100.0, taker, 0.0), 1.0);
+        assert_eq!(per_share.fee(-500.0, 100.0, taker, 0.0), 5.0);
+        assert_eq!(
+            Commission::Percentage(0.001).fee(-10.0, 100.0, taker, 0.0),
+            1.0
+        );
+
+        let kraken = Commission::kraken();
+        assert_eq!(kraken.fee(1.0, 10_000.0, Liquidity::Maker, 0.0), 16.0);
+        assert_eq!(kraken.fee(1.0, 10_000.0, taker, 0.0), 26.0);
+        assert!((kraken.fee(1.0, 10_000.0, taker, 60_000.0) - 24.0).abs() < 1e-9);
+        assert_eq!(kraken.fee(1.0, 10_000.0, Liquidity::Maker, 2e7), 0.0);
+    }
+
+    #[test]
+    fn slippage_test() {
+        let candle = candle();
+
+        assert_eq!(Slippage::None.fraction(10.0, &candle, None), 0.0);
+        assert_eq!(Slippage::Fixed(5.0).fraction(10.0, &candle, None), 0.0005);
+        let volume = Slippage::VolumeShare { impact: 0.5 };
+        assert_eq!(volume.fraction(-500.0, &candle, None), 0.125);
+        assert_eq!(volume.fraction(5000.0, &candle, None), 0.5);
+        let spread = Slippage::Spread { fallback: 10.0 };
+        assert_eq!(spread.fraction(10.0, &candle, Some(0.002)), 0.001);
+        assert_eq!(spread.fraction(10.0, &candle, None), 0.001);
+    }
+
+    #[test]
+    fn execute_test() {
+        let candle = candle();
+        let execution = Execution {
+            slippage: Slippage::Fixed(100.0),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            execution.execute(&Order::Market, 10.0, &candle, None),
+            Some((101.0, Liquidity::Taker))
+        );
+        assert_eq!(
+            execution.execute(&Order::Stop(95.0), -10.0, &candle, None),
+            Some((94.05, Liquidity::Taker))
+        );
+        assert_eq!(
+            execution.execute(&Order::Limit(95.0), 10.0, &candle, None),
+            Some((95.0, Liquidity::Maker)),
+            "resting limit orders don't slip"
+        );
+        assert_eq!(
+            execution.execute(&Order::Limit(100.5), 10.0, &candle, None),
+            Some((100.5, Liquidity::Taker)),
+            "slippage is capped at the limit"
+        );
+        assert_eq!(
+            execution.execute(&Order::Limit(80.0), 10.0, &candle, None),
+            None
+        );
+    }
+
+    #[test]
+    fn config_test() {
+        let execution: Execution = serde_json::from_str(
+            r#"{
+                "orders": {"limit": {"offset": 0.01}},
+                "commission": {"per_share": {"rate": 0.005, "minimum": 1.0}},
+                "slippage": {"spread": {"fallback": 2.0}}
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(execution.orders, OrderType::Limit { offset: 0.01 });
+        assert!(execution.check().is_ok());
+
+        let execution: Execution = serde_json::from_str(r#"{"commission": "none"}"#).unwrap();
+        assert_eq!(execution, Execution::default());
+
+        let invalid = |execution: Execution| execution.check().is_err();
+        assert!(invalid(Execution {
+            commission: Commission::Percentage(-0.1),
+            ..Default::default()
+        }));
+        assert!(invalid(Execution {
+            orders: OrderType::Stop { offset: 1.0 },
+            ..Default::default()
+        }));
+        assert!(invalid(Execution {
+            slippage: Slippage::Fixed(f64::NAN),
+            ..Default::default()
+        }));
+        assert!(invalid(Execution {
+            slippage: Slippage::Fixed(10_000.0),
+            ..Default::default()
+        }));
+        assert!(invalid(Execution {
+            slippage: Slippage::VolumeShare { impact: 1.5 },
+            ..Default::default()
+        }));
+        assert!(!invalid(Execution {
+            slippage: Slippage::VolumeShare { impact: 0.5 },
+            ..Default::default()
+        }));
+    }
+}
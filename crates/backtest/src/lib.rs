@@ -0,0 +1,17 @@
+pub mod data;
+pub mod error;
+pub use error::BacktestError;
+pub mod execution;
+pub use execution::{Commission, Execution, FeeTier, Liquidity, Order, OrderType, Slippage};
+pub mod metrics;
+pub use metrics::Summary;
+pub mod monte_carlo;
+pub use monte_carlo::{MonteCarlo, MonteCarloResult};
+pub mod optimize;
+pub use optimize::{Fold, Metric, Optimization, Optimizer, Search, Trial, Values, WalkForward};
+pub mod portfolio;
+pub use portfolio::{PortfolioBacktest, Timeline};
+pub mod report;
+pub use report::Report;
+pub mod simulator;
+pub use simulator::{Backtest, BacktestResult, EquityPoint, Fill, Trade};
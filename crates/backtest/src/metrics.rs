@@ -0,0 +1,274 @@
+use crate::{BacktestResult, EquityPoint};
+use serde::{Deserialize, Serialize};
+use ta::volume::DAY_MILLIS;
+
+/// The average length of a year, in milliseconds, which returns are annualized over
+pub const YEAR_MILLIS: usize = 365 * DAY_MILLIS + DAY_MILLIS / 4;
+
+/// Summary is how a backtest performed. Ratios which can't be calculated, such as the win rate
+/// without any trades, are `None`.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Summary {
+    pub capital: f64,
+    pub final_equity: f64,
+    /// the change in equity as a fraction of the starting capital, eg: 0.1 for 10%
+    pub total_return: f64,
+    /// the compound yearly return that would have given `total_return` over the backtest
+    pub annualized_return: Option<f64>,
+    /// the annualized mean return of each candle over its standard deviation
+    pub sharpe: Option<f64>,
+    /// like `sharpe`, but only counting losing candles as risk
+    pub sortino: Option<f64>,
+    /// the largest fall in equity from a peak, as a fraction of that peak
+    pub max_drawdown: f64,
+    /// the fraction of trades which made money
+    pub win_rate: Option<f64>,
+    /// the money trades made over the money trades lost
+    pub profit_factor: Option<f64>,
+    /// the fraction of the backtest a position was open for
+    pub exposure: f64,
+    pub trades: usize,
+    /// how long trades were held on average, in milliseconds
+    pub average_hold: Option<usize>,
+    /// the commission paid on every fill
+    pub fees: f64,
+}
+
+impl Summary {
+    pub fn new(result: &BacktestResult) -> Summary {
+        let values: Vec<f64> = result.equity.iter().map(|p| p.equity).collect();
+        let returns = returns(&values);
+        let years = years(&result.equity);
+        let periods_per_year = years
+            .filter(|years| *years > 0.0)
+            .map(|years| returns.len() as f64 / years);
+
+        let trades = &result.trades;
+        let won: f64 = trades.iter().map(|t| t.pnl.max(0.0)).sum();
+        let lost: f64 = trades.iter().map(|t| -t.pnl.min(0.0)).sum();
+        let held: usize = trades.iter().map(|t| t.duration()).sum();
+        let span = span(&result.equity);
+
+        Summary {
+            capital: result.capital,
+            final_equity: result.final_equity(),
+            total_return: result.total_return(),
+            annualized_return: years.filter(|years| *years > 0.0).and_then(|years| {
+                let growth = result.final_equity() / result.capital;
+                finite(growth.max(0.0).powf(1.0 / years) - 1.0)
+            }),
+            sharpe: periods_per_year.and_then(|periods| {
+                ratio(mean(&returns), deviation(&returns)).map(|r| r * periods.sqrt())
+            }),
+            sortino: periods_per_year.and_then(|periods| {
+                ratio(mean(&returns), downside_deviation(&returns)).map(|r| r * periods.sqrt())
+            }),
+            max_drawdown: max_drawdown(&values),
+            win_rate: ratio(
+                trades.iter().filter(|t| t.pnl > 0.0).count() as f64,
+                trades.len() as f64,
+            ),
+            profit_factor: ratio(won, lost),
+            exposure: if span > 0 {
+                (held as f64 / span as f64).min(1.0)
+            } else {
+                0.0
+            },
+            trades: trades.len(),
+            average_hold: held.checked_div(trades.len()),
+            fees: result.fills.iter().map(|f| f.commission).sum(),
+        }
+    }
+}
+
+/// returns is the fractional change from each value to the next
+pub fn returns(values: &[f64]) -> Vec<f64> {
+    values
+        .windows(2)
+        .map(|pair| {
+            if pair[0] == 0.0 {
+                0.0
+            } else {
+                pair[1] / pair[0] - 1.0
+            }
+        })
+        .collect()
+}
+
+/// max_drawdown is the largest fall from a peak in `values`, as a fraction of that peak
+pub fn max_drawdown(values: &[f64]) -> f64 {
+    let mut peak = f64::MIN;
+    let mut drawdown: f64 = 0.0;
+
+    for value in values {
+        peak = peak.max(*value);
+        if peak > 0.0 {
+            drawdown = drawdown.max((peak - value) / peak);
+        }
+    }
+
+    drawdown
+}
+
+/// drawdowns is the fall in equity from its peak so far at each point, as a fraction of that peak
+pub fn drawdowns(equity: &[EquityPoint]) -> Vec<f64> {
+    let mut peak = f64::MIN;
+    equity
+        .iter()
+        .map(|point| {
+            peak = peak.max(point.equity);
+            if peak > 0.0 {
+                (peak - point.equity) / peak
+            } else {
+                0.0
+            }
+        })
+        .collect()
+}
+
+fn span(equity: &[EquityPoint]) -> usize {
+    match (equity.first(), equity.last()) {
+        (Some(first), Some(last)) => last.datetime.saturating_sub(first.datetime),
+        _ => 0,
+    }
+}
+
+fn years(equity: &[EquityPoint]) -> Option<f64> {
+    if equity.len() < 2 {
+        return None;
+    }
+    Some(span(equity) as f64 / YEAR_MILLIS as f64)
+}
+
+fn mean(values: &[f64]) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    values.iter().sum::<f64>() / values.len() as f64
+}
+
+/// deviation is the sample standard deviation of `values`
+fn deviation(values: &[f64]) -> f64 {
+    if values.len() < 2 {
+        return 0.0;
+    }
+    let mean = mean(values);
+    let variance =
+        values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (values.len() - 1) as f64;
+    variance.sqrt()
+}
+
+/// downside_deviation is the root mean square of the losing `values`, counting gains as 0
+fn downside_deviation(values: &[f64]) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    let squares: f64 = values.iter().map(|v| v.min(0.0).powi(2)).sum();
+    (squares / values.len() as f64).sqrt()
+}
+
+fn ratio(numerator: f64, denominator: f64) -> Option<f64> {
+    if denominator == 0.0 {
+        return None;
+    }
+    finite(numerator / denominator)
+}
+
+fn finite(value: f64) -> Option<f64> {
+    Some(value).filter(|v| v.is_finite())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Fill, Trade};
+    use strategy::Side;
+
+    fn trade(entry_datetime: usize, exit_datetime: usize, pnl: f64) -> Trade {
+        Trade {
+            symbol: "MSFT".to_string(),
+            side: Side::Long,
+            quantity: 1.0,
+            entry_datetime,
+            entry_price: 100.0,
+            exit_datetime,
+            exit_price: 100.0 + pnl,
+            fees: 0.5,
+            pnl,
+        }
+    }
+
+    #[test]
+    fn summary_test() {
+        let values = [1000.0, 1100.0, 990.0, 1089.0, 1210.0];
+        let result = BacktestResult {
+            symbol: "MSFT".to_string(),
+            strategy: "script".to_string(),
+            capital: 1000.0,
+            fills: vec![Fill {
+                datetime: 0,
+                symbol: "MSFT".to_string(),
+                quantity: 1.0,
+                price: 100.0,
+                commission: 0.5,
+            }],
+            trades: vec![
+                trade(0, DAY_MILLIS, 100.0),
+                trade(DAY_MILLIS, 2 * DAY_MILLIS, -160.0),
+                trade(2 * DAY_MILLIS, 4 * DAY_MILLIS, 220.0),
+            ],
+            equity: values
+                .iter()
+                .enumerate()
+                .map(|(i, equity)| EquityPoint {
+                    datetime: i * YEAR_MILLIS / 2,
+                    equity: *equity,
+                })
+                .collect(),
+        };
+
+        let summary = Summary::new(&result);
+        assert_eq!(summary.final_equity, 1210.0);
+        assert!((summary.total_return - 0.21).abs() < 1e-12);
+        assert!((summary.annualized_return.unwrap() - 0.1).abs() < 1e-12);
+        assert_eq!(summary.max_drawdown, 0.1);
+        assert_eq!(summary.win_rate, Some(2.0 / 3.0));
+        assert_eq!(summary.profit_factor, Some(2.0));
+        assert_eq!(summary.trades, 3);
+        assert_eq!(summary.average_hold, Some(4 * DAY_MILLIS / 3));
+        assert_eq!(summary.fees, 0.5);
+
+        // returns are 10%, -10%, 10% and 11.1%, twice a year
+        let returns = returns(&values);
+        let sharpe = mean(&returns) / deviation(&returns) * 2f64.sqrt();
+        assert_eq!(summary.sharpe, Some(sharpe));
+        assert!(summary.sortino.unwrap() > summary.sharpe.unwrap());
+
+        let empty = Summary::new(&BacktestResult {
+            trades: Vec::new(),
+            equity: Vec::new(),
+            ..result
+        });
+        assert_eq!(empty.total_return, 0.0);
+        assert_eq!(empty.annualized_return, None);
+        assert_eq!(empty.sharpe, None);
+        assert_eq!(empty.win_rate, None);
+        assert_eq!(empty.average_hold, None);
+        assert_eq!(empty.exposure, 0.0);
+    }
+
+    #[test]
+    fn drawdown_test() {
+        assert_eq!(max_drawdown(&[]), 0.0);
+        assert_eq!(max_drawdown(&[100.0, 50.0, 200.0, 150.0]), 0.5);
+
+        let equity: Vec<EquityPoint> = [100.0, 50.0, 200.0, 150.0]
+            .iter()
+            .map(|equity| EquityPoint {
+                datetime: 0,
+                equity: *equity,
+            })
+            .collect();
+        assert_eq!(drawdowns(&equity), vec![0.0, 0.5, 0.0, 0.25]);
+    }
+}
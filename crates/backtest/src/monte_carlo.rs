@@ -0,0 +1,424 @@
+use crate::metrics::{max_drawdown, returns};
+use crate::{BacktestError, BacktestResult, Trade};
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use strategy::Side;
+
+/// The most simulations a Monte Carlo analysis may run
+pub const MAX_SIMULATIONS: usize = 1_000_000;
+
+/// Method is how each simulation rearranges a backtest's history
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Method {
+    /// trades in a random order, so final equity is unchanged but drawdowns vary
+    #[default]
+    Shuffle,
+    /// as many trades as the backtest made, drawn at random with replacement
+    Resample,
+    /// the return of each candle, drawn at random with replacement and compounded
+    Bootstrap,
+}
+
+/// MonteCarlo runs a backtest's trades through many random alternative histories, to show how
+/// much of its result was down to luck, eg: in TOML
+///
+/// ```toml
+/// method = "resample"
+/// simulations = 5000
+/// perturbation = 0.002
+/// ruin = 0.3
+/// ```
+///
+/// Simulations are repeatable for the same `seed`, and run in parallel across every CPU core.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(default)]
+pub struct MonteCarlo {
+    pub method: Method,
+    pub simulations: usize,
+    /// the most a trade's entry price is moved up or down at random, as a fraction of it, eg: 0.001
+    /// for 0.1%. Candle returns aren't trades, so aren't perturbed when bootstrapping.
+    pub perturbation: f64,
+    /// the loss, as a fraction of the starting capital, that counts as ruin, eg: 0.5 when equity
+    /// falls to half the capital
+    pub ruin: f64,
+    /// how much of each distribution its interval covers, eg: 0.95 for the 2.5th to 97.5th
+    /// percentiles
+    pub confidence: f64,
+    pub seed: u64,
+}
+
+impl Default for MonteCarlo {
+    /// default shuffles the trades 1000 times, counting the loss of half the capital as ruin
+    fn default() -> MonteCarlo {
+        MonteCarlo {
+            method: Method::Shuffle,
+            simulations: 1000,
+            perturbation: 0.0,
+            ruin: 0.5,
+            confidence: 0.95,
+            seed: 0,
+        }
+    }
+}
+
+/// Distribution describes the values a measure took across every simulation
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Distribution {
+    pub mean: f64,
+    pub median: f64,
+    /// the bottom of the confidence interval
+    pub lower: f64,
+    /// the top of the confidence interval
+    pub upper: f64,
+    pub min: f64,
+    pub max: f64,
+}
+
+impl Distribution {
+    /// new describes `values`, with an interval covering `confidence` of them. Values must be
+    /// finite, and there must be at least one.
+    fn new(values: &mut [f64], confidence: f64) -> Distribution {
+        values.sort_by(|a, b| a.total_cmp(b));
+        let tail = (1.0 - confidence) / 2.0;
+
+        Distribution {
+            mean: values.iter().sum::<f64>() / values.len() as f64,
+            median: percentile(values, 0.5),
+            lower: percentile(values, tail),
+            upper: percentile(values, 1.0 - tail),
+            min: values[0],
+            max: values[values.len() - 1],
+        }
+    }
+}
+
+/// Probability is how often something happened across every simulation, with a Wilson score
+/// interval for how often it would happen over infinitely many
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Probability {
+    pub estimate: f64,
+    pub lower: f64,
+    pub upper: f64,
+}
+
+impl Probability {
+    fn new(count: usize, total: usize, confidence: f64) -> Probability {
+        if total == 0 {
+            return Probability {
+                estimate: 0.0,
+                lower: 0.0,
+                upper: 0.0,
+            };
+        }
+
+        let n = total as f64;
+        let p = count as f64 / n;
+        let z = z_score((1.0 - confidence) / 2.0);
+        let z2 = z * z;
+        let centre = (p + z2 / (2.0 * n)) / (1.0 + z2 / n);
+        let margin = z / (1.0 + z2 / n) * (p * (1.0 - p) / n + z2 / (4.0 * n * n)).sqrt();
+
+        Probability {
+            estimate: p,
+            lower: (centre - margin).max(0.0),
+            upper: (centre + margin).min(1.0),
+        }
+    }
+}
+
+/// MonteCarloResult is what a Monte Carlo analysis found
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct MonteCarloResult {
+    pub method: Method,
+    pub simulations: usize,
+    pub confidence: f64,
+    pub final_equity: Distribution,
+    /// the largest fall in equity from a peak, as a fraction of that peak
+    pub max_drawdown: Distribution,
+    /// how often equity fell to the ruin level at any point
+    pub risk_of_ruin: Probability,
+}
+
+impl MonteCarlo {
+    /// check returns an error for settings that can't be simulated, such as a confidence of 2
+    pub fn check(&self) -> Result<(), BacktestError> {
+        if self.simulations == 0 || self.simulations > MAX_SIMULATIONS {
+            return Err(BacktestError::InvalidParameter {
+                name: "simulations",
+            });
+        }
+        if !(0.0..1.0).contains(&self.perturbation) {
+            return Err(BacktestError::InvalidParameter {
+                name: "perturbation",
+            });
+        }
+        if !(self.ruin > 0.0 && self.ruin <= 1.0) {
+            return Err(BacktestError::InvalidParameter { name: "ruin" });
+        }
+        if !(self.confidence > 0.0 && self.confidence < 1.0) {
+            return Err(BacktestError::InvalidParameter { name: "confidence" });
+        }
+        Ok(())
+    }
+
+    /// run simulates alternative histories for a backtest. A backtest without trades or candles
+    /// has nothing to rearrange, so every simulation ends with its starting capital.
+    pub fn run(&self, result: &BacktestResult) -> Result<MonteCarloResult, BacktestError> {
+        self.check()?;
+
+        let values: Vec<f64> = result.equity.iter().map(|p| p.equity).collect();
+        let candle_returns: Vec<f64> = returns(&values)
+            .into_iter()
+            .filter(|r| r.is_finite())
+            .collect();
+        let floor = result.capital * (1.0 - self.ruin);
+
+        // each simulation has its own generator, so results don't depend on how they're scheduled
+        let mut seeds = StdRng::seed_from_u64(self.seed);
+        let seeds: Vec<u64> = (0..self.simulations).map(|_| seeds.gen()).collect();
+
+        let paths: Vec<(f64, f64, bool)> = seeds
+            .par_iter()
+            .map(|seed| {
+                let mut rng = StdRng::seed_from_u64(*seed);
+                let path = match self.method {
+                    Method::Shuffle => {
+                        let mut pnls = self.pnls(&result.trades, &mut rng);
+                        pnls.shuffle(&mut rng);
+                        accumulate(result.capital, &pnls)
+                    }
+                    Method::Resample => {
+                        let pnls = self.pnls(&result.trades, &mut rng);
+                        let drawn: Vec<f64> = (0..pnls.len())
+                            .map(|_| pnls[rng.gen_range(0..pnls.len())])
+                            .collect();
+                        accumulate(result.capital, &drawn)
+                    }
+                    Method::Bootstrap => {
+                        let mut equity = result.capital;
+                        let mut path = Vec::with_capacity(candle_returns.len() + 1);
+                        path.push(equity);
+                        for _ in 0..candle_returns.len() {
+                            equity *= 1.0 + candle_returns[rng.gen_range(0..candle_returns.len())];
+                            path.push(equity);
+                        }
+                        path
+                    }
+                };
+
+                let ruined = path.iter().any(|equity| *equity <= floor);
+                let last = path.last().copied().unwrap_or(result.capital);
+                (last, max_drawdown(&path), ruined)
+            })
+            .collect();
+
+        let mut final_equity: Vec<f64> = paths.iter().map(|p| p.0).collect();
+        let mut drawdowns: Vec<f64> = paths.iter().map(|p| p.1).collect();
+        let ruined = paths.iter().filter(|p| p.2).count();
+
+        Ok(MonteCarloResult {
+            method: self.method,
+            simulations: self.simulations,
+            confidence: self.confidence,
+            final_equity: Distribution::new(&mut final_equity, self.confidence),
+            max_drawdown: Distribution::new(&mut drawdowns, self.confidence),
+            risk_of_ruin: Probability::new(ruined, self.simulations, self.confidence),
+        })
+    }
+
+    /// pnls is the profit or loss of each trade, had it been entered at a randomly perturbed price
+    fn pnls(&self, trades: &[Trade], rng: &mut StdRng) -> Vec<f64> {
+        trades
+            .iter()
+            .filter(|trade| trade.pnl.is_finite())
+            .map(|trade| {
+                if self.perturbation == 0.0 {
+                    return trade.pnl;
+                }
+                let moved = trade.entry_price
+                    * rng.gen_range(-self.perturbation..=self.perturbation)
+                    * trade.quantity;
+                match trade.side {
+                    Side::Long => trade.pnl - moved,
+                    Side::Short => trade.pnl + moved,
+                }
+            })
+            .collect()
+    }
+}
+
+/// accumulate is the equity after each of `pnls`, starting from `capital`
+fn accumulate(capital: f64, pnls: &[f64]) -> Vec<f64> {
+    let mut equity = capital;
+    let mut path = Vec::with_capacity(pnls.len() + 1);
+    path.push(equity);
+    for pnl in pnls {
+        equity += pnl;
+        path.push(equity);
+    }
+    path
+}
+
+/// percentile interpolates between the closest of the sorted `values` to `fraction` of the way
+/// through them
+fn percentile(values: &[f64], fraction: f64) -> f64 {
+    let position = fraction * (values.len() - 1) as f64;
+    let below = position.floor() as usize;
+    let above = position.ceil() as usize;
+    values[below] + (values[above] - values[below]) * (position - below as f64)
+}
+
+/// z_score is how many standard deviations above the mean of a normal distribution leaves `tail`
+/// of it above, using the approximation in Abramowitz and Stegun 26.2.23
+fn z_score(tail: f64) -> f64 {
+    let t = (-2.0 * tail.ln()).sqrt();
+    t - (2.515517 + 0.802853 * t + 0.010328 * t * t)
+        / (1.0 + 1.432788 * t + 0.189269 * t * t + 0.001308 * t * t * t)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::EquityPoint;
+
+    fn result(pnls: &[f64]) -> BacktestResult {
+        let trades: Vec<Trade> = pnls
+            .iter()
+            .enumerate()
+            .map(|(i, pnl)| Trade {
+                symbol: "MSFT".to_string(),
+                side: Side::Long,
+                quantity: 1.0,
+                entry_datetime: i,
+                entry_price: 100.0,
+                exit_datetime: i + 1,
+                exit_price: 100.0 + pnl,
+                fees: 0.0,
+                pnl: *pnl,
+            })
+            .collect();
+        let equity = accumulate(1000.0, pnls)
+            .into_iter()
+            .enumerate()
+            .map(|(datetime, equity)| EquityPoint { datetime, equity })
+            .collect();
+
+        BacktestResult {
+            symbol: "MSFT".to_string(),
+            strategy: "script".to_string(),
+            capital: 1000.0,
+            fills: Vec::new(),
+            trades,
+            equity,
+        }
+    }
+
+    #[test]
+    fn shuffle_test() {
+        let result = result(&[100.0, -200.0, 50.0, -250.0, 400.0, 100.0]);
+        let analysis = MonteCarlo::default().run(&result).unwrap();
+
+        // shuffling only changes the order trades happen in
+        assert_eq!(analysis.final_equity.min, 1200.0);
+        assert_eq!(analysis.final_equity.max, 1200.0);
+        assert!(analysis.max_drawdown.min < analysis.max_drawdown.max);
+        assert!(analysis.max_drawdown.lower <= analysis.max_drawdown.median);
+        assert!(analysis.max_drawdown.median <= analysis.max_drawdown.upper);
+        // 45% of the capital is the most that can be lost, so ruin needs a tighter limit
+        assert_eq!(analysis.risk_of_ruin.estimate, 0.0);
+
+        let strict = MonteCarlo {
+            ruin: 0.3,
+            ..Default::default()
+        };
+        let ruined = strict.run(&result).unwrap().risk_of_ruin;
+        assert!(ruined.estimate > 0.0 && ruined.estimate < 1.0);
+        assert!(ruined.lower < ruined.estimate && ruined.estimate < ruined.upper);
+        assert_eq!(strict.run(&result).unwrap().risk_of_ruin, ruined);
+    }
+
+    #[test]
+    fn resample_test() {
+        let result = result(&[100.0, -200.0, 50.0, -250.0, 400.0, 100.0]);
+        let resample = MonteCarlo {
+            method: Method::Resample,
+            perturbation: 0.01,
+            ..Default::default()
+        };
+        let analysis = resample.run(&result).unwrap();
+
+        assert!(analysis.final_equity.min < analysis.final_equity.max);
+        assert!(analysis.final_equity.lower < 1200.0 && 1200.0 < analysis.final_equity.upper);
+        // six trades of at most 401 dollars each
+        assert!(analysis.final_equity.max <= 1000.0 + 6.0 * 401.0);
+        assert_eq!(resample.run(&result).unwrap(), analysis);
+
+        let reseeded = MonteCarlo {
+            seed: 1,
+            ..resample
+        };
+        assert_ne!(reseeded.run(&result).unwrap(), analysis);
+    }
+
+    #[test]
+    fn bootstrap_test() {
+        let bootstrap = MonteCarlo {
+            method: Method::Bootstrap,
+            simulations: 200,
+            ..Default::default()
+        };
+        let analysis = bootstrap.run(&result(&[100.0, 100.0, 100.0])).unwrap();
+        // every candle gained, so every path does
+        assert!(analysis.final_equity.min > 1000.0);
+        assert_eq!(analysis.max_drawdown.max, 0.0);
+
+        let empty = bootstrap.run(&result(&[])).unwrap();
+        assert_eq!(empty.final_equity.mean, 1000.0);
+        assert_eq!(empty.risk_of_ruin.estimate, 0.0);
+    }
+
+    #[test]
+    fn statistics_test() {
+        assert_eq!(percentile(&[1.0, 2.0, 3.0, 4.0, 5.0], 0.5), 3.0);
+        assert_eq!(percentile(&[1.0, 2.0], 0.25), 1.25);
+        assert!((z_score(0.025) - 1.96).abs() < 1e-3);
+
+        let mut values = [3.0, 1.0, 2.0];
+        let distribution = Distribution::new(&mut values, 0.5);
+        assert_eq!(distribution.mean, 2.0);
+        assert_eq!((distribution.lower, distribution.upper), (1.5, 2.5));
+
+        let none = Probability::new(0, 100, 0.95);
+        assert_eq!(none.estimate, 0.0);
+        assert!(none.upper > 0.0 && none.upper < 0.05);
+    }
+
+    #[test]
+    fn check_test() {
+        assert!(MonteCarlo::default().check().is_ok());
+        for invalid in [
+            MonteCarlo {
+                simulations: 0,
+                ..Default::default()
+            },
+            MonteCarlo {
+                ruin: 0.0,
+                ..Default::default()
+            },
+            MonteCarlo {
+                confidence: 1.0,
+                ..Default::default()
+            },
+            MonteCarlo {
+                perturbation: f64::NAN,
+                ..Default::default()
+            },
+        ]
+        .iter()
+        {
+            assert!(invalid.check().is_err());
+        }
+    }
+}
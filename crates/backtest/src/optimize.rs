@@ -0,0 +1,721 @@
+use crate::data::Spreads;
+use crate::metrics::Summary;
+use crate::report::{csv, file_name};
+use crate::{Backtest, BacktestError};
+use log::debug;
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::{Number, Value};
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+};
+use strategy::{Strategy, StrategyConfig};
+use ta::Series;
+
+/// The most values a single parameter may take, so a tiny step can't exhaust memory
+pub const MAX_VALUES: usize = 10_000;
+
+/// Values are what an optimized parameter is tried at: a list, eg: `[10, 20, 50]`, or a range
+/// from `start` to `end` inclusive, eg: `{ start = 5, end = 50, step = 5 }`
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum Values {
+    List(Vec<f64>),
+    Range { start: f64, end: f64, step: f64 },
+}
+
+impl Values {
+    pub fn values(&self) -> Result<Vec<f64>, BacktestError> {
+        let values = match *self {
+            Values::List(ref values) => values.clone(),
+            Values::Range { start, end, step } => {
+                if step <= 0.0
+                    || !step.is_finite()
+                    || !start.is_finite()
+                    || !end.is_finite()
+                    || end < start
+                {
+                    return Err(BacktestError::InvalidParameter { name: "range" });
+                }
+                let count = ((end - start) / step + 1e-9).floor() as usize + 1;
+                if count > MAX_VALUES {
+                    return Err(BacktestError::InvalidParameter { name: "range" });
+                }
+                (0..count).map(|i| start + step * i as f64).collect()
+            }
+        };
+
+        if values.is_empty() || values.len() > MAX_VALUES || values.iter().any(|v| !v.is_finite()) {
+            return Err(BacktestError::InvalidParameter { name: "values" });
+        }
+        Ok(values)
+    }
+}
+
+/// Search is how parameter combinations are picked: every combination of every value, or
+/// `samples` combinations picked at random, which is repeatable for the same `seed`. Either is
+/// limited to `MAX_VALUES * 10` combinations.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Search {
+    #[default]
+    Grid,
+    Random {
+        samples: usize,
+        seed: u64,
+    },
+}
+
+/// Metric is what trials are ranked by. Every metric ranks higher values first, apart from
+/// `MaxDrawdown`, which ranks the smallest drawdown first.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Metric {
+    TotalReturn,
+    AnnualizedReturn,
+    #[default]
+    Sharpe,
+    Sortino,
+    MaxDrawdown,
+    WinRate,
+    ProfitFactor,
+}
+
+impl Metric {
+    /// score is the metric for `summary`, where higher is better. A metric which can't be
+    /// calculated, such as the Sharpe ratio of a strategy that never traded, scores lowest.
+    pub fn score(&self, summary: &Summary) -> f64 {
+        let value = match self {
+            Metric::TotalReturn => Some(summary.total_return),
+            Metric::AnnualizedReturn => summary.annualized_return,
+            Metric::Sharpe => summary.sharpe,
+            Metric::Sortino => summary.sortino,
+            Metric::MaxDrawdown => Some(-summary.max_drawdown),
+            Metric::WinRate => summary.win_rate,
+            Metric::ProfitFactor => summary.profit_factor,
+        };
+        value.filter(|v| v.is_finite()).unwrap_or(f64::NEG_INFINITY)
+    }
+}
+
+/// WalkForward splits history into rolling windows of `in_sample` candles to optimize on, each
+/// followed by `out_of_sample` candles to test the best parameters on. Windows move forward by
+/// `out_of_sample` candles, so the out of sample periods follow one another without overlapping.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub struct WalkForward {
+    pub in_sample: usize,
+    pub out_of_sample: usize,
+}
+
+/// Trial is a backtest of one combination of parameters
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Trial {
+    pub parameters: BTreeMap<String, f64>,
+    pub score: f64,
+    pub summary: Summary,
+}
+
+/// Fold is one window of a walk forward: the best trial on its in sample candles, and how those
+/// parameters did on the out of sample candles after them
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Fold {
+    /// the datetime of the first in sample candle
+    pub start: usize,
+    /// the datetime of the first out of sample candle
+    pub split: usize,
+    /// the datetime of the last out of sample candle
+    pub end: usize,
+    pub in_sample: Trial,
+    pub out_of_sample: Summary,
+}
+
+/// out_of_sample_return compounds the return of each fold's out of sample period, which is how the
+/// walk forward would have done had it been traded
+pub fn out_of_sample_return(folds: &[Fold]) -> f64 {
+    folds
+        .iter()
+        .map(|fold| 1.0 + fold.out_of_sample.total_return)
+        .product::<f64>()
+        - 1.0
+}
+
+/// Optimizer searches for the parameters of a strategy that did best over history, eg: the EMA
+/// periods of an `ema_crossover`. Parameters are the strategy's config fields, by name.
+///
+/// Trials run in parallel across every CPU core. Combinations a strategy rejects, such as a fast
+/// EMA slower than the slow one, are skipped.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Optimizer {
+    pub backtest: Backtest,
+    pub strategy: StrategyConfig,
+    pub parameters: BTreeMap<String, Values>,
+    pub search: Search,
+    pub metric: Metric,
+}
+
+impl Optimizer {
+    /// candidates returns the parameter combinations the search tries
+    pub fn candidates(&self) -> Result<Vec<BTreeMap<String, f64>>, BacktestError> {
+        let base = strategy_fields(&self.strategy);
+        let mut parameters: Vec<(&String, Vec<f64>)> = Vec::new();
+        for (name, values) in &self.parameters {
+            if name == "name" || !base.contains_key(name) {
+                return Err(BacktestError::UnknownParameter(name.clone()));
+            }
+            parameters.push((name, values.values()?));
+        }
+
+        let combinations = parameters
+            .iter()
+            .map(|(_, values)| values.len())
+            .fold(1usize, |total, count| total.saturating_mul(count));
+        // the nth combination, counting through the last parameter's values fastest
+        let nth = |mut n: usize| {
+            let mut index = vec![0; parameters.len()];
+            for (i, (_, values)) in parameters.iter().enumerate().rev() {
+                index[i] = n % values.len();
+                n /= values.len();
+            }
+            index
+        };
+
+        let indices: Vec<Vec<usize>> = match self.search {
+            Search::Grid => {
+                if combinations > MAX_VALUES * 10 {
+                    return Err(BacktestError::InvalidParameter {
+                        name: "grid, which has too many combinations",
+                    });
+                }
+                (0..combinations).map(nth).collect()
+            }
+            Search::Random { samples, seed } => {
+                if samples > MAX_VALUES * 10 {
+                    return Err(BacktestError::InvalidParameter {
+                        name: "samples, which are too many",
+                    });
+                }
+                let samples = samples.min(combinations);
+                let mut rng = StdRng::seed_from_u64(seed);
+
+                // drawing until unique slows down as the samples cover most combinations, so
+                // shuffle them all instead
+                if samples.saturating_mul(2) >= combinations {
+                    let mut all: Vec<usize> = (0..combinations).collect();
+                    all.shuffle(&mut rng);
+                    all.truncate(samples);
+                    return Ok(lookup(&parameters, all.into_iter().map(nth)));
+                }
+
+                let mut seen = HashSet::new();
+                let mut indices = Vec::new();
+                while indices.len() < samples {
+                    let index: Vec<usize> = parameters
+                        .iter()
+                        .map(|(_, values)| rng.gen_range(0..values.len()))
+                        .collect();
+                    if seen.insert(index.clone()) {
+                        indices.push(index);
+                    }
+                }
+                indices
+            }
+        };
+
+        Ok(lookup(&parameters, indices.into_iter()))
+    }
+
+    /// optimize backtests every candidate over `series`, returning the trials ranked best first
+    pub fn optimize(
+        &self,
+        series: &Series,
+        spreads: &Spreads,
+    ) -> Result<Vec<Trial>, BacktestError> {
+        Ok(self.trials(&self.candidates()?, series, spreads))
+    }
+
+    /// walk_forward optimizes over each in sample window and tests the best parameters on the out
+    /// of sample window after it, returning every fold in order. The in sample candles also warm
+    /// the strategy up before it trades out of sample.
+    pub fn walk_forward(
+        &self,
+        series: &Series,
+        spreads: &Spreads,
+        walk_forward: WalkForward,
+    ) -> Result<Vec<Fold>, BacktestError> {
+        let WalkForward {
+            in_sample,
+            out_of_sample,
+        } = walk_forward;
+        if in_sample == 0 || out_of_sample == 0 {
+            return Err(BacktestError::InvalidParameter {
+                name: "walk forward window",
+            });
+        }
+
+        let candidates = self.candidates()?;
+        let mut folds = Vec::new();
+        let mut start = 0;
+        while start + in_sample < series.len() {
+            let split = start + in_sample;
+            let end = (split + out_of_sample).min(series.len());
+            let window = |from: usize, to: usize| Series {
+                symbol: series.symbol.clone(),
+                candles: series[from..to].to_vec(),
+            };
+
+            let best = self.trials(&candidates, &window(start, split), spreads);
+            let best = match best.into_iter().next() {
+                Some(best) => best,
+                None => {
+                    start += out_of_sample;
+                    continue;
+                }
+            };
+
+            if let Some(mut strategy) = self.build(&best.parameters) {
+                let result = self.backtest.run_warmed(
+                    &window(start, end),
+                    spreads,
+                    strategy.as_mut(),
+                    in_sample,
+                );
+                folds.push(Fold {
+                    start: series[start].datetime,
+                    split: series[split].datetime,
+                    end: series[end - 1].datetime,
+                    in_sample: best,
+                    out_of_sample: Summary::new(&result),
+                });
+            }
+
+            start += out_of_sample;
+        }
+
+        Ok(folds)
+    }
+
+    fn trials(
+        &self,
+        candidates: &[BTreeMap<String, f64>],
+        series: &Series,
+        spreads: &Spreads,
+    ) -> Vec<Trial> {
+        let mut trials: Vec<Trial> = candidates
+            .par_iter()
+            .filter_map(|parameters| {
+                let mut strategy = self.build(parameters)?;
+                let result = self.backtest.run_quoted(series, spreads, strategy.as_mut());
+                let summary = Summary::new(&result);
+                Some(Trial {
+                    parameters: parameters.clone(),
+                    score: self.metric.score(&summary),
+                    summary,
+                })
+            })
+            .collect();
+
+        trials.sort_by(|a, b| b.score.total_cmp(&a.score));
+        trials
+    }
+
+    /// build returns the strategy configured with `parameters`, or `None` if it rejects them
+    fn build(&self, parameters: &BTreeMap<String, f64>) -> Option<Box<dyn Strategy>> {
+        let strategy = configure(&self.strategy, parameters).and_then(|c| c.build().ok());
+        if strategy.is_none() {
+            debug!("skipping invalid parameters {:?}", parameters);
+        }
+        strategy
+    }
+}
+
+/// Optimization is everything an optimizer tried on a symbol, which is written out like a `Report`
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Optimization {
+    pub symbol: String,
+    pub strategy: String,
+    /// every trial over the whole history, best first
+    pub trials: Vec<Trial>,
+    /// the walk forward, if one was run
+    pub folds: Vec<Fold>,
+}
+
+/// FoldRow is a row of the walk forward CSV
+#[derive(Serialize)]
+struct FoldRow {
+    start: usize,
+    split: usize,
+    end: usize,
+    parameters: String,
+    in_sample_score: f64,
+    out_of_sample_return: f64,
+    out_of_sample_max_drawdown: f64,
+    out_of_sample_trades: usize,
+}
+
+impl Optimization {
+    pub fn new(symbol: &str, optimizer: &Optimizer, trials: Vec<Trial>, folds: Vec<Fold>) -> Self {
+        let fields = strategy_fields(&optimizer.strategy);
+        Optimization {
+            symbol: symbol.to_string(),
+            strategy: fields
+                .get("name")
+                .and_then(Value::as_str)
+                .unwrap_or_default()
+                .to_string(),
+            trials,
+            folds,
+        }
+    }
+
+    /// name identifies the optimization's files, eg: `MSFT-ema_crossover-optimize`
+    pub fn name(&self) -> String {
+        format!("{}-optimize", file_name(&self.symbol, &self.strategy))
+    }
+
+    /// trials_csv has a row for each trial, best first, with a column for each parameter
+    pub fn trials_csv(&self) -> String {
+        let names: Vec<&String> = self
+            .trials
+            .first()
+            .map(|trial| trial.parameters.keys().collect())
+            .unwrap_or_default();
+        let optional = |value: Option<f64>| value.map(|v| v.to_string()).unwrap_or_default();
+
+        let mut writer = csv::Writer::from_writer(Vec::new());
+        let mut header: Vec<&str> = names.iter().map(|name| name.as_str()).collect();
+        header.extend(&[
+            "score",
+            "total_return",
+            "annualized_return",
+            "sharpe",
+            "sortino",
+            "max_drawdown",
+            "win_rate",
+            "profit_factor",
+            "trades",
+            "fees",
+        ]);
+        // writing to memory only fails for values csv can't represent, which rows don't contain
+        let _ = writer.write_record(&header);
+
+        for trial in &self.trials {
+            let summary = &trial.summary;
+            let mut row: Vec<String> = names
+                .iter()
+                .map(|name| trial.parameters[*name].to_string())
+                .collect();
+            row.extend(vec![
+                trial.score.to_string(),
+                summary.total_return.to_string(),
+                optional(summary.annualized_return),
+                optional(summary.sharpe),
+                optional(summary.sortino),
+                summary.max_drawdown.to_string(),
+                optional(summary.win_rate),
+                optional(summary.profit_factor),
+                summary.trades.to_string(),
+                summary.fees.to_string(),
+            ]);
+            let _ = writer.write_record(&row);
+        }
+
+        writer
+            .into_inner()
+            .ok()
+            .and_then(|bytes| String::from_utf8(bytes).ok())
+            .unwrap_or_default()
+    }
+
+    /// folds_csv has a row for each walk forward fold, with its parameters as `name=value` pairs
+    pub fn folds_csv(&self) -> String {
+        csv(self.folds.iter().map(|fold| FoldRow {
+            start: fold.start,
+            split: fold.split,
+            end: fold.end,
+            parameters: fold
+                .in_sample
+                .parameters
+                .iter()
+                .map(|(name, value)| format!("{}={}", name, value))
+                .collect::<Vec<String>>()
+                .join(" "),
+            in_sample_score: fold.in_sample.score,
+            out_of_sample_return: fold.out_of_sample.total_return,
+            out_of_sample_max_drawdown: fold.out_of_sample.max_drawdown,
+            out_of_sample_trades: fold.out_of_sample.trades,
+        }))
+    }
+
+    /// write saves the trials to `{name}.csv`, and the walk forward to `{name}-walk-forward.csv` if
+    /// there is one, returning the paths written
+    pub fn write(&self, dir: &Path) -> Result<Vec<PathBuf>, BacktestError> {
+        fs::create_dir_all(dir).map_err(|error| BacktestError::Io {
+            path: dir.to_path_buf(),
+            error,
+        })?;
+
+        let name = self.name();
+        let mut files = vec![(format!("{}.csv", name), self.trials_csv())];
+        if !self.folds.is_empty() {
+            files.push((format!("{}-walk-forward.csv", name), self.folds_csv()));
+        }
+
+        let mut paths = Vec::with_capacity(files.len());
+        for (file, contents) in files.iter() {
+            let path = dir.join(file);
+            fs::write(&path, contents).map_err(|error| BacktestError::Io {
+                path: path.clone(),
+                error,
+            })?;
+            paths.push(path);
+        }
+
+        Ok(paths)
+    }
+}
+
+/// strategy_fields is a strategy config as it's written in a config file, by field name
+fn strategy_fields(config: &StrategyConfig) -> serde_json::Map<String, Value> {
+    match serde_json::to_value(config) {
+        Ok(Value::Object(fields)) => fields,
+        _ => serde_json::Map::new(),
+    }
+}
+
+/// configure overrides fields of a strategy config, returning `None` if a value doesn't fit its
+/// field, eg: a fractional EMA period
+fn configure(base: &StrategyConfig, parameters: &BTreeMap<String, f64>) -> Option<StrategyConfig> {
+    let mut fields = strategy_fields(base);
+    for (name, value) in parameters {
+        // whole numbers are written as integers, so they can set integer fields such as periods
+        let number = if value.fract() == 0.0 && *value >= 0.0 && *value < u64::MAX as f64 {
+            Number::from(*value as u64)
+        } else {
+            Number::from_f64(*value)?
+        };
+        fields.insert(name.clone(), Value::Number(number));
+    }
+    serde_json::from_value(Value::Object(fields)).ok()
+}
+
+/// lookup returns the values each index picks out of `parameters`
+fn lookup(
+    parameters: &[(&String, Vec<f64>)],
+    indices: impl Iterator<Item = Vec<usize>>,
+) -> Vec<BTreeMap<String, f64>> {
+    indices
+        .map(|index| {
+            parameters
+                .iter()
+                .zip(index)
+                .map(|((name, values), i)| (name.to_string(), values[i]))
+                .collect()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use strategy::EmaCrossoverConfig;
+    use ta::Candle;
+
+    fn optimizer(search: Search) -> Optimizer {
+        let mut parameters = BTreeMap::new();
+        parameters.insert("fast".to_string(), Values::List(vec![2.0, 5.0, 20.0]));
+        parameters.insert(
+            "slow".to_string(),
+            Values::Range {
+                start: 10.0,
+                end: 20.0,
+                step: 5.0,
+            },
+        );
+
+        Optimizer {
+            backtest: Backtest::new(1000.0),
+            strategy: StrategyConfig::EmaCrossover(EmaCrossoverConfig {
+                stop_loss: None,
+                ..Default::default()
+            }),
+            parameters,
+            search,
+            metric: Metric::TotalReturn,
+        }
+    }
+
+    fn series() -> Series {
+        let candles = (0..400)
+            .map(|i| {
+                let close = 100.0 + 20.0 * (i as f64 / 12.0).sin() + i as f64 * 0.05;
+                Candle::new(i, close, close + 1.0, close - 1.0, close, 1000.0)
+            })
+            .collect();
+        Series::new("MSFT", candles)
+    }
+
+    #[test]
+    fn values_test() {
+        assert_eq!(
+            Values::Range {
+                start: 0.1,
+                end: 0.3,
+                step: 0.1
+            }
+            .values()
+            .unwrap()
+            .len(),
+            3
+        );
+        assert!(Values::List(Vec::new()).values().is_err());
+        assert!(Values::Range {
+            start: 1.0,
+            end: 2.0,
+            step: 0.0
+        }
+        .values()
+        .is_err());
+        assert!(Values::Range {
+            start: 0.0,
+            end: 1e9,
+            step: 1.0
+        }
+        .values()
+        .is_err());
+
+        let range: Values = serde_json::from_str(r#"{"start": 5, "end": 15, "step": 5}"#).unwrap();
+        assert_eq!(range.values().unwrap(), vec![5.0, 10.0, 15.0]);
+        let values: Values = serde_json::from_str("[1, 2]").unwrap();
+        assert_eq!(values, Values::List(vec![1.0, 2.0]));
+    }
+
+    #[test]
+    fn candidates_test() {
+        let grid = optimizer(Search::Grid).candidates().unwrap();
+        assert_eq!(grid.len(), 9);
+        assert_eq!(grid[0]["fast"], 2.0);
+        assert_eq!(grid[0]["slow"], 10.0);
+        assert_eq!(grid[1]["slow"], 15.0);
+
+        let random = optimizer(Search::Random {
+            samples: 4,
+            seed: 7,
+        });
+        let sampled = random.candidates().unwrap();
+        assert_eq!(sampled.len(), 4);
+        assert_eq!(
+            sampled,
+            random.candidates().unwrap(),
+            "the same seed repeats"
+        );
+        assert!(sampled.iter().all(|c| grid.contains(c)));
+
+        let all = optimizer(Search::Random {
+            samples: 100,
+            seed: 7,
+        });
+        let mut every = all.candidates().unwrap();
+        assert_eq!(every.len(), 9);
+        every.sort_by(|a, b| {
+            a["fast"]
+                .total_cmp(&b["fast"])
+                .then(a["slow"].total_cmp(&b["slow"]))
+        });
+        assert_eq!(every, grid);
+
+        let too_many = optimizer(Search::Random {
+            samples: MAX_VALUES * 10 + 1,
+            seed: 7,
+        });
+        assert!(too_many.candidates().is_err());
+
+        let mut unknown = optimizer(Search::Grid);
+        unknown
+            .parameters
+            .insert("period".to_string(), Values::List(vec![1.0]));
+        assert!(matches!(
+            unknown.candidates(),
+            Err(BacktestError::UnknownParameter(name)) if name == "period"
+        ));
+    }
+
+    #[test]
+    fn optimize_test() {
+        let optimizer = optimizer(Search::Grid);
+        let trials = optimizer.optimize(&series(), &Spreads::new()).unwrap();
+
+        // fast periods of 20 aren't faster than slow periods of 10, 15 or 20
+        assert_eq!(trials.len(), 6);
+        assert!(trials.windows(2).all(|pair| pair[0].score >= pair[1].score));
+        assert_eq!(trials[0].score, trials[0].summary.total_return);
+        assert!(trials
+            .iter()
+            .all(|t| t.parameters["fast"] < t.parameters["slow"]));
+    }
+
+    #[test]
+    fn walk_forward_test() {
+        let optimizer = optimizer(Search::Grid);
+        let series = series();
+        let folds = optimizer
+            .walk_forward(
+                &series,
+                &Spreads::new(),
+                WalkForward {
+                    in_sample: 200,
+                    out_of_sample: 75,
+                },
+            )
+            .unwrap();
+
+        assert_eq!(folds.len(), 3);
+        assert_eq!(
+            (folds[0].start, folds[0].split, folds[0].end),
+            (0, 200, 274)
+        );
+        assert_eq!(
+            (folds[2].start, folds[2].split, folds[2].end),
+            (150, 350, 399)
+        );
+        assert!(folds.iter().all(|f| f.out_of_sample.capital == 1000.0));
+
+        let compounded = folds
+            .iter()
+            .fold(1.0, |total, f| total * (1.0 + f.out_of_sample.total_return));
+        assert_eq!(out_of_sample_return(&folds), compounded - 1.0);
+
+        assert!(optimizer
+            .walk_forward(
+                &series,
+                &Spreads::new(),
+                WalkForward {
+                    in_sample: 0,
+                    out_of_sample: 10
+                }
+            )
+            .is_err());
+    }
+
+    #[test]
+    fn write_test() {
+        let optimizer = optimizer(Search::Grid);
+        let series = series();
+        let trials = optimizer.optimize(&series, &Spreads::new()).unwrap();
+        let optimization = Optimization::new("BTC/USD", &optimizer, trials, Vec::new());
+        assert_eq!(optimization.name(), "BTC_USD-ema_crossover-optimize");
+
+        let csv = optimization.trials_csv();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 7);
+        assert!(lines[0].starts_with("fast,slow,score,total_return,"));
+
+        let dir = std::env::temp_dir().join("mate-backtest-optimize");
+        let paths = optimization.write(&dir).unwrap();
+        assert_eq!(paths, vec![dir.join("BTC_USD-ema_crossover-optimize.csv")]);
+        assert_eq!(fs::read_to_string(&paths[0]).unwrap(), csv);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
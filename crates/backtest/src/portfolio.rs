@@ -0,0 +1,542 @@
+use crate::data::Spreads;
+use crate::execution::{Execution, Liquidity, Order};
+use crate::simulator::Book;
+use crate::{Backtest, BacktestError, BacktestResult, EquityPoint};
+use log::debug;
+use std::collections::{BTreeMap, HashMap};
+use strategy::{Engine, Market, Portfolio, RiskLimits, Signal, Size, Strategy};
+use ta::resample::Timeframe;
+use ta::Series;
+
+/// Timeline lines the candles of many series up on one clock, so markets with different
+/// calendars, such as stocks that trade on weekdays and crypto pairs that trade around the clock,
+/// can be replayed together. Each candle is placed in the `timeframe` period it starts in.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Timeline {
+    /// the start of every period any series has a candle in, oldest first
+    pub datetimes: Vec<usize>,
+    /// for each series, the index of its candle in each period, or `None` if its market was closed
+    pub candles: Vec<Vec<Option<usize>>>,
+}
+
+impl Timeline {
+    /// new aligns `series`, which should already be candles of `timeframe`, as `data::load`
+    /// returns them. If a series has more than one candle in a period, the last is used.
+    pub fn new(series: &[Series], timeframe: Timeframe) -> Timeline {
+        let periods: Vec<BTreeMap<usize, usize>> = series
+            .iter()
+            .map(|series| {
+                series
+                    .iter()
+                    .enumerate()
+                    .map(|(i, candle)| (timeframe.start(candle.datetime), i))
+                    .collect()
+            })
+            .collect();
+
+        let mut datetimes: Vec<usize> = periods.iter().flat_map(|p| p.keys().copied()).collect();
+        datetimes.sort_unstable();
+        datetimes.dedup();
+
+        let candles = periods
+            .iter()
+            .map(|periods| {
+                datetimes
+                    .iter()
+                    .map(|datetime| periods.get(datetime).copied())
+                    .collect()
+            })
+            .collect();
+
+        Timeline { datetimes, candles }
+    }
+}
+
+/// PortfolioBacktest replays a basket of symbols sharing one pool of cash. The strategies loaded
+/// for each symbol trade the pool as they signal, and at the start of every `rebalance` period
+/// the basket is traded back to its target weights on top of whatever they hold.
+///
+/// Strategy signals are limited by `risk` and placed as orders at the close they're given on, as
+/// `Backtest` places them, and are filled during the symbol's next candle, which for a stock
+/// decided on a Saturday is the following Monday. Rebalancing targets are set at the same closes,
+/// and traded at market during that next candle, once the strategies' orders have filled. Sales
+/// are filled before purchases, which are cut back to the cash left. A symbol whose market is
+/// closed is valued at its last close. Positions still open after the last candle are closed at
+/// their last close.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PortfolioBacktest {
+    /// the cash the portfolio starts with
+    pub capital: f64,
+    pub risk: RiskLimits,
+    pub execution: Execution,
+    /// the fraction of equity to hold in each symbol, with the rest kept as cash
+    pub weights: BTreeMap<String, f64>,
+    /// the period of each candle, which every series is lined up on
+    pub timeframe: Timeframe,
+    /// how often to trade back to the weights, eg: `Timeframe::Months(1)`
+    pub rebalance: Timeframe,
+    /// how far a symbol's weight may drift from its target before it's traded back, eg: 0.02 for
+    /// two percentage points
+    pub threshold: f64,
+}
+
+impl PortfolioBacktest {
+    /// check returns an error for weights that can't be held, such as those adding up to more
+    /// than the whole portfolio
+    pub fn check(&self) -> Result<(), BacktestError> {
+        if !self.capital.is_finite() || self.capital <= 0.0 {
+            return Err(BacktestError::InvalidParameter { name: "capital" });
+        }
+        if self.weights.values().any(|w| !w.is_finite() || *w < 0.0)
+            || self.weights.values().sum::<f64>() > 1.0 + 1e-9
+        {
+            return Err(BacktestError::InvalidParameter { name: "weights" });
+        }
+        if !self.threshold.is_finite() || self.threshold < 0.0 {
+            return Err(BacktestError::InvalidParameter { name: "threshold" });
+        }
+        self.execution.check()
+    }
+
+    /// run replays `series`, which must include every weighted symbol, through the strategies
+    /// loaded in `engine` and the rebalancing, with the bid-ask spreads quoted for each symbol,
+    /// see `data::load_spreads`. The engine is reset first.
+    pub fn run(
+        &self,
+        series: &[Series],
+        spreads: &HashMap<String, Spreads>,
+        engine: &mut Engine,
+    ) -> Result<BacktestResult, BacktestError> {
+        self.check()?;
+        let index: HashMap<&str, usize> = series
+            .iter()
+            .enumerate()
+            .map(|(i, series)| (series.symbol.as_str(), i))
+            .collect();
+        let weights = self
+            .weights
+            .iter()
+            .map(|(symbol, weight)| match index.get(symbol.as_str()) {
+                Some(i) => Ok((*i, *weight)),
+                None => Err(BacktestError::NoData(symbol.clone())),
+            })
+            .collect::<Result<Vec<(usize, f64)>, BacktestError>>()?;
+
+        engine.reset();
+        let timeline = Timeline::new(series, self.timeframe);
+        let no_spreads = Spreads::new();
+        let mut book = Book::new(self.capital);
+        let mut replayed: Vec<Series> = series
+            .iter()
+            .map(|series| Series::new(&series.symbol, Vec::new()))
+            .collect();
+        let mut closes: Vec<Option<f64>> = vec![None; series.len()];
+        let mut signals: Vec<Vec<(Signal, Order)>> = vec![Vec::new(); series.len()];
+        let mut targets: Vec<Option<f64>> = vec![None; series.len()];
+        let mut period = None;
+        let mut peak = self.capital;
+        let mut equity = Vec::with_capacity(timeline.datetimes.len());
+
+        for (step, datetime) in timeline.datetimes.iter().enumerate() {
+            let candle_at = |i: usize| {
+                timeline.candles[i][step]
+                    .map(|c| &series[i][c])
+                    .filter(|candle| candle.is_finite())
+            };
+            let spread_at = |symbol: &str, datetime: usize| {
+                spreads
+                    .get(symbol)
+                    .unwrap_or(&no_spreads)
+                    .get(&datetime)
+                    .copied()
+            };
+
+            for (i, pending) in signals.iter_mut().enumerate() {
+                let candle = match candle_at(i) {
+                    Some(candle) => candle,
+                    None => continue,
+                };
+                let symbol = series[i].symbol.as_str();
+                for (signal, order) in pending.drain(..) {
+                    let quantity = book.planned(symbol, &signal, candle.open);
+                    let spread = spread_at(symbol, candle.datetime);
+                    match self.execution.execute(&order, quantity, candle, spread) {
+                        Some((price, liquidity)) => {
+                            let fee = |quantity: f64, volume: f64| {
+                                self.execution
+                                    .commission
+                                    .fee(quantity, price, liquidity, volume)
+                            };
+                            book.fill(symbol, signal, price, candle.datetime, fee);
+                        }
+                        None => debug!(
+                            "{}: {:?} order to {:?} cancelled unfilled at {}",
+                            symbol, order, signal, candle.datetime
+                        ),
+                    }
+                }
+            }
+
+            // rebalancing trades from what the strategies hold now, sales first, so their proceeds
+            // can pay for purchases
+            let mut orders: Vec<(usize, f64)> = (0..series.len())
+                .filter(|i| candle_at(*i).is_some())
+                .filter_map(|i| {
+                    let target = targets[i].take()?;
+                    let held = book
+                        .portfolio
+                        .position(&series[i].symbol)
+                        .map_or(0.0, |p| p.quantity);
+                    Some((i, target - held)).filter(|(_, quantity)| *quantity != 0.0)
+                })
+                .collect();
+            orders.sort_by(|a, b| a.1.total_cmp(&b.1));
+            for (i, quantity) in orders {
+                let (symbol, candle) = (series[i].symbol.as_str(), candle_at(i).unwrap());
+                let spread = spread_at(symbol, candle.datetime);
+                let (price, liquidity) =
+                    match self
+                        .execution
+                        .execute(&Order::Market, quantity, candle, spread)
+                    {
+                        Some(filled) => filled,
+                        None => continue,
+                    };
+                let fee = |quantity: f64, volume: f64| {
+                    self.execution
+                        .commission
+                        .fee(quantity, price, liquidity, volume)
+                };
+
+                // purchases are cut back to the cash left, see `Book::affordable`
+                book.trade(symbol, quantity, price, candle.datetime, fee);
+            }
+
+            for (i, close) in closes.iter_mut().enumerate() {
+                if let Some(candle) = candle_at(i) {
+                    *close = Some(candle.close);
+                    replayed[i].push(*candle);
+                }
+            }
+            let value = book
+                .portfolio
+                .equity(|symbol| index.get(symbol).and_then(|i| closes[*i]));
+            peak = peak.max(value);
+            equity.push(EquityPoint {
+                datetime: *datetime,
+                equity: value,
+            });
+
+            for (i, replayed) in replayed.iter().enumerate() {
+                let candle = match candle_at(i) {
+                    Some(candle) => candle,
+                    None => continue,
+                };
+                for decision in engine.update(replayed, &book.portfolio) {
+                    let signal = self.risk.limit(
+                        &book.portfolio,
+                        &replayed.symbol,
+                        decision.signal,
+                        candle.close,
+                        value,
+                        peak,
+                    );
+                    let order = match signal {
+                        Signal::EnterLong(_) => self.execution.orders.order(true, candle.close),
+                        Signal::EnterShort(_) => self.execution.orders.order(false, candle.close),
+                        Signal::Exit => Order::Market,
+                        Signal::Hold => continue,
+                    };
+                    signals[i].push((signal, order));
+                }
+            }
+
+            // the first rebalance waits for every weighted symbol to have a price
+            let start = self.rebalance.start(*datetime);
+            if period == Some(start)
+                || value <= 0.0
+                || weights.iter().any(|(i, _)| closes[*i].is_none())
+            {
+                continue;
+            }
+            period = Some(start);
+            for (i, weight) in &weights {
+                let close = closes[*i].unwrap_or_default();
+                let held = book
+                    .portfolio
+                    .position(&series[*i].symbol)
+                    .map_or(0.0, |p| p.quantity);
+                if (held * close / value - weight).abs() > self.threshold && close > 0.0 {
+                    targets[*i] = Some(weight * value / close);
+                }
+            }
+        }
+
+        if let Some(last) = equity.pop() {
+            for (i, close) in closes.iter().enumerate() {
+                let symbol = series[i].symbol.as_str();
+                if let (Some(close), Some(position)) = (close, book.portfolio.position(symbol)) {
+                    let quantity = -position.quantity;
+                    let fee = |quantity: f64, volume: f64| {
+                        self.execution
+                            .commission
+                            .fee(quantity, *close, Liquidity::Taker, volume)
+                    };
+                    book.trade(symbol, quantity, *close, last.datetime, fee);
+                }
+            }
+            equity.push(EquityPoint {
+                datetime: last.datetime,
+                equity: book.portfolio.equity(|_| None),
+            });
+        }
+
+        Ok(BacktestResult {
+            symbol: "portfolio".to_string(),
+            strategy: "rebalance".to_string(),
+            capital: self.capital,
+            fills: book.fills,
+            trades: book.trades,
+            equity,
+        })
+    }
+
+    /// benchmark buys `series` with all of the capital and holds it over the same periods as
+    /// `result`, filling and charging for the purchase like any other order
+    pub fn benchmark(
+        &self,
+        result: &BacktestResult,
+        series: &Series,
+        spreads: &Spreads,
+    ) -> BacktestResult {
+        let (first, last) = match (result.equity.first(), result.equity.last()) {
+            (Some(first), Some(last)) => (first.datetime, last.datetime),
+            _ => (0, 0),
+        };
+        let held = Series {
+            symbol: series.symbol.clone(),
+            candles: series
+                .iter()
+                .filter(|c| (first..=last).contains(&self.timeframe.start(c.datetime)))
+                .copied()
+                .collect(),
+        };
+
+        let backtest = Backtest {
+            capital: self.capital,
+            execution: self.execution.clone(),
+            ..Default::default()
+        };
+        backtest.run_quoted(&held, spreads, &mut BuyAndHold)
+    }
+}
+
+/// BuyAndHold buys with all of its cash on the first candle and never sells
+struct BuyAndHold;
+
+impl Strategy for BuyAndHold {
+    fn name(&self) -> &str {
+        "buy_and_hold"
+    }
+
+    fn next(&mut self, market: &Market, portfolio: &Portfolio) -> Signal {
+        match portfolio.position(market.symbol) {
+            Some(_) => Signal::Hold,
+            None => Signal::EnterLong(Size::Fraction(1.0)),
+        }
+    }
+
+    fn reset(&mut self) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Commission;
+    use ta::volume::DAY_MILLIS;
+    use ta::Candle;
+
+    /// series has a candle with the same open and close each day in `days`
+    fn series(symbol: &str, days: &[usize], prices: &[f64]) -> Series {
+        let candles = days
+            .iter()
+            .zip(prices)
+            .map(|(day, price)| Candle::new(day * DAY_MILLIS, *price, *price, *price, *price, 1e6))
+            .collect();
+        Series::new(symbol, candles)
+    }
+
+    fn backtest(weights: &[(&str, f64)]) -> PortfolioBacktest {
+        PortfolioBacktest {
+            capital: 1000.0,
+            risk: RiskLimits::default(),
+            execution: Execution::default(),
+            weights: weights
+                .iter()
+                .map(|(symbol, weight)| (symbol.to_string(), *weight))
+                .collect(),
+            timeframe: Timeframe::Days(1),
+            rebalance: Timeframe::Weeks(1),
+            threshold: 0.0,
+        }
+    }
+
+    #[test]
+    fn timeline_test() {
+        // a stock closed over the weekend, and a pair trading through it, an hour after midnight
+        let stock = series("MSFT", &[0, 1, 4], &[1.0, 1.0, 1.0]);
+        let mut pair = series("XXBTZUSD", &[0, 1, 2, 3, 4], &[1.0; 5]);
+        for candle in pair.candles.iter_mut() {
+            candle.datetime += DAY_MILLIS / 24;
+        }
+
+        let timeline = Timeline::new(&[stock, pair], Timeframe::Days(1));
+        assert_eq!(
+            timeline.datetimes,
+            (0..5).map(|day| day * DAY_MILLIS).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            timeline.candles[0],
+            vec![Some(0), Some(1), None, None, Some(2)]
+        );
+        assert_eq!(timeline.candles[1], (0..5).map(Some).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn rebalance_test() {
+        // the first week of 1970 started on Thursday the 1st, so the 4th is the start of the next
+        let stock = series("MSFT", &[0, 1, 4, 5], &[10.0, 10.0, 10.0, 10.0]);
+        let pair = series(
+            "XXBTZUSD",
+            &[0, 1, 2, 3, 4, 5],
+            &[100.0, 100.0, 200.0, 200.0, 200.0, 200.0],
+        );
+        let result = backtest(&[("MSFT", 0.5), ("XXBTZUSD", 0.5)])
+            .run(&[stock, pair], &HashMap::new(), &mut Engine::new())
+            .unwrap();
+
+        // both are bought on the 2nd day, smallest order first, the pair doubles, then half its gain is sold into the
+        // stock at the start of the next week
+        assert_eq!(
+            result
+                .fills
+                .iter()
+                .map(|f| (f.symbol.as_str(), f.datetime / DAY_MILLIS, f.quantity))
+                .collect::<Vec<_>>(),
+            vec![
+                ("XXBTZUSD", 1, 5.0),
+                ("MSFT", 1, 50.0),
+                ("XXBTZUSD", 5, -1.25),
+                ("MSFT", 5, 25.0),
+                ("MSFT", 5, -75.0),
+                ("XXBTZUSD", 5, -3.75),
+            ]
+        );
+        assert_eq!(
+            result.equity.iter().map(|p| p.equity).collect::<Vec<_>>(),
+            vec![1000.0, 1000.0, 1500.0, 1500.0, 1500.0, 1500.0]
+        );
+        assert_eq!(result.trades.len(), 3);
+        assert_eq!(result.trades[0].symbol, "XXBTZUSD");
+        assert_eq!(result.trades[0].pnl, 125.0);
+    }
+
+    /// Scripted gives its signals in order, then holds
+    struct Scripted(Vec<Signal>);
+
+    impl Strategy for Scripted {
+        fn name(&self) -> &str {
+            "scripted"
+        }
+
+        fn next(&mut self, _: &Market, _: &Portfolio) -> Signal {
+            match self.0.is_empty() {
+                true => Signal::Hold,
+                false => self.0.remove(0),
+            }
+        }
+
+        fn reset(&mut self) {}
+    }
+
+    #[test]
+    fn strategies_test() {
+        // a strategy trades the stock out of the shared cash, and the pair is rebalanced alongside it
+        let stock = series("MSFT", &[0, 1, 2, 3, 4, 5], &[10.0; 6]);
+        let pair = series("XXBTZUSD", &[0, 1, 2, 3, 4, 5], &[100.0; 6]);
+        let mut engine = Engine::new();
+        let signals = vec![
+            Signal::EnterLong(Size::Fraction(0.5)),
+            Signal::Hold,
+            Signal::Exit,
+        ];
+        engine.add("MSFT", Box::new(Scripted(signals)));
+        let result = backtest(&[("XXBTZUSD", 0.5)])
+            .run(&[stock, pair], &HashMap::new(), &mut engine)
+            .unwrap();
+
+        // the stock's purchase fills first, leaving half the cash for the pair's weight
+        assert_eq!(
+            result
+                .fills
+                .iter()
+                .map(|f| (f.symbol.as_str(), f.datetime / DAY_MILLIS, f.quantity))
+                .collect::<Vec<_>>(),
+            vec![
+                ("MSFT", 1, 50.0),
+                ("XXBTZUSD", 1, 5.0),
+                ("MSFT", 3, -50.0),
+                ("XXBTZUSD", 5, -5.0),
+            ]
+        );
+        assert!(result.equity.iter().all(|p| p.equity == 1000.0));
+        assert_eq!(result.trades.len(), 2);
+    }
+
+    #[test]
+    fn cash_test() {
+        // commission makes a full allocation cost more than the cash, so it's cut back
+        let mut backtest = backtest(&[("MSFT", 1.0)]);
+        backtest.execution.commission = Commission::Percentage(0.01);
+        let stock = series("MSFT", &[0, 1, 2], &[10.0, 10.0, 10.0]);
+        let result = backtest
+            .run(&[stock], &HashMap::new(), &mut Engine::new())
+            .unwrap();
+
+        assert!((result.fills[0].quantity - 1000.0 / 10.1).abs() < 1e-9);
+        assert!(result.equity.iter().all(|p| p.equity > 0.0));
+        assert!(result.fills.iter().all(|f| f.commission > 0.0));
+
+        assert!(matches!(
+            backtest.run(&[], &HashMap::new(), &mut Engine::new()),
+            Err(BacktestError::NoData(symbol)) if symbol == "MSFT"
+        ));
+        assert!(self::backtest(&[("MSFT", 0.7), ("AAPL", 0.7)])
+            .check()
+            .is_err());
+    }
+
+    #[test]
+    fn benchmark_test() {
+        let stock = series("MSFT", &[0, 1, 2, 3], &[10.0, 10.0, 20.0, 20.0]);
+        let pair = series("XXBTZUSD", &[1, 2, 3, 4], &[1.0, 1.0, 1.0, 1.0]);
+        let backtest = backtest(&[("MSFT", 1.0)]);
+        let result = backtest
+            .run(
+                std::slice::from_ref(&stock),
+                &HashMap::new(),
+                &mut Engine::new(),
+            )
+            .unwrap();
+        assert_eq!(result.final_equity(), 2000.0);
+
+        let benchmark = backtest.benchmark(&result, &stock, &Spreads::new());
+        assert_eq!(benchmark.strategy, "buy_and_hold");
+        assert_eq!(benchmark.final_equity(), 2000.0);
+
+        // only the pair's candles within the portfolio's first and last day are held
+        let pair = backtest.benchmark(&result, &pair, &Spreads::new());
+        assert_eq!(pair.equity.len(), 3);
+        assert_eq!(pair.fills[0].datetime, 2 * DAY_MILLIS);
+    }
+}
@@ -0,0 +1,618 @@
+use crate::metrics::{drawdowns, Summary};
+use crate::monte_carlo::Distribution;
+use crate::{BacktestError, BacktestResult, EquityPoint, MonteCarloResult, Trade};
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt::Write,
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// report_dir is where reports are written for a collector `--filepath`, alongside its `equity`
+/// and `crypto` directories
+pub fn report_dir(filepath: &Path) -> PathBuf {
+    filepath.join("backtest")
+}
+
+/// file_name is the start of a report's file names, eg: `MSFT-ema_crossover`, with any
+/// characters that aren't safe in a path replaced
+pub(crate) fn file_name(symbol: &str, strategy: &str) -> String {
+    format!("{}-{}", symbol, strategy)
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// Report is a backtest's summary along with everything it did
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Report {
+    pub summary: Summary,
+    pub result: BacktestResult,
+    /// the Monte Carlo analysis of the result, if one was run
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub monte_carlo: Option<MonteCarloResult>,
+    /// how buying and holding a symbol did over the same period, if compared against
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub benchmark: Option<Benchmark>,
+}
+
+/// Benchmark is how buying and holding a single symbol did over the same period as a backtest
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Benchmark {
+    pub symbol: String,
+    pub summary: Summary,
+    pub equity: Vec<EquityPoint>,
+}
+
+/// MonteCarloRow is a row of the Monte Carlo CSV, describing the distribution of one measure
+#[derive(Serialize)]
+struct MonteCarloRow {
+    measure: &'static str,
+    mean: f64,
+    median: f64,
+    lower: f64,
+    upper: f64,
+    min: f64,
+    max: f64,
+}
+
+/// EquityRow is a row of the equity curve CSV
+#[derive(Serialize)]
+struct EquityRow {
+    datetime: usize,
+    equity: f64,
+    drawdown: f64,
+}
+
+impl Report {
+    pub fn new(result: BacktestResult) -> Report {
+        Report {
+            summary: Summary::new(&result),
+            result,
+            monte_carlo: None,
+            benchmark: None,
+        }
+    }
+
+    /// with_benchmark compares the backtest against `benchmark`, eg: from
+    /// `PortfolioBacktest::benchmark`
+    pub fn with_benchmark(mut self, benchmark: BacktestResult) -> Report {
+        self.benchmark = Some(Benchmark {
+            symbol: benchmark.symbol.clone(),
+            summary: Summary::new(&benchmark),
+            equity: benchmark.equity,
+        });
+        self
+    }
+
+    /// name identifies the report's files, eg: `MSFT-ema_crossover`
+    pub fn name(&self) -> String {
+        file_name(&self.result.symbol, &self.result.strategy)
+    }
+
+    pub fn to_json(&self) -> String {
+        // a report is plain data, with non-finite numbers serialized as null
+        serde_json::to_string_pretty(self).unwrap_or_default()
+    }
+
+    /// summary_csv is the summary as a header and a single row
+    pub fn summary_csv(&self) -> String {
+        csv(std::iter::once(&self.summary))
+    }
+
+    /// trades_csv is every completed trade, one per row
+    pub fn trades_csv(&self) -> String {
+        csv(self.result.trades.iter())
+    }
+
+    /// equity_csv is the equity curve, with the drawdown from its peak at each candle
+    pub fn equity_csv(&self) -> String {
+        let drawdowns = drawdowns(&self.result.equity);
+        csv(self
+            .result
+            .equity
+            .iter()
+            .zip(drawdowns)
+            .map(|(point, drawdown)| EquityRow {
+                datetime: point.datetime,
+                equity: point.equity,
+                drawdown,
+            }))
+    }
+
+    /// monte_carlo_csv has a row for each measure the Monte Carlo analysis describes, with risk of
+    /// ruin as a distribution over its confidence interval
+    pub fn monte_carlo_csv(&self) -> Option<String> {
+        let analysis = self.monte_carlo.as_ref()?;
+        let row = |measure, d: &Distribution| MonteCarloRow {
+            measure,
+            mean: d.mean,
+            median: d.median,
+            lower: d.lower,
+            upper: d.upper,
+            min: d.min,
+            max: d.max,
+        };
+        let ruin = analysis.risk_of_ruin;
+
+        Some(csv(vec![
+            row("final_equity", &analysis.final_equity),
+            row("max_drawdown", &analysis.max_drawdown),
+            MonteCarloRow {
+                measure: "risk_of_ruin",
+                mean: ruin.estimate,
+                median: ruin.estimate,
+                lower: ruin.lower,
+                upper: ruin.upper,
+                min: ruin.lower,
+                max: ruin.upper,
+            },
+        ]
+        .into_iter()))
+    }
+
+    /// to_html renders the report as a single page, with the summary, equity and drawdown charts,
+    /// and the trades. Charts are inline SVG, so the page needs nothing else to be viewed.
+    pub fn to_html(&self) -> String {
+        let mut html = String::new();
+        let title = format!(
+            "{} {} backtest",
+            escape(&self.result.symbol),
+            escape(&self.result.strategy)
+        );
+        let _ = write!(
+            html,
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>{}</style>\n</head>\n<body>\n<h1>{}</h1>\n",
+            title, STYLE, title
+        );
+        if let (Some(first), Some(last)) = (self.result.equity.first(), self.result.equity.last()) {
+            let _ = writeln!(
+                html,
+                "<p>{} to {}</p>",
+                date(first.datetime),
+                date(last.datetime)
+            );
+        }
+
+        html.push_str("<table class=\"summary\">\n");
+        let rows = summary_rows(&self.summary);
+        match &self.benchmark {
+            Some(benchmark) => {
+                let _ = writeln!(
+                    html,
+                    "<tr><th></th><th>{}</th><th>Buy and hold {}</th></tr>",
+                    escape(&self.result.strategy),
+                    escape(&benchmark.symbol)
+                );
+                for ((name, value), (_, held)) in rows.iter().zip(summary_rows(&benchmark.summary))
+                {
+                    let _ = writeln!(
+                        html,
+                        "<tr><th>{}</th><td>{}</td><td>{}</td></tr>",
+                        name, value, held
+                    );
+                }
+            }
+            None => {
+                for (name, value) in rows.iter() {
+                    let _ = writeln!(html, "<tr><th>{}</th><td>{}</td></tr>", name, value);
+                }
+            }
+        }
+        html.push_str("</table>\n");
+
+        let equity: Vec<(usize, f64)> = self
+            .result
+            .equity
+            .iter()
+            .map(|p| (p.datetime, p.equity))
+            .collect();
+        let drawdown: Vec<(usize, f64)> = self
+            .result
+            .equity
+            .iter()
+            .zip(drawdowns(&self.result.equity))
+            .map(|(p, d)| (p.datetime, -d * 100.0))
+            .collect();
+        let mut lines = vec![(equity, "#2a6fdb")];
+        if let Some(benchmark) = &self.benchmark {
+            html.push_str("<h2>Equity, against buy and hold in grey</h2>\n");
+            let held = benchmark.equity.iter().map(|p| (p.datetime, p.equity));
+            lines.push((held.collect(), "#999999"));
+        } else {
+            html.push_str("<h2>Equity</h2>\n");
+        }
+        html.push_str(&chart(&lines));
+        html.push_str("<h2>Drawdown (%)</h2>\n");
+        html.push_str(&chart(&[(drawdown, "#d9534f")]));
+
+        if let Some(analysis) = &self.monte_carlo {
+            html.push_str(&monte_carlo_table(analysis));
+        }
+
+        html.push_str("<h2>Trades</h2>\n<table class=\"trades\">\n");
+        html.push_str("<tr><th>Side</th><th>Quantity</th><th>Entry</th><th>Entry price</th><th>Exit</th><th>Exit price</th><th>Fees</th><th>P&amp;L</th><th>Return</th></tr>\n");
+        for trade in &self.result.trades {
+            html.push_str(&trade_row(trade));
+        }
+        html.push_str("</table>\n</body>\n</html>\n");
+
+        html
+    }
+
+    /// write saves the report as JSON, CSV and HTML files in `dir`, creating it if needed, and
+    /// returns the paths written
+    pub fn write(&self, dir: &Path) -> Result<Vec<PathBuf>, BacktestError> {
+        fs::create_dir_all(dir).map_err(|error| BacktestError::Io {
+            path: dir.to_path_buf(),
+            error,
+        })?;
+
+        let name = self.name();
+        let mut files = vec![
+            (format!("{}.json", name), self.to_json()),
+            (format!("{}-summary.csv", name), self.summary_csv()),
+            (format!("{}-trades.csv", name), self.trades_csv()),
+            (format!("{}-equity.csv", name), self.equity_csv()),
+            (format!("{}.html", name), self.to_html()),
+        ];
+        if let Some(monte_carlo) = self.monte_carlo_csv() {
+            files.push((format!("{}-monte-carlo.csv", name), monte_carlo));
+        }
+        if let Some(benchmark) = &self.benchmark {
+            let summary = csv(std::iter::once(&benchmark.summary));
+            files.push((format!("{}-benchmark.csv", name), summary));
+        }
+
+        let mut paths = Vec::with_capacity(files.len());
+        for (file, contents) in files.iter() {
+            let path = dir.join(file);
+            fs::write(&path, contents).map_err(|error| BacktestError::Io {
+                path: path.clone(),
+                error,
+            })?;
+            paths.push(path);
+        }
+
+        Ok(paths)
+    }
+}
+
+const STYLE: &str = "body{font-family:sans-serif;margin:2em;color:#222}table{border-collapse:collapse}th,td{padding:4px 10px;border-bottom:1px solid #ddd;text-align:right}.summary th{text-align:left}svg{border:1px solid #ddd}.win{color:#2e7d32}.loss{color:#c62828}";
+
+const CHART_WIDTH: f64 = 900.0;
+const CHART_HEIGHT: f64 = 240.0;
+const CHART_MARGIN: f64 = 60.0;
+
+/// chart draws `points` as a line, with the range of each axis labelled
+fn chart(lines: &[(Vec<(usize, f64)>, &str)]) -> String {
+    let points = || lines.iter().flat_map(|(points, _)| points.iter());
+    let (first, last) = match (points().map(|p| p.0).min(), points().map(|p| p.0).max()) {
+        (Some(first), Some(last)) => (first, last),
+        _ => return "<p>No data</p>\n".to_string(),
+    };
+    let low = points().map(|p| p.1).fold(f64::INFINITY, f64::min);
+    let high = points().map(|p| p.1).fold(f64::NEG_INFINITY, f64::max);
+    let span = (last - first).max(1) as f64;
+    let range = if high > low { high - low } else { 1.0 };
+
+    let x = |datetime: usize| CHART_MARGIN + (datetime - first) as f64 / span * CHART_WIDTH;
+    let y = |value: f64| 10.0 + (high - value) / range * CHART_HEIGHT;
+    let polylines: Vec<String> = lines
+        .iter()
+        .map(|(points, color)| {
+            let line: Vec<String> = points
+                .iter()
+                .map(|(datetime, value)| format!("{:.1},{:.1}", x(*datetime), y(*value)))
+                .collect();
+            format!(
+                "<polyline fill=\"none\" stroke=\"{}\" stroke-width=\"1.5\" points=\"{}\"/>\n",
+                color,
+                line.join(" ")
+            )
+        })
+        .collect();
+
+    let width = CHART_WIDTH + CHART_MARGIN + 10.0;
+    let height = CHART_HEIGHT + 40.0;
+    format!(
+        concat!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" font-size=\"11\">\n",
+            "<text x=\"4\" y=\"16\">{high:.2}</text>\n",
+            "<text x=\"4\" y=\"{bottom:.1}\">{low:.2}</text>\n",
+            "<text x=\"{m}\" y=\"{labels:.1}\">{start}</text>\n",
+            "<text x=\"{end:.1}\" y=\"{labels:.1}\" text-anchor=\"end\">{finish}</text>\n",
+            "{polylines}",
+            "</svg>\n"
+        ),
+        w = width,
+        h = height,
+        high = high,
+        low = low,
+        bottom = 10.0 + CHART_HEIGHT,
+        m = CHART_MARGIN,
+        labels = CHART_HEIGHT + 30.0,
+        start = date(first),
+        end = CHART_MARGIN + CHART_WIDTH,
+        finish = date(last),
+        polylines = polylines.concat(),
+    )
+}
+
+/// summary_rows labels and formats each figure in `summary` for the HTML report
+fn summary_rows(summary: &Summary) -> Vec<(&'static str, String)> {
+    let percent =
+        |value: Option<f64>| value.map_or("-".to_string(), |v| format!("{:.2}%", v * 100.0));
+    let number = |value: Option<f64>| value.map_or("-".to_string(), |v| format!("{:.2}", v));
+    vec![
+        ("Capital", format!("{:.2}", summary.capital)),
+        ("Final equity", format!("{:.2}", summary.final_equity)),
+        ("Total return", percent(Some(summary.total_return))),
+        ("Annualized return", percent(summary.annualized_return)),
+        ("Sharpe ratio", number(summary.sharpe)),
+        ("Sortino ratio", number(summary.sortino)),
+        ("Max drawdown", percent(Some(summary.max_drawdown))),
+        ("Win rate", percent(summary.win_rate)),
+        ("Profit factor", number(summary.profit_factor)),
+        ("Exposure", percent(Some(summary.exposure))),
+        ("Trades", summary.trades.to_string()),
+        (
+            "Average hold",
+            summary.average_hold.map_or("-".to_string(), duration),
+        ),
+        ("Fees", format!("{:.2}", summary.fees)),
+    ]
+}
+
+/// monte_carlo_table describes the spread of each measure across the simulations
+fn monte_carlo_table(analysis: &MonteCarloResult) -> String {
+    let money = |value: f64| format!("{:.2}", value);
+    let percent = |value: f64| format!("{:.2}%", value * 100.0);
+    let row = |name: &str, d: &Distribution, format: &dyn Fn(f64) -> String| {
+        format!(
+            "<tr><th>{}</th><td>{}</td><td>{}</td><td>{} to {}</td><td>{}</td><td>{}</td></tr>\n",
+            name,
+            format(d.mean),
+            format(d.median),
+            format(d.lower),
+            format(d.upper),
+            format(d.min),
+            format(d.max)
+        )
+    };
+    let ruin = analysis.risk_of_ruin;
+
+    let mut html = format!(
+        "<h2>Monte Carlo</h2>\n<p>{} simulations, {:?}, with {} confidence intervals</p>\n<table class=\"summary\">\n",
+        analysis.simulations,
+        analysis.method,
+        percent(analysis.confidence)
+    );
+    html.push_str(
+        "<tr><th></th><th>Mean</th><th>Median</th><th>Interval</th><th>Min</th><th>Max</th></tr>\n",
+    );
+    html.push_str(&row("Final equity", &analysis.final_equity, &money));
+    html.push_str(&row("Max drawdown", &analysis.max_drawdown, &percent));
+    let _ = writeln!(
+        html,
+        "<tr><th>Risk of ruin</th><td>{}</td><td></td><td>{} to {}</td><td></td><td></td></tr>",
+        percent(ruin.estimate),
+        percent(ruin.lower),
+        percent(ruin.upper)
+    );
+    html.push_str("</table>\n");
+    html
+}
+
+fn trade_row(trade: &Trade) -> String {
+    let class = if trade.pnl >= 0.0 { "win" } else { "loss" };
+    format!(
+        "<tr class=\"{}\"><td>{:?}</td><td>{}</td><td>{}</td><td>{:.2}</td><td>{}</td><td>{:.2}</td><td>{:.2}</td><td>{:.2}</td><td>{:.2}%</td></tr>\n",
+        class,
+        trade.side,
+        trade.quantity,
+        date(trade.entry_datetime),
+        trade.entry_price,
+        date(trade.exit_datetime),
+        trade.exit_price,
+        trade.fees,
+        trade.pnl,
+        trade.returns() * 100.0
+    )
+}
+
+/// date formats epoch milliseconds as a UTC date and time
+fn date(datetime: usize) -> String {
+    let seconds = (datetime / 1000) as i64;
+    let nanos = (datetime % 1000) as u32 * 1_000_000;
+    NaiveDateTime::from_timestamp_opt(seconds, nanos).map_or_else(
+        || datetime.to_string(),
+        |d| d.format("%Y-%m-%d %H:%M").to_string(),
+    )
+}
+
+/// duration formats milliseconds as days and hours, eg: 3d 4h
+fn duration(millis: usize) -> String {
+    let hours = millis / 3_600_000;
+    match (hours / 24, hours % 24) {
+        (0, hours) => format!("{}h", hours),
+        (days, hours) => format!("{}d {}h", days, hours),
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// csv serializes `rows` with a header taken from their fields
+pub(crate) fn csv<T: Serialize, I: Iterator<Item = T>>(rows: I) -> String {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    for row in rows {
+        // writing to memory only fails for values csv can't represent, which rows don't contain
+        let _ = writer.serialize(row);
+    }
+    writer
+        .into_inner()
+        .ok()
+        .and_then(|bytes| String::from_utf8(bytes).ok())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{EquityPoint, Fill, MonteCarlo};
+    use strategy::Side;
+    use ta::volume::DAY_MILLIS;
+
+    fn report() -> Report {
+        Report::new(BacktestResult {
+            symbol: "MSFT".to_string(),
+            strategy: "ema_crossover".to_string(),
+            capital: 1000.0,
+            fills: vec![Fill {
+                datetime: DAY_MILLIS,
+                symbol: "MSFT".to_string(),
+                quantity: 10.0,
+                price: 10.0,
+                commission: 1.0,
+            }],
+            trades: vec![Trade {
+                symbol: "MSFT".to_string(),
+                side: Side::Long,
+                quantity: 10.0,
+                entry_datetime: DAY_MILLIS,
+                entry_price: 10.0,
+                exit_datetime: 3 * DAY_MILLIS,
+                exit_price: 12.0,
+                fees: 1.0,
+                pnl: 19.0,
+            }],
+            equity: [1000.0, 999.0, 1009.0, 1019.0]
+                .iter()
+                .enumerate()
+                .map(|(i, equity)| EquityPoint {
+                    datetime: i * DAY_MILLIS,
+                    equity: *equity,
+                })
+                .collect(),
+        })
+    }
+
+    #[test]
+    fn csv_test() {
+        let report = report();
+
+        assert_eq!(
+            report.trades_csv(),
+            "symbol,side,quantity,entry_datetime,entry_price,exit_datetime,exit_price,fees,pnl\n\
+             MSFT,long,10.0,86400000,10.0,259200000,12.0,1.0,19.0\n"
+        );
+        assert_eq!(
+            report.equity_csv().lines().take(3).collect::<Vec<_>>(),
+            vec![
+                "datetime,equity,drawdown",
+                "0,1000.0,0.0",
+                "86400000,999.0,0.001"
+            ]
+        );
+
+        let summary = report.summary_csv();
+        let mut lines = summary.lines();
+        assert!(lines
+            .next()
+            .unwrap()
+            .starts_with("capital,final_equity,total_return"));
+        assert!(lines.next().unwrap().starts_with("1000.0,1019.0,"));
+    }
+
+    #[test]
+    fn json_test() {
+        let report = report();
+        let parsed: Report = serde_json::from_str(&report.to_json()).unwrap();
+
+        assert_eq!(parsed, report);
+        assert_eq!(parsed.summary.trades, 1);
+    }
+
+    #[test]
+    fn html_test() {
+        let html = report().to_html();
+
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("<title>MSFT ema_crossover backtest</title>"));
+        assert!(html.contains("1970-01-01 00:00 to 1970-01-04 00:00"));
+        assert_eq!(html.matches("<svg").count(), 2);
+        assert!(html.contains("<td>2d 0h</td>"));
+        assert!(html.contains("<td>19.00%</td>"), "trade return");
+        assert!(!html.contains("src=\""), "nothing is loaded from elsewhere");
+        assert!(!html.contains("Monte Carlo"));
+    }
+
+    #[test]
+    fn monte_carlo_test() {
+        let mut report = report();
+        assert_eq!(report.monte_carlo_csv(), None);
+
+        report.monte_carlo = Some(MonteCarlo::default().run(&report.result).unwrap());
+        let csv = report.monte_carlo_csv().unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], "measure,mean,median,lower,upper,min,max");
+        assert!(lines[1].starts_with("final_equity,1019.0,1019.0,"));
+        assert!(lines[3].starts_with("risk_of_ruin,0.0,0.0,0.0,"));
+
+        let html = report.to_html();
+        assert!(html.contains("<h2>Monte Carlo</h2>"));
+        assert!(html.contains("1000 simulations, Shuffle, with 95.00% confidence intervals"));
+
+        let parsed: Report = serde_json::from_str(&report.to_json()).unwrap();
+        assert_eq!(parsed, report);
+    }
+
+    #[test]
+    fn benchmark_test() {
+        let mut held = report().result;
+        held.symbol = "QQQ".to_string();
+        held.strategy = "buy_and_hold".to_string();
+        held.equity[3].equity = 1100.0;
+        let report = report().with_benchmark(held);
+
+        let benchmark = report.benchmark.as_ref().unwrap();
+        assert_eq!(benchmark.symbol, "QQQ");
+        assert_eq!(benchmark.summary.final_equity, 1100.0);
+
+        let html = report.to_html();
+        assert!(html.contains("<th>Buy and hold QQQ</th>"));
+        assert!(html.contains("<tr><th>Final equity</th><td>1019.00</td><td>1100.00</td></tr>"));
+        assert_eq!(html.matches("<polyline").count(), 3);
+
+        let parsed: Report = serde_json::from_str(&report.to_json()).unwrap();
+        assert_eq!(parsed, report);
+    }
+
+    #[test]
+    fn write_test() {
+        let dir = std::env::temp_dir().join("mate-backtest-report");
+        let _ = fs::remove_dir_all(&dir);
+
+        let mut report = report();
+        report.result.symbol = "BTC/USD".to_string();
+        let paths = report.write(&dir).unwrap();
+
+        assert_eq!(paths.len(), 5);
+        assert_eq!(paths[0], dir.join("BTC_USD-ema_crossover.json"));
+        assert!(paths.iter().all(|path| path.is_file()));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
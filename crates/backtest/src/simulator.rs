@@ -0,0 +1,682 @@
+use crate::data::Spreads;
+use crate::execution::{Execution, Liquidity, Order};
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use strategy::{Market, Portfolio, RiskLimits, Side, Signal, Size, Strategy};
+use ta::volume::DAY_MILLIS;
+use ta::Series;
+
+/// Fill is an order executed during a backtest. `quantity` is positive for a buy and negative for
+/// a sell.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Fill {
+    pub datetime: usize,
+    pub symbol: String,
+    pub quantity: f64,
+    pub price: f64,
+    pub commission: f64,
+}
+
+/// Trade is a position from when it was opened to when it was closed
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Trade {
+    pub symbol: String,
+    pub side: Side,
+    /// the quantity held when the position was closed, which is always positive
+    pub quantity: f64,
+    pub entry_datetime: usize,
+    /// the average price the position was entered at
+    pub entry_price: f64,
+    pub exit_datetime: usize,
+    pub exit_price: f64,
+    /// the commission paid entering and exiting the position
+    pub fees: f64,
+    /// the profit or loss, net of fees
+    pub pnl: f64,
+}
+
+impl Trade {
+    /// returns is the profit or loss as a fraction of the position's cost, eg: 0.05 for a 5% gain
+    pub fn returns(&self) -> f64 {
+        self.pnl / (self.entry_price * self.quantity)
+    }
+
+    /// duration is how long the position was held, in milliseconds
+    pub fn duration(&self) -> usize {
+        self.exit_datetime.saturating_sub(self.entry_datetime)
+    }
+}
+
+/// EquityPoint is the value of the backtest portfolio at the close of a candle
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub struct EquityPoint {
+    pub datetime: usize,
+    pub equity: f64,
+}
+
+/// BacktestResult is everything a backtest did: each fill, each completed trade, and the equity
+/// curve, with one point per candle
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct BacktestResult {
+    pub symbol: String,
+    pub strategy: String,
+    pub capital: f64,
+    pub fills: Vec<Fill>,
+    pub trades: Vec<Trade>,
+    pub equity: Vec<EquityPoint>,
+}
+
+impl BacktestResult {
+    /// final_equity is the equity at the last candle, or the starting capital if there were none
+    pub fn final_equity(&self) -> f64 {
+        self.equity
+            .last()
+            .map_or(self.capital, |point| point.equity)
+    }
+
+    /// total_return is the change in equity as a fraction of the starting capital
+    pub fn total_return(&self) -> f64 {
+        (self.final_equity() - self.capital) / self.capital
+    }
+}
+
+/// Backtest replays history through a strategy, bar by bar, as if it were trading live.
+///
+/// On each candle the strategy sees every candle up to and including that one, and any signal it
+/// gives is limited by `risk` and placed as an order, see `Execution`. Orders are filled during
+/// the next candle, since the close the signal was given on has already passed, and any left
+/// unfilled by its close are cancelled. Positions still open after the last candle are closed at
+/// its close, so every position shows up as a trade.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Backtest {
+    /// the cash the portfolio starts with
+    pub capital: f64,
+    pub risk: RiskLimits,
+    pub execution: Execution,
+}
+
+impl Backtest {
+    pub fn new(capital: f64) -> Backtest {
+        Backtest {
+            capital,
+            risk: RiskLimits::default(),
+            execution: Execution::default(),
+        }
+    }
+
+    /// run resets `strategy` and replays `series` through it
+    pub fn run(&self, series: &Series, strategy: &mut dyn Strategy) -> BacktestResult {
+        self.run_quoted(series, &Spreads::new(), strategy)
+    }
+
+    /// run_quoted is `run` with the bid-ask spread quoted during each candle, which
+    /// `Slippage::Spread` fills pay half of, see `data::load_spreads`
+    pub fn run_quoted(
+        &self,
+        series: &Series,
+        spreads: &Spreads,
+        strategy: &mut dyn Strategy,
+    ) -> BacktestResult {
+        self.run_warmed(series, spreads, strategy, 0)
+    }
+
+    /// run_warmed is `run_quoted`, except the first `warmup` candles only warm the strategy's
+    /// indicators up: its signals on them are ignored, and the equity curve starts after them.
+    /// This lets a strategy trade a period of history from its first candle, as it would live.
+    pub fn run_warmed(
+        &self,
+        series: &Series,
+        spreads: &Spreads,
+        strategy: &mut dyn Strategy,
+        warmup: usize,
+    ) -> BacktestResult {
+        strategy.reset();
+
+        let symbol = series.symbol.as_str();
+        let mut book = Book::new(self.capital);
+        let mut equity = Vec::with_capacity(series.len());
+        let mut peak = self.capital;
+        let mut pending: Option<(Signal, Order)> = None;
+
+        for (i, candle) in series.iter().enumerate() {
+            if !candle.is_finite() {
+                warn!(
+                    "{}: skipping candle at {} with a non-finite value",
+                    symbol, candle.datetime
+                );
+                continue;
+            }
+            if i < warmup {
+                strategy.next(&Market::new(symbol, &series[..=i]), &book.portfolio);
+                continue;
+            }
+
+            if let Some((signal, order)) = pending.take() {
+                let quantity = book.planned(symbol, &signal, candle.open);
+                let spread = spreads.get(&candle.datetime).copied();
+                match self.execution.execute(&order, quantity, candle, spread) {
+                    Some((price, liquidity)) => {
+                        let fee = |quantity: f64, volume: f64| {
+                            self.execution
+                                .commission
+                                .fee(quantity, price, liquidity, volume)
+                        };
+                        book.fill(symbol, signal, price, candle.datetime, fee);
+                    }
+                    None => debug!(
+                        "{}: {:?} order to {:?} cancelled unfilled at {}",
+                        symbol, order, signal, candle.datetime
+                    ),
+                }
+            }
+
+            let value = book.portfolio.equity(|_| Some(candle.close));
+            peak = peak.max(value);
+            equity.push(EquityPoint {
+                datetime: candle.datetime,
+                equity: value,
+            });
+
+            let signal = strategy.next(&Market::new(symbol, &series[..=i]), &book.portfolio);
+            if signal.is_hold() {
+                continue;
+            }
+            let signal =
+                self.risk
+                    .limit(&book.portfolio, symbol, signal, candle.close, value, peak);
+            let order = match signal {
+                Signal::EnterLong(_) => self.execution.orders.order(true, candle.close),
+                Signal::EnterShort(_) => self.execution.orders.order(false, candle.close),
+                Signal::Exit => Order::Market,
+                Signal::Hold => continue,
+            };
+            pending = Some((signal, order));
+        }
+
+        if let Some(last) = equity.pop() {
+            let close = series
+                .iter()
+                .rev()
+                .find(|c| c.is_finite())
+                .map_or(0.0, |c| c.close);
+            let fee = |quantity: f64, volume: f64| {
+                self.execution
+                    .commission
+                    .fee(quantity, close, Liquidity::Taker, volume)
+            };
+            book.fill(symbol, Signal::Exit, close, last.datetime, fee);
+            equity.push(EquityPoint {
+                datetime: last.datetime,
+                equity: book.portfolio.cash,
+            });
+        }
+
+        BacktestResult {
+            symbol: symbol.to_string(),
+            strategy: strategy.name().to_string(),
+            capital: self.capital,
+            fills: book.fills,
+            trades: book.trades,
+            equity,
+        }
+    }
+}
+
+/// Book fills signals against a portfolio, recording each fill and completed trade
+pub(crate) struct Book {
+    pub(crate) portfolio: Portfolio,
+    pub(crate) fills: Vec<Fill>,
+    pub(crate) trades: Vec<Trade>,
+    /// when the open position in each symbol was entered, and the commission paid on it so far
+    opened: HashMap<String, (usize, f64)>,
+}
+
+impl Book {
+    pub(crate) fn new(capital: f64) -> Book {
+        Book {
+            portfolio: Portfolio::new(capital),
+            fills: Vec::new(),
+            trades: Vec::new(),
+            opened: HashMap::new(),
+        }
+    }
+
+    /// planned estimates the quantity filling `signal` for `symbol` at `price` would trade
+    pub(crate) fn planned(&self, symbol: &str, signal: &Signal, price: f64) -> f64 {
+        let held = self.portfolio.position(symbol).map_or(0.0, |p| p.quantity);
+        match *signal {
+            Signal::Hold => 0.0,
+            Signal::Exit => -held,
+            Signal::EnterLong(size) => {
+                held.min(0.0).abs() + size.quantity(self.portfolio.cash, price)
+            }
+            Signal::EnterShort(size) => -held.max(0.0) - size.quantity(self.portfolio.cash, price),
+        }
+    }
+
+    /// volume is the value filled over the 30 days before `datetime`, which fee tiers are set by
+    pub(crate) fn volume(&self, datetime: usize) -> f64 {
+        let since = datetime.saturating_sub(30 * DAY_MILLIS);
+        self.fills
+            .iter()
+            .rev()
+            .take_while(|fill| fill.datetime >= since)
+            .map(|fill| (fill.quantity * fill.price).abs())
+            .sum()
+    }
+
+    /// affordable returns how much of `size` buying `symbol` at `price` can be paid for, with the
+    /// cash left after covering any short position, once the commission on the whole trade is
+    /// taken. Buying on margin isn't simulated, so a purchase is cut back rather than leaving cash
+    /// below 0.
+    fn affordable<F: Fn(f64, f64) -> f64>(
+        &self,
+        symbol: &str,
+        size: Size,
+        price: f64,
+        datetime: usize,
+        fee: &F,
+    ) -> f64 {
+        let covered = self
+            .portfolio
+            .position(symbol)
+            .map_or(0.0, |p| p.quantity.min(0.0).abs());
+        let cash = self.portfolio.cash - covered * price;
+        let quantity = size.quantity(cash, price);
+        if quantity <= 0.0 || price <= 0.0 {
+            return quantity;
+        }
+
+        let volume = self.volume(datetime);
+        let cost = quantity * price + fee(covered + quantity, volume);
+        if cost <= cash {
+            return quantity;
+        }
+        debug!(
+            "{}: cutting a purchase of {} back to the cash left",
+            symbol, quantity
+        );
+
+        // scaling is exact for fees in proportion to value, and a minimum fee is taken off what's
+        // left
+        let scaled = quantity * cash.max(0.0) / cost;
+        let commission = fee(covered + scaled, volume);
+        if scaled * price + commission <= cash {
+            scaled
+        } else {
+            ((cash - commission) / price).max(0.0)
+        }
+    }
+
+    /// fill applies `signal` for `symbol` to the portfolio at `price`, charging the commission
+    /// `fee` gives for the quantity traded and the 30 day volume before it. Purchases are cut back
+    /// to what the cash pays for, see `affordable`. The open trade is closed if the position is
+    /// exited or reversed, and its commission is netted from its pnl.
+    pub(crate) fn fill<F: Fn(f64, f64) -> f64>(
+        &mut self,
+        symbol: &str,
+        signal: Signal,
+        price: f64,
+        datetime: usize,
+        fee: F,
+    ) {
+        let signal = match signal {
+            Signal::EnterLong(size) => {
+                let quantity = self.affordable(symbol, size, price, datetime, &fee);
+                Signal::EnterLong(Size::Units(quantity))
+            }
+            signal => signal,
+        };
+        let before = self.portfolio.position(symbol).copied();
+        let quantity = self.portfolio.apply(symbol, &signal, price);
+        if quantity == 0.0 {
+            return;
+        }
+        let commission = fee(quantity, self.volume(datetime));
+        self.portfolio.cash -= commission;
+        self.fills.push(Fill {
+            datetime,
+            symbol: symbol.to_string(),
+            quantity,
+            price,
+            commission,
+        });
+
+        // a reversal is charged one commission, split between the trade it closes and opens
+        let mut opening = commission;
+        let after = self.portfolio.position(symbol).copied();
+        if let Some(before) = before {
+            if after.is_none_or(|after| after.is_long() != before.is_long()) {
+                let closing = commission * (before.quantity.abs() / quantity.abs()).min(1.0);
+                opening -= closing;
+
+                let (entry_datetime, paid) = self.opened.remove(symbol).unwrap_or((datetime, 0.0));
+                let fees = paid + closing;
+                self.trades.push(Trade {
+                    symbol: symbol.to_string(),
+                    side: if before.is_long() {
+                        Side::Long
+                    } else {
+                        Side::Short
+                    },
+                    quantity: before.quantity.abs(),
+                    entry_datetime,
+                    entry_price: before.entry_price,
+                    exit_datetime: datetime,
+                    exit_price: price,
+                    fees,
+                    pnl: before.unrealized_pnl(price) - fees,
+                });
+            }
+        }
+
+        if after.is_some() {
+            self.opened
+                .entry(symbol.to_string())
+                .or_insert((datetime, 0.0))
+                .1 += opening;
+        }
+    }
+
+    /// trade buys a positive `quantity` of `symbol`, or sells a negative one, at `price`, like
+    /// `fill`. Selling part of a long position records a trade for the part sold, with its share of
+    /// the commission paid entering the position.
+    pub(crate) fn trade<F: Fn(f64, f64) -> f64>(
+        &mut self,
+        symbol: &str,
+        quantity: f64,
+        price: f64,
+        datetime: usize,
+        fee: F,
+    ) {
+        let held = self.portfolio.position(symbol).map_or(0.0, |p| p.quantity);
+        if quantity >= 0.0 {
+            return self.fill(
+                symbol,
+                Signal::EnterLong(Size::Units(quantity)),
+                price,
+                datetime,
+                fee,
+            );
+        }
+        if held <= 0.0 {
+            let signal = Signal::EnterShort(Size::Units(-quantity));
+            return self.fill(symbol, signal, price, datetime, fee);
+        }
+        if -quantity >= held {
+            self.fill(symbol, Signal::Exit, price, datetime, &fee);
+            let rest = quantity + held;
+            if rest < 0.0 {
+                self.fill(
+                    symbol,
+                    Signal::EnterShort(Size::Units(-rest)),
+                    price,
+                    datetime,
+                    fee,
+                );
+            }
+            return;
+        }
+
+        let sold = -quantity;
+        let commission = fee(quantity, self.volume(datetime));
+        self.portfolio.cash += sold * price - commission;
+        self.fills.push(Fill {
+            datetime,
+            symbol: symbol.to_string(),
+            quantity,
+            price,
+            commission,
+        });
+
+        let share = sold / held;
+        let (entry_datetime, paid) = self
+            .opened
+            .get_mut(symbol)
+            .map(|opened| {
+                let paid = opened.1 * share;
+                opened.1 -= paid;
+                (opened.0, paid)
+            })
+            .unwrap_or((datetime, 0.0));
+        let position = self.portfolio.positions.get_mut(symbol).unwrap();
+        position.quantity -= sold;
+        let fees = paid + commission;
+        self.trades.push(Trade {
+            symbol: symbol.to_string(),
+            side: Side::Long,
+            quantity: sold,
+            entry_datetime,
+            entry_price: position.entry_price,
+            exit_datetime: datetime,
+            exit_price: price,
+            fees,
+            pnl: (price - position.entry_price) * sold - fees,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::execution::{Commission, OrderType, Slippage};
+    use strategy::{EmaCrossover, Size};
+    use ta::Candle;
+
+    /// Script gives a fixed signal on each candle, holding once it runs out
+    struct Script {
+        signals: Vec<Signal>,
+        seen: usize,
+    }
+
+    impl Strategy for Script {
+        fn name(&self) -> &str {
+            "script"
+        }
+
+        fn next(&mut self, _market: &Market, _portfolio: &Portfolio) -> Signal {
+            self.seen += 1;
+            self.signals
+                .get(self.seen - 1)
+                .copied()
+                .unwrap_or(Signal::Hold)
+        }
+
+        fn reset(&mut self) {
+            self.seen = 0;
+        }
+    }
+
+    fn series(prices: &[(f64, f64)]) -> Series {
+        let candles = prices
+            .iter()
+            .enumerate()
+            .map(|(i, (open, close))| {
+                let (high, low) = (open.max(*close), open.min(*close));
+                Candle::new(i, *open, high, low, *close, 100.0)
+            })
+            .collect();
+        Series::new("MSFT", candles)
+    }
+
+    #[test]
+    fn run_test() {
+        let series = series(&[
+            (10.0, 10.0),
+            (10.0, 11.0),
+            (12.0, 13.0),
+            (14.0, 12.0),
+            (12.0, 11.0),
+            (10.0, 9.0),
+        ]);
+        let mut strategy = Script {
+            signals: vec![
+                Signal::EnterLong(Size::Units(10.0)),
+                Signal::Hold,
+                Signal::EnterShort(Size::Units(5.0)),
+                Signal::Hold,
+                Signal::EnterShort(Size::Units(5.0)),
+            ],
+            seen: 0,
+        };
+
+        let result = Backtest::new(1000.0).run(&series, &mut strategy);
+
+        assert_eq!(result.strategy, "script");
+        // signals fill at the next candle's open, a reversal closes the long and sells short in
+        // one fill, and the short is covered at the last close
+        assert_eq!(
+            result
+                .fills
+                .iter()
+                .map(|f| (f.datetime, f.quantity, f.price))
+                .collect::<Vec<_>>(),
+            vec![
+                (1, 10.0, 10.0),
+                (3, -15.0, 14.0),
+                (5, -5.0, 10.0),
+                (5, 10.0, 9.0)
+            ]
+        );
+        assert_eq!(
+            result.equity.iter().map(|p| p.equity).collect::<Vec<_>>(),
+            vec![1000.0, 1010.0, 1030.0, 1050.0, 1055.0, 1070.0]
+        );
+
+        assert_eq!(result.trades.len(), 2);
+        assert_eq!(result.trades[0].side, Side::Long);
+        assert_eq!(result.trades[0].pnl, 40.0);
+        assert_eq!(result.trades[0].returns(), 0.4);
+        assert_eq!(result.trades[0].duration(), 2);
+
+        let short = &result.trades[1];
+        assert_eq!(short.side, Side::Short);
+        assert_eq!(short.quantity, 10.0);
+        assert_eq!((short.entry_datetime, short.exit_datetime), (3, 5));
+        assert_eq!(short.entry_price, 12.0);
+        assert_eq!(short.pnl, 30.0);
+
+        assert_eq!(result.final_equity(), 1070.0);
+        assert_eq!(result.total_return(), 0.07);
+    }
+
+    #[test]
+    fn risk_test() {
+        let series = series(&[(10.0, 10.0), (10.0, 10.0), (10.0, 10.0)]);
+        let mut strategy = Script {
+            signals: vec![Signal::EnterLong(Size::Fraction(1.0))],
+            seen: 0,
+        };
+
+        let mut backtest = Backtest::new(1000.0);
+        backtest.risk.max_position = Some(0.25);
+        let result = backtest.run(&series, &mut strategy);
+
+        assert_eq!(result.fills[0].quantity, 25.0);
+        assert_eq!(result.trades.len(), 1);
+        assert_eq!(result.trades[0].pnl, 0.0);
+    }
+
+    #[test]
+    fn execution_test() {
+        let series = series(&[(10.0, 10.0), (10.0, 11.0), (12.0, 13.0), (14.0, 12.0)]);
+        let script = || Script {
+            signals: vec![
+                Signal::EnterLong(Size::Units(10.0)),
+                Signal::Hold,
+                Signal::Exit,
+            ],
+            seen: 0,
+        };
+        let close = |a: f64, b: f64| (a - b).abs() < 1e-9;
+
+        let mut backtest = Backtest::new(1000.0);
+        backtest.execution.commission = Commission::Percentage(0.01);
+        backtest.execution.slippage = Slippage::Fixed(100.0);
+        let result = backtest.run(&series, &mut script());
+
+        assert!(close(result.fills[0].price, 10.1));
+        assert!(close(result.fills[0].commission, 1.01));
+        assert!(close(result.fills[1].price, 13.86));
+        let trade = &result.trades[0];
+        assert!(close(trade.fees, 1.01 + 1.386));
+        assert!(close(trade.pnl, 37.6 - 2.396));
+        assert!(close(result.final_equity(), 1000.0 + trade.pnl));
+
+        // a buy limit half the close away never fills, so is cancelled
+        backtest.execution.orders = OrderType::Limit { offset: 0.5 };
+        let result = backtest.run(&series, &mut script());
+        assert!(result.fills.is_empty());
+        assert_eq!(result.final_equity(), 1000.0);
+    }
+
+    #[test]
+    fn all_in_test() {
+        let all_in = Signal::EnterLong(Size::Fraction(1.0));
+        let close = |a: f64, b: f64| (a - b).abs() < 1e-9;
+
+        // the commission on an all in purchase comes out of what's bought, rather than leaving
+        // cash below 0
+        let mut book = Book::new(1000.0);
+        book.fill("MSFT", all_in, 10.0, 0, |q: f64, _| q.abs() * 10.0 * 0.01);
+        assert!(close(book.portfolio.cash, 0.0));
+        assert!(close(book.fills[0].quantity, 1000.0 / 10.1));
+
+        // a flat fee is taken off the cash first
+        let mut book = Book::new(1000.0);
+        book.fill("MSFT", all_in, 10.0, 0, |_, _| 5.0);
+        assert!(close(book.fills[0].quantity, 99.5));
+        assert!(close(book.portfolio.cash, 0.0));
+    }
+
+    #[test]
+    fn warmup_test() {
+        let series = series(&[(10.0, 10.0), (10.0, 11.0), (12.0, 13.0), (14.0, 12.0)]);
+        let mut strategy = Script {
+            signals: vec![
+                Signal::EnterLong(Size::Units(10.0)),
+                Signal::Hold,
+                Signal::EnterLong(Size::Units(10.0)),
+            ],
+            seen: 0,
+        };
+
+        let result = Backtest::new(1000.0).run_warmed(&series, &Spreads::new(), &mut strategy, 2);
+
+        assert_eq!(
+            strategy.seen, 4,
+            "warmup candles are still fed to the strategy"
+        );
+        assert_eq!(result.equity.len(), 2);
+        assert_eq!(result.equity[0].datetime, 2);
+        assert_eq!(result.fills[0].datetime, 3);
+    }
+
+    #[test]
+    fn repeatable_test() {
+        let closes: Vec<(f64, f64)> = (0..120)
+            .map(|i| {
+                let close = 100.0 + 20.0 * (i as f64 / 10.0).sin();
+                (close, close)
+            })
+            .collect();
+        let series = series(&closes);
+        let mut strategy = EmaCrossover::new(5, 10, Size::Fraction(1.0), None).unwrap();
+
+        let first = Backtest::new(1000.0).run(&series, &mut strategy);
+        let second = Backtest::new(1000.0).run(&series, &mut strategy);
+
+        assert!(!first.trades.is_empty());
+        assert_eq!(first, second, "each run resets the strategy");
+        assert!(first
+            .trades
+            .iter()
+            .all(|t| t.entry_datetime < t.exit_datetime));
+        assert_eq!(first.equity.len(), series.len());
+        assert!(Backtest::new(1000.0)
+            .run(&Series::new("MSFT", Vec::new()), &mut strategy)
+            .trades
+            .is_empty());
+    }
+}
@@ -0,0 +1,213 @@
+use crate::{Market, Portfolio, Signal, Size, Strategy, StrategyError};
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+use ta::average::Ema;
+use ta::Indicator;
+
+/// EmaCrossoverConfig holds the parameters of an `EmaCrossover`, any left out of a config file
+/// taking their default
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(default)]
+pub struct EmaCrossoverConfig {
+    pub fast: usize,
+    pub slow: usize,
+    pub size: Size,
+    pub stop_loss: Option<f64>,
+}
+
+impl Default for EmaCrossoverConfig {
+    /// default is the EMA20 / EMA50 crossover with a 95% stop loss, going all in on entry
+    fn default() -> EmaCrossoverConfig {
+        EmaCrossoverConfig {
+            fast: 20,
+            slow: 50,
+            size: Size::Fraction(1.0),
+            stop_loss: Some(0.95),
+        }
+    }
+}
+
+/// EmaCrossover goes long while the fast EMA is above the slow one, and exits when it falls back
+/// below. An optional stop loss exits early once the close drops below a fraction of the entry
+/// price, and the strategy then waits for the next cross before buying again.
+#[derive(Clone, Debug)]
+pub struct EmaCrossover {
+    fast: Ema,
+    slow: Ema,
+    size: Size,
+    stop_loss: Option<f64>,
+    stopped: bool,
+}
+
+impl EmaCrossover {
+    /// new creates a crossover of `fast` and `slow` period EMAs, where `stop_loss` is the fraction
+    /// of the entry price to exit at, eg: 0.95 to exit after a 5% loss.
+    pub fn new(
+        fast: usize,
+        slow: usize,
+        size: Size,
+        stop_loss: Option<f64>,
+    ) -> Result<EmaCrossover, StrategyError> {
+        if fast == 0 || fast >= slow {
+            return Err(StrategyError::InvalidParameter { name: "fast" });
+        }
+        if stop_loss.is_some_and(|stop| !(stop > 0.0 && stop < 1.0)) {
+            return Err(StrategyError::InvalidParameter { name: "stop_loss" });
+        }
+
+        Ok(EmaCrossover {
+            fast: Ema::new(fast),
+            slow: Ema::new(slow),
+            size,
+            stop_loss,
+            stopped: false,
+        })
+    }
+}
+
+impl Default for EmaCrossover {
+    fn default() -> EmaCrossover {
+        let config = EmaCrossoverConfig::default();
+        EmaCrossover {
+            fast: Ema::new(config.fast),
+            slow: Ema::new(config.slow),
+            size: config.size,
+            stop_loss: config.stop_loss,
+            stopped: false,
+        }
+    }
+}
+
+impl TryFrom<EmaCrossoverConfig> for EmaCrossover {
+    type Error = StrategyError;
+
+    fn try_from(config: EmaCrossoverConfig) -> Result<EmaCrossover, StrategyError> {
+        EmaCrossover::new(config.fast, config.slow, config.size, config.stop_loss)
+    }
+}
+
+impl Strategy for EmaCrossover {
+    fn name(&self) -> &str {
+        "ema_crossover"
+    }
+
+    fn next(&mut self, market: &Market, portfolio: &Portfolio) -> Signal {
+        let candle = match market.current() {
+            Some(candle) if candle.is_finite() => candle,
+            _ => return Signal::Hold,
+        };
+
+        // only trade once both averages have enough history, so a missing value never becomes an order
+        let (fast, slow) = match (self.fast.next(candle), self.slow.next(candle)) {
+            (Some(fast), Some(slow)) => (fast, slow),
+            _ => return Signal::Hold,
+        };
+        let position = portfolio.position(market.symbol);
+        let long = position.is_some_and(|p| p.is_long());
+
+        if let (Some(position), Some(stop_loss)) = (position, self.stop_loss) {
+            if long && candle.close < position.entry_price * stop_loss {
+                self.stopped = true;
+                return Signal::Exit;
+            }
+        }
+
+        if fast > slow {
+            if !long && !self.stopped {
+                return Signal::EnterLong(self.size);
+            }
+        } else {
+            self.stopped = false;
+            if long {
+                return Signal::Exit;
+            }
+        }
+
+        Signal::Hold
+    }
+
+    fn reset(&mut self) {
+        Ema::reset(&mut self.fast);
+        Ema::reset(&mut self.slow);
+        self.stopped = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ta::Candle;
+
+    fn run(strategy: &mut EmaCrossover, closes: &[f64]) -> Vec<Signal> {
+        let candles: Vec<Candle> = closes
+            .iter()
+            .enumerate()
+            .map(|(i, close)| Candle::new(i, *close, *close, *close, *close, 1.0))
+            .collect();
+        let mut portfolio = Portfolio::new(1000.0);
+
+        (0..candles.len())
+            .map(|i| {
+                let market = Market::new("MSFT", &candles[..=i]);
+                let signal = strategy.next(&market, &portfolio);
+                portfolio.apply("MSFT", &signal, candles[i].close);
+                signal
+            })
+            .collect()
+    }
+
+    #[test]
+    fn ema_crossover_test() {
+        let mut strategy = EmaCrossover::new(2, 4, Size::Units(1.0), None).unwrap();
+        let signals = run(
+            &mut strategy,
+            &[10.0, 10.0, 10.0, 10.0, 11.0, 12.0, 13.0, 11.0, 9.0, 8.0],
+        );
+
+        assert_eq!(
+            signals,
+            vec![
+                Signal::Hold,
+                Signal::Hold,
+                Signal::Hold,
+                Signal::Hold,
+                Signal::EnterLong(Size::Units(1.0)),
+                Signal::Hold,
+                Signal::Hold,
+                Signal::Hold,
+                Signal::Exit,
+                Signal::Hold,
+            ]
+        );
+    }
+
+    #[test]
+    fn stop_loss_test() {
+        let mut strategy = EmaCrossover::new(2, 4, Size::Units(1.0), Some(0.95)).unwrap();
+        let signals = run(
+            &mut strategy,
+            &[10.0, 10.0, 10.0, 10.0, 20.0, 18.9, 19.0, 20.0],
+        );
+
+        // entered at 20, stopped out below 19, and not bought back while still above the slow EMA
+        assert_eq!(signals[4], Signal::EnterLong(Size::Units(1.0)));
+        assert_eq!(signals[5], Signal::Exit);
+        assert_eq!(&signals[6..], &[Signal::Hold, Signal::Hold]);
+
+        strategy.reset();
+        assert_eq!(
+            run(&mut strategy, &[10.0, 10.0, 10.0, 10.0, 20.0])[4],
+            signals[4]
+        );
+    }
+
+    #[test]
+    fn new_test() {
+        assert!(EmaCrossover::new(50, 20, Size::default(), None).is_err());
+        assert!(EmaCrossover::new(0, 20, Size::default(), None).is_err());
+        assert_eq!(
+            EmaCrossover::new(20, 50, Size::default(), Some(1.5)).err(),
+            Some(StrategyError::InvalidParameter { name: "stop_loss" })
+        );
+    }
+}
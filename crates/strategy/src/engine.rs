@@ -0,0 +1,176 @@
+use crate::{Market, Portfolio, Signal, Strategy};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use ta::Series;
+
+/// Decision is a signal a strategy gave for a symbol on the candle at `datetime`
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Decision {
+    pub symbol: String,
+    pub strategy: String,
+    pub datetime: usize,
+    pub signal: Signal,
+}
+
+/// Engine runs the strategies loaded for each symbol over that symbol's candles as they arrive.
+///
+/// Like `ta::Indicator`, strategies keep state between candles, so the engine remembers the last
+/// candle each symbol was fed and only passes newer ones on.
+#[derive(Default)]
+pub struct Engine {
+    strategies: HashMap<String, Vec<Box<dyn Strategy>>>,
+    last_datetime: HashMap<String, usize>,
+}
+
+impl Engine {
+    pub fn new() -> Engine {
+        Engine::default()
+    }
+
+    /// add loads a strategy to run on `symbol`, alongside any already loaded for it
+    pub fn add(&mut self, symbol: &str, strategy: Box<dyn Strategy>) {
+        self.strategies
+            .entry(symbol.to_string())
+            .or_default()
+            .push(strategy);
+    }
+
+    /// symbols returns every symbol with at least one strategy loaded, sorted
+    pub fn symbols(&self) -> Vec<String> {
+        let mut symbols: Vec<String> = self.strategies.keys().cloned().collect();
+        symbols.sort();
+        symbols
+    }
+
+    /// strategies returns the names of the strategies loaded for `symbol`
+    pub fn strategies(&self, symbol: &str) -> Vec<&str> {
+        self.strategies
+            .get(symbol)
+            .map(|strategies| strategies.iter().map(|s| s.name()).collect())
+            .unwrap_or_default()
+    }
+
+    /// update feeds the strategies for `series.symbol` any candles newer than the last one seen,
+    /// and returns their decisions on the latest candle. Signals for older candles are already
+    /// stale, so those candles only warm the strategies up.
+    pub fn update(&mut self, series: &Series, portfolio: &Portfolio) -> Vec<Decision> {
+        let strategies = match self.strategies.get_mut(&series.symbol) {
+            Some(strategies) => strategies,
+            None => return Vec::new(),
+        };
+        let last = self.last_datetime.get(&series.symbol).copied();
+        let mut decisions = Vec::new();
+
+        for (i, candle) in series.iter().enumerate() {
+            if last.is_some_and(|last| candle.datetime <= last) {
+                continue;
+            }
+            if !candle.is_finite() {
+                warn!(
+                    "{}: skipping candle at {} with a non-finite value",
+                    series.symbol, candle.datetime
+                );
+                continue;
+            }
+
+            let market = Market::new(&series.symbol, &series[..=i]);
+            let latest = i + 1 == series.len();
+
+            for strategy in strategies.iter_mut() {
+                let signal = strategy.next(&market, portfolio);
+                if latest {
+                    decisions.push(Decision {
+                        symbol: series.symbol.clone(),
+                        strategy: strategy.name().to_string(),
+                        datetime: candle.datetime,
+                        signal,
+                    });
+                }
+            }
+        }
+
+        if let Some(candle) = series.last() {
+            self.last_datetime
+                .insert(series.symbol.clone(), candle.datetime);
+        }
+
+        decisions
+    }
+
+    /// reset clears the state of every strategy, so the next update replays all candles
+    pub fn reset(&mut self) {
+        for strategy in self.strategies.values_mut().flatten() {
+            strategy.reset();
+        }
+        self.last_datetime.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{EmaCrossover, Size};
+    use ta::Candle;
+
+    /// Counter holds, counting the candles it has seen
+    struct Counter {
+        seen: usize,
+    }
+
+    impl Strategy for Counter {
+        fn name(&self) -> &str {
+            "counter"
+        }
+
+        fn next(&mut self, _: &Market, _: &Portfolio) -> Signal {
+            self.seen += 1;
+            Signal::Hold
+        }
+
+        fn reset(&mut self) {
+            self.seen = 0;
+        }
+    }
+
+    fn series(closes: &[f64]) -> Series {
+        let candles = closes
+            .iter()
+            .enumerate()
+            .map(|(i, close)| Candle::new(i, *close, *close, *close, *close, 1.0))
+            .collect();
+        Series::new("MSFT", candles)
+    }
+
+    #[test]
+    fn update_test() {
+        let mut engine = Engine::new();
+        engine.add(
+            "MSFT",
+            Box::new(EmaCrossover::new(2, 4, Size::Units(1.0), None).unwrap()),
+        );
+        engine.add("MSFT", Box::new(Counter { seen: 0 }));
+        assert_eq!(engine.symbols(), vec!["MSFT"]);
+        assert_eq!(engine.strategies("MSFT"), vec!["ema_crossover", "counter"]);
+
+        let portfolio = Portfolio::new(1000.0);
+        let mut history = series(&[10.0, 10.0, 10.0, 10.0]);
+        let decisions = engine.update(&history, &portfolio);
+        assert_eq!(decisions.len(), 2);
+        assert!(decisions
+            .iter()
+            .all(|d| d.datetime == 3 && d.signal.is_hold()));
+
+        // only the new candle is fed in
+        history.push(Candle::new(4, 11.0, 11.0, 11.0, 11.0, 1.0));
+        let decisions = engine.update(&history, &portfolio);
+        assert_eq!(decisions[0].signal, Signal::EnterLong(Size::Units(1.0)));
+        assert!(engine.update(&history, &portfolio).is_empty());
+
+        let other = Series::new("AAPL", history.candles.clone());
+        assert!(engine.update(&other, &portfolio).is_empty());
+
+        engine.reset();
+        assert_eq!(engine.update(&history, &portfolio).len(), 2);
+    }
+}
@@ -0,0 +1,27 @@
+use crate::rules::ParseError;
+use std::fmt;
+
+/// StrategyError describes why a strategy couldn't be created
+#[derive(Clone, Debug, PartialEq)]
+pub enum StrategyError {
+    /// no built in strategy has the given name
+    UnknownStrategy(String),
+    /// a strategy parameter is out of range, eg: a fast average slower than the slow one
+    InvalidParameter { name: &'static str },
+    /// a rule of a `RuleStrategy` couldn't be parsed
+    InvalidRule(ParseError),
+}
+
+impl fmt::Display for StrategyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StrategyError::UnknownStrategy(name) => write!(f, "unknown strategy {}", name),
+            StrategyError::InvalidParameter { name } => {
+                write!(f, "invalid strategy parameter {}", name)
+            }
+            StrategyError::InvalidRule(e) => write!(f, "invalid rule: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for StrategyError {}
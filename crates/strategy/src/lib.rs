@@ -0,0 +1,156 @@
+pub mod crossover;
+pub use crossover::{EmaCrossover, EmaCrossoverConfig};
+pub mod engine;
+pub use engine::{Decision, Engine};
+pub mod error;
+pub use error::StrategyError;
+pub mod portfolio;
+pub use portfolio::{Portfolio, Position};
+pub mod risk;
+pub use risk::RiskLimits;
+pub mod rules;
+pub use rules::{Rule, RuleStrategy, RulesConfig};
+pub mod signal;
+pub use signal::{Side, Signal, Size};
+
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+use ta::Candle;
+
+/// Strategy is implemented by every trading strategy mate can run.
+///
+/// Strategies are fed one candle at a time, like `ta::Indicator`, so the same strategy can run in
+/// the live loop or over history. They only decide what to do; the caller places orders and keeps
+/// the portfolio, which is handed back on the next candle.
+pub trait Strategy {
+    /// name identifies the strategy in logs and reports
+    fn name(&self) -> &str;
+
+    /// next is called once for each new candle, the last of `market.candles`, and returns what the
+    /// strategy wants to do about it
+    fn next(&mut self, market: &Market, portfolio: &Portfolio) -> Signal;
+
+    /// reset clears all state, returning the strategy to how it was when constructed
+    fn reset(&mut self);
+}
+
+/// Market is the data a strategy sees for a symbol: its candles up to and including the current one.
+#[derive(Clone, Copy, Debug)]
+pub struct Market<'a> {
+    pub symbol: &'a str,
+    pub candles: &'a [Candle],
+}
+
+impl<'a> Market<'a> {
+    pub fn new(symbol: &'a str, candles: &'a [Candle]) -> Market<'a> {
+        Market { symbol, candles }
+    }
+
+    /// current is the candle being decided on
+    pub fn current(&self) -> Option<&'a Candle> {
+        self.candles.last()
+    }
+}
+
+/// built_in returns a new instance of a strategy that ships with mate, by name, with its default
+/// parameters
+pub fn built_in(name: &str) -> Result<Box<dyn Strategy>, StrategyError> {
+    match name.to_lowercase().as_str() {
+        "ema_crossover" | "ema-crossover" => Ok(Box::new(EmaCrossover::default())),
+        _ => Err(StrategyError::UnknownStrategy(name.to_string())),
+    }
+}
+
+/// StrategyConfig names a built in strategy along with its parameters, eg: in TOML
+///
+/// ```toml
+/// name = "ema_crossover"
+/// fast = 10
+/// slow = 30
+/// ```
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(tag = "name", rename_all = "snake_case")]
+pub enum StrategyConfig {
+    EmaCrossover(EmaCrossoverConfig),
+    Rules(RulesConfig),
+}
+
+impl StrategyConfig {
+    /// build returns a new instance of the configured strategy, checking its parameters
+    pub fn build(&self) -> Result<Box<dyn Strategy>, StrategyError> {
+        match self {
+            StrategyConfig::EmaCrossover(config) => Ok(Box::new(EmaCrossover::try_from(*config)?)),
+            StrategyConfig::Rules(config) => Ok(Box::new(RuleStrategy::try_from(config)?)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn built_in_test() {
+        assert_eq!(built_in("ema-crossover").unwrap().name(), "ema_crossover");
+        assert_eq!(built_in("EMA_CROSSOVER").unwrap().name(), "ema_crossover");
+        assert_eq!(
+            built_in("martingale").err(),
+            Some(StrategyError::UnknownStrategy("martingale".to_string()))
+        );
+    }
+
+    #[test]
+    fn strategy_config_test() {
+        let config: StrategyConfig =
+            serde_json::from_str(r#"{"name": "ema_crossover", "fast": 10, "size": {"units": 5}}"#)
+                .unwrap();
+
+        assert_eq!(
+            config,
+            StrategyConfig::EmaCrossover(EmaCrossoverConfig {
+                fast: 10,
+                size: Size::Units(5.0),
+                ..EmaCrossoverConfig::default()
+            })
+        );
+        assert_eq!(config.build().unwrap().name(), "ema_crossover");
+
+        let invalid: StrategyConfig =
+            serde_json::from_str(r#"{"name": "ema_crossover", "fast": 60}"#).unwrap();
+        assert_eq!(
+            invalid.build().err(),
+            Some(StrategyError::InvalidParameter { name: "fast" })
+        );
+    }
+
+    #[test]
+    fn rules_config_test() {
+        let config: StrategyConfig = serde_json::from_str(
+            r#"{"name": "rules", "entry": "rsi(14) < 30", "exit": "rsi(14) > 70", "side": "short"}"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            config,
+            StrategyConfig::Rules(RulesConfig {
+                entry: "rsi(14) < 30".to_string(),
+                exit: "rsi(14) > 70".to_string(),
+                side: Side::Short,
+                size: Size::Fraction(1.0),
+            })
+        );
+        assert_eq!(config.build().unwrap().name(), "rules");
+    }
+
+    #[test]
+    fn market_test() {
+        let candles = vec![
+            Candle::new(1, 1.0, 1.0, 1.0, 1.0, 1.0),
+            Candle::new(2, 2.0, 2.0, 2.0, 2.0, 1.0),
+        ];
+        let market = Market::new("MSFT", &candles);
+
+        assert_eq!(market.current().map(|c| c.datetime), Some(2));
+        assert!(Market::new("MSFT", &[]).current().is_none());
+    }
+}
@@ -0,0 +1,144 @@
+use crate::Signal;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Position is an open holding in a symbol. Short positions have a negative quantity.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct Position {
+    pub quantity: f64,
+    pub entry_price: f64,
+}
+
+impl Position {
+    pub fn is_long(&self) -> bool {
+        self.quantity > 0.0
+    }
+
+    pub fn is_short(&self) -> bool {
+        self.quantity < 0.0
+    }
+
+    /// unrealized_pnl is the profit or loss if the position were closed at `price`
+    pub fn unrealized_pnl(&self, price: f64) -> f64 {
+        (price - self.entry_price) * self.quantity
+    }
+}
+
+/// Portfolio is the cash and open positions strategies size and manage their trades against.
+///
+/// Short sales credit their proceeds to cash, and buying them back debits it, so equity is always
+/// cash plus the market value of every position.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct Portfolio {
+    pub cash: f64,
+    pub positions: HashMap<String, Position>,
+}
+
+impl Portfolio {
+    pub fn new(cash: f64) -> Portfolio {
+        Portfolio {
+            cash,
+            positions: HashMap::new(),
+        }
+    }
+
+    pub fn position(&self, symbol: &str) -> Option<&Position> {
+        self.positions.get(symbol)
+    }
+
+    /// equity is cash plus every position valued at the price `price_of` gives for its symbol
+    pub fn equity<F: Fn(&str) -> Option<f64>>(&self, price_of: F) -> f64 {
+        self.positions
+            .iter()
+            .map(|(symbol, position)| {
+                let price = price_of(symbol).unwrap_or(position.entry_price);
+                position.quantity * price
+            })
+            .sum::<f64>()
+            + self.cash
+    }
+
+    /// apply fills `signal` for `symbol` at `price`, returning the quantity traded, which is
+    /// positive for a buy and negative for a sell.
+    pub fn apply(&mut self, symbol: &str, signal: &Signal, price: f64) -> f64 {
+        match *signal {
+            Signal::Hold => 0.0,
+            Signal::Exit => self.close(symbol, price),
+            Signal::EnterLong(size) => {
+                let mut traded = 0.0;
+                if self.position(symbol).is_some_and(Position::is_short) {
+                    traded += self.close(symbol, price);
+                }
+                traded + self.open(symbol, size.quantity(self.cash, price), price)
+            }
+            Signal::EnterShort(size) => {
+                let mut traded = 0.0;
+                if self.position(symbol).is_some_and(Position::is_long) {
+                    traded += self.close(symbol, price);
+                }
+                traded + self.open(symbol, -size.quantity(self.cash, price), price)
+            }
+        }
+    }
+
+    /// open adds `quantity` to the position in `symbol`, averaging the entry price
+    fn open(&mut self, symbol: &str, quantity: f64, price: f64) -> f64 {
+        if quantity == 0.0 {
+            return 0.0;
+        }
+
+        let position = self.positions.entry(symbol.to_string()).or_default();
+        let total = position.quantity + quantity;
+        position.entry_price =
+            (position.entry_price * position.quantity + price * quantity) / total;
+        position.quantity = total;
+        self.cash -= quantity * price;
+
+        quantity
+    }
+
+    fn close(&mut self, symbol: &str, price: f64) -> f64 {
+        match self.positions.remove(symbol) {
+            Some(position) => {
+                self.cash += position.quantity * price;
+                -position.quantity
+            }
+            None => 0.0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Size;
+
+    #[test]
+    fn apply_test() {
+        let mut portfolio = Portfolio::new(1000.0);
+
+        assert_eq!(
+            portfolio.apply("MSFT", &Signal::EnterLong(Size::Fraction(0.5)), 50.0),
+            10.0
+        );
+        assert_eq!(portfolio.cash, 500.0);
+        assert_eq!(portfolio.equity(|_| Some(60.0)), 1100.0);
+
+        portfolio.apply("MSFT", &Signal::EnterLong(Size::Units(10.0)), 70.0);
+        let position = *portfolio.position("MSFT").unwrap();
+        assert_eq!(position.quantity, 20.0);
+        assert_eq!(position.entry_price, 60.0);
+        assert_eq!(position.unrealized_pnl(65.0), 100.0);
+
+        // reversing closes the long, then sells short
+        let traded = portfolio.apply("MSFT", &Signal::EnterShort(Size::Units(5.0)), 80.0);
+        assert_eq!(traded, -25.0);
+        assert!(portfolio.position("MSFT").unwrap().is_short());
+        assert_eq!(portfolio.cash, -200.0 + 1600.0 + 400.0);
+
+        assert_eq!(portfolio.apply("MSFT", &Signal::Exit, 100.0), 5.0);
+        assert!(portfolio.position("MSFT").is_none());
+        assert_eq!(portfolio.cash, 1300.0);
+        assert_eq!(portfolio.apply("MSFT", &Signal::Hold, 100.0), 0.0);
+    }
+}
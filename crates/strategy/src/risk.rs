@@ -0,0 +1,122 @@
+use crate::{Portfolio, Signal, Size};
+use serde::{Deserialize, Serialize};
+
+/// RiskLimits cap what strategies may enter into, whatever their signals ask for. Limits left
+/// unset aren't enforced. Exits are never limited, so a position can always be closed.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(default)]
+pub struct RiskLimits {
+    /// the largest fraction of equity a single position may be worth when entered, eg: 0.25
+    pub max_position: Option<f64>,
+    /// the most positions that may be open at once, across every symbol
+    pub max_open_positions: Option<usize>,
+    /// stop entering positions once equity has fallen this fraction below its peak, eg: 0.2
+    pub max_drawdown: Option<f64>,
+}
+
+impl RiskLimits {
+    /// limit returns `signal` for `symbol` at `price` adjusted to fit the limits: an entry is
+    /// shrunk to the largest allowed size, or turned into a hold if no entry is allowed at all.
+    /// `equity` and `peak` are the portfolio's current and highest equity.
+    pub fn limit(
+        &self,
+        portfolio: &Portfolio,
+        symbol: &str,
+        signal: Signal,
+        price: f64,
+        equity: f64,
+        peak: f64,
+    ) -> Signal {
+        let size = match signal {
+            Signal::EnterLong(size) | Signal::EnterShort(size) => size,
+            Signal::Exit | Signal::Hold => return signal,
+        };
+
+        if let Some(max_drawdown) = self.max_drawdown {
+            if peak > 0.0 && (peak - equity) / peak >= max_drawdown {
+                return Signal::Hold;
+            }
+        }
+
+        if let Some(max_open_positions) = self.max_open_positions {
+            let open = portfolio.positions.len();
+            if portfolio.position(symbol).is_none() && open >= max_open_positions {
+                return Signal::Hold;
+            }
+        }
+
+        let quantity = size.quantity(portfolio.cash, price);
+        let size = match self.max_position {
+            Some(max_position) if price > 0.0 => {
+                let largest = (equity * max_position / price).max(0.0);
+                if largest <= 0.0 {
+                    return Signal::Hold;
+                }
+                if quantity > largest {
+                    Size::Units(largest)
+                } else {
+                    size
+                }
+            }
+            _ => size,
+        };
+
+        match signal {
+            Signal::EnterLong(_) => Signal::EnterLong(size),
+            _ => Signal::EnterShort(size),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn limit_test() {
+        let mut portfolio = Portfolio::new(1000.0);
+        let all_in = Signal::EnterLong(Size::Fraction(1.0));
+
+        assert_eq!(
+            RiskLimits::default().limit(&portfolio, "MSFT", all_in, 10.0, 1000.0, 1000.0),
+            all_in
+        );
+
+        let limits = RiskLimits {
+            max_position: Some(0.25),
+            max_open_positions: Some(1),
+            max_drawdown: Some(0.2),
+        };
+        assert_eq!(
+            limits.limit(&portfolio, "MSFT", all_in, 10.0, 1000.0, 1000.0),
+            Signal::EnterLong(Size::Units(25.0))
+        );
+        assert_eq!(
+            limits.limit(
+                &portfolio,
+                "MSFT",
+                Signal::EnterShort(Size::Units(5.0)),
+                10.0,
+                1000.0,
+                1000.0
+            ),
+            Signal::EnterShort(Size::Units(5.0))
+        );
+        assert_eq!(
+            limits.limit(&portfolio, "MSFT", all_in, 10.0, 790.0, 1000.0),
+            Signal::Hold,
+            "no entries past the max drawdown"
+        );
+
+        portfolio.apply("AAPL", &Signal::EnterLong(Size::Units(1.0)), 100.0);
+        assert_eq!(
+            limits.limit(&portfolio, "MSFT", all_in, 10.0, 1000.0, 1000.0),
+            Signal::Hold,
+            "no new positions past the max open"
+        );
+        assert_eq!(
+            limits.limit(&portfolio, "AAPL", Signal::Exit, 10.0, 0.0, 1000.0),
+            Signal::Exit
+        );
+    }
+}
@@ -0,0 +1,453 @@
+//! A small expression language for writing strategies as rules rather than Rust, eg:
+//!
+//! ```text
+//! ema(20) crosses_above ema(50) and rsi(14) < 70
+//! ```
+//!
+//! Rules compare series, which are candle fields (`open`, `high`, `low`, `close`, `volume`) and
+//! `ta` indicators, with numbers and with each other:
+//!
+//! | Series                                  | Outputs, the first being the default          |
+//! | --------------------------------------- | --------------------------------------------- |
+//! | `sma`, `ema`, `wma`, `dema`, `tema`, `hma`, `rsi`, `cci`, `mfi`, `williams_r`, `atr`, `cmf`, `volume_sma` `(period)` | |
+//! | `obv`, `vwap`, `ad`                     |                                               |
+//! | `stochastic(k, d)`                      | `k`, `d`                                      |
+//! | `macd(fast, slow, signal)`              | `macd`, `signal`, `histogram`                 |
+//! | `adx(period)`                           | `adx`, `plus_di`, `minus_di`                  |
+//! | `aroon(period)`                         | `oscillator`, `up`, `down`                    |
+//! | `bollinger(period, deviations)`         | `middle`, `upper`, `lower`, `percent_b`, `bandwidth` |
+//! | `keltner(ema, atr, multiplier)`         | `middle`, `upper`, `lower`                    |
+//! | `donchian(period)`                      | `middle`, `upper`, `lower`                    |
+//!
+//! An output is picked with a dot, eg: `macd(12, 26, 9).signal`, and earlier values with a lookback
+//! in brackets, eg: `close[1]` for the previous close. Series can be combined with `+ - * /`,
+//! compared with `< <= > >= == !=`, `crosses_above` and `crosses_below`, and conditions joined
+//! with `and`, `or`, `not` and parentheses. A condition on a series that is still warming up is
+//! never true.
+mod parser;
+pub use parser::{parse, ArithmeticOp, CompareOp, Expr, ParseError};
+mod source;
+
+use crate::{Market, Portfolio, Side, Signal, Size, Strategy, StrategyError};
+use serde::{Deserialize, Serialize};
+use source::Source;
+use std::convert::TryFrom;
+use std::str::FromStr;
+use ta::Candle;
+
+/// Node is an `Expr` with each series resolved to the source it reads
+enum Node {
+    Number(f64),
+    Series { source: usize, lookback: usize },
+    Negate(Box<Node>),
+    Arithmetic(ArithmeticOp, Box<Node>, Box<Node>),
+    Compare(CompareOp, Box<Node>, Box<Node>),
+    CrossesAbove(Box<Node>, Box<Node>),
+    CrossesBelow(Box<Node>, Box<Node>),
+    And(Box<Node>, Box<Node>),
+    Or(Box<Node>, Box<Node>),
+    Not(Box<Node>),
+}
+
+/// Rule is a parsed condition, evaluated on each candle as it arrives
+pub struct Rule {
+    text: String,
+    node: Node,
+    sources: Vec<Source>,
+}
+
+impl Rule {
+    /// parse parses a rule, checking every series it reads exists and has valid arguments
+    pub fn parse(text: &str) -> Result<Rule, ParseError> {
+        let expr = parse(text)?;
+        let depth = depth(&expr) + 1;
+
+        let mut keys = Vec::new();
+        let mut sources = Vec::new();
+        let node =
+            resolve(&expr, depth, &mut keys, &mut sources).map_err(|(position, message)| {
+                ParseError {
+                    rule: text.to_string(),
+                    position,
+                    message,
+                }
+            })?;
+
+        Ok(Rule {
+            text: text.to_string(),
+            node,
+            sources,
+        })
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// next feeds the next candle to every series in the rule, and returns whether it holds
+    pub fn next(&mut self, candle: &Candle) -> bool {
+        for source in &mut self.sources {
+            source.next(candle);
+        }
+        self.holds(&self.node, 0).unwrap_or(false)
+    }
+
+    pub fn reset(&mut self) {
+        for source in &mut self.sources {
+            source.reset();
+        }
+    }
+
+    fn value(&self, node: &Node, offset: usize) -> Option<f64> {
+        let value = match node {
+            Node::Number(n) => *n,
+            Node::Series { source, lookback } => self.sources[*source].get(lookback + offset)?,
+            Node::Negate(node) => -self.value(node, offset)?,
+            Node::Arithmetic(op, left, right) => {
+                let (left, right) = (self.value(left, offset)?, self.value(right, offset)?);
+                match op {
+                    ArithmeticOp::Add => left + right,
+                    ArithmeticOp::Subtract => left - right,
+                    ArithmeticOp::Multiply => left * right,
+                    ArithmeticOp::Divide => left / right,
+                }
+            }
+            _ => return None,
+        };
+
+        // dividing by zero gives no value, rather than an infinity that compares as true
+        Some(value).filter(|v| v.is_finite())
+    }
+
+    /// holds evaluates a condition `offset` candles ago, being `None` while a series it reads has
+    /// no value, so that `not` of a condition that's warming up is still unknown
+    fn holds(&self, node: &Node, offset: usize) -> Option<bool> {
+        match node {
+            Node::Compare(op, left, right) => {
+                let (left, right) = (self.value(left, offset)?, self.value(right, offset)?);
+                Some(match op {
+                    CompareOp::Lt => left < right,
+                    CompareOp::Le => left <= right,
+                    CompareOp::Gt => left > right,
+                    CompareOp::Ge => left >= right,
+                    CompareOp::Eq => left == right,
+                    CompareOp::Ne => left != right,
+                })
+            }
+            Node::CrossesAbove(left, right) | Node::CrossesBelow(left, right) => {
+                let now = self.value(left, offset)? - self.value(right, offset)?;
+                let before = self.value(left, offset + 1)? - self.value(right, offset + 1)?;
+                match node {
+                    Node::CrossesAbove(..) => Some(before <= 0.0 && now > 0.0),
+                    _ => Some(before >= 0.0 && now < 0.0),
+                }
+            }
+            Node::And(left, right) => match (self.holds(left, offset), self.holds(right, offset)) {
+                (Some(false), _) | (_, Some(false)) => Some(false),
+                (Some(true), Some(true)) => Some(true),
+                _ => None,
+            },
+            Node::Or(left, right) => match (self.holds(left, offset), self.holds(right, offset)) {
+                (Some(true), _) | (_, Some(true)) => Some(true),
+                (Some(false), Some(false)) => Some(false),
+                _ => None,
+            },
+            Node::Not(node) => self.holds(node, offset).map(|holds| !holds),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for Rule {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Rule, ParseError> {
+        Rule::parse(s)
+    }
+}
+
+/// depth is how many candles back a rule reads, counting lookbacks and the candle before a crossover
+fn depth(expr: &Expr) -> usize {
+    match expr {
+        Expr::Number(_) => 0,
+        Expr::Series { lookback, .. } => *lookback,
+        Expr::Negate(expr) | Expr::Not(expr) => depth(expr),
+        Expr::CrossesAbove(left, right) | Expr::CrossesBelow(left, right) => {
+            1 + depth(left).max(depth(right))
+        }
+        Expr::Arithmetic(_, left, right)
+        | Expr::Compare(_, left, right)
+        | Expr::And(left, right)
+        | Expr::Or(left, right) => depth(left).max(depth(right)),
+    }
+}
+
+/// resolve builds the sources an expression reads, sharing one between identical series, eg: both
+/// uses of `ema(20)` in `ema(20) > ema(20)[1]`
+fn resolve(
+    expr: &Expr,
+    depth: usize,
+    keys: &mut Vec<String>,
+    sources: &mut Vec<Source>,
+) -> Result<Node, (usize, String)> {
+    let pair = |left: &Expr, right: &Expr, keys: &mut Vec<String>, sources: &mut Vec<Source>| {
+        Ok::<_, (usize, String)>((
+            Box::new(resolve(left, depth, keys, sources)?),
+            Box::new(resolve(right, depth, keys, sources)?),
+        ))
+    };
+
+    Ok(match expr {
+        Expr::Number(n) => Node::Number(*n),
+        Expr::Series {
+            name,
+            args,
+            output,
+            lookback,
+            position,
+        } => {
+            let key = format!("{}{:?}.{:?}", name, args, output);
+            let source = match keys.iter().position(|k| *k == key) {
+                Some(source) => source,
+                None => {
+                    let source = Source::new(name, args, output.as_deref(), depth)
+                        .map_err(|message| (*position, message))?;
+                    keys.push(key);
+                    sources.push(source);
+                    sources.len() - 1
+                }
+            };
+            Node::Series {
+                source,
+                lookback: *lookback,
+            }
+        }
+        Expr::Negate(expr) => Node::Negate(Box::new(resolve(expr, depth, keys, sources)?)),
+        Expr::Not(expr) => Node::Not(Box::new(resolve(expr, depth, keys, sources)?)),
+        Expr::Arithmetic(op, left, right) => {
+            let (left, right) = pair(left, right, keys, sources)?;
+            Node::Arithmetic(*op, left, right)
+        }
+        Expr::Compare(op, left, right) => {
+            let (left, right) = pair(left, right, keys, sources)?;
+            Node::Compare(*op, left, right)
+        }
+        Expr::CrossesAbove(left, right) => {
+            let (left, right) = pair(left, right, keys, sources)?;
+            Node::CrossesAbove(left, right)
+        }
+        Expr::CrossesBelow(left, right) => {
+            let (left, right) = pair(left, right, keys, sources)?;
+            Node::CrossesBelow(left, right)
+        }
+        Expr::And(left, right) => {
+            let (left, right) = pair(left, right, keys, sources)?;
+            Node::And(left, right)
+        }
+        Expr::Or(left, right) => {
+            let (left, right) = pair(left, right, keys, sources)?;
+            Node::Or(left, right)
+        }
+    })
+}
+
+/// RulesConfig holds the rules of a `RuleStrategy`, eg: in TOML
+///
+/// ```toml
+/// name = "rules"
+/// entry = "ema(20) crosses_above ema(50) and rsi(14) < 70"
+/// exit = "ema(20) crosses_below ema(50) or rsi(14) > 80"
+/// side = "long"
+/// ```
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct RulesConfig {
+    pub entry: String,
+    pub exit: String,
+    #[serde(default)]
+    pub side: Side,
+    #[serde(default)]
+    pub size: Size,
+}
+
+/// RuleStrategy enters a position on `side` when its entry rule holds, and exits it when its exit
+/// rule holds. Both rules see every candle, so their indicators stay current while waiting.
+pub struct RuleStrategy {
+    entry: Rule,
+    exit: Rule,
+    side: Side,
+    size: Size,
+}
+
+impl RuleStrategy {
+    pub fn new(
+        entry: &str,
+        exit: &str,
+        side: Side,
+        size: Size,
+    ) -> Result<RuleStrategy, StrategyError> {
+        Ok(RuleStrategy {
+            entry: Rule::parse(entry).map_err(StrategyError::InvalidRule)?,
+            exit: Rule::parse(exit).map_err(StrategyError::InvalidRule)?,
+            side,
+            size,
+        })
+    }
+}
+
+impl TryFrom<&RulesConfig> for RuleStrategy {
+    type Error = StrategyError;
+
+    fn try_from(config: &RulesConfig) -> Result<RuleStrategy, StrategyError> {
+        RuleStrategy::new(&config.entry, &config.exit, config.side, config.size)
+    }
+}
+
+impl Strategy for RuleStrategy {
+    fn name(&self) -> &str {
+        "rules"
+    }
+
+    fn next(&mut self, market: &Market, portfolio: &Portfolio) -> Signal {
+        let candle = match market.current() {
+            Some(candle) if candle.is_finite() => candle,
+            _ => return Signal::Hold,
+        };
+
+        let entry = self.entry.next(candle);
+        let exit = self.exit.next(candle);
+        let open = portfolio
+            .position(market.symbol)
+            .is_some_and(|p| match self.side {
+                Side::Long => p.is_long(),
+                Side::Short => p.is_short(),
+            });
+
+        if open && exit {
+            Signal::Exit
+        } else if !open && entry {
+            self.side.enter(self.size)
+        } else {
+            Signal::Hold
+        }
+    }
+
+    fn reset(&mut self) {
+        self.entry.reset();
+        self.exit.reset();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candles(closes: &[f64]) -> Vec<Candle> {
+        closes
+            .iter()
+            .enumerate()
+            .map(|(i, close)| Candle::new(i, *close, *close, *close, *close, 1.0))
+            .collect()
+    }
+
+    fn evaluate(rule: &str, closes: &[f64]) -> Vec<bool> {
+        let mut rule = Rule::parse(rule).unwrap();
+        candles(closes).iter().map(|c| rule.next(c)).collect()
+    }
+
+    #[test]
+    fn rule_test() {
+        let closes = [1.0, 2.0, 3.0, 2.0, 1.0, 2.0];
+
+        assert_eq!(
+            evaluate("close > close[1]", &closes),
+            vec![false, true, true, false, false, true]
+        );
+        assert_eq!(
+            evaluate(
+                "close crosses_above 1.5 or close crosses_below 2.5",
+                &closes
+            ),
+            vec![false, true, false, true, false, true]
+        );
+        assert_eq!(
+            evaluate("sma(2) < close and not close >= 3", &closes),
+            vec![false, true, false, false, false, true]
+        );
+        assert_eq!(
+            evaluate("(close - close[2]) / close[2] * 100 >= 50", &closes),
+            vec![false, false, true, false, false, false]
+        );
+        assert_eq!(
+            evaluate("close / (close - close) > 0", &closes),
+            vec![false; 6],
+            "division by zero never holds"
+        );
+    }
+
+    #[test]
+    fn warmup_test() {
+        // not, and or of an unknown condition stay unknown, rather than trading during warm up
+        assert_eq!(
+            evaluate("not sma(3) > 100", &[1.0, 2.0, 3.0]),
+            vec![false, false, true]
+        );
+        assert_eq!(
+            evaluate("close > 0 or sma(3) > 100", &[1.0, 2.0, 3.0]),
+            vec![true, true, true]
+        );
+        assert_eq!(
+            evaluate("close < 0 and sma(3) > 100", &[1.0, 2.0, 3.0]),
+            vec![false, false, false]
+        );
+    }
+
+    #[test]
+    fn parse_error_test() {
+        let error = Rule::parse("ema(20) > emma(50)").err().unwrap();
+        assert_eq!(error.position, 10);
+        assert_eq!(error.message, "unknown series 'emma'");
+
+        let error = "macd(12, 26, 9).hist > 0".parse::<Rule>().err().unwrap();
+        assert_eq!(error.position, 0);
+        assert!(error.message.starts_with("macd has no output 'hist'"));
+    }
+
+    #[test]
+    fn rule_strategy_test() {
+        let mut strategy = RuleStrategy::new(
+            "close crosses_above sma(3)",
+            "close < close[1]",
+            Side::Long,
+            Size::Units(1.0),
+        )
+        .unwrap();
+        let candles = candles(&[5.0, 4.0, 3.0, 4.0, 5.0, 4.0, 6.0]);
+        let mut portfolio = Portfolio::new(100.0);
+
+        let signals: Vec<Signal> = (0..candles.len())
+            .map(|i| {
+                let signal = strategy.next(&Market::new("BTC", &candles[..=i]), &portfolio);
+                portfolio.apply("BTC", &signal, candles[i].close);
+                signal
+            })
+            .collect();
+
+        assert_eq!(
+            signals,
+            vec![
+                Signal::Hold,
+                Signal::Hold,
+                Signal::Hold,
+                Signal::EnterLong(Size::Units(1.0)),
+                Signal::Hold,
+                Signal::Exit,
+                Signal::EnterLong(Size::Units(1.0)),
+            ]
+        );
+
+        assert!(matches!(
+            RuleStrategy::new("close >", "close < 1", Side::Short, Size::default()),
+            Err(StrategyError::InvalidRule(_))
+        ));
+    }
+}
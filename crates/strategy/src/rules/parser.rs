@@ -0,0 +1,672 @@
+use std::fmt;
+
+/// The furthest back a rule can look, as every series keeps that many values
+pub const MAX_LOOKBACK: usize = 1000;
+
+/// The deepest a rule can nest, counting brackets, `not`, negation and each chained operator, as
+/// parsing and evaluating a rule recurse once per level
+pub const MAX_DEPTH: usize = 100;
+
+/// ParseError points at where a rule stopped making sense, and why
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParseError {
+    pub rule: String,
+    /// byte offset into `rule` of the offending token
+    pub position: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    /// the message is followed by the rule, with a caret under the offending token, eg:
+    ///
+    /// ```text
+    /// expected ',', found 'and' at column 8
+    ///     ema(20 and rsi(14) < 70
+    ///            ^
+    /// ```
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} at column {}\n    {}\n    {}^",
+            self.message,
+            self.position + 1,
+            self.rule,
+            " ".repeat(self.position)
+        )
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CompareOp {
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Eq,
+    Ne,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ArithmeticOp {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+}
+
+/// Expr is a parsed rule. Numeric expressions are series, evaluated at each candle, which boolean
+/// expressions compare.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Expr {
+    Number(f64),
+    /// a price field or indicator output, `lookback` candles ago, eg: `macd(12, 26, 9).signal[1]`
+    Series {
+        name: String,
+        args: Vec<f64>,
+        output: Option<String>,
+        lookback: usize,
+        position: usize,
+    },
+    Negate(Box<Expr>),
+    Arithmetic(ArithmeticOp, Box<Expr>, Box<Expr>),
+    Compare(CompareOp, Box<Expr>, Box<Expr>),
+    /// true on the candle the left series moves from at or below the right one, to above it
+    CrossesAbove(Box<Expr>, Box<Expr>),
+    /// true on the candle the left series moves from at or above the right one, to below it
+    CrossesBelow(Box<Expr>, Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+}
+
+impl Expr {
+    pub fn is_boolean(&self) -> bool {
+        matches!(
+            self,
+            Expr::Compare(..)
+                | Expr::CrossesAbove(..)
+                | Expr::CrossesBelow(..)
+                | Expr::And(..)
+                | Expr::Or(..)
+                | Expr::Not(..)
+        )
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Number(f64),
+    Ident(String),
+    LeftParen,
+    RightParen,
+    LeftBracket,
+    RightBracket,
+    Comma,
+    Dot,
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    Compare(CompareOp),
+    End,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Number(n) => write!(f, "number {}", n),
+            Token::Ident(name) => write!(f, "'{}'", name),
+            Token::LeftParen => write!(f, "'('"),
+            Token::RightParen => write!(f, "')'"),
+            Token::LeftBracket => write!(f, "'['"),
+            Token::RightBracket => write!(f, "']'"),
+            Token::Comma => write!(f, "','"),
+            Token::Dot => write!(f, "'.'"),
+            Token::Plus => write!(f, "'+'"),
+            Token::Minus => write!(f, "'-'"),
+            Token::Star => write!(f, "'*'"),
+            Token::Slash => write!(f, "'/'"),
+            Token::Compare(_) => write!(f, "comparison"),
+            Token::End => write!(f, "end of rule"),
+        }
+    }
+}
+
+/// tokenize splits a rule into tokens, each with its byte offset
+fn tokenize(rule: &str) -> Result<Vec<(Token, usize)>, ParseError> {
+    let bytes = rule.as_bytes();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    let error = |position: usize, message: String| ParseError {
+        rule: rule.to_string(),
+        position,
+        message,
+    };
+
+    while i < bytes.len() {
+        let c = bytes[i];
+        let start = i;
+
+        if c.is_ascii_whitespace() {
+            i += 1;
+            continue;
+        }
+
+        if c.is_ascii_digit() || (c == b'.' && bytes.get(i + 1).is_some_and(u8::is_ascii_digit)) {
+            while i < bytes.len() && (bytes[i].is_ascii_digit() || bytes[i] == b'.') {
+                i += 1;
+            }
+            let text = &rule[start..i];
+            let number = text
+                .parse()
+                .map_err(|_| error(start, format!("invalid number {}", text)))?;
+            tokens.push((Token::Number(number), start));
+            continue;
+        }
+
+        if c.is_ascii_alphabetic() || c == b'_' {
+            while i < bytes.len() && (bytes[i].is_ascii_alphanumeric() || bytes[i] == b'_') {
+                i += 1;
+            }
+            tokens.push((Token::Ident(rule[start..i].to_lowercase()), start));
+            continue;
+        }
+
+        let (token, length) = match (c, bytes.get(i + 1).copied()) {
+            (b'<', Some(b'=')) => (Token::Compare(CompareOp::Le), 2),
+            (b'>', Some(b'=')) => (Token::Compare(CompareOp::Ge), 2),
+            (b'=', Some(b'=')) => (Token::Compare(CompareOp::Eq), 2),
+            (b'!', Some(b'=')) => (Token::Compare(CompareOp::Ne), 2),
+            (b'<', _) => (Token::Compare(CompareOp::Lt), 1),
+            (b'>', _) => (Token::Compare(CompareOp::Gt), 1),
+            (b'(', _) => (Token::LeftParen, 1),
+            (b')', _) => (Token::RightParen, 1),
+            (b'[', _) => (Token::LeftBracket, 1),
+            (b']', _) => (Token::RightBracket, 1),
+            (b',', _) => (Token::Comma, 1),
+            (b'.', _) => (Token::Dot, 1),
+            (b'+', _) => (Token::Plus, 1),
+            (b'-', _) => (Token::Minus, 1),
+            (b'*', _) => (Token::Star, 1),
+            (b'/', _) => (Token::Slash, 1),
+            _ => {
+                let c = rule[start..].chars().next().unwrap_or_default();
+                return Err(error(start, format!("unexpected character '{}'", c)));
+            }
+        };
+        tokens.push((token, start));
+        i += length;
+    }
+
+    tokens.push((Token::End, rule.len()));
+    Ok(tokens)
+}
+
+/// parse parses a rule, which must be a boolean expression, eg:
+/// `ema(20) crosses_above ema(50) and rsi(14) < 70`
+pub fn parse(rule: &str) -> Result<Expr, ParseError> {
+    let mut parser = Parser {
+        rule,
+        tokens: tokenize(rule)?,
+        index: 0,
+        depth: 0,
+    };
+
+    let expr = parser.or()?;
+    match parser.peek() {
+        Token::End => (),
+        token => return Err(parser.error(format!("unexpected {}", token))),
+    }
+    if !expr.is_boolean() {
+        return Err(ParseError {
+            rule: rule.to_string(),
+            position: 0,
+            message: "rule must be a condition, eg: a comparison or crossover".to_string(),
+        });
+    }
+
+    Ok(expr)
+}
+
+/// Parser is a recursive descent parser, each method parsing one level of precedence, from
+/// `or` binding loosest to `primary` binding tightest
+struct Parser<'a> {
+    rule: &'a str,
+    tokens: Vec<(Token, usize)>,
+    index: usize,
+    /// how deeply nested the expression being parsed is, see `nest`
+    depth: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> &Token {
+        &self.tokens[self.index].0
+    }
+
+    fn position(&self) -> usize {
+        self.tokens[self.index].1
+    }
+
+    fn advance(&mut self) -> Token {
+        let token = self.tokens[self.index].0.clone();
+        if self.index + 1 < self.tokens.len() {
+            self.index += 1;
+        }
+        token
+    }
+
+    fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Token::Ident(name) if name == keyword)
+    }
+
+    fn error(&self, message: String) -> ParseError {
+        self.error_at(self.position(), message)
+    }
+
+    fn error_at(&self, position: usize, message: String) -> ParseError {
+        ParseError {
+            rule: self.rule.to_string(),
+            position,
+            message,
+        }
+    }
+
+    /// nest counts one more level of nesting, failing past `MAX_DEPTH` rather than overflowing the
+    /// stack. Each method restores the depth it started at once it has parsed its expression.
+    fn nest(&mut self) -> Result<(), ParseError> {
+        self.nest_at(self.position())
+    }
+
+    fn nest_at(&mut self, position: usize) -> Result<(), ParseError> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            return Err(self.error_at(
+                position,
+                format!("rule nests deeper than {} levels", MAX_DEPTH),
+            ));
+        }
+        Ok(())
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<(), ParseError> {
+        if *self.peek() == expected {
+            self.advance();
+            return Ok(());
+        }
+        Err(self.error(format!("expected {}, found {}", expected, self.peek())))
+    }
+
+    /// boolean parses an operand of `and`, `or` or `not`, which must be a condition
+    fn boolean(
+        &mut self,
+        parse: fn(&mut Parser<'a>) -> Result<Expr, ParseError>,
+    ) -> Result<Expr, ParseError> {
+        let position = self.position();
+        let expr = parse(self)?;
+        if !expr.is_boolean() {
+            return Err(self.error_at(
+                position,
+                "expected a condition, eg: a comparison or crossover".to_string(),
+            ));
+        }
+        Ok(expr)
+    }
+
+    /// numeric parses an operand of a comparison or arithmetic, which must be a value
+    fn numeric(
+        &mut self,
+        parse: fn(&mut Parser<'a>) -> Result<Expr, ParseError>,
+    ) -> Result<Expr, ParseError> {
+        let position = self.position();
+        let expr = parse(self)?;
+        if expr.is_boolean() {
+            return Err(self.error_at(position, "expected a value, not a condition".to_string()));
+        }
+        Ok(expr)
+    }
+
+    fn or(&mut self) -> Result<Expr, ParseError> {
+        let depth = self.depth;
+        let mut left = self.and()?;
+        while self.is_keyword("or") {
+            left = self.boolean_operand(left, "or")?;
+            self.nest()?;
+            self.advance();
+            let right = self.boolean(Parser::and)?;
+            left = Expr::Or(Box::new(left), Box::new(right));
+        }
+        self.depth = depth;
+        Ok(left)
+    }
+
+    fn and(&mut self) -> Result<Expr, ParseError> {
+        let depth = self.depth;
+        let mut left = self.not()?;
+        while self.is_keyword("and") {
+            left = self.boolean_operand(left, "and")?;
+            self.nest()?;
+            self.advance();
+            let right = self.boolean(Parser::not)?;
+            left = Expr::And(Box::new(left), Box::new(right));
+        }
+        self.depth = depth;
+        Ok(left)
+    }
+
+    /// boolean_operand checks the already parsed left side of `keyword` is a condition
+    fn boolean_operand(&self, left: Expr, keyword: &str) -> Result<Expr, ParseError> {
+        if !left.is_boolean() {
+            return Err(self.error(format!("'{}' needs a condition on its left", keyword)));
+        }
+        Ok(left)
+    }
+
+    fn not(&mut self) -> Result<Expr, ParseError> {
+        if self.is_keyword("not") {
+            self.nest()?;
+            self.advance();
+            let expr = self.boolean(Parser::not)?;
+            self.depth -= 1;
+            return Ok(Expr::Not(Box::new(expr)));
+        }
+        self.comparison()
+    }
+
+    fn comparison(&mut self) -> Result<Expr, ParseError> {
+        let left = self.sum()?;
+
+        let expr = match self.peek().clone() {
+            Token::Compare(op) => {
+                self.numeric_operand(&left)?;
+                self.advance();
+                let right = self.numeric(Parser::sum)?;
+                Expr::Compare(op, Box::new(left), Box::new(right))
+            }
+            Token::Ident(name) if name == "crosses_above" || name == "crosses_below" => {
+                self.numeric_operand(&left)?;
+                self.advance();
+                let right = self.numeric(Parser::sum)?;
+                match name.as_str() {
+                    "crosses_above" => Expr::CrossesAbove(Box::new(left), Box::new(right)),
+                    _ => Expr::CrossesBelow(Box::new(left), Box::new(right)),
+                }
+            }
+            _ => return Ok(left),
+        };
+
+        // comparisons don't chain, as `a < b < c` would compare a condition with a value
+        if let Token::Compare(_) = self.peek() {
+            return Err(self.error("comparisons can't be chained, use 'and'".to_string()));
+        }
+        Ok(expr)
+    }
+
+    fn numeric_operand(&self, left: &Expr) -> Result<(), ParseError> {
+        if left.is_boolean() {
+            return Err(self.error(format!("expected a value on the left of {}", self.peek())));
+        }
+        Ok(())
+    }
+
+    fn sum(&mut self) -> Result<Expr, ParseError> {
+        let depth = self.depth;
+        let mut left = self.product()?;
+        loop {
+            let op = match self.peek() {
+                Token::Plus => ArithmeticOp::Add,
+                Token::Minus => ArithmeticOp::Subtract,
+                _ => {
+                    self.depth = depth;
+                    return Ok(left);
+                }
+            };
+            self.numeric_operand(&left)?;
+            self.nest()?;
+            self.advance();
+            let right = self.numeric(Parser::product)?;
+            left = Expr::Arithmetic(op, Box::new(left), Box::new(right));
+        }
+    }
+
+    fn product(&mut self) -> Result<Expr, ParseError> {
+        let depth = self.depth;
+        let mut left = self.unary()?;
+        loop {
+            let op = match self.peek() {
+                Token::Star => ArithmeticOp::Multiply,
+                Token::Slash => ArithmeticOp::Divide,
+                _ => {
+                    self.depth = depth;
+                    return Ok(left);
+                }
+            };
+            self.numeric_operand(&left)?;
+            self.nest()?;
+            self.advance();
+            let right = self.numeric(Parser::unary)?;
+            left = Expr::Arithmetic(op, Box::new(left), Box::new(right));
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr, ParseError> {
+        if *self.peek() == Token::Minus {
+            self.nest()?;
+            self.advance();
+            let expr = self.numeric(Parser::unary)?;
+            self.depth -= 1;
+            return Ok(Expr::Negate(Box::new(expr)));
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<Expr, ParseError> {
+        let position = self.position();
+
+        match self.advance() {
+            Token::Number(n) => Ok(Expr::Number(n)),
+            Token::LeftParen => {
+                self.nest_at(position)?;
+                let expr = self.or()?;
+                self.expect(Token::RightParen)?;
+                self.depth -= 1;
+                Ok(expr)
+            }
+            Token::Ident(name) if is_keyword(&name) => {
+                Err(self.error_at(position, format!("expected a value, found '{}'", name)))
+            }
+            Token::Ident(name) => self.series(name, position),
+            token => Err(self.error_at(position, format!("expected a value, found {}", token))),
+        }
+    }
+
+    /// series parses the rest of `name(args).output[lookback]`, where each part is optional
+    fn series(&mut self, name: String, position: usize) -> Result<Expr, ParseError> {
+        let mut args = Vec::new();
+        if *self.peek() == Token::LeftParen {
+            self.advance();
+            while *self.peek() != Token::RightParen {
+                if !args.is_empty() {
+                    self.expect(Token::Comma)?;
+                }
+                match self.advance() {
+                    Token::Number(n) => args.push(n),
+                    token => {
+                        return Err(self.error_at(
+                            self.tokens[self.index - 1].1,
+                            format!("expected a number argument, found {}", token),
+                        ))
+                    }
+                }
+            }
+            self.advance();
+        }
+
+        let mut output = None;
+        if *self.peek() == Token::Dot {
+            self.advance();
+            match self.advance() {
+                Token::Ident(name) => output = Some(name),
+                token => {
+                    return Err(self.error_at(
+                        self.tokens[self.index - 1].1,
+                        format!("expected an output name after '.', found {}", token),
+                    ))
+                }
+            }
+        }
+
+        let mut lookback = 0;
+        if *self.peek() == Token::LeftBracket {
+            self.advance();
+            let lookback_position = self.position();
+            lookback = match self.advance() {
+                Token::Number(n) if n >= 0.0 && n.fract() == 0.0 && n <= MAX_LOOKBACK as f64 => {
+                    n as usize
+                }
+                _ => {
+                    return Err(self.error_at(
+                        lookback_position,
+                        format!(
+                            "lookback must be a whole number of candles up to {}, eg: close[1]",
+                            MAX_LOOKBACK
+                        ),
+                    ))
+                }
+            };
+            self.expect(Token::RightBracket)?;
+        }
+
+        Ok(Expr::Series {
+            name,
+            args,
+            output,
+            lookback,
+            position,
+        })
+    }
+}
+
+fn is_keyword(name: &str) -> bool {
+    matches!(
+        name,
+        "and" | "or" | "not" | "crosses_above" | "crosses_below"
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn series(name: &str, args: &[f64], position: usize) -> Box<Expr> {
+        Box::new(Expr::Series {
+            name: name.to_string(),
+            args: args.to_vec(),
+            output: None,
+            lookback: 0,
+            position,
+        })
+    }
+
+    #[test]
+    fn parse_test() {
+        let expr = parse("ema(20) crosses_above ema(50) and rsi(14) < 70").unwrap();
+
+        assert_eq!(
+            expr,
+            Expr::And(
+                Box::new(Expr::CrossesAbove(
+                    series("ema", &[20.0], 0),
+                    series("ema", &[50.0], 22)
+                )),
+                Box::new(Expr::Compare(
+                    CompareOp::Lt,
+                    series("rsi", &[14.0], 34),
+                    Box::new(Expr::Number(70.0))
+                )),
+            )
+        );
+    }
+
+    #[test]
+    fn precedence_test() {
+        let expr = parse("not close > 1 + 2 * 3 or volume >= 10").unwrap();
+
+        match expr {
+            Expr::Or(left, _) => match *left {
+                Expr::Not(inner) => match *inner {
+                    Expr::Compare(CompareOp::Gt, _, right) => match *right {
+                        Expr::Arithmetic(ArithmeticOp::Add, _, product) => assert!(matches!(
+                            *product,
+                            Expr::Arithmetic(ArithmeticOp::Multiply, _, _)
+                        )),
+                        e => panic!("expected addition, got {:?}", e),
+                    },
+                    e => panic!("expected comparison, got {:?}", e),
+                },
+                e => panic!("expected not, got {:?}", e),
+            },
+            e => panic!("expected or, got {:?}", e),
+        }
+    }
+
+    #[test]
+    fn series_test() {
+        let expr = parse("MACD(12, 26, 9).Signal[2] > -0.5").unwrap();
+
+        assert_eq!(
+            expr,
+            Expr::Compare(
+                CompareOp::Gt,
+                Box::new(Expr::Series {
+                    name: "macd".to_string(),
+                    args: vec![12.0, 26.0, 9.0],
+                    output: Some("signal".to_string()),
+                    lookback: 2,
+                    position: 0,
+                }),
+                Box::new(Expr::Negate(Box::new(Expr::Number(0.5)))),
+            )
+        );
+    }
+
+    #[test]
+    fn error_test() {
+        let error = |rule: &str| parse(rule).unwrap_err();
+
+        let e = error("ema(20 and rsi(14) < 70");
+        assert_eq!(e.position, 7);
+        assert_eq!(e.message, "expected ',', found 'and'");
+        assert_eq!(
+            e.to_string(),
+            "expected ',', found 'and' at column 8\n    ema(20 and rsi(14) < 70\n           ^"
+        );
+
+        assert_eq!(
+            error("close > ").message,
+            "expected a value, found end of rule"
+        );
+        assert_eq!(error("close").position, 0);
+        assert_eq!(error("close and volume > 1").position, 6);
+        assert_eq!(error("close > 1 < 2").position, 10);
+        assert_eq!(error("close > 1 $").message, "unexpected character '$'");
+        assert_eq!(error("close[-1] > 1").position, 6);
+        assert_eq!(error("close[1001] > 1").position, 6);
+        assert_eq!(error("(close > 1) + 2 > 1").position, 12);
+        assert_eq!(error("close > 1)").message, "unexpected ')'");
+        assert_eq!(error("1.2.3 > 1").message, "invalid number 1.2.3");
+
+        // deep nesting is an error rather than a stack overflow
+        let nested = format!("{}close > 1{}", "(".repeat(1000), ")".repeat(1000));
+        let e = error(&nested);
+        assert_eq!(e.message, "rule nests deeper than 100 levels");
+        assert_eq!(e.position, MAX_DEPTH);
+        assert!(error(&format!("{}close > 1", "not ".repeat(1000))).position > 0);
+        assert!(error(&format!("close{} > 1", " + -close".repeat(1000))).position > 0);
+
+        let shallow = format!("{}close > 1{}", "(".repeat(50), ")".repeat(50));
+        assert!(parse(&shallow).is_ok());
+    }
+}
@@ -0,0 +1,353 @@
+use std::collections::VecDeque;
+use ta::average::{Dema, Ema, Hma, Sma, Tema, Wma};
+use ta::momentum::{Cci, Mfi, Rsi, Stochastic, WilliamsR};
+use ta::trend::{Adx, Aroon, Macd};
+use ta::volatility::{Atr, BollingerBands, DonchianChannels, KeltnerChannels};
+use ta::volume::{AccumulationDistribution, ChaikinMoneyFlow, Obv, VolumeSma, Vwap};
+use ta::{Candle, Indicator};
+
+type Boxed = Box<dyn Indicator<Output = f64>>;
+
+/// Source is a series a rule reads, with its most recent values kept for lookbacks, newest first
+pub(crate) struct Source {
+    indicator: Boxed,
+    history: VecDeque<Option<f64>>,
+    depth: usize,
+}
+
+impl Source {
+    /// new builds the indicator for `name(args).output`, keeping `depth` values of history
+    pub(crate) fn new(
+        name: &str,
+        args: &[f64],
+        output: Option<&str>,
+        depth: usize,
+    ) -> Result<Source, String> {
+        Ok(Source {
+            indicator: indicator(name, args, output)?,
+            history: VecDeque::with_capacity(depth),
+            depth,
+        })
+    }
+
+    pub(crate) fn next(&mut self, candle: &Candle) {
+        self.history.push_front(self.indicator.next(candle));
+        self.history.truncate(self.depth);
+    }
+
+    /// get returns the value `lookback` candles ago, or `None` if it hasn't been calculated
+    pub(crate) fn get(&self, lookback: usize) -> Option<f64> {
+        self.history.get(lookback).copied().flatten()
+    }
+
+    pub(crate) fn reset(&mut self) {
+        self.indicator.reset();
+        self.history.clear();
+    }
+}
+
+/// Select narrows an indicator with several outputs, eg: MACD, down to the one a rule reads
+struct Select<I: Indicator> {
+    indicator: I,
+    select: fn(I::Output) -> Option<f64>,
+}
+
+impl<I: Indicator> Indicator for Select<I> {
+    type Output = f64;
+
+    fn next(&mut self, candle: &Candle) -> Option<f64> {
+        self.indicator.next(candle).and_then(self.select)
+    }
+
+    fn reset(&mut self) {
+        self.indicator.reset();
+    }
+}
+
+/// Field reads a value straight from each candle
+struct Field(fn(&Candle) -> f64);
+
+impl Indicator for Field {
+    type Output = f64;
+
+    fn next(&mut self, candle: &Candle) -> Option<f64> {
+        Some((self.0)(candle))
+    }
+
+    fn reset(&mut self) {}
+}
+
+fn boxed<I: Indicator + 'static>(indicator: I, select: fn(I::Output) -> Option<f64>) -> Boxed {
+    Box::new(Select { indicator, select })
+}
+
+/// indicator builds the named series, checking its arguments and output
+fn indicator(name: &str, args: &[f64], output: Option<&str>) -> Result<Boxed, String> {
+    let arity = |count: usize| {
+        if args.len() != count {
+            return Err(format!(
+                "{} takes {} argument{}, not {}",
+                name,
+                count,
+                if count == 1 { "" } else { "s" },
+                args.len()
+            ));
+        }
+        Ok(())
+    };
+    let outputs = |allowed: &[&'static str]| -> Result<&'static str, String> {
+        match output {
+            None => Ok(allowed[0]),
+            Some(output) => allowed
+                .iter()
+                .find(|a| **a == output)
+                .copied()
+                .ok_or_else(|| {
+                    format!(
+                        "{} has no output '{}', expected one of {}",
+                        name,
+                        output,
+                        allowed.join(", ")
+                    )
+                }),
+        }
+    };
+    if let Some(output) = output {
+        if FIELDS.contains(&name) || SINGLE.contains(&name) {
+            return Err(format!("{} has no output '{}'", name, output));
+        }
+    }
+
+    let indicator = match name {
+        "open" | "high" | "low" | "close" | "volume" => {
+            arity(0)?;
+            let field: fn(&Candle) -> f64 = match name {
+                "open" => |c| c.open,
+                "high" => |c| c.high,
+                "low" => |c| c.low,
+                "close" => |c| c.close,
+                _ => |c| c.volume,
+            };
+            Box::new(Field(field)) as Boxed
+        }
+        "obv" | "vwap" | "ad" => {
+            arity(0)?;
+            match name {
+                "obv" => boxed(Obv::new(), Some),
+                "vwap" => boxed(Vwap::new(), Some),
+                _ => boxed(AccumulationDistribution::new(), Some),
+            }
+        }
+        "sma" | "ema" | "wma" | "dema" | "tema" | "hma" | "rsi" | "cci" | "mfi" | "williams_r"
+        | "atr" | "cmf" | "volume_sma" => {
+            arity(1)?;
+            let p = period(args[0])?;
+            match name {
+                "sma" => boxed(Sma::<f64>::new(p), Some),
+                "ema" => boxed(Ema::<f64>::new(p), Some),
+                "wma" => boxed(Wma::<f64>::new(p), Some),
+                "dema" => boxed(Dema::<f64>::new(p), Some),
+                "tema" => boxed(Tema::<f64>::new(p), Some),
+                "hma" => boxed(Hma::<f64>::new(p), Some),
+                "rsi" => boxed(Rsi::new(p), Some),
+                "cci" => boxed(Cci::new(p), Some),
+                "mfi" => boxed(Mfi::new(p), Some),
+                "williams_r" => boxed(WilliamsR::new(p), Some),
+                "atr" => boxed(Atr::new(p), Some),
+                "cmf" => boxed(ChaikinMoneyFlow::new(p), Some),
+                _ => boxed(VolumeSma::new(p), Some),
+            }
+        }
+        "stochastic" => {
+            arity(2)?;
+            let indicator = Stochastic::new(period(args[0])?, period(args[1])?);
+            match outputs(&["k", "d"])? {
+                "k" => boxed(indicator, |v| Some(v.k)),
+                _ => boxed(indicator, |v| v.d),
+            }
+        }
+        "macd" => {
+            arity(3)?;
+            let indicator = Macd::new(period(args[0])?, period(args[1])?, period(args[2])?);
+            match outputs(&["macd", "signal", "histogram"])? {
+                "macd" => boxed(indicator, |v| Some(v.macd)),
+                "signal" => boxed(indicator, |v| v.signal),
+                _ => boxed(indicator, |v| v.histogram),
+            }
+        }
+        "adx" => {
+            arity(1)?;
+            let indicator = Adx::new(period(args[0])?);
+            match outputs(&["adx", "plus_di", "minus_di"])? {
+                "adx" => boxed(indicator, |v| v.adx),
+                "plus_di" => boxed(indicator, |v| Some(v.plus_di)),
+                _ => boxed(indicator, |v| Some(v.minus_di)),
+            }
+        }
+        "aroon" => {
+            arity(1)?;
+            let indicator = Aroon::new(period(args[0])?);
+            match outputs(&["oscillator", "up", "down"])? {
+                "oscillator" => boxed(indicator, |v| Some(v.oscillator())),
+                "up" => boxed(indicator, |v| Some(v.up)),
+                _ => boxed(indicator, |v| Some(v.down)),
+            }
+        }
+        "bollinger" => {
+            arity(2)?;
+            let indicator = BollingerBands::new(period(args[0])?, positive(args[1])?);
+            match outputs(&["middle", "upper", "lower", "percent_b", "bandwidth"])? {
+                "middle" => boxed(indicator, |v| Some(v.bands.middle)),
+                "upper" => boxed(indicator, |v| Some(v.bands.upper)),
+                "lower" => boxed(indicator, |v| Some(v.bands.lower)),
+                "percent_b" => boxed(indicator, |v| Some(v.percent_b)),
+                _ => boxed(indicator, |v| Some(v.bandwidth)),
+            }
+        }
+        "keltner" | "donchian" => {
+            let output = outputs(&["middle", "upper", "lower"])?;
+            let select: fn(ta::volatility::Bands) -> Option<f64> = match output {
+                "middle" => |b| Some(b.middle),
+                "upper" => |b| Some(b.upper),
+                _ => |b| Some(b.lower),
+            };
+            match name {
+                "keltner" => {
+                    arity(3)?;
+                    let (ema, atr) = (period(args[0])?, period(args[1])?);
+                    boxed(KeltnerChannels::new(ema, atr, positive(args[2])?), select)
+                }
+                _ => {
+                    arity(1)?;
+                    boxed(DonchianChannels::new(period(args[0])?), select)
+                }
+            }
+        }
+        _ => return Err(format!("unknown series '{}'", name)),
+    };
+
+    Ok(indicator)
+}
+
+/// FIELDS are the candle values a rule can read directly
+const FIELDS: [&str; 5] = ["open", "high", "low", "close", "volume"];
+
+/// SINGLE are the indicators with a single output
+const SINGLE: [&str; 16] = [
+    "obv",
+    "vwap",
+    "ad",
+    "sma",
+    "ema",
+    "wma",
+    "dema",
+    "tema",
+    "hma",
+    "rsi",
+    "cci",
+    "mfi",
+    "williams_r",
+    "atr",
+    "cmf",
+    "volume_sma",
+];
+
+fn period(arg: f64) -> Result<usize, String> {
+    if arg < 1.0 || arg.fract() != 0.0 || arg > u32::MAX as f64 {
+        return Err(format!(
+            "period must be a whole number above 0, not {}",
+            arg
+        ));
+    }
+    Ok(arg as usize)
+}
+
+fn positive(arg: f64) -> Result<f64, String> {
+    if !arg.is_finite() || arg <= 0.0 {
+        return Err(format!("multiplier must be above 0, not {}", arg));
+    }
+    Ok(arg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values(name: &str, args: &[f64], output: Option<&str>, closes: &[f64]) -> Vec<Option<f64>> {
+        let mut source = Source::new(name, args, output, 2).unwrap();
+        closes
+            .iter()
+            .enumerate()
+            .map(|(i, close)| {
+                source.next(&Candle::new(
+                    i,
+                    *close,
+                    *close + 1.0,
+                    *close - 1.0,
+                    *close,
+                    10.0,
+                ));
+                source.get(0)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn source_test() {
+        let closes = [1.0, 2.0, 3.0, 4.0];
+
+        assert_eq!(
+            values("close", &[], None, &closes),
+            vec![Some(1.0), Some(2.0), Some(3.0), Some(4.0)]
+        );
+        assert_eq!(
+            values("high", &[], None, &closes),
+            vec![Some(2.0), Some(3.0), Some(4.0), Some(5.0)]
+        );
+        assert_eq!(
+            values("sma", &[2.0], None, &closes),
+            vec![None, Some(1.5), Some(2.5), Some(3.5)]
+        );
+        assert_eq!(
+            values("donchian", &[2.0], Some("upper"), &closes),
+            vec![None, Some(3.0), Some(4.0), Some(5.0)]
+        );
+
+        let mut source = Source::new("close", &[], None, 2).unwrap();
+        for i in 0..4 {
+            source.next(&Candle::new(i, 0.0, 0.0, 0.0, i as f64, 0.0));
+        }
+        assert_eq!(source.get(1), Some(2.0));
+        assert_eq!(source.get(2), None, "only `depth` values are kept");
+
+        source.reset();
+        assert_eq!(source.get(0), None);
+    }
+
+    #[test]
+    fn invalid_test() {
+        let error = |name: &str, args: &[f64], output: Option<&str>| {
+            Source::new(name, args, output, 1).err().unwrap()
+        };
+
+        assert_eq!(error("ema", &[], None), "ema takes 1 argument, not 0");
+        assert_eq!(
+            error("macd", &[12.0], None),
+            "macd takes 3 arguments, not 1"
+        );
+        assert_eq!(
+            error("ema", &[2.5], None),
+            "period must be a whole number above 0, not 2.5"
+        );
+        assert_eq!(error("close", &[], Some("x")), "close has no output 'x'");
+        assert_eq!(
+            error("macd", &[12.0, 26.0, 9.0], Some("x")),
+            "macd has no output 'x', expected one of macd, signal, histogram"
+        );
+        assert_eq!(
+            error("bollinger", &[20.0, 0.0], None),
+            "multiplier must be above 0, not 0"
+        );
+        assert_eq!(error("ichimoku", &[], None), "unknown series 'ichimoku'");
+    }
+}
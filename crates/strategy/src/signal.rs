@@ -0,0 +1,79 @@
+use serde::{Deserialize, Serialize};
+
+/// Size is how large a position a signal asks for
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Size {
+    /// a fixed number of shares or coins
+    Units(f64),
+    /// a fraction of the portfolio's cash, eg: 0.5 to put half of it in
+    Fraction(f64),
+}
+
+impl Size {
+    /// quantity converts the size into units at `price`, given the cash available
+    pub fn quantity(&self, cash: f64, price: f64) -> f64 {
+        match *self {
+            Size::Units(units) => units,
+            Size::Fraction(fraction) if price > 0.0 => cash.max(0.0) * fraction / price,
+            Size::Fraction(_) => 0.0,
+        }
+    }
+}
+
+impl Default for Size {
+    fn default() -> Size {
+        Size::Fraction(1.0)
+    }
+}
+
+/// Side is the direction of a position
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Side {
+    #[default]
+    Long,
+    Short,
+}
+
+impl Side {
+    /// enter returns the signal entering a position of `size` on this side
+    pub fn enter(&self, size: Size) -> Signal {
+        match self {
+            Side::Long => Signal::EnterLong(size),
+            Side::Short => Signal::EnterShort(size),
+        }
+    }
+}
+
+/// Signal is what a strategy wants done with its symbol after seeing a candle
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub enum Signal {
+    /// buy, closing any short position first
+    EnterLong(Size),
+    /// sell short, closing any long position first
+    EnterShort(Size),
+    /// close whatever position is open
+    Exit,
+    /// do nothing
+    Hold,
+}
+
+impl Signal {
+    pub fn is_hold(&self) -> bool {
+        *self == Signal::Hold
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quantity_test() {
+        assert_eq!(Size::Units(3.0).quantity(0.0, 10.0), 3.0);
+        assert_eq!(Size::Fraction(0.5).quantity(1000.0, 25.0), 20.0);
+        assert_eq!(Size::Fraction(1.0).quantity(-50.0, 25.0), 0.0);
+        assert_eq!(Size::Fraction(1.0).quantity(1000.0, 0.0), 0.0);
+    }
+}
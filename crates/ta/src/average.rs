@@ -1,66 +1,504 @@
-use tda_sdk::responses::Candle;
+use crate::error::{check_candles, check_length, check_period};
+use crate::number::{Number, Rounding};
+use crate::{Candle, Indicator, TaError};
+use std::collections::VecDeque;
+use std::convert::TryFrom;
 
 // https://www.investopedia.com/terms/e/ema.asp
-// symbol - ticker symbol of the security you want to query
 // period - number in days to grab info for
 //
 // You can cross check https://www.tradingview.com/symbols/$exchange-$symbol/technicals/ for validity/manual checking
 // eg: https://www.tradingview.com/symbols/NASDAQ-MSFT/technicals/
 //
-// returns computed ema as f64
-pub fn ema(candles: &[Candle], period: i32) -> f64 {
-    let sma_i = (period) as usize;
-    let sma_e = (2 * period) as usize;
-    let base_case = sma(candles, sma_i as usize, sma_e as usize);
+// returns the most recent value of ema_series at full precision, see `Rounding` to round it for display,
+// or an error when the period is zero or negative, any candle isn't finite, or there are fewer than `period` candles
+pub fn ema(candles: &[Candle], period: i32) -> Result<f64, TaError> {
+    let period = usize::try_from(period).unwrap_or(0);
+    check_period(period)?;
+    check_length(candles, period)?;
 
-    let len_candles = candles.len();
-    let close = candles[len_candles - (period as usize)].close;
-    let smoothing_factor = 2.0;
-    let multiplier = smoothing_factor / (1.0 + f64::from(period));
-    let ema0 = round((close - base_case) * multiplier + base_case);
+    match ema_series(candles, period)?.last() {
+        Some(Some(value)) => Ok(*value),
+        _ => Err(TaError::InsufficientData {
+            required: period,
+            available: candles.len(),
+        }),
+    }
+}
+
+// sma returns the simple moving average of the `period` closes ending `start` candles before the most recent one
+// at full precision, or an error when the period is zero, any of those candles isn't finite, or there are fewer than `start + period` candles
+pub fn sma(candles: &[Candle], start: usize, period: usize) -> Result<f64, TaError> {
+    check_period(period)?;
+    let required = start.checked_add(period).ok_or(TaError::InsufficientData {
+        required: usize::MAX,
+        available: candles.len(),
+    })?;
+    check_length(candles, required)?;
+
+    let window = &candles[candles.len() - start - period..candles.len() - start];
+    check_candles(window)?;
+
+    let sum: f64 = window.iter().map(|candle| candle.close).sum();
+    Ok(sum / (period as f64))
+}
+
+/// sma_series returns the simple moving average of closes for every candle.
+///
+/// The result is aligned with `candles`, the first `period - 1` values are `None` as there isn't
+/// enough history to fill the window yet.
+pub fn sma_series<T: Number>(
+    candles: &[Candle<T>],
+    period: usize,
+) -> Result<Vec<Option<T>>, TaError> {
+    check_period(period)?;
+
+    Sma::new(period).try_calculate(candles)
+}
+
+/// ema_series returns the exponential moving average of closes for every candle, see `Ema`.
+pub fn ema_series<T: Number>(
+    candles: &[Candle<T>],
+    period: usize,
+) -> Result<Vec<Option<T>>, TaError> {
+    check_period(period)?;
+
+    Ema::new(period).try_calculate(candles)
+}
+
+/// wma_series returns the linearly weighted moving average of closes for every candle, see `Wma`.
+pub fn wma_series<T: Number>(
+    candles: &[Candle<T>],
+    period: usize,
+) -> Result<Vec<Option<T>>, TaError> {
+    check_period(period)?;
+
+    Wma::new(period).try_calculate(candles)
+}
+
+/// dema_series returns the double exponential moving average of closes for every candle, see `Dema`.
+/// The first `2 x (period - 1)` values are `None`.
+pub fn dema_series<T: Number>(
+    candles: &[Candle<T>],
+    period: usize,
+) -> Result<Vec<Option<T>>, TaError> {
+    check_period(period)?;
+
+    Dema::new(period).try_calculate(candles)
+}
+
+/// tema_series returns the triple exponential moving average of closes for every candle, see `Tema`.
+/// The first `3 x (period - 1)` values are `None`.
+pub fn tema_series<T: Number>(
+    candles: &[Candle<T>],
+    period: usize,
+) -> Result<Vec<Option<T>>, TaError> {
+    check_period(period)?;
+
+    Tema::new(period).try_calculate(candles)
+}
+
+/// hma_series returns the Hull moving average of closes for every candle, see `Hma`.
+pub fn hma_series<T: Number>(
+    candles: &[Candle<T>],
+    period: usize,
+) -> Result<Vec<Option<T>>, TaError> {
+    check_period(period)?;
 
-    let mut emas = vec![ema0];
-    // EMA = Closing price x multiplier + EMA (previous day) x (1-multiplier)
-    for i in 0..period - 1 {
-        let len_candles = candles.len();
-        let close = candles[len_candles - ((period - i) as usize)].close;
-        let previous_ema = emas[i as usize];
+    Hma::new(period).try_calculate(candles)
+}
+
+/// sma_values returns the simple moving average of every value, for series held in another
+/// `Number` type, eg: `rust_decimal::Decimal` prices of sub-cent crypto pairs.
+pub fn sma_values<T: Number>(values: &[T], period: usize) -> Result<Vec<Option<T>>, TaError> {
+    check_period(period)?;
+
+    let mut sma = Sma::new(period);
+    Ok(values.iter().map(|value| sma.update(*value)).collect())
+}
+
+/// ema_values returns the exponential moving average of every value, see `sma_values`.
+pub fn ema_values<T: Number>(values: &[T], period: usize) -> Result<Vec<Option<T>>, TaError> {
+    check_period(period)?;
+
+    let mut ema = Ema::new(period);
+    Ok(values.iter().map(|value| ema.update(*value)).collect())
+}
+
+// round is a helper for f64 that rounds the number to a decimal point notation used for representing money,
+// the same as `Rounding::CENTS.apply`
+pub fn round(i: f64) -> f64 {
+    Rounding::CENTS.apply(i)
+}
+
+/// Sma is a streaming simple moving average.
+///
+/// Each average type exposes `update`, which takes a raw value rather than a candle, so that
+/// averages can be chained onto other indicators, eg: the EMA of an EMA for `Dema`.
+///
+/// The averages are generic over `Number`, so that `update` can be fed `rust_decimal::Decimal`
+/// values, eg: `Sma::<Decimal>::new(20)`, which implements `Indicator<Decimal>` over
+/// `Candle<Decimal>`.
+#[derive(Clone, Debug)]
+pub struct Sma<T = f64> {
+    period: usize,
+    window: VecDeque<T>,
+    sum: T,
+}
+
+impl<T: Number> Sma<T> {
+    pub fn new(period: usize) -> Sma<T> {
+        Sma {
+            period,
+            window: VecDeque::with_capacity(period + 1),
+            sum: T::zero(),
+        }
+    }
 
-        let ema_i = round((close - previous_ema) * multiplier + previous_ema);
-        emas.push(ema_i);
+    pub fn update(&mut self, value: T) -> Option<T> {
+        if self.period == 0 {
+            return None;
+        }
+
+        self.window.push_back(value);
+        self.sum += value;
+        if self.window.len() > self.period {
+            self.sum -= self.window.pop_front().unwrap_or_default();
+        }
+
+        if self.window.len() < self.period {
+            return None;
+        }
+        Some(self.sum / T::from_count(self.period))
     }
 
-    emas[emas.len() - 1]
+    pub fn reset(&mut self) {
+        self.window.clear();
+        self.sum = T::zero();
+    }
 }
 
-pub fn sma(candles: &[Candle], start: usize, period: usize) -> f64 {
-    let mut sum = 0.0;
+impl<T: Number> Indicator<T> for Sma<T> {
+    type Output = T;
 
-    for i in start..start + period {
-        let i_usize = i as usize;
-        sum += candles[candles.len() - 1 - i_usize].close;
+    fn next(&mut self, candle: &Candle<T>) -> Option<T> {
+        self.update(candle.close)
     }
 
-    let average = sum / (period as f64);
+    fn reset(&mut self) {
+        Sma::reset(self);
+    }
+}
 
-    round(average)
+/// Ema is a streaming exponential moving average.
+///
+/// The EMA is seeded with the SMA of the first `period` values, and then smoothed with a
+/// multiplier of `2 / (period + 1)`:
+///     EMA = (value - previous EMA) x multiplier + previous EMA
+#[derive(Clone, Debug)]
+pub struct Ema<T = f64> {
+    multiplier: T,
+    seed: Sma<T>,
+    current: Option<T>,
 }
 
-// round is a helper for f64 that rounds the number to a decimal point notation used for representing money
-pub fn round(i: f64) -> f64 {
-    (i * 100.0).round() / 100.0
+impl<T: Number> Ema<T> {
+    pub fn new(period: usize) -> Ema<T> {
+        Ema {
+            multiplier: T::from_count(2) / T::from_count(period + 1),
+            seed: Sma::new(period),
+            current: None,
+        }
+    }
+
+    pub fn update(&mut self, value: T) -> Option<T> {
+        self.current = match self.current {
+            Some(previous) => Some((value - previous) * self.multiplier + previous),
+            None => self.seed.update(value),
+        };
+        self.current
+    }
+
+    pub fn reset(&mut self) {
+        self.seed.reset();
+        self.current = None;
+    }
+}
+
+impl<T: Number> Indicator<T> for Ema<T> {
+    type Output = T;
+
+    fn next(&mut self, candle: &Candle<T>) -> Option<T> {
+        self.update(candle.close)
+    }
+
+    fn reset(&mut self) {
+        Ema::reset(self);
+    }
+}
+
+/// Rma is Wilder's running moving average, used by RSI, ATR and ADX.
+///
+/// It is seeded with the SMA of the first `period` values, after which each value is smoothed as
+/// `(previous x (period - 1) + value) / period`, the same as an EMA with a multiplier of `1 / period`.
+#[derive(Clone, Debug)]
+pub struct Rma<T = f64> {
+    period: usize,
+    seed: Sma<T>,
+    current: Option<T>,
+}
+
+impl<T: Number> Rma<T> {
+    pub fn new(period: usize) -> Rma<T> {
+        Rma {
+            period,
+            seed: Sma::new(period),
+            current: None,
+        }
+    }
+
+    pub fn update(&mut self, value: T) -> Option<T> {
+        self.current = match self.current {
+            Some(previous) => {
+                let period = T::from_count(self.period);
+                Some((previous * (period - T::one()) + value) / period)
+            }
+            None => self.seed.update(value),
+        };
+        self.current
+    }
+
+    pub fn reset(&mut self) {
+        self.seed.reset();
+        self.current = None;
+    }
+}
+
+impl<T: Number> Indicator<T> for Rma<T> {
+    type Output = T;
+
+    fn next(&mut self, candle: &Candle<T>) -> Option<T> {
+        self.update(candle.close)
+    }
+
+    fn reset(&mut self) {
+        Rma::reset(self);
+    }
+}
+
+/// Wma is a streaming linearly weighted moving average, where the most recent value in the window
+/// has a weight of `period` and the oldest a weight of 1.
+#[derive(Clone, Debug)]
+pub struct Wma<T = f64> {
+    period: usize,
+    window: VecDeque<T>,
+    sum: T,
+    weighted_sum: T,
+}
+
+impl<T: Number> Wma<T> {
+    pub fn new(period: usize) -> Wma<T> {
+        Wma {
+            period,
+            window: VecDeque::with_capacity(period),
+            sum: T::zero(),
+            weighted_sum: T::zero(),
+        }
+    }
+
+    pub fn update(&mut self, value: T) -> Option<T> {
+        if self.period == 0 {
+            return None;
+        }
+
+        if self.window.len() == self.period {
+            // every value in the window loses one weight, and the oldest drops out entirely
+            self.weighted_sum += T::from_count(self.period) * value - self.sum;
+            self.sum += value - self.window.pop_front().unwrap_or_default();
+        } else {
+            self.weighted_sum += T::from_count(self.window.len() + 1) * value;
+            self.sum += value;
+        }
+        self.window.push_back(value);
+
+        if self.window.len() < self.period {
+            return None;
+        }
+        let denominator = T::from_count(self.period * (self.period + 1) / 2);
+        Some(self.weighted_sum / denominator)
+    }
+
+    pub fn reset(&mut self) {
+        self.window.clear();
+        self.sum = T::zero();
+        self.weighted_sum = T::zero();
+    }
+}
+
+impl<T: Number> Indicator<T> for Wma<T> {
+    type Output = T;
+
+    fn next(&mut self, candle: &Candle<T>) -> Option<T> {
+        self.update(candle.close)
+    }
+
+    fn reset(&mut self) {
+        Wma::reset(self);
+    }
+}
+
+/// Dema is a streaming double exponential moving average, `2 x EMA - EMA(EMA)`.
+#[derive(Clone, Debug)]
+pub struct Dema<T = f64> {
+    ema1: Ema<T>,
+    ema2: Ema<T>,
+}
+
+impl<T: Number> Dema<T> {
+    pub fn new(period: usize) -> Dema<T> {
+        Dema {
+            ema1: Ema::new(period),
+            ema2: Ema::new(period),
+        }
+    }
+
+    pub fn update(&mut self, value: T) -> Option<T> {
+        let ema1 = self.ema1.update(value)?;
+        let ema2 = self.ema2.update(ema1)?;
+
+        Some(T::from_count(2) * ema1 - ema2)
+    }
+
+    pub fn reset(&mut self) {
+        self.ema1.reset();
+        self.ema2.reset();
+    }
+}
+
+impl<T: Number> Indicator<T> for Dema<T> {
+    type Output = T;
+
+    fn next(&mut self, candle: &Candle<T>) -> Option<T> {
+        self.update(candle.close)
+    }
+
+    fn reset(&mut self) {
+        Dema::reset(self);
+    }
+}
+
+/// Tema is a streaming triple exponential moving average, `3 x EMA - 3 x EMA(EMA) + EMA(EMA(EMA))`.
+#[derive(Clone, Debug)]
+pub struct Tema<T = f64> {
+    ema1: Ema<T>,
+    ema2: Ema<T>,
+    ema3: Ema<T>,
+}
+
+impl<T: Number> Tema<T> {
+    pub fn new(period: usize) -> Tema<T> {
+        Tema {
+            ema1: Ema::new(period),
+            ema2: Ema::new(period),
+            ema3: Ema::new(period),
+        }
+    }
+
+    pub fn update(&mut self, value: T) -> Option<T> {
+        let ema1 = self.ema1.update(value)?;
+        let ema2 = self.ema2.update(ema1)?;
+        let ema3 = self.ema3.update(ema2)?;
+
+        let three = T::from_count(3);
+        Some(three * ema1 - three * ema2 + ema3)
+    }
+
+    pub fn reset(&mut self) {
+        self.ema1.reset();
+        self.ema2.reset();
+        self.ema3.reset();
+    }
+}
+
+impl<T: Number> Indicator<T> for Tema<T> {
+    type Output = T;
+
+    fn next(&mut self, candle: &Candle<T>) -> Option<T> {
+        self.update(candle.close)
+    }
+
+    fn reset(&mut self) {
+        Tema::reset(self);
+    }
+}
+
+/// Hma is a streaming Hull moving average, `WMA(2 x WMA(period / 2) - WMA(period), sqrt(period))`.
+/// <https://alanhull.com/hull-moving-average>
+#[derive(Clone, Debug)]
+pub struct Hma<T = f64> {
+    half: Wma<T>,
+    full: Wma<T>,
+    smooth: Wma<T>,
+}
+
+impl<T: Number> Hma<T> {
+    pub fn new(period: usize) -> Hma<T> {
+        Hma {
+            half: Wma::new(period / 2),
+            full: Wma::new(period),
+            smooth: Wma::new((period as f64).sqrt() as usize),
+        }
+    }
+
+    pub fn update(&mut self, value: T) -> Option<T> {
+        let half = self.half.update(value);
+        let full = self.full.update(value)?;
+
+        self.smooth.update(T::from_count(2) * half? - full)
+    }
+
+    pub fn reset(&mut self) {
+        self.half.reset();
+        self.full.reset();
+        self.smooth.reset();
+    }
+}
+
+impl<T: Number> Indicator<T> for Hma<T> {
+    type Output = T;
+
+    fn next(&mut self, candle: &Candle<T>) -> Option<T> {
+        self.update(candle.close)
+    }
+
+    fn reset(&mut self) {
+        Hma::reset(self);
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::fixtures::*;
+    use rust_decimal::prelude::ToPrimitive;
+    use rust_decimal::Decimal;
+    use rust_decimal_macros::dec;
     use tda_sdk::responses::GetPriceHistoryResponse;
 
     #[test]
     fn sma_test() {
         let candles = &test_helper();
-        let sma = sma(candles, 0, 3);
-        assert_eq!(sma, 10.76);
+        assert_eq!(sma(candles, 0, 3).map(round), Ok(10.76));
+        assert_eq!(sma(candles, 1, 3).map(round), Ok(11.09));
+
+        assert_eq!(sma(candles, 0, 0), Err(TaError::ZeroPeriod));
+        assert_eq!(
+            sma(&candles[..2], 0, 3),
+            Err(TaError::InsufficientData {
+                required: 3,
+                available: 2
+            })
+        );
     }
 
     #[test]
@@ -81,6 +519,170 @@ mod tests {
         assert_eq!(rounded, 4.57);
     }
 
+    #[test]
+    fn streaming_matches_series_test() {
+        let candles = stockcharts_candles();
+        let expected = tema_series(&candles, 5).unwrap();
+
+        let mut tema = Tema::new(5);
+        let streamed: Vec<Option<f64>> = candles.iter().map(|c| tema.next(c)).collect();
+        assert_series(&streamed, &expected);
+
+        tema.reset();
+        assert_series(&tema.calculate(&candles), &expected);
+    }
+
+    #[test]
+    fn ema_test() {
+        let candles = stockcharts_candles();
+        assert_eq!(ema(&candles, 5).map(round), Ok(43.51));
+
+        assert_eq!(
+            ema(&candles[..4], 5),
+            Err(TaError::InsufficientData {
+                required: 5,
+                available: 4
+            })
+        );
+        assert_eq!(ema(&candles, 0), Err(TaError::ZeroPeriod));
+        assert_eq!(ema(&candles, -5), Err(TaError::ZeroPeriod));
+
+        let mut candles = candles;
+        candles[3].close = f64::NAN;
+        assert_eq!(
+            ema(&candles, 5),
+            Err(TaError::NonFinite {
+                datetime: candles[3].datetime
+            })
+        );
+    }
+
+    #[test]
+    fn sma_series_test() {
+        let candles = stockcharts_candles();
+        let expected = warmup(
+            4,
+            &[
+                44.104, 44.202, 44.404, 44.658, 45.104, 45.454, 45.666, 45.852, 45.89, 45.978,
+                46.018, 46.04, 46.04, 46.2, 46.188, 46.06, 46.102, 46.146, 46.006, 46.052, 46.08,
+                45.908, 45.464, 45.158, 44.712, 44.47, 44.084, 43.81, 43.6,
+            ],
+        );
+
+        assert_series(&sma_series(&candles, 5).unwrap(), &expected);
+    }
+
+    #[test]
+    fn ema_series_test() {
+        let candles = stockcharts_candles();
+        let expected = warmup(
+            4,
+            &[
+                44.104, 44.346, 44.5973, 44.8716, 45.1944, 45.4896, 45.6231, 45.7587, 45.7091,
+                45.8994, 46.0263, 46.0175, 46.0217, 46.1511, 46.1741, 45.9961, 46.0674, 46.1282,
+                45.9888, 46.1426, 46.0217, 45.7978, 45.2085, 44.8657, 44.6505, 44.6236, 44.2224,
+                43.7016, 43.5111,
+            ],
+        );
+
+        assert_series(&ema_series(&candles, 5).unwrap(), &expected);
+    }
+
+    #[test]
+    fn wma_series_test() {
+        let candles = stockcharts_candles();
+        let expected = warmup(
+            4,
+            &[
+                44.0707, 44.3127, 44.612, 44.9507, 45.3447, 45.67, 45.8153, 45.9367, 45.856,
+                45.986, 46.0867, 46.0807, 46.0773, 46.2007, 46.2073, 46.0247, 46.0747, 46.124,
+                45.9787, 46.1267, 46.036, 45.7927, 45.1667, 44.7387, 44.426, 44.3787, 44.0287,
+                43.554, 43.3273,
+            ],
+        );
+
+        assert_series(&wma_series(&candles, 5).unwrap(), &expected);
+    }
+
+    #[test]
+    fn dema_series_test() {
+        let candles = stockcharts_candles();
+        let expected = warmup(
+            8,
+            &[
+                45.7661, 46.0675, 46.0973, 46.1653, 45.9472, 46.185, 46.3012, 46.195, 46.1428,
+                46.3181, 46.3007, 45.9618, 46.0921, 46.1853, 45.9339, 46.2084, 45.9851, 45.6241,
+                44.6999, 44.298, 44.1285, 44.2578, 43.7111, 43.0135, 42.9253,
+            ],
+        );
+
+        assert_series(&dema_series(&candles, 5).unwrap(), &expected);
+    }
+
+    #[test]
+    fn tema_series_test() {
+        let candles = stockcharts_candles();
+        let expected = warmup(
+            12,
+            &[
+                45.8154, 46.1288, 46.2567, 46.1003, 46.042, 46.2816, 46.2495, 45.8204, 46.0371,
+                46.1702, 45.8492, 46.2325, 45.9327, 45.4979, 44.3924, 44.0537, 43.9961, 44.2736,
+                43.6246, 42.838, 42.8765,
+            ],
+        );
+
+        assert_series(&tema_series(&candles, 5).unwrap(), &expected);
+    }
+
+    #[test]
+    fn hma_series_test() {
+        let candles = stockcharts_candles();
+        let expected = warmup(
+            10,
+            &[
+                46.2907, 46.3395, 46.1566, 46.1036, 46.1609, 46.1946, 46.1796, 46.2223, 46.2613,
+                46.1176, 46.046, 46.051, 45.9826, 46.0869, 46.0426, 45.8119, 45.1154, 44.3669,
+                43.8546, 43.794, 43.6837, 43.2665, 42.8811,
+            ],
+        );
+
+        assert_series(&hma_series(&candles, 9).unwrap(), &expected);
+    }
+
+    #[test]
+    fn decimal_values_test() {
+        // XDG trades in fractions of a cent, which rounding to cents would turn into 0
+        let prices = [
+            dec!(0.00001234),
+            dec!(0.00001250),
+            dec!(0.00001198),
+            dec!(0.00001301),
+            dec!(0.00001277),
+        ];
+
+        let sma = sma_values(&prices, 3).unwrap();
+        assert_eq!(sma[..2], [None, None]);
+        assert_eq!(sma[2], Some(dec!(0.0000122733333333333333333333)));
+        assert_eq!(
+            Rounding::DecimalPlaces(8).apply(sma[4].unwrap()),
+            dec!(0.00001259)
+        );
+        assert_eq!(Rounding::CENTS.apply(sma[4].unwrap()), dec!(0));
+
+        // the Decimal and f64 averages agree, apart from f64's representation error
+        let floats: Vec<f64> = prices.iter().map(|p| p.to_f64().unwrap()).collect();
+        let decimal_ema = ema_values(&prices, 3).unwrap();
+        let float_ema = ema_values(&floats, 3).unwrap();
+        for (decimal, float) in decimal_ema.iter().zip(float_ema.iter()) {
+            match (decimal, float) {
+                (Some(d), Some(f)) => assert!((d.to_f64().unwrap() - f).abs() < 1e-15),
+                (d, f) => assert_eq!(d.is_none(), f.is_none()),
+            }
+        }
+
+        assert_eq!(sma_values::<Decimal>(&prices, 0), Err(TaError::ZeroPeriod));
+    }
+
     fn test_helper() -> Vec<Candle> {
         let sample_ticker_data = r#"{
             "candles": [
@@ -124,6 +726,6 @@ mod tests {
 
         let sample: GetPriceHistoryResponse = serde_json::from_str(sample_ticker_data).unwrap();
 
-        sample.candles
+        sample.candles.into_iter().map(Candle::from).collect()
     }
 }
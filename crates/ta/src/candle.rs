@@ -0,0 +1,245 @@
+use crate::number::{max, Number};
+use serde::{Deserialize, Serialize};
+use std::{convert::TryFrom, ops::Deref, str::FromStr};
+
+/// Candle is a vendor neutral OHLCV bar that every indicator in this crate operates on.
+///
+/// `datetime` is an epoch timestamp in milliseconds, which is what TD Ameritrade returns and
+/// what the collector stores under `equity/daily/*.json`. `volume` is fractional so that crypto
+/// volumes can be represented without truncation.
+///
+/// Prices and volume are `f64` by default, or any other `Number`, eg: `Candle<Decimal>` for
+/// sub-cent crypto pairs parsed exactly from Kraken, which every indicator is then calculated in.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct Candle<T = f64> {
+    pub close: T,
+    pub datetime: usize,
+    pub high: T,
+    pub low: T,
+    pub open: T,
+    pub volume: T,
+}
+
+impl<T: Number> Candle<T> {
+    pub fn new(datetime: usize, open: T, high: T, low: T, close: T, volume: T) -> Candle<T> {
+        Candle {
+            close,
+            datetime,
+            high,
+            low,
+            open,
+            volume,
+        }
+    }
+
+    /// is_finite is true when none of the candle's prices or volume are NaN or infinite
+    pub fn is_finite(&self) -> bool {
+        self.open.is_finite()
+            && self.high.is_finite()
+            && self.low.is_finite()
+            && self.close.is_finite()
+            && self.volume.is_finite()
+    }
+
+    /// true_range is the greatest of the candle's range, and the distance from the previous close to
+    /// either the high or low. Without a previous close, it is just the candle's range.
+    pub fn true_range(&self, previous_close: Option<T>) -> T {
+        let range = self.high - self.low;
+
+        match previous_close {
+            Some(close) => max(
+                max(range, (self.high - close).abs()),
+                (self.low - close).abs(),
+            ),
+            None => range,
+        }
+    }
+}
+
+impl From<tda_sdk::responses::Candle> for Candle {
+    fn from(candle: tda_sdk::responses::Candle) -> Candle {
+        Candle {
+            close: candle.close,
+            datetime: candle.datetime,
+            high: candle.high,
+            low: candle.low,
+            open: candle.open,
+            volume: candle.volume as f64,
+        }
+    }
+}
+
+/// KrakenOhlc models a single row of the Kraken `OHLC` endpoint, which is returned as an array of
+/// `[time, open, high, low, close, vwap, volume, count]`, with prices and volume given as strings.
+/// <https://docs.kraken.com/rest/#operation/getOHLCData>
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct KrakenOhlc(
+    pub u64,
+    pub String,
+    pub String,
+    pub String,
+    pub String,
+    pub String,
+    pub String,
+    pub u64,
+);
+
+/// Kraken's prices are parsed straight into the candle's `Number`, so a `Candle<Decimal>` holds
+/// them exactly
+impl<T: Number + FromStr> TryFrom<KrakenOhlc> for Candle<T> {
+    type Error = &'static str;
+
+    /// Kraken reports time in epoch seconds, so it is converted to milliseconds here
+    fn try_from(ohlc: KrakenOhlc) -> Result<Candle<T>, Self::Error> {
+        let parse = |s: &str| T::from_str(s).map_err(|_| "invalid number in Kraken OHLC data");
+
+        Ok(Candle {
+            close: parse(&ohlc.4)?,
+            datetime: (ohlc.0 * 1000) as usize,
+            high: parse(&ohlc.2)?,
+            low: parse(&ohlc.3)?,
+            open: parse(&ohlc.1)?,
+            volume: parse(&ohlc.6)?,
+        })
+    }
+}
+
+/// Series is an ordered time series of `f64` candles for a single symbol or pair.
+///
+/// Series dereferences to `[Candle]`, so it can be handed to any indicator directly.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct Series {
+    pub symbol: String,
+    pub candles: Vec<Candle>,
+}
+
+impl Series {
+    /// new builds a series, sorting the given candles from oldest to newest
+    pub fn new(symbol: &str, candles: Vec<Candle>) -> Series {
+        let mut candles = candles;
+        candles.sort_by_key(|c| c.datetime);
+
+        Series {
+            symbol: symbol.to_string(),
+            candles,
+        }
+    }
+
+    pub fn push(&mut self, candle: Candle) {
+        self.candles.push(candle);
+    }
+
+    pub fn closes(&self) -> Vec<f64> {
+        self.candles.iter().map(|c| c.close).collect()
+    }
+
+    pub fn highs(&self) -> Vec<f64> {
+        self.candles.iter().map(|c| c.high).collect()
+    }
+
+    pub fn lows(&self) -> Vec<f64> {
+        self.candles.iter().map(|c| c.low).collect()
+    }
+
+    pub fn opens(&self) -> Vec<f64> {
+        self.candles.iter().map(|c| c.open).collect()
+    }
+
+    pub fn volumes(&self) -> Vec<f64> {
+        self.candles.iter().map(|c| c.volume).collect()
+    }
+}
+
+impl Deref for Series {
+    type Target = [Candle];
+
+    fn deref(&self) -> &[Candle] {
+        &self.candles
+    }
+}
+
+impl From<Vec<Candle>> for Series {
+    fn from(candles: Vec<Candle>) -> Series {
+        Series::new("", candles)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal::Decimal;
+    use rust_decimal_macros::dec;
+    use tda_sdk::responses::GetPriceHistoryResponse;
+
+    #[test]
+    fn from_tda_candle_test() {
+        let sample = r#"{
+            "candles": [
+                {
+                    "open": 10.525,
+                    "high": 11.395,
+                    "low": 10.34,
+                    "close": 12,
+                    "volume": 54379431,
+                    "datetime": 1606284000000
+                }
+            ],
+            "symbol": "M",
+            "empty": false
+        }"#;
+
+        let response: GetPriceHistoryResponse = serde_json::from_str(sample).unwrap();
+        let candle = Candle::from(response.candles[0]);
+
+        assert_eq!(
+            candle,
+            Candle::new(1606284000000, 10.525, 11.395, 10.34, 12.0, 54379431.0)
+        );
+    }
+
+    #[test]
+    fn from_kraken_ohlc_test() {
+        let row = r#"[1641237300, "0.169500000", "0.169700000", "0.169400000", "0.169608900", "0.169550000", "22144.5", 12]"#;
+
+        let ohlc: KrakenOhlc = serde_json::from_str(row).unwrap();
+        let candle: Candle = Candle::try_from(ohlc.clone()).unwrap();
+
+        assert_eq!(candle.datetime, 1641237300000);
+        assert_eq!(candle.open, 0.1695);
+        assert_eq!(candle.close, 0.1696089);
+        assert_eq!(candle.volume, 22144.5);
+
+        // the same row parsed exactly
+        let candle: Candle<Decimal> = Candle::try_from(ohlc).unwrap();
+        assert_eq!(candle.close, dec!(0.169608900));
+        assert_eq!(candle.volume, dec!(22144.5));
+
+        let bad = KrakenOhlc(
+            0,
+            "x".into(),
+            "".into(),
+            "".into(),
+            "".into(),
+            "".into(),
+            "".into(),
+            0,
+        );
+        assert!(Candle::<f64>::try_from(bad).is_err());
+    }
+
+    #[test]
+    fn series_test() {
+        let series = Series::new(
+            "MSFT",
+            vec![
+                Candle::new(2, 1.0, 2.0, 0.5, 1.5, 10.0),
+                Candle::new(1, 1.0, 2.0, 0.5, 1.0, 20.0),
+            ],
+        );
+
+        assert_eq!(series.len(), 2);
+        assert_eq!(series.closes(), vec![1.0, 1.5]);
+        assert_eq!(series.volumes(), vec![20.0, 10.0]);
+        assert_eq!(series[1].datetime, 2);
+    }
+}
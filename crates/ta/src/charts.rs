@@ -0,0 +1,368 @@
+use crate::error::{check_candles, check_parameter, check_period};
+use crate::volatility::atr_series;
+use crate::{Candle, Series, TaError};
+use serde::{Deserialize, Serialize};
+
+/// Absorbs float error when snapping a price to a box, so 0.3 / 0.1 lands on box 3 rather than 2
+const BOX_EPSILON: f64 = 1e-9;
+
+/// BoxSize is the price move each Renko brick or Point & Figure box represents
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub enum BoxSize {
+    /// a fixed price move, eg: 1.0 for a dollar
+    Fixed(f64),
+    /// the latest average true range over the given period, so the box scales with volatility
+    Atr(usize),
+}
+
+impl BoxSize {
+    /// resolve returns the price move for the given candles, calculating the ATR if needed
+    pub fn resolve(&self, candles: &[Candle]) -> Result<f64, TaError> {
+        let size = match *self {
+            BoxSize::Fixed(size) => size,
+            BoxSize::Atr(period) => {
+                check_period(period)?;
+
+                match atr_series(candles, period)?.last() {
+                    Some(Some(atr)) => *atr,
+                    _ => {
+                        return Err(TaError::InsufficientData {
+                            required: period,
+                            available: candles.len(),
+                        })
+                    }
+                }
+            }
+        };
+        check_parameter("box size", size)?;
+
+        Ok(size)
+    }
+}
+
+/// heikin_ashi converts candles into Heikin-Ashi candles, which average each bar with the one
+/// before it to smooth out noise. Datetimes and volumes are kept, so there's a Heikin-Ashi candle
+/// for every input candle.
+/// <https://school.stockcharts.com/doku.php?id=chart_analysis:heikin_ashi>
+pub fn heikin_ashi(candles: &[Candle]) -> Result<Vec<Candle>, TaError> {
+    check_candles(candles)?;
+
+    let mut previous: Option<Candle> = None;
+    let mut output = Vec::with_capacity(candles.len());
+
+    for candle in candles {
+        let close = (candle.open + candle.high + candle.low + candle.close) / 4.0;
+        let open = match previous {
+            Some(previous) => (previous.open + previous.close) / 2.0,
+            None => (candle.open + candle.close) / 2.0,
+        };
+
+        let ha = Candle::new(
+            candle.datetime,
+            open,
+            candle.high.max(open).max(close),
+            candle.low.min(open).min(close),
+            close,
+            candle.volume,
+        );
+        output.push(ha);
+        previous = Some(ha);
+    }
+
+    Ok(output)
+}
+
+/// renko converts candles into Renko bricks, which only print when the close moves a full box
+/// from the last brick, ignoring time entirely. Continuing the trend takes one box, while a
+/// reversal takes two, as it has to clear the last brick first.
+///
+/// Each brick is a candle with an open and close a box apart, stamped with the datetime of the
+/// candle that completed it. The volume traded since the previous brick is given to the first
+/// brick a candle prints.
+/// <https://school.stockcharts.com/doku.php?id=chart_analysis:renko>
+pub fn renko(candles: &[Candle], box_size: BoxSize) -> Result<Vec<Candle>, TaError> {
+    check_candles(candles)?;
+    let size = box_size.resolve(candles)?;
+
+    let mut bricks = Vec::new();
+    let (mut top, mut bottom) = match candles.first() {
+        Some(first) => (first.close, first.close),
+        None => return Ok(bricks),
+    };
+    let mut volume = 0.0;
+
+    for candle in &candles[1..] {
+        volume += candle.volume;
+
+        loop {
+            let (open, close) = if candle.close >= top + size {
+                (top, top + size)
+            } else if candle.close <= bottom - size {
+                (bottom, bottom - size)
+            } else {
+                break;
+            };
+
+            bricks.push(Candle::new(
+                candle.datetime,
+                open,
+                open.max(close),
+                open.min(close),
+                close,
+                volume,
+            ));
+            volume = 0.0;
+            top = open.max(close);
+            bottom = open.min(close);
+        }
+    }
+
+    Ok(bricks)
+}
+
+/// point_and_figure converts candles into Point & Figure columns using the close. A column of Xs
+/// rises a box at a time until the close falls `reversal` boxes below its top, starting a column
+/// of Os one box lower, and the other way around. The traditional reversal is 3 boxes.
+///
+/// Each column is a candle stamped with the datetime of the candle that started it, opening at
+/// its first box and closing at its last, so X columns are bullish candles and O columns bearish.
+/// Volume is the total traded while the column was the current one.
+/// <https://school.stockcharts.com/doku.php?id=chart_analysis:pnf_charts>
+pub fn point_and_figure(
+    candles: &[Candle],
+    box_size: BoxSize,
+    reversal: usize,
+) -> Result<Vec<Candle>, TaError> {
+    check_period(reversal)?;
+    check_candles(candles)?;
+    let size = box_size.resolve(candles)?;
+    let reversal = reversal as f64 * size;
+
+    let floor = |price: f64| (price / size + BOX_EPSILON).floor() * size;
+    let ceil = |price: f64| (price / size - BOX_EPSILON).ceil() * size;
+
+    let mut columns: Vec<Candle> = Vec::new();
+    let reference = match candles.first() {
+        Some(first) => floor(first.close),
+        None => return Ok(columns),
+    };
+    let mut volume = candles[0].volume;
+
+    for candle in &candles[1..] {
+        let column = match columns.last_mut() {
+            Some(column) => column,
+            None => {
+                volume += candle.volume;
+
+                if candle.close >= reference + size {
+                    let top = floor(candle.close);
+                    columns.push(Candle::new(
+                        candle.datetime,
+                        reference,
+                        top,
+                        reference,
+                        top,
+                        volume,
+                    ));
+                } else if candle.close <= reference - size {
+                    let bottom = ceil(candle.close);
+                    columns.push(Candle::new(
+                        candle.datetime,
+                        reference,
+                        reference,
+                        bottom,
+                        bottom,
+                        volume,
+                    ));
+                }
+                continue;
+            }
+        };
+
+        let reversed = if column.close > column.open {
+            if candle.close >= column.close + size {
+                column.close = floor(candle.close);
+                column.high = column.close;
+                None
+            } else if candle.close <= column.close - reversal {
+                let open = column.close - size;
+                Some((open, ceil(candle.close)))
+            } else {
+                None
+            }
+        } else if candle.close <= column.close - size {
+            column.close = ceil(candle.close);
+            column.low = column.close;
+            None
+        } else if candle.close >= column.close + reversal {
+            let open = column.close + size;
+            Some((open, floor(candle.close)))
+        } else {
+            None
+        };
+
+        match reversed {
+            Some((open, close)) => columns.push(Candle::new(
+                candle.datetime,
+                open,
+                open.max(close),
+                open.min(close),
+                close,
+                candle.volume,
+            )),
+            None => column.volume += candle.volume,
+        }
+    }
+
+    Ok(columns)
+}
+
+impl Series {
+    /// heikin_ashi returns the series as Heikin-Ashi candles, see `charts::heikin_ashi`
+    pub fn heikin_ashi(&self) -> Result<Series, TaError> {
+        Ok(Series::new(&self.symbol, heikin_ashi(self)?))
+    }
+
+    /// renko returns the series as Renko bricks, see `charts::renko`
+    pub fn renko(&self, box_size: BoxSize) -> Result<Series, TaError> {
+        Ok(Series::new(&self.symbol, renko(self, box_size)?))
+    }
+
+    /// point_and_figure returns the series as Point & Figure columns, see
+    /// `charts::point_and_figure`
+    pub fn point_and_figure(&self, box_size: BoxSize, reversal: usize) -> Result<Series, TaError> {
+        Ok(Series::new(
+            &self.symbol,
+            point_and_figure(self, box_size, reversal)?,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::average::sma_series;
+    use crate::fixtures::{from_closes, stockcharts_candles};
+
+    /// traded gives each candle a distinct volume, so it can be tracked into bricks and columns
+    fn traded(closes: &[f64]) -> Vec<Candle> {
+        let mut candles = from_closes(closes);
+        for (i, candle) in candles.iter_mut().enumerate() {
+            candle.volume = (i + 1) as f64 * 100.0;
+        }
+        candles
+    }
+
+    fn bodies(candles: &[Candle]) -> Vec<(f64, f64)> {
+        candles.iter().map(|c| (c.open, c.close)).collect()
+    }
+
+    #[test]
+    fn heikin_ashi_test() {
+        let candles = vec![
+            Candle::new(1, 10.0, 12.0, 9.0, 11.0, 100.0),
+            Candle::new(2, 11.0, 13.0, 10.0, 12.0, 200.0),
+            Candle::new(3, 12.0, 12.5, 8.0, 9.0, 300.0),
+        ];
+        let ha = heikin_ashi(&candles).unwrap();
+
+        assert_eq!(
+            ha[0],
+            Candle::new(1, 10.5, 12.0, 9.0, 10.5, 100.0),
+            "first open is the midpoint of the body"
+        );
+        assert_eq!(ha[1], Candle::new(2, 10.5, 13.0, 10.0, 11.5, 200.0));
+        assert_eq!(ha[2], Candle::new(3, 11.0, 12.5, 8.0, 10.375, 300.0));
+
+        let mut nan = candles;
+        nan[1].high = f64::NAN;
+        assert_eq!(heikin_ashi(&nan), Err(TaError::NonFinite { datetime: 2 }));
+    }
+
+    #[test]
+    fn renko_test() {
+        let candles = traded(&[10.0, 10.6, 11.2, 12.1, 11.5, 10.9, 9.8, 7.5]);
+        let bricks = renko(&candles, BoxSize::Fixed(1.0)).unwrap();
+
+        assert_eq!(
+            bodies(&bricks),
+            vec![
+                (10.0, 11.0),
+                (11.0, 12.0),
+                (11.0, 10.0),
+                (10.0, 9.0),
+                (9.0, 8.0)
+            ]
+        );
+        assert_eq!(bricks[2].datetime, candles[6].datetime);
+        assert_eq!(bricks[3].datetime, candles[7].datetime);
+
+        let volume: f64 = bricks.iter().map(|b| b.volume).sum();
+        let traded: f64 = candles[1..].iter().map(|c| c.volume).sum();
+        assert_eq!(volume, traded);
+
+        assert!(renko(&[], BoxSize::Fixed(1.0)).unwrap().is_empty());
+        assert_eq!(
+            renko(&candles, BoxSize::Fixed(0.0)),
+            Err(TaError::InvalidParameter { name: "box size" })
+        );
+    }
+
+    #[test]
+    fn renko_atr_test() {
+        let candles = stockcharts_candles();
+        let atr = BoxSize::Atr(14).resolve(&candles).unwrap();
+        let bricks = renko(&candles, BoxSize::Atr(14)).unwrap();
+
+        assert!(!bricks.is_empty());
+        assert!(bricks
+            .iter()
+            .all(|b| ((b.close - b.open).abs() - atr).abs() < 1e-9));
+        assert_eq!(
+            renko(&candles[..5], BoxSize::Atr(14)),
+            Err(TaError::InsufficientData {
+                required: 14,
+                available: 5
+            })
+        );
+    }
+
+    #[test]
+    fn point_and_figure_test() {
+        let candles = traded(&[
+            10.0, 11.2, 12.5, 13.1, 12.0, 11.4, 10.2, 9.5, 11.0, 12.6, 13.4,
+        ]);
+        let columns = point_and_figure(&candles, BoxSize::Fixed(1.0), 3).unwrap();
+
+        assert_eq!(
+            bodies(&columns),
+            vec![(10.0, 13.0), (12.0, 10.0), (11.0, 13.0)]
+        );
+        assert_eq!(columns[0].high, 13.0);
+        assert_eq!(columns[1].low, 10.0);
+        assert_eq!(columns[1].datetime, candles[7].datetime);
+
+        let volume: f64 = columns.iter().map(|c| c.volume).sum();
+        let traded: f64 = candles.iter().map(|c| c.volume).sum();
+        assert!((volume - traded).abs() < 1e-9);
+
+        assert_eq!(
+            point_and_figure(&candles, BoxSize::Fixed(1.0), 0),
+            Err(TaError::ZeroPeriod)
+        );
+    }
+
+    #[test]
+    fn series_test() {
+        let series = Series::new("SPY", stockcharts_candles());
+        let ha = series.heikin_ashi().unwrap();
+
+        assert_eq!(ha.symbol, "SPY");
+        assert_eq!(ha.len(), series.len());
+        assert_eq!(sma_series(&ha, 10).unwrap().len(), ha.len());
+
+        let bricks = series.renko(BoxSize::Fixed(0.25)).unwrap();
+        assert!(sma_series(&bricks, 3).is_ok());
+        assert!(series.point_and_figure(BoxSize::Atr(14), 3).is_ok());
+    }
+}
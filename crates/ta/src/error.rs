@@ -0,0 +1,149 @@
+use crate::number::Number;
+use crate::Candle;
+use std::fmt;
+
+/// TaError describes why an indicator couldn't be calculated. Calculations validate their inputs
+/// up front, so a NaN or a too short history is reported instead of reaching a trading decision.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TaError {
+    /// fewer candles were given than the calculation needs
+    InsufficientData { required: usize, available: usize },
+    /// the candle at `datetime` has a NaN or infinite price or volume
+    NonFinite { datetime: usize },
+    /// a period of 0 was given, which would average over nothing
+    ZeroPeriod,
+    /// a multiplier or step parameter is NaN, infinite, or not positive
+    InvalidParameter { name: &'static str },
+    /// the value at `index` of a price or balance series is NaN, infinite, or not positive
+    InvalidValue { index: usize },
+}
+
+impl fmt::Display for TaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TaError::InsufficientData {
+                required,
+                available,
+            } => write!(
+                f,
+                "insufficient data: {} candles are required but only {} are available",
+                required, available
+            ),
+            TaError::NonFinite { datetime } => {
+                write!(f, "candle at {} has a non-finite value", datetime)
+            }
+            TaError::ZeroPeriod => write!(f, "period must be greater than 0"),
+            TaError::InvalidParameter { name } => {
+                write!(f, "{} must be a finite number greater than 0", name)
+            }
+            TaError::InvalidValue { index } => {
+                write!(
+                    f,
+                    "value at {} must be a finite number greater than 0",
+                    index
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for TaError {}
+
+/// check_period returns `TaError::ZeroPeriod` for a period of 0
+pub fn check_period(period: usize) -> Result<(), TaError> {
+    if period == 0 {
+        return Err(TaError::ZeroPeriod);
+    }
+    Ok(())
+}
+
+/// check_parameter returns `TaError::InvalidParameter` unless `value` is finite and greater than 0
+pub fn check_parameter(name: &'static str, value: f64) -> Result<(), TaError> {
+    if !value.is_finite() || value <= 0.0 {
+        return Err(TaError::InvalidParameter { name });
+    }
+    Ok(())
+}
+
+/// check_candles returns `TaError::NonFinite` for the first candle with a NaN or infinite value
+pub fn check_candles<T: Number>(candles: &[Candle<T>]) -> Result<(), TaError> {
+    match candles.iter().find(|candle| !candle.is_finite()) {
+        Some(candle) => Err(TaError::NonFinite {
+            datetime: candle.datetime,
+        }),
+        None => Ok(()),
+    }
+}
+
+/// check_values returns `TaError::InvalidValue` for the first value that is NaN, infinite, or not
+/// positive, as returns divide by and take the log of each value
+pub fn check_values<T: Number>(values: &[T]) -> Result<(), TaError> {
+    match values
+        .iter()
+        .position(|v| !v.is_finite() || *v <= T::zero())
+    {
+        Some(index) => Err(TaError::InvalidValue { index }),
+        None => Ok(()),
+    }
+}
+
+/// check_length returns `TaError::InsufficientData` when there are fewer than `required` candles
+/// or values
+pub fn check_length<T>(values: &[T], required: usize) -> Result<(), TaError> {
+    if values.len() < required {
+        return Err(TaError::InsufficientData {
+            required,
+            available: values.len(),
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_test() {
+        let mut candles = vec![Candle::new(1, 1.0, 1.0, 1.0, 1.0, 1.0); 3];
+        assert_eq!(check_candles(&candles), Ok(()));
+        assert_eq!(check_length(&candles, 3), Ok(()));
+        assert_eq!(
+            check_length(&candles, 4),
+            Err(TaError::InsufficientData {
+                required: 4,
+                available: 3
+            })
+        );
+
+        candles[2].datetime = 2;
+        candles[2].volume = f64::INFINITY;
+        assert_eq!(
+            check_candles(&candles),
+            Err(TaError::NonFinite { datetime: 2 })
+        );
+
+        assert_eq!(check_period(0), Err(TaError::ZeroPeriod));
+        assert!(check_parameter("step", f64::NAN).is_err());
+        assert!(check_parameter("step", -0.02).is_err());
+        assert_eq!(check_parameter("step", 0.02), Ok(()));
+
+        assert_eq!(check_values(&[1.0, 2.0]), Ok(()));
+        assert_eq!(
+            check_values(&[1.0, 0.0, f64::NAN]),
+            Err(TaError::InvalidValue { index: 1 })
+        );
+    }
+
+    #[test]
+    fn display_test() {
+        let error = TaError::InsufficientData {
+            required: 20,
+            available: 5,
+        };
+        assert_eq!(
+            error.to_string(),
+            "insufficient data: 20 candles are required but only 5 are available"
+        );
+    }
+}
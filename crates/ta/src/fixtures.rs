@@ -0,0 +1,96 @@
+//! Shared datasets for unit tests.
+use crate::Candle;
+use rust_decimal::prelude::FromPrimitive;
+use rust_decimal::Decimal;
+
+/// The 33 closes used by StockCharts to walk through Wilder's RSI calculation
+/// <https://school.stockcharts.com/doku.php?id=technical_indicators:relative_strength_index_rsi>
+pub const STOCKCHARTS_CLOSES: [f64; 33] = [
+    44.34, 44.09, 44.15, 43.61, 44.33, 44.83, 45.10, 45.42, 45.84, 46.08, 45.89, 46.03, 45.61,
+    46.28, 46.28, 46.00, 46.03, 46.41, 46.22, 45.64, 46.21, 46.25, 45.71, 46.45, 45.78, 45.35,
+    44.03, 44.18, 44.22, 44.57, 43.42, 42.66, 43.13,
+];
+
+/// stockcharts_candles builds daily candles from STOCKCHARTS_CLOSES, with every price set to the close
+pub fn stockcharts_candles() -> Vec<Candle> {
+    from_closes(&STOCKCHARTS_CLOSES)
+}
+
+/// stockcharts_hlcv_candles gives the STOCKCHARTS_CLOSES a range and volume, for the indicators that
+/// need more than closes. The ranges and volumes are made up, so this isn't a published dataset.
+pub fn stockcharts_hlcv_candles() -> Vec<Candle> {
+    let highs: Vec<f64> = (0..STOCKCHARTS_CLOSES.len())
+        .map(|i| STOCKCHARTS_CLOSES[i] + 0.1 * (1 + i % 3) as f64)
+        .collect();
+    let lows: Vec<f64> = (0..STOCKCHARTS_CLOSES.len())
+        .map(|i| STOCKCHARTS_CLOSES[i] - 0.1 * (1 + (i + 1) % 4) as f64)
+        .collect();
+    let volumes: Vec<f64> = (0..STOCKCHARTS_CLOSES.len())
+        .map(|i| 1000.0 + 250.0 * (i % 5) as f64)
+        .collect();
+    from_hlcv(&highs, &lows, &STOCKCHARTS_CLOSES, &volumes)
+}
+
+/// from_closes builds one daily candle per close, with every price set to the close
+pub fn from_closes(closes: &[f64]) -> Vec<Candle> {
+    closes
+        .iter()
+        .enumerate()
+        .map(|(i, close)| Candle::new(i * 86_400_000, *close, *close, *close, *close, 0.0))
+        .collect()
+}
+
+/// from_hlcv builds one daily candle per high, low, close and volume, opening at the close, for
+/// worked examples of indicators that need more than closes
+pub fn from_hlcv(highs: &[f64], lows: &[f64], closes: &[f64], volumes: &[f64]) -> Vec<Candle> {
+    (0..closes.len())
+        .map(|i| {
+            let close = closes[i];
+            Candle::new(i * 86_400_000, close, highs[i], lows[i], close, volumes[i])
+        })
+        .collect()
+}
+
+/// to_decimal converts candles to `Decimal`, for checking an indicator calculates the same in both
+pub fn to_decimal(candles: &[Candle]) -> Vec<Candle<Decimal>> {
+    let decimal = |v: f64| Decimal::from_f64(v).unwrap();
+    candles
+        .iter()
+        .map(|c| {
+            Candle::new(
+                c.datetime,
+                decimal(c.open),
+                decimal(c.high),
+                decimal(c.low),
+                decimal(c.close),
+                decimal(c.volume),
+            )
+        })
+        .collect()
+}
+
+/// assert_series compares a computed series against expected values to 4 decimal places
+pub fn assert_series(actual: &[Option<f64>], expected: &[Option<f64>]) {
+    assert_eq!(actual.len(), expected.len());
+
+    for (i, (a, e)) in actual.iter().zip(expected.iter()).enumerate() {
+        match (a, e) {
+            (Some(a), Some(e)) => assert!(
+                (a - e).abs() < 0.0001,
+                "index {}: expected {}, got {}",
+                i,
+                e,
+                a
+            ),
+            (None, None) => (),
+            _ => panic!("index {}: expected {:?}, got {:?}", i, e, a),
+        }
+    }
+}
+
+/// warmup prefixes `values` with `n` missing values, mirroring the warm up period of an indicator
+pub fn warmup(n: usize, values: &[f64]) -> Vec<Option<f64>> {
+    let mut series = vec![None; n];
+    series.extend(values.iter().map(|v| Some(*v)));
+    series
+}
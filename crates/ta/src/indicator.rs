@@ -0,0 +1,46 @@
+use crate::error::{check_candles, TaError};
+use crate::number::Number;
+use crate::Candle;
+
+/// Indicator is implemented by every streaming indicator in this crate.
+///
+/// Indicators hold just enough state to update in constant time as each new candle arrives, which
+/// lets a live loop or a backtester feed bars one at a time instead of recomputing from scratch.
+///
+/// Indicators are generic over the `Number` their candles hold, `f64` unless given, eg:
+/// `Rsi::<Decimal>::new(14)` implements `Indicator<Decimal>`.
+pub trait Indicator<T: Number = f64> {
+    type Output;
+
+    /// next feeds the next candle into the indicator, returning `None` while it is still warming up
+    fn next(&mut self, candle: &Candle<T>) -> Option<Self::Output>;
+
+    /// reset clears all state, returning the indicator to how it was when constructed
+    fn reset(&mut self);
+
+    /// calculate resets the indicator and runs it over `candles`, returning a value for every candle
+    fn calculate(&mut self, candles: &[Candle<T>]) -> Vec<Option<Self::Output>> {
+        self.reset();
+        candles.iter().map(|candle| self.next(candle)).collect()
+    }
+
+    /// try_next is `next`, but rejects a candle with a NaN or infinite value before it can reach
+    /// the indicator's state, which would otherwise poison every value that follows.
+    fn try_next(&mut self, candle: &Candle<T>) -> Result<Option<Self::Output>, TaError> {
+        if !candle.is_finite() {
+            return Err(TaError::NonFinite {
+                datetime: candle.datetime,
+            });
+        }
+        Ok(self.next(candle))
+    }
+
+    /// try_calculate is `calculate`, but checks every candle is finite first
+    fn try_calculate(
+        &mut self,
+        candles: &[Candle<T>],
+    ) -> Result<Vec<Option<Self::Output>>, TaError> {
+        check_candles(candles)?;
+        Ok(self.calculate(candles))
+    }
+}
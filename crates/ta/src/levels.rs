@@ -0,0 +1,457 @@
+use crate::error::{check_candles, check_parameter, check_period};
+use crate::{Candle, TaError};
+use serde::{Deserialize, Serialize};
+
+/// The Fibonacci ratios retracement levels are drawn at, from the end of a swing back to its start
+pub const RETRACEMENT_RATIOS: [f64; 7] = [0.0, 0.236, 0.382, 0.5, 0.618, 0.786, 1.0];
+
+/// The Fibonacci ratios extension levels are drawn at, projected past the end of a swing
+pub const EXTENSION_RATIOS: [f64; 4] = [1.272, 1.618, 2.0, 2.618];
+
+/// PivotMethod selects the formula used by `pivot_points`
+/// <https://school.stockcharts.com/doku.php?id=technical_indicators:pivot_points>
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum PivotMethod {
+    /// P = (H + L + C) / 3, with levels at multiples of the range from the pivot
+    Classic,
+    /// P = (H + L + C) / 3, with levels at 38.2%, 61.8% and 100% of the range from the pivot
+    Fibonacci,
+    /// levels at 1.1 / 12, 1.1 / 6, 1.1 / 4 and 1.1 / 2 of the range from the close
+    Camarilla,
+    /// P = (H + L + 2C) / 4, weighting the close, with classic levels
+    Woodie,
+}
+
+/// PivotLevels are the pivot and the resistance and support levels around it, nearest first, eg:
+/// `resistance[0]` is R1.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct PivotLevels {
+    pub pivot: f64,
+    pub resistance: Vec<f64>,
+    pub support: Vec<f64>,
+}
+
+/// pivot_points calculates floor trader pivots for the next period from the `previous` period's
+/// candle. Pass a daily candle for intraday levels, or a weekly or monthly bar built with
+/// `resample::resample` for longer term levels.
+pub fn pivot_points(previous: &Candle, method: PivotMethod) -> Result<PivotLevels, TaError> {
+    check_candles(std::slice::from_ref(previous))?;
+
+    let (high, low, close) = (previous.high, previous.low, previous.close);
+    let range = high - low;
+
+    let levels = match method {
+        PivotMethod::Classic | PivotMethod::Woodie => {
+            let pivot = match method {
+                PivotMethod::Woodie => (high + low + 2.0 * close) / 4.0,
+                _ => (high + low + close) / 3.0,
+            };
+
+            PivotLevels {
+                pivot,
+                resistance: vec![2.0 * pivot - low, pivot + range, high + 2.0 * (pivot - low)],
+                support: vec![
+                    2.0 * pivot - high,
+                    pivot - range,
+                    low - 2.0 * (high - pivot),
+                ],
+            }
+        }
+        PivotMethod::Fibonacci => {
+            let pivot = (high + low + close) / 3.0;
+            let ratios = [0.382, 0.618, 1.0];
+
+            PivotLevels {
+                pivot,
+                resistance: ratios.iter().map(|r| pivot + r * range).collect(),
+                support: ratios.iter().map(|r| pivot - r * range).collect(),
+            }
+        }
+        PivotMethod::Camarilla => {
+            let divisors = [12.0, 6.0, 4.0, 2.0];
+
+            PivotLevels {
+                pivot: (high + low + close) / 3.0,
+                resistance: divisors.iter().map(|d| close + range * 1.1 / d).collect(),
+                support: divisors.iter().map(|d| close - range * 1.1 / d).collect(),
+            }
+        }
+    };
+
+    Ok(levels)
+}
+
+/// SwingKind is whether a swing is a local high or low
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum SwingKind {
+    High,
+    Low,
+}
+
+/// Swing is a turning point in price, at the candle `index`. The price is the candle's high for a
+/// swing high, and its low for a swing low.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Swing {
+    pub index: usize,
+    pub price: f64,
+    pub kind: SwingKind,
+}
+
+/// swing_points finds candles whose high is above, or low is below, the `strength` candles either
+/// side of it. The last `strength` candles can't be confirmed yet, so never produce a swing.
+pub fn swing_points(candles: &[Candle], strength: usize) -> Result<Vec<Swing>, TaError> {
+    check_period(strength)?;
+    check_candles(candles)?;
+
+    let mut swings = Vec::new();
+    if candles.len() < 2 * strength + 1 {
+        return Ok(swings);
+    }
+
+    for index in strength..candles.len() - strength {
+        let (high, low) = (candles[index].high, candles[index].low);
+        let neighbours = candles[index - strength..=index + strength]
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| *i != strength);
+
+        let (mut is_high, mut is_low) = (true, true);
+        for (_, candle) in neighbours {
+            is_high &= high > candle.high;
+            is_low &= low < candle.low;
+        }
+
+        if is_high {
+            swings.push(Swing {
+                index,
+                price: high,
+                kind: SwingKind::High,
+            });
+        }
+        if is_low {
+            swings.push(Swing {
+                index,
+                price: low,
+                kind: SwingKind::Low,
+            });
+        }
+    }
+
+    Ok(swings)
+}
+
+/// zigzag filters price into alternating swing highs and lows, ignoring any move smaller than
+/// `threshold`, a fraction such as 0.05 for 5%.
+///
+/// A swing is only confirmed once price has reversed from it by the threshold, so the extreme of
+/// the final, still forming, leg isn't included.
+/// <https://school.stockcharts.com/doku.php?id=technical_indicators:zigzag>
+pub fn zigzag(candles: &[Candle], threshold: f64) -> Result<Vec<Swing>, TaError> {
+    check_parameter("threshold", threshold)?;
+    check_candles(candles)?;
+
+    let mut swings = Vec::new();
+    let (mut highest, mut lowest) = (0, 0);
+    let mut leg: Option<SwingKind> = None;
+
+    let swing = |index: usize, kind: SwingKind| Swing {
+        index,
+        price: match kind {
+            SwingKind::High => candles[index].high,
+            SwingKind::Low => candles[index].low,
+        },
+        kind,
+    };
+
+    for (i, candle) in candles.iter().enumerate().skip(1) {
+        match leg {
+            // until the first move beyond the threshold, whichever extreme came first starts the zigzag
+            None => {
+                if candle.high > candles[highest].high {
+                    highest = i;
+                }
+                if candle.low < candles[lowest].low {
+                    lowest = i;
+                }
+
+                if candles[highest].high >= candles[lowest].low * (1.0 + threshold) {
+                    if lowest < highest {
+                        swings.push(swing(lowest, SwingKind::Low));
+                        leg = Some(SwingKind::High);
+                    } else {
+                        swings.push(swing(highest, SwingKind::High));
+                        leg = Some(SwingKind::Low);
+                    }
+                }
+            }
+            Some(SwingKind::High) => {
+                if candle.high > candles[highest].high {
+                    highest = i;
+                } else if candle.low <= candles[highest].high * (1.0 - threshold) {
+                    swings.push(swing(highest, SwingKind::High));
+                    leg = Some(SwingKind::Low);
+                    lowest = i;
+                }
+            }
+            Some(SwingKind::Low) => {
+                if candle.low < candles[lowest].low {
+                    lowest = i;
+                } else if candle.high >= candles[lowest].low * (1.0 + threshold) {
+                    swings.push(swing(lowest, SwingKind::Low));
+                    leg = Some(SwingKind::High);
+                    highest = i;
+                }
+            }
+        }
+    }
+
+    Ok(swings)
+}
+
+/// Zone is a price band where several swings turned, which tends to act as support or resistance
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Zone {
+    pub low: f64,
+    pub high: f64,
+    /// the average price of the swings in the zone
+    pub price: f64,
+    pub touches: usize,
+    /// the index of the most recent swing in the zone
+    pub last_index: usize,
+}
+
+impl Zone {
+    /// is_support is true when `price` sits above the zone, so the zone is below as support
+    pub fn is_support(&self, price: f64) -> bool {
+        price > self.high
+    }
+
+    /// is_resistance is true when `price` sits below the zone, so the zone is above as resistance
+    pub fn is_resistance(&self, price: f64) -> bool {
+        price < self.low
+    }
+}
+
+/// zones clusters swing prices within `tolerance` of each other, a fraction such as 0.01 for 1%,
+/// into support and resistance zones. Swing highs and lows are clustered together, as broken
+/// resistance often becomes support. Zones with fewer than `min_touches` swings are dropped, and
+/// the rest are returned from the lowest price to the highest.
+pub fn zones(swings: &[Swing], tolerance: f64, min_touches: usize) -> Result<Vec<Zone>, TaError> {
+    check_parameter("tolerance", tolerance)?;
+
+    let mut sorted = swings.to_vec();
+    sorted.sort_by(|a, b| a.price.total_cmp(&b.price));
+
+    let mut zones: Vec<Zone> = Vec::new();
+    for swing in sorted {
+        match zones.last_mut() {
+            Some(zone) if swing.price <= zone.low * (1.0 + tolerance) => {
+                zone.price =
+                    (zone.price * zone.touches as f64 + swing.price) / (zone.touches + 1) as f64;
+                zone.high = swing.price;
+                zone.touches += 1;
+                zone.last_index = zone.last_index.max(swing.index);
+            }
+            _ => zones.push(Zone {
+                low: swing.price,
+                high: swing.price,
+                price: swing.price,
+                touches: 1,
+                last_index: swing.index,
+            }),
+        }
+    }
+
+    zones.retain(|zone| zone.touches >= min_touches);
+    Ok(zones)
+}
+
+/// FibonacciLevel is a price drawn at a Fibonacci ratio of a swing
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub struct FibonacciLevel {
+    pub ratio: f64,
+    pub price: f64,
+}
+
+/// retracements returns the levels a move from `start` to `end` may pull back to, see
+/// `RETRACEMENT_RATIOS`. For an up move, 0.618 is 61.8% of the way back down from the end.
+pub fn retracements(start: &Swing, end: &Swing) -> Vec<FibonacciLevel> {
+    let change = end.price - start.price;
+
+    RETRACEMENT_RATIOS
+        .iter()
+        .map(|&ratio| FibonacciLevel {
+            ratio,
+            price: end.price - change * ratio,
+        })
+        .collect()
+}
+
+/// extensions returns the levels a move from `start` to `end` may carry on to, see `EXTENSION_RATIOS`
+pub fn extensions(start: &Swing, end: &Swing) -> Vec<FibonacciLevel> {
+    let change = end.price - start.price;
+
+    EXTENSION_RATIOS
+        .iter()
+        .map(|&ratio| FibonacciLevel {
+            ratio,
+            price: start.price + change * ratio,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::*;
+
+    fn levels(levels: &PivotLevels) -> Vec<Option<f64>> {
+        let mut values = vec![Some(levels.pivot)];
+        values.extend(levels.resistance.iter().map(|&v| Some(v)));
+        values.extend(levels.support.iter().map(|&v| Some(v)));
+        values
+    }
+
+    fn swing(index: usize, price: f64, kind: SwingKind) -> Swing {
+        Swing { index, price, kind }
+    }
+
+    const CLOSES: [f64; 10] = [10.0, 11.0, 12.0, 11.5, 10.8, 11.0, 12.5, 13.0, 12.2, 11.5];
+
+    #[test]
+    fn pivot_points_test() {
+        let candle = Candle::new(0, 102.0, 110.0, 100.0, 105.0, 1000.0);
+
+        let classic = pivot_points(&candle, PivotMethod::Classic).unwrap();
+        assert_series(
+            &levels(&classic),
+            &warmup(0, &[105.0, 110.0, 115.0, 120.0, 100.0, 95.0, 90.0]),
+        );
+
+        let fibonacci = pivot_points(&candle, PivotMethod::Fibonacci).unwrap();
+        assert_series(
+            &levels(&fibonacci),
+            &warmup(0, &[105.0, 108.82, 111.18, 115.0, 101.18, 98.82, 95.0]),
+        );
+
+        let camarilla = pivot_points(&candle, PivotMethod::Camarilla).unwrap();
+        assert_series(
+            &levels(&camarilla),
+            &warmup(
+                0,
+                &[
+                    105.0, 105.9167, 106.8333, 107.75, 110.5, 104.0833, 103.1667, 102.25, 99.5,
+                ],
+            ),
+        );
+
+        let candle = Candle::new(0, 102.0, 110.0, 100.0, 108.0, 1000.0);
+        let woodie = pivot_points(&candle, PivotMethod::Woodie).unwrap();
+        assert_series(
+            &levels(&woodie),
+            &warmup(0, &[106.5, 113.0, 116.5, 123.0, 103.0, 96.5, 93.0]),
+        );
+
+        let candle = Candle::new(7, 102.0, f64::NAN, 100.0, 108.0, 1000.0);
+        assert_eq!(
+            pivot_points(&candle, PivotMethod::Classic),
+            Err(TaError::NonFinite { datetime: 7 })
+        );
+    }
+
+    #[test]
+    fn swing_points_test() {
+        let candles = from_closes(&CLOSES);
+
+        assert_eq!(
+            swing_points(&candles, 1).unwrap(),
+            vec![
+                swing(2, 12.0, SwingKind::High),
+                swing(4, 10.8, SwingKind::Low),
+                swing(7, 13.0, SwingKind::High),
+            ]
+        );
+        assert_eq!(swing_points(&candles, 0), Err(TaError::ZeroPeriod));
+        assert_eq!(swing_points(&candles[..2], 1), Ok(vec![]));
+    }
+
+    #[test]
+    fn zigzag_test() {
+        let candles = from_closes(&CLOSES);
+
+        assert_eq!(
+            zigzag(&candles, 0.05).unwrap(),
+            vec![
+                swing(0, 10.0, SwingKind::Low),
+                swing(2, 12.0, SwingKind::High),
+                swing(4, 10.8, SwingKind::Low),
+                swing(7, 13.0, SwingKind::High),
+            ]
+        );
+
+        // a larger threshold filters out the pullback to 10.8, and the high at 13 is unconfirmed
+        assert_eq!(
+            zigzag(&candles, 0.12).unwrap(),
+            vec![swing(0, 10.0, SwingKind::Low)]
+        );
+        assert!(zigzag(&candles, 0.0).is_err());
+    }
+
+    #[test]
+    fn zones_test() {
+        let swings = vec![
+            swing(0, 10.0, SwingKind::Low),
+            swing(2, 12.0, SwingKind::High),
+            swing(4, 10.05, SwingKind::Low),
+            swing(6, 12.1, SwingKind::High),
+            swing(8, 10.8, SwingKind::Low),
+            swing(9, 13.0, SwingKind::High),
+        ];
+
+        let zones = zones(&swings, 0.01, 2).unwrap();
+        assert_eq!(
+            zones,
+            vec![
+                Zone {
+                    low: 10.0,
+                    high: 10.05,
+                    price: 10.025,
+                    touches: 2,
+                    last_index: 4
+                },
+                Zone {
+                    low: 12.0,
+                    high: 12.1,
+                    price: 12.05,
+                    touches: 2,
+                    last_index: 6
+                },
+            ]
+        );
+        assert!(zones[0].is_support(11.0));
+        assert!(zones[1].is_resistance(11.0));
+    }
+
+    #[test]
+    fn fibonacci_test() {
+        let (start, end) = (
+            swing(0, 10.0, SwingKind::Low),
+            swing(2, 12.0, SwingKind::High),
+        );
+
+        let prices: Vec<Option<f64>> = retracements(&start, &end)
+            .iter()
+            .map(|level| Some(level.price))
+            .collect();
+        assert_series(
+            &prices,
+            &warmup(0, &[12.0, 11.528, 11.236, 11.0, 10.764, 10.428, 10.0]),
+        );
+
+        let prices: Vec<Option<f64>> = extensions(&start, &end)
+            .iter()
+            .map(|level| Some(level.price))
+            .collect();
+        assert_series(&prices, &warmup(0, &[12.544, 13.236, 14.0, 15.236]));
+    }
+}
@@ -1 +1,38 @@
+//! Technical analysis indicators, statistics and charts, calculated from vendor neutral candles.
+//!
+//! # Precision
+//!
+//! `Candle` and every indicator are generic over `Number`, which is implemented for `f64`, the
+//! default, and for `rust_decimal::Decimal`. Parsing Kraken's OHLC into a `Candle<Decimal>` keeps
+//! sub-cent prices exact, and `Rsi::<Decimal>`, `BollingerBands::<Decimal>` and the rest calculate
+//! in `Decimal` from there, as do their `*_series` functions. The moving averages can also be fed
+//! raw values through `update`, or a slice through `average::sma_values` and `average::ema_values`.
+//! `Rounding::apply` is the explicit output step for either type.
+//!
+//! `Series`, `stats`, `levels`, `patterns`, `charts` and `resample` analyse whole series rather
+//! than stream candles, and are `f64` only.
 pub mod average;
+pub mod candle;
+pub use candle::{Candle, KrakenOhlc, Series};
+pub mod charts;
+pub mod error;
+pub use error::TaError;
+pub mod indicator;
+pub use indicator::Indicator;
+pub mod levels;
+pub mod momentum;
+pub mod number;
+pub use number::{Number, Rounding};
+pub mod patterns;
+pub mod resample;
+pub mod stats;
+pub mod trend;
+pub mod volatility;
+pub mod volume;
+mod window;
+
+#[cfg(test)]
+mod fixtures;
+
+#[cfg(test)]
+mod properties;
@@ -0,0 +1,528 @@
+use crate::average::{Rma, Sma};
+use crate::error::check_period;
+use crate::number::Number;
+use crate::window::Extremes;
+use crate::{Candle, Indicator, TaError};
+use std::collections::VecDeque;
+
+/// rsi_series returns the relative strength index of closes for every candle, see `Rsi`.
+pub fn rsi_series<T: Number>(
+    candles: &[Candle<T>],
+    period: usize,
+) -> Result<Vec<Option<T>>, TaError> {
+    check_period(period)?;
+
+    Rsi::new(period).try_calculate(candles)
+}
+
+/// stochastic_series returns %K and %D for every candle, see `Stochastic`.
+pub fn stochastic_series<T: Number>(
+    candles: &[Candle<T>],
+    k_period: usize,
+    d_period: usize,
+) -> Result<Vec<Option<StochasticValue<T>>>, TaError> {
+    check_period(k_period)?;
+    check_period(d_period)?;
+
+    Stochastic::new(k_period, d_period).try_calculate(candles)
+}
+
+/// williams_r_series returns Williams %R for every candle, see `WilliamsR`.
+pub fn williams_r_series<T: Number>(
+    candles: &[Candle<T>],
+    period: usize,
+) -> Result<Vec<Option<T>>, TaError> {
+    check_period(period)?;
+
+    WilliamsR::new(period).try_calculate(candles)
+}
+
+/// cci_series returns the commodity channel index for every candle, see `Cci`.
+pub fn cci_series<T: Number>(
+    candles: &[Candle<T>],
+    period: usize,
+) -> Result<Vec<Option<T>>, TaError> {
+    check_period(period)?;
+
+    Cci::new(period).try_calculate(candles)
+}
+
+/// mfi_series returns the money flow index for every candle, see `Mfi`.
+pub fn mfi_series<T: Number>(
+    candles: &[Candle<T>],
+    period: usize,
+) -> Result<Vec<Option<T>>, TaError> {
+    check_period(period)?;
+
+    Mfi::new(period).try_calculate(candles)
+}
+
+/// typical_price is the average of a candle's high, low and close
+pub fn typical_price<T: Number>(candle: &Candle<T>) -> T {
+    (candle.high + candle.low + candle.close) / T::from_count(3)
+}
+
+/// Rsi is the relative strength index, using Wilder's smoothing.
+///
+/// The first average gain and loss are simple averages over `period` changes, after which each is
+/// smoothed as `(previous x (period - 1) + current) / period`. The first value is produced on the
+/// candle after `period` changes have been seen, eg: index 14 for RSI(14).
+/// <https://school.stockcharts.com/doku.php?id=technical_indicators:relative_strength_index_rsi>
+#[derive(Clone, Debug)]
+pub struct Rsi<T = f64> {
+    previous: Option<T>,
+    gains: Rma<T>,
+    losses: Rma<T>,
+}
+
+impl<T: Number> Rsi<T> {
+    pub fn new(period: usize) -> Rsi<T> {
+        Rsi {
+            previous: None,
+            gains: Rma::new(period),
+            losses: Rma::new(period),
+        }
+    }
+
+    pub fn update(&mut self, value: T) -> Option<T> {
+        let previous = self.previous.replace(value)?;
+
+        let change = value - previous;
+        let zero = T::zero();
+        let average_gain = self.gains.update(if change > zero { change } else { zero });
+        let average_loss = self
+            .losses
+            .update(if change < zero { -change } else { zero })?;
+
+        let hundred = T::from_count(100);
+        if average_loss == zero {
+            return Some(hundred);
+        }
+        let rs = average_gain? / average_loss;
+        Some(hundred - hundred / (T::one() + rs))
+    }
+}
+
+impl<T: Number> Indicator<T> for Rsi<T> {
+    type Output = T;
+
+    fn next(&mut self, candle: &Candle<T>) -> Option<T> {
+        self.update(candle.close)
+    }
+
+    fn reset(&mut self) {
+        self.previous = None;
+        self.gains.reset();
+        self.losses.reset();
+    }
+}
+
+/// StochasticValue holds the %K and %D lines of the stochastic oscillator. %D is `None` until
+/// `d_period` values of %K are available.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct StochasticValue<T = f64> {
+    pub k: T,
+    pub d: Option<T>,
+}
+
+/// Stochastic is the fast stochastic oscillator.
+///     %K = (close - lowest low) / (highest high - lowest low) x 100
+///     %D = SMA(%K, d_period)
+/// When the range is flat, %K is reported as 50.
+/// <https://school.stockcharts.com/doku.php?id=technical_indicators:stochastic_oscillator_fast_slow_and_full>
+#[derive(Clone, Debug)]
+pub struct Stochastic<T = f64> {
+    extremes: Extremes<T>,
+    d: Sma<T>,
+}
+
+impl<T: Number> Stochastic<T> {
+    pub fn new(k_period: usize, d_period: usize) -> Stochastic<T> {
+        Stochastic {
+            extremes: Extremes::new(k_period),
+            d: Sma::new(d_period),
+        }
+    }
+}
+
+impl<T: Number> Indicator<T> for Stochastic<T> {
+    type Output = StochasticValue<T>;
+
+    fn next(&mut self, candle: &Candle<T>) -> Option<StochasticValue<T>> {
+        self.extremes.push(candle.high, candle.low);
+        if !self.extremes.is_full() {
+            return None;
+        }
+
+        let (highest, lowest) = (self.extremes.highest()?, self.extremes.lowest()?);
+        let k = if highest > lowest {
+            (candle.close - lowest) / (highest - lowest) * T::from_count(100)
+        } else {
+            T::from_count(50)
+        };
+
+        Some(StochasticValue {
+            k,
+            d: self.d.update(k),
+        })
+    }
+
+    fn reset(&mut self) {
+        self.extremes.reset();
+        self.d.reset();
+    }
+}
+
+/// WilliamsR is Williams %R, `(highest high - close) / (highest high - lowest low) x -100`.
+/// When the range is flat, %R is reported as -50.
+/// <https://school.stockcharts.com/doku.php?id=technical_indicators:williams_r>
+#[derive(Clone, Debug)]
+pub struct WilliamsR<T = f64> {
+    extremes: Extremes<T>,
+}
+
+impl<T: Number> WilliamsR<T> {
+    pub fn new(period: usize) -> WilliamsR<T> {
+        WilliamsR {
+            extremes: Extremes::new(period),
+        }
+    }
+}
+
+impl<T: Number> Indicator<T> for WilliamsR<T> {
+    type Output = T;
+
+    fn next(&mut self, candle: &Candle<T>) -> Option<T> {
+        self.extremes.push(candle.high, candle.low);
+        if !self.extremes.is_full() {
+            return None;
+        }
+
+        let (highest, lowest) = (self.extremes.highest()?, self.extremes.lowest()?);
+        if highest > lowest {
+            Some((highest - candle.close) / (highest - lowest) * -T::from_count(100))
+        } else {
+            Some(-T::from_count(50))
+        }
+    }
+
+    fn reset(&mut self) {
+        self.extremes.reset();
+    }
+}
+
+/// Cci is the commodity channel index, `(typical price - SMA(typical price)) / (0.015 x mean deviation)`.
+/// When the mean deviation is zero, the CCI is reported as 0.
+/// <https://school.stockcharts.com/doku.php?id=technical_indicators:commodity_channel_index_cci>
+#[derive(Clone, Debug)]
+pub struct Cci<T = f64> {
+    period: usize,
+    window: VecDeque<T>,
+    average: Sma<T>,
+}
+
+impl<T: Number> Cci<T> {
+    pub fn new(period: usize) -> Cci<T> {
+        Cci {
+            period,
+            window: VecDeque::with_capacity(period + 1),
+            average: Sma::new(period),
+        }
+    }
+}
+
+impl<T: Number> Indicator<T> for Cci<T> {
+    type Output = T;
+
+    fn next(&mut self, candle: &Candle<T>) -> Option<T> {
+        let typical = typical_price(candle);
+
+        self.window.push_back(typical);
+        if self.window.len() > self.period {
+            self.window.pop_front();
+        }
+        let mean = self.average.update(typical)?;
+
+        // mean deviation has to be measured against the current mean, so it can't be kept as a running sum
+        let deviation =
+            self.window.iter().map(|v| (*v - mean).abs()).sum::<T>() / T::from_count(self.period);
+        if deviation == T::zero() {
+            return Some(T::zero());
+        }
+        // Lambert's constant of 0.015, as a ratio so it's exact in any `Number`
+        let constant = T::from_count(15) / T::from_count(1000);
+        Some((typical - mean) / (constant * deviation))
+    }
+
+    fn reset(&mut self) {
+        self.window.clear();
+        self.average.reset();
+    }
+}
+
+/// Mfi is the money flow index, a volume weighted RSI over the typical price.
+///
+/// Money flow (typical price x volume) is positive when the typical price rises from the previous
+/// candle, and negative when it falls. The first value is produced once `period` flows are known.
+/// <https://school.stockcharts.com/doku.php?id=technical_indicators:money_flow_index_mfi>
+#[derive(Clone, Debug)]
+pub struct Mfi<T = f64> {
+    period: usize,
+    previous: Option<T>,
+    flows: VecDeque<(T, T)>,
+    positive: T,
+    negative: T,
+}
+
+impl<T: Number> Mfi<T> {
+    pub fn new(period: usize) -> Mfi<T> {
+        Mfi {
+            period,
+            previous: None,
+            flows: VecDeque::with_capacity(period + 1),
+            positive: T::zero(),
+            negative: T::zero(),
+        }
+    }
+}
+
+impl<T: Number> Indicator<T> for Mfi<T> {
+    type Output = T;
+
+    fn next(&mut self, candle: &Candle<T>) -> Option<T> {
+        let typical = typical_price(candle);
+        let previous = self.previous.replace(typical)?;
+        if self.period == 0 {
+            return None;
+        }
+
+        let zero = T::zero();
+        let flow = typical * candle.volume;
+        let (positive, negative) = if typical > previous {
+            (flow, zero)
+        } else if typical < previous {
+            (zero, flow)
+        } else {
+            (zero, zero)
+        };
+
+        self.flows.push_back((positive, negative));
+        self.positive += positive;
+        self.negative += negative;
+        if self.flows.len() > self.period {
+            let (p, n) = self.flows.pop_front().unwrap_or_default();
+            self.positive -= p;
+            self.negative -= n;
+        }
+
+        if self.flows.len() < self.period {
+            return None;
+        }
+        let hundred = T::from_count(100);
+        if self.negative <= zero {
+            return Some(hundred);
+        }
+        Some(hundred - hundred / (T::one() + self.positive / self.negative))
+    }
+
+    fn reset(&mut self) {
+        self.previous = None;
+        self.flows.clear();
+        self.positive = T::zero();
+        self.negative = T::zero();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::*;
+    use num_traits::ToPrimitive;
+
+    #[test]
+    fn rsi_series_test() {
+        let candles = stockcharts_candles();
+        let rsi = rsi_series(&candles, 14).unwrap();
+
+        // StockCharts rounds its intermediate averages to cents, so its published values can
+        // differ from an exact calculation in the first decimal place
+        let published = [
+            70.53, 66.32, 66.55, 69.41, 66.36, 57.97, 62.93, 63.26, 56.06, 62.38, 54.71, 50.42,
+            39.99, 41.46, 41.87, 45.46, 37.30, 33.08, 37.77,
+        ];
+
+        assert!(rsi[..14].iter().all(|v| v.is_none()));
+        for (actual, expected) in rsi[14..].iter().zip(published.iter()) {
+            assert!((actual.unwrap() - expected).abs() < 0.1);
+        }
+    }
+
+    #[test]
+    fn rsi_decimal_test() {
+        let candles = stockcharts_candles();
+        let expected = rsi_series(&candles, 14).unwrap();
+        let actual: Vec<Option<f64>> = rsi_series(&to_decimal(&candles), 14)
+            .unwrap()
+            .iter()
+            .map(|v| v.and_then(|v| v.to_f64()))
+            .collect();
+
+        assert_series(&actual, &expected);
+    }
+
+    #[test]
+    fn rsi_flat_test() {
+        let candles = from_closes(&[1.0, 2.0, 3.0, 4.0]);
+        assert_eq!(rsi_series(&candles, 3).unwrap()[3], Some(100.0));
+    }
+
+    /// the highs, lows and closes the %K and %R examples are worked through by hand with
+    fn range_candles() -> Vec<Candle> {
+        from_hlcv(
+            &[10.0, 11.0, 12.0, 12.0, 14.0],
+            &[8.0, 9.0, 9.0, 10.0, 11.0],
+            &[9.0, 10.0, 11.0, 10.0, 14.0],
+            &[0.0; 5],
+        )
+    }
+
+    #[test]
+    fn stochastic_series_test() {
+        let stochastic = stochastic_series(&range_candles(), 3, 2).unwrap();
+
+        let k: Vec<Option<f64>> = stochastic.iter().map(|s| s.map(|s| s.k)).collect();
+        let d: Vec<Option<f64>> = stochastic.iter().map(|s| s.and_then(|s| s.d)).collect();
+
+        // %K = (close - lowest low) / (highest high - lowest low) x 100, over 3 candles:
+        // (11 - 8) / (12 - 8), (10 - 9) / (12 - 9) and (14 - 9) / (14 - 9)
+        assert_series(&k, &warmup(2, &[75.0, 33.3333, 100.0]));
+        // %D is the 2 candle SMA of %K
+        assert_series(&d, &warmup(3, &[54.1667, 66.6667]));
+
+        let flat = from_hlcv(&[5.0; 3], &[5.0; 3], &[5.0; 3], &[0.0; 3]);
+        assert_eq!(stochastic_series(&flat, 3, 1).unwrap()[2].unwrap().k, 50.0);
+    }
+
+    #[test]
+    fn williams_r_series_test() {
+        // %R = (highest high - close) / (highest high - lowest low) x -100, which is %K - 100:
+        // (12 - 11) / (12 - 8), (12 - 10) / (12 - 9) and (14 - 14) / (14 - 9)
+        assert_series(
+            &williams_r_series(&range_candles(), 3).unwrap(),
+            &warmup(2, &[-25.0, -66.6667, 0.0]),
+        );
+    }
+
+    #[test]
+    fn cci_series_test() {
+        // typical prices of 1, 2, 3, 2 and 6
+        let candles = from_hlcv(
+            &[3.0, 4.0, 5.0, 4.0, 8.0],
+            &[0.0, 1.0, 2.0, 1.0, 5.0],
+            &[0.0, 1.0, 2.0, 1.0, 5.0],
+            &[0.0; 5],
+        );
+
+        // CCI = (typical price - SMA) / (0.015 x mean deviation), over 3 candles:
+        // (3 - 2) / (0.015 x 2/3), (2 - 7/3) / (0.015 x 4/9) and (6 - 11/3) / (0.015 x 14/9)
+        assert_series(
+            &cci_series(&candles, 3).unwrap(),
+            &warmup(2, &[100.0, -50.0, 100.0]),
+        );
+    }
+
+    #[test]
+    fn mfi_series_test() {
+        // typical prices of 10, 11, 10, 10 and 12
+        let candles = from_hlcv(
+            &[11.0, 12.0, 11.0, 11.0, 13.0],
+            &[9.0, 10.0, 9.0, 9.0, 11.0],
+            &[10.0, 11.0, 10.0, 10.0, 12.0],
+            &[100.0, 200.0, 100.0, 300.0, 100.0],
+        );
+
+        // money flows of +2200, -1000, none for an unchanged typical price, then +1200, so over 2
+        // flows the money flow ratio is 2200 / 1000, then 0 / 1000, then 1200 with no outflow
+        assert_series(
+            &mfi_series(&candles, 2).unwrap(),
+            &warmup(2, &[68.75, 0.0, 100.0]),
+        );
+    }
+
+    /// reference recalculates each value of a `period` indicator from its own window of candles,
+    /// as the formulas are written, to check the streaming state over a longer series
+    fn reference(
+        candles: &[Candle],
+        period: usize,
+        calculate: impl Fn(&[Candle]) -> f64,
+    ) -> Vec<Option<f64>> {
+        (0..candles.len())
+            .map(|i| Some(calculate(&candles[(i + 1).checked_sub(period)?..=i])))
+            .collect()
+    }
+
+    fn highest_lowest(window: &[Candle]) -> (f64, f64) {
+        let highest = window.iter().map(|c| c.high).fold(f64::MIN, f64::max);
+        let lowest = window.iter().map(|c| c.low).fold(f64::MAX, f64::min);
+        (highest, lowest)
+    }
+
+    #[test]
+    fn reference_series_test() {
+        let candles = stockcharts_hlcv_candles();
+
+        let k = reference(&candles, 14, |window| {
+            let (highest, lowest) = highest_lowest(window);
+            (window[window.len() - 1].close - lowest) / (highest - lowest) * 100.0
+        });
+        let stochastic = stochastic_series(&candles, 14, 3).unwrap();
+        let actual: Vec<Option<f64>> = stochastic.iter().map(|s| s.map(|s| s.k)).collect();
+        assert_series(&actual, &k);
+        let d: Vec<Option<f64>> = (0..k.len())
+            .map(|i| {
+                Some(
+                    k[(i + 1).checked_sub(3)?..=i]
+                        .iter()
+                        .copied()
+                        .sum::<Option<f64>>()?
+                        / 3.0,
+                )
+            })
+            .collect();
+        let actual: Vec<Option<f64>> = stochastic.iter().map(|s| s.and_then(|s| s.d)).collect();
+        assert_series(&actual, &d);
+
+        let r = reference(&candles, 14, |window| {
+            let (highest, lowest) = highest_lowest(window);
+            (highest - window[window.len() - 1].close) / (highest - lowest) * -100.0
+        });
+        assert_series(&williams_r_series(&candles, 14).unwrap(), &r);
+
+        let cci = reference(&candles, 20, |window| {
+            let typical: Vec<f64> = window.iter().map(typical_price).collect();
+            let mean = typical.iter().sum::<f64>() / 20.0;
+            let deviation = typical.iter().map(|t| (t - mean).abs()).sum::<f64>() / 20.0;
+            (typical[19] - mean) / (0.015 * deviation)
+        });
+        assert_series(&cci_series(&candles, 20).unwrap(), &cci);
+
+        // each of the 14 money flows is compared with the typical price before it, so the window
+        // holds 15 candles
+        let mfi = reference(&candles, 15, |window| {
+            let (mut positive, mut negative) = (0.0, 0.0);
+            for pair in window.windows(2) {
+                let (before, after) = (typical_price(&pair[0]), typical_price(&pair[1]));
+                if after > before {
+                    positive += after * pair[1].volume;
+                } else if after < before {
+                    negative += after * pair[1].volume;
+                }
+            }
+            100.0 - 100.0 / (1.0 + positive / negative)
+        });
+        assert_series(&mfi_series(&candles, 14).unwrap(), &mfi);
+        assert!(mfi[14..]
+            .iter()
+            .all(|v| v.unwrap() > 0.0 && v.unwrap() < 100.0));
+    }
+}
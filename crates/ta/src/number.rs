@@ -0,0 +1,174 @@
+use num_traits::{FromPrimitive, NumAssign, Signed, ToPrimitive};
+use rust_decimal::prelude::RoundingStrategy;
+use rust_decimal::Decimal;
+use rust_decimal::MathematicalOps;
+use serde::{Deserialize, Serialize};
+use std::fmt::Debug;
+use std::iter::Sum;
+
+/// Number is the numeric type candles hold and indicators are calculated in.
+///
+/// It is implemented for `f64`, which is fast and what candles hold by default, and for
+/// `rust_decimal::Decimal`, which keeps exact precision for prices far below a cent, such as the
+/// XDG pairs on Kraken. Decimal arithmetic panics on overflow, at around 7.9e28, so keep inputs to
+/// realistic prices.
+pub trait Number:
+    Copy + Debug + Default + PartialOrd + NumAssign + Signed + FromPrimitive + ToPrimitive + Sum<Self>
+{
+    /// from_count converts a period or weight, falling back to 0 if it can't be represented
+    fn from_count(count: usize) -> Self {
+        Self::from_usize(count).unwrap_or_else(Self::zero)
+    }
+
+    /// from_parameter converts a multiplier or step given as `f64`, falling back to 0 if it can't
+    /// be represented
+    fn from_parameter(value: f64) -> Self {
+        Self::from_f64(value).unwrap_or_else(Self::zero)
+    }
+
+    /// round_dp rounds to `places` decimal places, with midpoints rounded away from zero
+    fn round_dp(self, places: u32) -> Self;
+
+    /// is_finite is false for NaN and infinite values, which only `f64` has
+    fn is_finite(self) -> bool;
+
+    /// sqrt is the square root, or 0 for a negative value
+    fn sqrt(self) -> Self;
+
+    /// ln is the natural logarithm, or `None` for a value that isn't positive
+    fn ln(self) -> Option<Self>;
+}
+
+impl Number for f64 {
+    fn round_dp(self, places: u32) -> Self {
+        let scale = 10f64.powi(places as i32);
+        (self * scale).round() / scale
+    }
+
+    fn is_finite(self) -> bool {
+        f64::is_finite(self)
+    }
+
+    fn sqrt(self) -> Self {
+        if self < 0.0 {
+            return 0.0;
+        }
+        f64::sqrt(self)
+    }
+
+    fn ln(self) -> Option<Self> {
+        Some(f64::ln(self)).filter(|ln| ln.is_finite())
+    }
+}
+
+impl Number for Decimal {
+    fn round_dp(self, places: u32) -> Self {
+        self.round_dp_with_strategy(places, RoundingStrategy::MidpointAwayFromZero)
+    }
+
+    fn is_finite(self) -> bool {
+        true
+    }
+
+    /// sqrt refines the `f64` square root with Newton's method, which doubles the correct digits
+    /// each step, stopping once a step changes nothing or after enough steps to fill a `Decimal`
+    fn sqrt(self) -> Self {
+        if self <= Decimal::ZERO {
+            return Decimal::ZERO;
+        }
+
+        let mut root = self
+            .to_f64()
+            .and_then(|value| Decimal::from_f64(value.sqrt()))
+            .filter(|root| *root > Decimal::ZERO)
+            .unwrap_or(self);
+        for _ in 0..8 {
+            let next = (root + self / root) / Decimal::TWO;
+            if next == root {
+                break;
+            }
+            root = next;
+        }
+        root
+    }
+
+    fn ln(self) -> Option<Self> {
+        self.checked_ln()
+    }
+}
+
+/// max is the larger of two numbers, which unlike `f64::max` works for any `Number`
+pub(crate) fn max<T: Number>(a: T, b: T) -> T {
+    if b > a {
+        b
+    } else {
+        a
+    }
+}
+
+/// min is the smaller of two numbers, see `max`
+pub(crate) fn min<T: Number>(a: T, b: T) -> T {
+    if b < a {
+        b
+    } else {
+        a
+    }
+}
+
+/// Rounding is the step applied to a calculated value before it is displayed or used in an order.
+///
+/// Indicators are always calculated at full precision, rounding only the output, as rounding every
+/// intermediate value compounds error, and cents are meaningless for sub-cent crypto pairs.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub enum Rounding {
+    /// leave values unrounded
+    #[default]
+    Exact,
+    /// round values to a number of decimal places
+    DecimalPlaces(u32),
+}
+
+impl Rounding {
+    /// Cents rounds to 2 decimal places, the precision US equities are quoted in
+    pub const CENTS: Rounding = Rounding::DecimalPlaces(2);
+
+    pub fn apply<T: Number>(&self, value: T) -> T {
+        match *self {
+            Rounding::Exact => value,
+            Rounding::DecimalPlaces(places) => value.round_dp(places),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn rounding_test() {
+        assert_eq!(Rounding::CENTS.apply(3.456789), 3.46);
+        assert_eq!(Rounding::Exact.apply(3.456789), 3.456789);
+        assert_eq!(Rounding::DecimalPlaces(6).apply(0.0000123456), 0.000012);
+
+        assert_eq!(Rounding::CENTS.apply(dec!(2.345)), dec!(2.35));
+        assert_eq!(
+            Rounding::DecimalPlaces(8).apply(dec!(0.0000123456789)),
+            dec!(0.00001235)
+        );
+    }
+
+    #[test]
+    fn decimal_test() {
+        assert_eq!(Number::sqrt(dec!(2.25)), dec!(1.5));
+        assert_eq!(Number::sqrt(dec!(-1)), dec!(0));
+        let root = Number::sqrt(dec!(0.0000000002));
+        assert!((root * root - dec!(0.0000000002)).abs() < dec!(0.000000000000000000001));
+
+        assert_eq!(Number::ln(dec!(1)), Some(dec!(0)));
+        assert!(Number::ln(dec!(0)).is_none());
+        assert!(Number::ln(-1.0).is_none());
+        assert_eq!(max(dec!(1), dec!(2)), dec!(2));
+        assert_eq!(min(1.0, 2.0), 1.0);
+    }
+}
@@ -0,0 +1,456 @@
+use crate::error::{check_candles, TaError};
+use crate::Candle;
+use serde::{Deserialize, Serialize};
+
+/// The number of candles looked back over to decide whether a pattern follows an up or down trend
+pub const TREND_LOOKBACK: usize = 5;
+
+/// Direction is the reversal or continuation a pattern suggests
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub enum Direction {
+    Bullish,
+    Bearish,
+    Neutral,
+}
+
+/// PatternKind enumerates the candlestick patterns `detect` can recognize
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub enum PatternKind {
+    Doji,
+    Hammer,
+    HangingMan,
+    InvertedHammer,
+    ShootingStar,
+    Engulfing,
+    Harami,
+    PiercingLine,
+    DarkCloudCover,
+    MorningStar,
+    EveningStar,
+    ThreeWhiteSoldiers,
+    ThreeBlackCrows,
+}
+
+/// Pattern is a detected candlestick pattern. `index` is the position of the pattern's last candle.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Pattern {
+    pub index: usize,
+    pub kind: PatternKind,
+    pub direction: Direction,
+}
+
+/// detect scans `candles` for every pattern, returning them ordered by index. A single candle can
+/// complete more than one pattern, eg: a doji that is also the middle of a morning star.
+///
+/// Patterns that depend on the prior trend (hammer, hanging man, inverted hammer and shooting star)
+/// compare the previous close against the close `TREND_LOOKBACK` candles earlier, so they aren't
+/// reported in the first few candles.
+/// <https://school.stockcharts.com/doku.php?id=chart_analysis:introduction_to_candlesticks>
+///
+/// It is an error when any candle isn't finite.
+pub fn detect(candles: &[Candle]) -> Result<Vec<Pattern>, TaError> {
+    check_candles(candles)?;
+    let mut patterns = Vec::new();
+
+    for index in 0..candles.len() {
+        patterns.extend(patterns_at(candles, index));
+    }
+
+    Ok(patterns)
+}
+
+/// detect_at returns the patterns completed by the candle at `index`, useful for checking only the
+/// most recent candle in a live loop. It is an error when any candle the patterns look back over
+/// isn't finite, and there are no patterns past the last candle.
+pub fn detect_at(candles: &[Candle], index: usize) -> Result<Vec<Pattern>, TaError> {
+    if index >= candles.len() {
+        return Ok(Vec::new());
+    }
+
+    check_candles(&candles[index.saturating_sub(TREND_LOOKBACK + 1)..=index])?;
+    Ok(patterns_at(candles, index))
+}
+
+/// patterns_at finds the patterns completed by the candle at `index`, which must be in range and
+/// have finite candles before it
+fn patterns_at(candles: &[Candle], index: usize) -> Vec<Pattern> {
+    let mut patterns = Vec::new();
+
+    let mut found = |kind: PatternKind, direction: Direction| {
+        patterns.push(Pattern {
+            index,
+            kind,
+            direction,
+        })
+    };
+
+    let current = &candles[index];
+    if is_doji(current) {
+        found(PatternKind::Doji, Direction::Neutral);
+    }
+
+    match trend(candles, index) {
+        Some(Direction::Bearish) if is_hammer(current) => {
+            found(PatternKind::Hammer, Direction::Bullish)
+        }
+        Some(Direction::Bullish) if is_hammer(current) => {
+            found(PatternKind::HangingMan, Direction::Bearish)
+        }
+        Some(Direction::Bearish) if is_inverted_hammer(current) => {
+            found(PatternKind::InvertedHammer, Direction::Bullish)
+        }
+        Some(Direction::Bullish) if is_inverted_hammer(current) => {
+            found(PatternKind::ShootingStar, Direction::Bearish)
+        }
+        _ => (),
+    }
+
+    if index >= 1 {
+        let previous = &candles[index - 1];
+
+        if let Some(direction) = engulfing(previous, current) {
+            found(PatternKind::Engulfing, direction);
+        }
+        if let Some(direction) = harami(previous, current) {
+            found(PatternKind::Harami, direction);
+        }
+        if is_piercing_line(previous, current) {
+            found(PatternKind::PiercingLine, Direction::Bullish);
+        }
+        if is_dark_cloud_cover(previous, current) {
+            found(PatternKind::DarkCloudCover, Direction::Bearish);
+        }
+    }
+
+    if index >= 2 {
+        let (first, second) = (&candles[index - 2], &candles[index - 1]);
+
+        if is_morning_star(first, second, current) {
+            found(PatternKind::MorningStar, Direction::Bullish);
+        }
+        if is_evening_star(first, second, current) {
+            found(PatternKind::EveningStar, Direction::Bearish);
+        }
+        if is_three_white_soldiers(first, second, current) {
+            found(PatternKind::ThreeWhiteSoldiers, Direction::Bullish);
+        }
+        if is_three_black_crows(first, second, current) {
+            found(PatternKind::ThreeBlackCrows, Direction::Bearish);
+        }
+    }
+
+    patterns
+}
+
+fn body(candle: &Candle) -> f64 {
+    (candle.close - candle.open).abs()
+}
+
+fn range(candle: &Candle) -> f64 {
+    candle.high - candle.low
+}
+
+fn upper_shadow(candle: &Candle) -> f64 {
+    candle.high - candle.close.max(candle.open)
+}
+
+fn lower_shadow(candle: &Candle) -> f64 {
+    candle.close.min(candle.open) - candle.low
+}
+
+fn is_bullish(candle: &Candle) -> bool {
+    candle.close > candle.open
+}
+
+fn is_bearish(candle: &Candle) -> bool {
+    candle.close < candle.open
+}
+
+/// is_long_body is true when the body makes up at least half of the candle's range
+fn is_long_body(candle: &Candle) -> bool {
+    range(candle) > 0.0 && body(candle) >= 0.5 * range(candle)
+}
+
+fn midpoint(candle: &Candle) -> f64 {
+    (candle.open + candle.close) / 2.0
+}
+
+/// trend compares the close before `index` against the close `TREND_LOOKBACK` candles earlier
+fn trend(candles: &[Candle], index: usize) -> Option<Direction> {
+    if index < TREND_LOOKBACK + 1 {
+        return None;
+    }
+
+    let previous = candles[index - 1].close;
+    let earlier = candles[index - 1 - TREND_LOOKBACK].close;
+    if previous > earlier {
+        Some(Direction::Bullish)
+    } else if previous < earlier {
+        Some(Direction::Bearish)
+    } else {
+        Some(Direction::Neutral)
+    }
+}
+
+/// a doji opens and closes at nearly the same price, with a body under 10% of its range
+fn is_doji(candle: &Candle) -> bool {
+    range(candle) > 0.0 && body(candle) <= 0.1 * range(candle)
+}
+
+/// a hammer has a small body at the top of the range, with a lower shadow at least twice the body
+fn is_hammer(candle: &Candle) -> bool {
+    let (body, range) = (body(candle), range(candle));
+
+    !is_doji(candle)
+        && body <= range / 3.0
+        && lower_shadow(candle) >= 2.0 * body
+        && upper_shadow(candle) <= 0.1 * range
+}
+
+/// an inverted hammer has a small body at the bottom of the range, with an upper shadow at least
+/// twice the body
+fn is_inverted_hammer(candle: &Candle) -> bool {
+    let (body, range) = (body(candle), range(candle));
+
+    !is_doji(candle)
+        && body <= range / 3.0
+        && upper_shadow(candle) >= 2.0 * body
+        && lower_shadow(candle) <= 0.1 * range
+}
+
+/// engulfing is when the current body completely covers the previous, opposite colored, body
+fn engulfing(previous: &Candle, current: &Candle) -> Option<Direction> {
+    if body(current) <= body(previous) {
+        return None;
+    }
+
+    if is_bearish(previous)
+        && is_bullish(current)
+        && current.open <= previous.close
+        && current.close >= previous.open
+    {
+        return Some(Direction::Bullish);
+    }
+    if is_bullish(previous)
+        && is_bearish(current)
+        && current.open >= previous.close
+        && current.close <= previous.open
+    {
+        return Some(Direction::Bearish);
+    }
+    None
+}
+
+/// harami is when a small, opposite colored, body sits within the previous long body
+fn harami(previous: &Candle, current: &Candle) -> Option<Direction> {
+    if !is_long_body(previous) || body(current) >= 0.5 * body(previous) {
+        return None;
+    }
+
+    if is_bearish(previous)
+        && is_bullish(current)
+        && current.open >= previous.close
+        && current.close <= previous.open
+    {
+        return Some(Direction::Bullish);
+    }
+    if is_bullish(previous)
+        && is_bearish(current)
+        && current.open <= previous.close
+        && current.close >= previous.open
+    {
+        return Some(Direction::Bearish);
+    }
+    None
+}
+
+/// a piercing line opens below a long bearish candle's close, then closes above its midpoint
+fn is_piercing_line(previous: &Candle, current: &Candle) -> bool {
+    is_bearish(previous)
+        && is_long_body(previous)
+        && is_bullish(current)
+        && current.open < previous.close
+        && current.close > midpoint(previous)
+        && current.close < previous.open
+}
+
+/// a dark cloud cover opens above a long bullish candle's close, then closes below its midpoint
+fn is_dark_cloud_cover(previous: &Candle, current: &Candle) -> bool {
+    is_bullish(previous)
+        && is_long_body(previous)
+        && is_bearish(current)
+        && current.open > previous.close
+        && current.close < midpoint(previous)
+        && current.close > previous.open
+}
+
+/// a morning star is a long bearish candle, a small body below its close, and a bullish candle
+/// closing above the first candle's midpoint
+fn is_morning_star(first: &Candle, second: &Candle, third: &Candle) -> bool {
+    is_bearish(first)
+        && is_long_body(first)
+        && body(second) <= 0.3 * body(first)
+        && second.open.max(second.close) < first.close
+        && is_bullish(third)
+        && third.close > midpoint(first)
+}
+
+/// an evening star is a long bullish candle, a small body above its close, and a bearish candle
+/// closing below the first candle's midpoint
+fn is_evening_star(first: &Candle, second: &Candle, third: &Candle) -> bool {
+    is_bullish(first)
+        && is_long_body(first)
+        && body(second) <= 0.3 * body(first)
+        && second.open.min(second.close) > first.close
+        && is_bearish(third)
+        && third.close < midpoint(first)
+}
+
+/// three white soldiers are three long bullish candles, each opening within the previous body and
+/// closing at a new high near the top of its range
+fn is_three_white_soldiers(first: &Candle, second: &Candle, third: &Candle) -> bool {
+    let soldier = |previous: &Candle, candle: &Candle| {
+        is_bullish(candle)
+            && is_long_body(candle)
+            && candle.open > previous.open
+            && candle.open <= previous.close
+            && candle.close > previous.close
+            && upper_shadow(candle) <= 0.3 * body(candle)
+    };
+
+    is_bullish(first) && is_long_body(first) && soldier(first, second) && soldier(second, third)
+}
+
+/// three black crows are three long bearish candles, each opening within the previous body and
+/// closing at a new low near the bottom of its range
+fn is_three_black_crows(first: &Candle, second: &Candle, third: &Candle) -> bool {
+    let crow = |previous: &Candle, candle: &Candle| {
+        is_bearish(candle)
+            && is_long_body(candle)
+            && candle.open < previous.open
+            && candle.open >= previous.close
+            && candle.close < previous.close
+            && lower_shadow(candle) <= 0.3 * body(candle)
+    };
+
+    is_bearish(first) && is_long_body(first) && crow(first, second) && crow(second, third)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candle(open: f64, high: f64, low: f64, close: f64) -> Candle {
+        Candle::new(0, open, high, low, close, 1000.0)
+    }
+
+    /// falling builds a steady downtrend ending just above `close`, to give trend patterns context
+    fn falling(close: f64) -> Vec<Candle> {
+        (0..6)
+            .rev()
+            .map(|i| {
+                let open = close + i as f64 + 1.0;
+                candle(open, open + 0.2, open - 1.2, open - 1.0)
+            })
+            .collect()
+    }
+
+    fn kinds(candles: &[Candle]) -> Vec<(PatternKind, Direction)> {
+        detect_at(candles, candles.len() - 1)
+            .unwrap()
+            .iter()
+            .map(|p| (p.kind, p.direction))
+            .collect()
+    }
+
+    #[test]
+    fn doji_test() {
+        let candles = vec![candle(10.0, 11.0, 9.0, 10.05)];
+
+        assert_eq!(
+            detect(&candles),
+            Ok(vec![Pattern {
+                index: 0,
+                kind: PatternKind::Doji,
+                direction: Direction::Neutral
+            }])
+        );
+        assert_eq!(detect_at(&candles, 1), Ok(vec![]));
+
+        let candles = vec![candle(10.0, f64::NAN, 9.0, 10.05)];
+        assert_eq!(detect(&candles), Err(TaError::NonFinite { datetime: 0 }));
+    }
+
+    #[test]
+    fn hammer_and_hanging_man_test() {
+        let hammer = candle(10.0, 10.55, 8.5, 10.5);
+
+        let mut candles = falling(11.0);
+        candles.push(hammer);
+        assert!(kinds(&candles).contains(&(PatternKind::Hammer, Direction::Bullish)));
+
+        let mut candles: Vec<Candle> = falling(11.0).into_iter().rev().collect();
+        candles.push(hammer);
+        assert!(kinds(&candles).contains(&(PatternKind::HangingMan, Direction::Bearish)));
+    }
+
+    #[test]
+    fn shooting_star_test() {
+        let mut candles: Vec<Candle> = falling(11.0).into_iter().rev().collect();
+        candles.push(candle(10.5, 12.0, 9.95, 10.0));
+
+        assert!(kinds(&candles).contains(&(PatternKind::ShootingStar, Direction::Bearish)));
+    }
+
+    #[test]
+    fn engulfing_and_harami_test() {
+        let candles = vec![candle(10.0, 10.2, 9.4, 9.5), candle(9.4, 10.3, 9.3, 10.2)];
+        assert!(kinds(&candles).contains(&(PatternKind::Engulfing, Direction::Bullish)));
+
+        let candles = vec![candle(10.0, 10.1, 8.9, 9.0), candle(9.3, 9.7, 9.2, 9.6)];
+        assert!(kinds(&candles).contains(&(PatternKind::Harami, Direction::Bullish)));
+
+        let candles = vec![candle(9.0, 10.1, 8.9, 10.0), candle(9.7, 9.8, 9.2, 9.3)];
+        assert!(kinds(&candles).contains(&(PatternKind::Harami, Direction::Bearish)));
+    }
+
+    #[test]
+    fn piercing_line_test() {
+        let candles = vec![candle(10.0, 10.1, 8.9, 9.0), candle(8.8, 9.8, 8.7, 9.7)];
+        assert!(kinds(&candles).contains(&(PatternKind::PiercingLine, Direction::Bullish)));
+    }
+
+    #[test]
+    fn stars_test() {
+        let candles = vec![
+            candle(10.0, 10.1, 8.9, 9.0),
+            candle(8.8, 8.9, 8.6, 8.75),
+            candle(8.9, 9.8, 8.85, 9.7),
+        ];
+        assert!(kinds(&candles).contains(&(PatternKind::MorningStar, Direction::Bullish)));
+
+        let candles = vec![
+            candle(9.0, 10.1, 8.9, 10.0),
+            candle(10.2, 10.4, 10.1, 10.25),
+            candle(10.1, 10.15, 9.2, 9.3),
+        ];
+        assert!(kinds(&candles).contains(&(PatternKind::EveningStar, Direction::Bearish)));
+    }
+
+    #[test]
+    fn soldiers_and_crows_test() {
+        let candles = vec![
+            candle(10.0, 11.05, 9.95, 11.0),
+            candle(10.5, 11.55, 10.45, 11.5),
+            candle(11.0, 12.05, 10.95, 12.0),
+        ];
+        assert!(kinds(&candles).contains(&(PatternKind::ThreeWhiteSoldiers, Direction::Bullish)));
+
+        let candles = vec![
+            candle(12.0, 12.05, 10.95, 11.0),
+            candle(11.5, 11.55, 10.45, 10.5),
+            candle(11.0, 11.05, 9.95, 10.0),
+        ];
+        assert!(kinds(&candles).contains(&(PatternKind::ThreeBlackCrows, Direction::Bearish)));
+    }
+}
@@ -0,0 +1,309 @@
+//! Property-based tests showing that every calculation in this crate returns an error or a value,
+//! rather than panicking, for any candles and parameters.
+use crate::charts::{self, BoxSize};
+use crate::error::TaError;
+use crate::momentum::StochasticValue;
+use crate::resample::{fill_gaps, resample, resample_ticks, Tick, Timeframe};
+use crate::trend::{AdxValue, AroonValue, IchimokuValue, MacdValue, ParabolicSarValue};
+use crate::volatility::{Bands, BollingerValue};
+use crate::{average, levels, momentum, patterns, stats, trend, volatility, volume, Candle};
+use proptest::prelude::*;
+
+/// price generates mostly ordinary prices, along with zeros, negatives and non-finite values
+fn price() -> impl Strategy<Value = f64> {
+    prop_oneof![
+        8 => 0.01f64..1e6,
+        1 => Just(0.0),
+        1 => -1e6f64..0.0,
+        1 => prop_oneof![Just(f64::NAN), Just(f64::INFINITY), Just(f64::NEG_INFINITY)],
+    ]
+}
+
+fn candle() -> impl Strategy<Value = Candle> {
+    (
+        0usize..4_000_000_000_000,
+        price(),
+        price(),
+        price(),
+        price(),
+        price(),
+    )
+        .prop_map(|(datetime, open, high, low, close, volume)| {
+            Candle::new(datetime, open, high, low, close, volume)
+        })
+}
+
+fn candles() -> impl Strategy<Value = Vec<Candle>> {
+    prop::collection::vec(candle(), 0..64).prop_map(|mut candles| {
+        candles.sort_by_key(|candle| candle.datetime);
+        candles
+    })
+}
+
+/// Finite is implemented by every indicator output, so `check` can tell that none of its numbers
+/// are NaN or infinite
+trait Finite {
+    fn is_finite(&self) -> bool;
+}
+
+impl Finite for f64 {
+    fn is_finite(&self) -> bool {
+        f64::is_finite(*self)
+    }
+}
+
+impl<T: Finite> Finite for Option<T> {
+    fn is_finite(&self) -> bool {
+        self.as_ref().is_none_or(Finite::is_finite)
+    }
+}
+
+impl Finite for StochasticValue {
+    fn is_finite(&self) -> bool {
+        self.k.is_finite() && Finite::is_finite(&self.d)
+    }
+}
+
+impl Finite for MacdValue {
+    fn is_finite(&self) -> bool {
+        self.macd.is_finite()
+            && Finite::is_finite(&self.signal)
+            && Finite::is_finite(&self.histogram)
+    }
+}
+
+impl Finite for AdxValue {
+    fn is_finite(&self) -> bool {
+        Finite::is_finite(&self.adx) && self.plus_di.is_finite() && self.minus_di.is_finite()
+    }
+}
+
+impl Finite for AroonValue {
+    fn is_finite(&self) -> bool {
+        self.up.is_finite() && self.down.is_finite()
+    }
+}
+
+impl Finite for ParabolicSarValue {
+    fn is_finite(&self) -> bool {
+        self.sar.is_finite()
+    }
+}
+
+impl Finite for IchimokuValue {
+    fn is_finite(&self) -> bool {
+        [self.close, self.tenkan, self.kijun, self.chikou]
+            .iter()
+            .all(|v| v.is_finite())
+            && [self.senkou_a, self.senkou_b, self.chikou_price]
+                .iter()
+                .all(Finite::is_finite)
+    }
+}
+
+impl Finite for Bands {
+    fn is_finite(&self) -> bool {
+        self.upper.is_finite() && self.middle.is_finite() && self.lower.is_finite()
+    }
+}
+
+impl Finite for BollingerValue {
+    fn is_finite(&self) -> bool {
+        self.bands.is_finite() && self.percent_b.is_finite() && self.bandwidth.is_finite()
+    }
+}
+
+/// check asserts that a series either failed with the expected error, or has a finite value or
+/// `None` for every candle. Only calculations that take the log of closes reject ones that aren't
+/// positive.
+fn check<T: Finite + std::fmt::Debug>(
+    series: Result<Vec<Option<T>>, TaError>,
+    candles: &[Candle],
+    periods: &[usize],
+) -> Result<(), TestCaseError> {
+    match series {
+        Ok(values) => {
+            prop_assert_eq!(values.len(), candles.len());
+            prop_assert!(candles.iter().all(Candle::is_finite));
+            prop_assert!(periods.iter().all(|&p| p > 0));
+            if let Some(value) = values.iter().find(|v| !v.is_finite()) {
+                prop_assert!(false, "non-finite output {:?}", value);
+            }
+        }
+        Err(TaError::ZeroPeriod) => prop_assert!(periods.contains(&0)),
+        Err(TaError::NonFinite { .. }) => prop_assert!(!candles.iter().all(Candle::is_finite)),
+        Err(TaError::InvalidValue { index }) => {
+            prop_assert!(candles.get(index).is_some_and(|c| c.close <= 0.0))
+        }
+        Err(e) => prop_assert!(false, "unexpected error {}", e),
+    }
+    Ok(())
+}
+
+proptest! {
+    #[test]
+    fn averages_never_panic(
+        candles in candles(),
+        period in 0usize..40,
+        start in prop_oneof![0usize..80, usize::MAX - 40..=usize::MAX],
+    ) {
+        check(average::sma_series(&candles, period), &candles, &[period])?;
+        check(average::ema_series(&candles, period), &candles, &[period])?;
+        check(average::wma_series(&candles, period), &candles, &[period])?;
+        check(average::dema_series(&candles, period), &candles, &[period])?;
+        check(average::tema_series(&candles, period), &candles, &[period])?;
+        check(average::hma_series(&candles, period), &candles, &[period])?;
+
+        match average::sma(&candles, start, period) {
+            Ok(value) => prop_assert!(candles.len().checked_sub(start).is_some_and(|n| n >= period) && period > 0 && !value.is_nan()),
+            Err(TaError::InsufficientData { required, available }) => {
+                prop_assert!(available < required)
+            }
+            Err(_) => (),
+        }
+
+        match average::ema(&candles, period as i32) {
+            Ok(_) => prop_assert!(candles.len() >= period && period > 0),
+            Err(TaError::InsufficientData { required, available }) => {
+                prop_assert!(available < required)
+            }
+            Err(_) => (),
+        }
+    }
+
+    #[test]
+    fn momentum_never_panics(candles in candles(), period in 0usize..30, d in 0usize..5) {
+        check(momentum::rsi_series(&candles, period), &candles, &[period])?;
+        check(momentum::stochastic_series(&candles, period, d), &candles, &[period, d])?;
+        check(momentum::williams_r_series(&candles, period), &candles, &[period])?;
+        check(momentum::cci_series(&candles, period), &candles, &[period])?;
+        check(momentum::mfi_series(&candles, period), &candles, &[period])?;
+    }
+
+    #[test]
+    fn trend_never_panics(
+        candles in candles(),
+        fast in 0usize..15,
+        slow in 0usize..30,
+        signal in 0usize..10,
+        step in 0.001f64..0.5,
+    ) {
+        check(trend::macd_series(&candles, fast, slow, signal), &candles, &[fast, slow, signal])?;
+        check(trend::adx_series(&candles, slow), &candles, &[slow])?;
+        check(trend::aroon_series(&candles, slow), &candles, &[slow])?;
+        check(trend::ichimoku_series(&candles, fast, slow, slow * 2, signal), &candles, &[fast, slow, signal])?;
+        check(trend::parabolic_sar_series(&candles, step, step * 10.0), &candles, &[])?;
+        prop_assert!(trend::parabolic_sar_series(&candles, f64::NAN, 0.2).is_err());
+    }
+
+    #[test]
+    fn volatility_never_panics(candles in candles(), period in 0usize..30, multiplier in 0.1f64..4.0) {
+        check(volatility::atr_series(&candles, period), &candles, &[period])?;
+        check(volatility::bollinger_series(&candles, period, multiplier), &candles, &[period])?;
+        check(volatility::keltner_series(&candles, period, period, multiplier), &candles, &[period])?;
+        check(volatility::donchian_series(&candles, period), &candles, &[period])?;
+        check(
+            volatility::historical_volatility_series(&candles, period, stats::TRADING_DAYS),
+            &candles,
+            &[period],
+        )?;
+    }
+
+    #[test]
+    fn volume_never_panics(candles in candles(), period in 0usize..30) {
+        check(volume::vwap_series(&candles), &candles, &[])?;
+        check(volume::obv_series(&candles), &candles, &[])?;
+        check(volume::ad_series(&candles), &candles, &[])?;
+        check(volume::cmf_series(&candles, period), &candles, &[period])?;
+        check(volume::volume_sma_series(&candles, period), &candles, &[period])?;
+    }
+
+    #[test]
+    fn candle_utilities_never_panic(candles in candles(), count in 0usize..5) {
+        let finite = candles.iter().all(Candle::is_finite);
+        match patterns::detect(&candles) {
+            Ok(found) => {
+                prop_assert!(finite);
+                prop_assert!(found.iter().all(|p| p.index < candles.len()));
+            }
+            Err(e) => prop_assert_eq!(e, TaError::NonFinite { datetime: candles.iter().find(|c| !c.is_finite()).unwrap().datetime }),
+        }
+        for index in 0..=candles.len() {
+            let _ = patterns::detect_at(&candles, index);
+        }
+
+        if let Ok(swings) = levels::zigzag(&candles, 0.05) {
+            prop_assert!(swings.windows(2).all(|w| w[0].kind != w[1].kind));
+            levels::zones(&swings, 0.01, 1)?;
+        }
+        if let Some(candle) = candles.first() {
+            let _ = levels::pivot_points(candle, levels::PivotMethod::Camarilla);
+        }
+        let _ = levels::swing_points(&candles, count);
+
+        if let Ok(ha) = charts::heikin_ashi(&candles) {
+            prop_assert_eq!(ha.len(), candles.len());
+        }
+        // boxes are kept large, as a tiny box over a wide range of prices prints millions of bricks
+        let _ = BoxSize::Atr(count).resolve(&candles);
+        let box_size = BoxSize::Fixed(count as f64 * 1e4);
+        let _ = charts::renko(&candles, box_size);
+        let _ = charts::point_and_figure(&candles, box_size, count);
+
+        for timeframe in &[
+            Timeframe::Minutes(count),
+            Timeframe::Hours(count),
+            Timeframe::Days(count),
+            Timeframe::Weeks(count),
+            Timeframe::Months(count),
+        ] {
+            match resample(&candles, *timeframe) {
+                Ok(bars) => {
+                    prop_assert!(finite && bars.len() <= candles.len());
+                    prop_assert!(fill_gaps(&bars, *timeframe).is_ok());
+                }
+                Err(e) => prop_assert!(!finite, "unexpected error {}", e),
+            }
+
+            let ticks: Vec<Tick> = candles
+                .iter()
+                .map(|c| Tick { datetime: c.datetime, price: c.close, volume: c.volume })
+                .collect();
+            let _ = resample_ticks(&ticks, *timeframe);
+        }
+    }
+
+    #[test]
+    fn stats_never_panic(values in prop::collection::vec(price(), 0..64), period in 0usize..10) {
+        let valid = |v: f64| v.is_finite() && v > 0.0;
+        let positive = values.iter().all(|&v| valid(v));
+        let returns = match stats::simple_returns(&values) {
+            Ok(returns) => {
+                prop_assert!(positive && returns.iter().all(|r| r.is_finite()));
+                returns
+            }
+            Err(TaError::InvalidValue { index }) => {
+                prop_assert!(!valid(values[index]));
+                Vec::new()
+            }
+            Err(e) => return Err(TestCaseError::fail(format!("unexpected error {}", e))),
+        };
+        prop_assert_eq!(stats::log_returns(&values).is_ok(), positive);
+
+        stats::rolling_volatility(&returns, period, stats::TRADING_DAYS);
+        stats::drawdown_series(&values);
+        stats::max_drawdown(&values);
+        match stats::summarize(&values, stats::TRADING_DAYS) {
+            Ok(summary) => prop_assert!(positive && values.len() >= 2 && summary.total_return.is_finite()),
+            Err(TaError::InsufficientData { required, available }) => {
+                prop_assert!(required == 2 && available == values.len() && available < 2)
+            }
+            Err(TaError::InvalidValue { .. }) => prop_assert!(!positive),
+            Err(e) => prop_assert!(false, "unexpected error {}", e),
+        }
+        stats::beta(&returns, &values);
+        stats::correlation(&values, &returns);
+        stats::historical_var(&returns, 0.95);
+        stats::parametric_var(&returns, 0.99);
+    }
+}
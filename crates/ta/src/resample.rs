@@ -0,0 +1,461 @@
+use crate::error::{check_candles, TaError};
+use crate::volume::DAY_MILLIS;
+use crate::Candle;
+use chrono::{Datelike, NaiveDate};
+use serde_json::{Map, Value};
+use std::{convert::TryFrom, str::FromStr};
+
+/// One minute in milliseconds
+pub const MINUTE_MILLIS: usize = 60_000;
+
+/// One hour in milliseconds
+pub const HOUR_MILLIS: usize = 3_600_000;
+
+/// The longest a timeframe can be, 100 years, which keeps period arithmetic in range
+pub const MAX_TIMEFRAME_DAYS: usize = 36_525;
+
+/// 1970-01-05, the first Monday after the epoch, which weekly periods are aligned to
+const FIRST_MONDAY_MILLIS: i64 = 4 * DAY_MILLIS as i64;
+
+/// Timeframe is the length of a resampled bar. Periods are aligned to UTC: minutes, hours and days
+/// to the epoch, weeks to Monday and months to the first of the month. Periods past the dates that
+/// can be represented saturate rather than overflow.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Timeframe {
+    Minutes(usize),
+    Hours(usize),
+    Days(usize),
+    Weeks(usize),
+    Months(usize),
+}
+
+impl Timeframe {
+    /// start returns the epoch milliseconds at which the period containing `datetime` starts
+    pub fn start(&self, datetime: usize) -> usize {
+        match *self {
+            Timeframe::Minutes(minutes) => align(datetime, length(minutes, MINUTE_MILLIS)),
+            Timeframe::Hours(hours) => align(datetime, length(hours, HOUR_MILLIS)),
+            Timeframe::Days(days) => align(datetime, length(days, DAY_MILLIS)),
+            Timeframe::Weeks(weeks) => {
+                let length = length(weeks, 7 * DAY_MILLIS) as i128;
+                let monday = FIRST_MONDAY_MILLIS as i128;
+                let offset = (datetime as i128 - monday).div_euclid(length);
+                (offset * length + monday).max(0) as usize
+            }
+            Timeframe::Months(months) => {
+                let months = month_count(months);
+                from_month_index(month_index(datetime).div_euclid(months) * months)
+            }
+        }
+    }
+
+    /// next returns the start of the period following the one that starts at `start`
+    pub fn next(&self, start: usize) -> usize {
+        match *self {
+            Timeframe::Minutes(minutes) => start.saturating_add(length(minutes, MINUTE_MILLIS)),
+            Timeframe::Hours(hours) => start.saturating_add(length(hours, HOUR_MILLIS)),
+            Timeframe::Days(days) => start.saturating_add(length(days, DAY_MILLIS)),
+            Timeframe::Weeks(weeks) => start.saturating_add(length(weeks, 7 * DAY_MILLIS)),
+            Timeframe::Months(months) => {
+                from_month_index(month_index(start).saturating_add(month_count(months)))
+            }
+        }
+    }
+}
+
+/// Timeframes are parsed from a count and a unit, eg: `15m`, `4h`, `1d`, `1w` or `3M`, and can be at
+/// most `MAX_TIMEFRAME_DAYS` long
+impl FromStr for Timeframe {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let split = s
+            .find(|c: char| !c.is_ascii_digit())
+            .ok_or("timeframe is missing a unit")?;
+        let (count, unit) = s.split_at(split);
+
+        let count: usize = count.parse().map_err(|_| "timeframe is missing a count")?;
+        if count == 0 {
+            return Err("timeframe count must be greater than 0");
+        }
+
+        let (timeframe, days) = match unit {
+            "m" => (Timeframe::Minutes(count), count / (24 * 60)),
+            "h" => (Timeframe::Hours(count), count / 24),
+            "d" => (Timeframe::Days(count), count),
+            "w" => (Timeframe::Weeks(count), count.saturating_mul(7)),
+            "M" => (Timeframe::Months(count), count.saturating_mul(365) / 12),
+            _ => return Err("timeframe unit must be one of m, h, d, w or M"),
+        };
+        if days > MAX_TIMEFRAME_DAYS {
+            return Err("timeframe can't be longer than 100 years");
+        }
+
+        Ok(timeframe)
+    }
+}
+
+/// Tick is a single price snapshot, with `datetime` in epoch milliseconds
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Tick {
+    pub datetime: usize,
+    pub price: f64,
+    pub volume: f64,
+}
+
+/// ticks_from_map parses a tick map as stored by the collector in `crypto/tick/<pair>/<yyyymmdd>.json`.
+///
+/// Keys are epoch seconds and values are Kraken ticker responses. The price is the last trade
+/// price, `c[0]`, and the volume is that trade's lot volume, `c[1]`. Kraken's ticker doesn't report
+/// volume between snapshots, so bar volumes built from ticks are only an approximation.
+pub fn ticks_from_map(map: &Map<String, Value>) -> Result<Vec<Tick>, &'static str> {
+    let mut ticks = Vec::with_capacity(map.len());
+
+    for (epoch, value) in map {
+        let seconds: usize = epoch.parse().map_err(|_| "tick key is not an epoch")?;
+        let last_trade = value
+            .get("c")
+            .and_then(Value::as_array)
+            .ok_or("tick is missing its last trade")?;
+
+        let field = |index: usize| -> Result<f64, &'static str> {
+            last_trade
+                .get(index)
+                .and_then(Value::as_str)
+                .ok_or("tick last trade is missing a field")?
+                .parse()
+                .map_err(|_| "tick last trade is not a number")
+        };
+
+        ticks.push(Tick {
+            datetime: seconds
+                .checked_mul(1000)
+                .ok_or("tick key is not an epoch")?,
+            price: field(0)?,
+            volume: field(1)?,
+        });
+    }
+
+    ticks.sort_by_key(|tick| tick.datetime);
+    Ok(ticks)
+}
+
+/// resample_ticks aggregates ticks into one candle per period of `timeframe`. Each candle's datetime
+/// is the start of its period, and periods without any ticks produce no candle, see `fill_gaps`.
+/// Ticks must be sorted by datetime, as `ticks_from_map` returns them, and have finite prices and
+/// volumes.
+pub fn resample_ticks(ticks: &[Tick], timeframe: Timeframe) -> Result<Vec<Candle>, TaError> {
+    let candles: Vec<Candle> = ticks
+        .iter()
+        .map(|tick| {
+            Candle::new(
+                tick.datetime,
+                tick.price,
+                tick.price,
+                tick.price,
+                tick.price,
+                tick.volume,
+            )
+        })
+        .collect();
+
+    resample(&candles, timeframe)
+}
+
+/// resample aggregates candles into one candle per period of `timeframe`, eg: daily candles into
+/// weekly or monthly bars. Candles must be sorted by datetime, as `Series` keeps them.
+///
+/// Each bar opens at its first candle's open, closes at its last candle's close, and spans the
+/// highest high and lowest low. Its datetime is the start of the period, even when the first
+/// candle falls later, eg: a week starting on a Tuesday after a Monday holiday. Periods without any
+/// candles produce no bar, and the final bar may be partial, see `is_complete`. It is an error when
+/// any candle isn't finite.
+pub fn resample(candles: &[Candle], timeframe: Timeframe) -> Result<Vec<Candle>, TaError> {
+    check_candles(candles)?;
+    let mut bars: Vec<Candle> = Vec::new();
+
+    for candle in candles {
+        let start = timeframe.start(candle.datetime);
+
+        match bars.last_mut() {
+            Some(bar) if bar.datetime == start => {
+                bar.high = bar.high.max(candle.high);
+                bar.low = bar.low.min(candle.low);
+                bar.close = candle.close;
+                bar.volume += candle.volume;
+            }
+            _ => bars.push(Candle {
+                datetime: start,
+                ..*candle
+            }),
+        }
+    }
+
+    Ok(bars)
+}
+
+/// fill_gaps inserts a flat candle, with no volume, at the previous close for every period of
+/// `timeframe` missing between the first and last bar. This suits markets that trade around the
+/// clock, like crypto. Filling daily equity bars would invent candles for weekends and holidays. It
+/// is an error when any bar isn't finite.
+pub fn fill_gaps(bars: &[Candle], timeframe: Timeframe) -> Result<Vec<Candle>, TaError> {
+    check_candles(bars)?;
+    let mut filled: Vec<Candle> = Vec::with_capacity(bars.len());
+
+    for bar in bars {
+        if let Some(previous) = filled.last().copied() {
+            let mut start = timeframe.next(previous.datetime);
+            while start < bar.datetime {
+                let close = previous.close;
+                filled.push(Candle::new(start, close, close, close, close, 0.0));
+                start = timeframe.next(start);
+            }
+        }
+        filled.push(*bar);
+    }
+
+    Ok(filled)
+}
+
+/// is_complete reports whether the period of a resampled bar has ended by `now`, in epoch
+/// milliseconds. The last bar built from live data is usually still forming.
+pub fn is_complete(bar: &Candle, timeframe: Timeframe, now: usize) -> bool {
+    timeframe.next(timeframe.start(bar.datetime)) <= now
+}
+
+fn align(datetime: usize, length: usize) -> usize {
+    datetime / length * length
+}
+
+/// length returns `count` periods of `unit` milliseconds, saturating rather than overflowing
+fn length(count: usize, unit: usize) -> usize {
+    count.max(1).saturating_mul(unit)
+}
+
+/// month_count converts a count of months to the type month indices are kept in
+fn month_count(months: usize) -> i32 {
+    i32::try_from(months).unwrap_or(i32::MAX).max(1)
+}
+
+/// month_index counts months since year 0 for the UTC date of `datetime`, or returns `i32::MAX`
+/// past the last date chrono represents
+fn month_index(datetime: usize) -> i32 {
+    match date_from_millis(datetime) {
+        Some(date) => date.year() * 12 + date.month0() as i32,
+        None => i32::MAX,
+    }
+}
+
+/// from_month_index returns the epoch milliseconds at the start of the month `index` months after
+/// year 0, saturating at both ends of the dates chrono represents
+fn from_month_index(index: i32) -> usize {
+    let (year, month0) = (index.div_euclid(12), index.rem_euclid(12));
+    match NaiveDate::from_ymd_opt(year, month0 as u32 + 1, 1) {
+        Some(date) => {
+            let days = date.signed_duration_since(epoch()).num_days().max(0);
+            days as usize * DAY_MILLIS
+        }
+        None if index < 0 => 0,
+        None => usize::MAX,
+    }
+}
+
+fn date_from_millis(datetime: usize) -> Option<NaiveDate> {
+    // capped well past chrono's last date, so building the duration can't panic
+    let days = (datetime / DAY_MILLIS).min(i32::MAX as usize) as i64;
+    epoch().checked_add_signed(chrono::Duration::days(days))
+}
+
+fn epoch() -> NaiveDate {
+    NaiveDate::from_ymd_opt(1970, 1, 1).unwrap_or_else(|| unreachable!())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// millis returns epoch milliseconds for a UTC date and hour
+    fn millis(year: i32, month: u32, day: u32, hour: usize) -> usize {
+        let date = NaiveDate::from_ymd_opt(year, month, day).unwrap();
+        date.signed_duration_since(epoch()).num_days() as usize * DAY_MILLIS + hour * HOUR_MILLIS
+    }
+
+    fn daily(year: i32, month: u32, day: u32, close: f64) -> Candle {
+        Candle::new(
+            millis(year, month, day, 5),
+            close - 1.0,
+            close + 1.0,
+            close - 2.0,
+            close,
+            100.0,
+        )
+    }
+
+    #[test]
+    fn timeframe_from_str_test() {
+        assert_eq!("15m".parse(), Ok(Timeframe::Minutes(15)));
+        assert_eq!("4h".parse(), Ok(Timeframe::Hours(4)));
+        assert_eq!("1w".parse(), Ok(Timeframe::Weeks(1)));
+        assert_eq!("3M".parse(), Ok(Timeframe::Months(3)));
+        assert!("0d".parse::<Timeframe>().is_err());
+        assert!("d".parse::<Timeframe>().is_err());
+        assert!("5y".parse::<Timeframe>().is_err());
+
+        // counts are bounded, so periods can't overflow
+        assert_eq!("36525d".parse(), Ok(Timeframe::Days(36_525)));
+        assert_eq!("1200M".parse(), Ok(Timeframe::Months(1200)));
+        assert!("36526d".parse::<Timeframe>().is_err());
+        assert!("1201M".parse::<Timeframe>().is_err());
+        assert!("99999999999999999m".parse::<Timeframe>().is_err());
+        assert!("99999999999999999999999m".parse::<Timeframe>().is_err());
+    }
+
+    #[test]
+    fn timeframe_start_test() {
+        // 2022-01-06 was a Thursday
+        let thursday = millis(2022, 1, 6, 13);
+
+        assert_eq!(Timeframe::Hours(4).start(thursday), millis(2022, 1, 6, 12));
+        assert_eq!(Timeframe::Weeks(1).start(thursday), millis(2022, 1, 3, 0));
+        assert_eq!(Timeframe::Months(1).start(thursday), millis(2022, 1, 1, 0));
+        assert_eq!(
+            Timeframe::Months(3).start(millis(2022, 5, 20, 0)),
+            millis(2022, 4, 1, 0)
+        );
+        assert_eq!(
+            Timeframe::Months(1).next(millis(2021, 12, 1, 0)),
+            millis(2022, 1, 1, 0)
+        );
+
+        // timeframes built directly aren't bounded, and saturate instead
+        assert_eq!(Timeframe::Minutes(usize::MAX).start(thursday), 0);
+        assert_eq!(Timeframe::Minutes(usize::MAX).next(thursday), usize::MAX);
+        assert_eq!(Timeframe::Weeks(usize::MAX).next(thursday), usize::MAX);
+        assert_eq!(Timeframe::Months(1 << 32).start(thursday), 0);
+        assert_eq!(Timeframe::Months(usize::MAX).next(thursday), usize::MAX);
+        assert_eq!(Timeframe::Months(1).start(usize::MAX), usize::MAX);
+    }
+
+    #[test]
+    fn ticks_from_map_test() {
+        let map = json!({
+            "1641470460": {"a": ["2.0", "1", "1.000"], "b": ["1.0", "1", "1.000"], "c": ["1.5", "0.2"]},
+            "1641470400": {"a": ["2.0", "1", "1.000"], "b": ["1.0", "1", "1.000"], "c": ["1.0", "0.1"]}
+        });
+
+        let ticks = ticks_from_map(map.as_object().unwrap()).unwrap();
+        assert_eq!(
+            ticks,
+            vec![
+                Tick {
+                    datetime: 1641470400000,
+                    price: 1.0,
+                    volume: 0.1
+                },
+                Tick {
+                    datetime: 1641470460000,
+                    price: 1.5,
+                    volume: 0.2
+                },
+            ]
+        );
+
+        let bad = json!({"1641470400": {"c": ["not a price", "0.1"]}});
+        assert!(ticks_from_map(bad.as_object().unwrap()).is_err());
+    }
+
+    #[test]
+    fn resample_ticks_test() {
+        let tick = |second: usize, price: f64| Tick {
+            datetime: 1641470400000 + second * 1000,
+            price,
+            volume: 1.0,
+        };
+        let ticks = vec![
+            tick(0, 10.0),
+            tick(20, 12.0),
+            tick(40, 9.0),
+            tick(50, 11.0),
+            // nothing trades in the second and third minutes
+            tick(190, 13.0),
+        ];
+
+        let bars = resample_ticks(&ticks, Timeframe::Minutes(1)).unwrap();
+        assert_eq!(
+            bars,
+            vec![
+                Candle::new(1641470400000, 10.0, 12.0, 9.0, 11.0, 4.0),
+                Candle::new(1641470580000, 13.0, 13.0, 13.0, 13.0, 1.0),
+            ]
+        );
+
+        let filled = fill_gaps(&bars, Timeframe::Minutes(1)).unwrap();
+        assert_eq!(filled.len(), 4);
+        assert_eq!(
+            filled[1],
+            Candle::new(1641470460000, 11.0, 11.0, 11.0, 11.0, 0.0)
+        );
+        assert_eq!(filled[2].datetime, 1641470520000);
+
+        let ticks = vec![tick(0, 10.0), tick(20, f64::NAN)];
+        assert_eq!(
+            resample_ticks(&ticks, Timeframe::Minutes(1)),
+            Err(TaError::NonFinite {
+                datetime: 1641470420000
+            })
+        );
+    }
+
+    #[test]
+    fn resample_weekly_test() {
+        let candles = vec![
+            daily(2022, 1, 6, 10.0),
+            daily(2022, 1, 7, 12.0),
+            // Monday the 17th is a holiday, so the week starts on Tuesday
+            daily(2022, 1, 18, 11.0),
+            daily(2022, 1, 19, 15.0),
+            daily(2022, 1, 21, 14.0),
+        ];
+
+        let weekly = resample(&candles, Timeframe::Weeks(1)).unwrap();
+        assert_eq!(
+            weekly,
+            vec![
+                Candle::new(millis(2022, 1, 3, 0), 9.0, 13.0, 8.0, 12.0, 200.0),
+                Candle::new(millis(2022, 1, 17, 0), 10.0, 16.0, 9.0, 14.0, 300.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn resample_monthly_test() {
+        let candles = vec![
+            daily(2021, 12, 30, 10.0),
+            daily(2021, 12, 31, 11.0),
+            daily(2022, 1, 3, 12.0),
+            daily(2022, 1, 31, 9.0),
+            daily(2022, 2, 1, 13.0),
+        ];
+
+        let monthly = resample(&candles, Timeframe::Months(1)).unwrap();
+        assert_eq!(
+            monthly.iter().map(|c| c.datetime).collect::<Vec<usize>>(),
+            vec![
+                millis(2021, 12, 1, 0),
+                millis(2022, 1, 1, 0),
+                millis(2022, 2, 1, 0)
+            ]
+        );
+        assert_eq!(
+            monthly[1],
+            Candle::new(millis(2022, 1, 1, 0), 11.0, 13.0, 7.0, 9.0, 200.0)
+        );
+
+        // February is still forming mid-month, but January has closed
+        let now = millis(2022, 2, 14, 0);
+        assert!(is_complete(&monthly[1], Timeframe::Months(1), now));
+        assert!(!is_complete(&monthly[2], Timeframe::Months(1), now));
+    }
+}
@@ -0,0 +1,485 @@
+use crate::error::{check_length, check_values, TaError};
+use crate::resample::Timeframe;
+use serde::{Deserialize, Serialize};
+
+/// The number of trading days in a year, used to annualize statistics of daily equity candles
+pub const TRADING_DAYS: f64 = 252.0;
+
+/// The number of calendar days in a year, used to annualize crypto and account balance statistics
+pub const CALENDAR_DAYS: f64 = 365.0;
+
+/// simple_returns returns the percent change between each value and the next, as fractions, or an
+/// error when any value is NaN, infinite, or not positive
+pub fn simple_returns(values: &[f64]) -> Result<Vec<f64>, TaError> {
+    check_values(values)?;
+    Ok(values.windows(2).map(|w| w[1] / w[0] - 1.0).collect())
+}
+
+/// log_returns returns the natural log of the ratio between each value and the next, or an error
+/// when any value is NaN, infinite, or not positive
+pub fn log_returns(values: &[f64]) -> Result<Vec<f64>, TaError> {
+    check_values(values)?;
+    Ok(values.windows(2).map(|w| (w[1] / w[0]).ln()).collect())
+}
+
+/// mean returns the arithmetic mean of `values`, or `None` when there are none
+pub fn mean(values: &[f64]) -> Option<f64> {
+    if values.is_empty() {
+        return None;
+    }
+    Some(values.iter().sum::<f64>() / values.len() as f64)
+}
+
+/// stdev returns the sample standard deviation of `values`, or `None` with fewer than 2 values
+pub fn stdev(values: &[f64]) -> Option<f64> {
+    if values.len() < 2 {
+        return None;
+    }
+
+    let mean = mean(values)?;
+    let squares: f64 = values.iter().map(|v| (v - mean).powi(2)).sum();
+    Some((squares / (values.len() - 1) as f64).sqrt())
+}
+
+/// rolling_volatility returns the annualized sample standard deviation of each `period` returns,
+/// aligned with `returns`. Use `TRADING_DAYS` or `CALENDAR_DAYS` for `periods_per_year` with daily returns.
+pub fn rolling_volatility(
+    returns: &[f64],
+    period: usize,
+    periods_per_year: f64,
+) -> Vec<Option<f64>> {
+    (0..returns.len())
+        .map(|i| {
+            if period < 2 || i + 1 < period {
+                return None;
+            }
+            stdev(&returns[i + 1 - period..=i]).map(|s| s * periods_per_year.sqrt())
+        })
+        .collect()
+}
+
+/// drawdown_series returns how far each value sits below the highest value before it, as a fraction
+pub fn drawdown_series(values: &[f64]) -> Vec<f64> {
+    let mut peak = f64::MIN;
+
+    values
+        .iter()
+        .map(|&value| {
+            peak = peak.max(value);
+            if peak > 0.0 {
+                1.0 - value / peak
+            } else {
+                0.0
+            }
+        })
+        .collect()
+}
+
+/// Drawdown describes the largest peak to trough decline of a series of values.
+///
+/// `max` is the decline as a fraction of the peak, `peak` and `trough` are its indexes, and
+/// `recovery` is the index where the value first got back to the peak, if it has. `duration` is the
+/// longest number of periods spent below a previous peak, which isn't necessarily the same
+/// stretch as the deepest decline.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct Drawdown {
+    pub max: f64,
+    pub peak: usize,
+    pub trough: usize,
+    pub recovery: Option<usize>,
+    pub duration: usize,
+}
+
+/// max_drawdown returns the largest decline in `values`, see `Drawdown`
+pub fn max_drawdown(values: &[f64]) -> Drawdown {
+    let mut drawdown = Drawdown::default();
+    let mut peak = 0;
+
+    for (i, &value) in values.iter().enumerate() {
+        if value >= values[peak] {
+            peak = i;
+            if drawdown.recovery.is_none() && drawdown.max > 0.0 && i > drawdown.trough {
+                drawdown.recovery = Some(i);
+            }
+            continue;
+        }
+
+        drawdown.duration = drawdown.duration.max(i - peak);
+        let decline = 1.0 - value / values[peak];
+        if decline > drawdown.max {
+            drawdown.max = decline;
+            drawdown.peak = peak;
+            drawdown.trough = i;
+            drawdown.recovery = None;
+        }
+    }
+
+    drawdown
+}
+
+/// cagr is the compound annual growth rate between the first and last value, where each value is
+/// one period apart.
+pub fn cagr(values: &[f64], periods_per_year: f64) -> Option<f64> {
+    let (first, last) = (*values.first()?, *values.last()?);
+    if values.len() < 2 || first <= 0.0 || last < 0.0 {
+        return None;
+    }
+
+    let years = (values.len() - 1) as f64 / periods_per_year;
+    Some((last / first).powf(1.0 / years) - 1.0)
+}
+
+/// sharpe is the annualized Sharpe ratio of `returns`, their mean excess over `risk_free` (per
+/// period) divided by their standard deviation. It is `None` when returns don't vary.
+/// <https://www.investopedia.com/terms/s/sharperatio.asp>
+pub fn sharpe(returns: &[f64], risk_free: f64, periods_per_year: f64) -> Option<f64> {
+    let deviation = stdev(returns)?;
+    if deviation == 0.0 {
+        return None;
+    }
+
+    Some((mean(returns)? - risk_free) / deviation * periods_per_year.sqrt())
+}
+
+/// sortino is the annualized Sortino ratio of `returns`, like `sharpe` but only penalizing returns
+/// below `target` (per period). It is `None` when no return falls below the target.
+/// <https://www.investopedia.com/terms/s/sortinoratio.asp>
+pub fn sortino(returns: &[f64], target: f64, periods_per_year: f64) -> Option<f64> {
+    let shortfalls: f64 = returns.iter().map(|r| (r - target).min(0.0).powi(2)).sum();
+    let downside = (shortfalls / returns.len() as f64).sqrt();
+    if downside == 0.0 || downside.is_nan() {
+        return None;
+    }
+
+    Some((mean(returns)? - target) / downside * periods_per_year.sqrt())
+}
+
+/// calmar is the compound annual growth rate divided by the max drawdown of `values`
+/// <https://www.investopedia.com/terms/c/calmarratio.asp>
+pub fn calmar(values: &[f64], periods_per_year: f64) -> Option<f64> {
+    let drawdown = max_drawdown(values).max;
+    if drawdown == 0.0 {
+        return None;
+    }
+
+    Some(cagr(values, periods_per_year)? / drawdown)
+}
+
+/// covariance returns the sample covariance of two return series, paired up from their starts
+pub fn covariance(returns: &[f64], benchmark: &[f64]) -> Option<f64> {
+    let length = returns.len().min(benchmark.len());
+    if length < 2 {
+        return None;
+    }
+
+    let (returns, benchmark) = (&returns[..length], &benchmark[..length]);
+    let (a, b) = (mean(returns)?, mean(benchmark)?);
+    let products: f64 = returns
+        .iter()
+        .zip(benchmark)
+        .map(|(r, m)| (r - a) * (m - b))
+        .sum();
+
+    Some(products / (length - 1) as f64)
+}
+
+/// beta is how strongly `returns` move with `benchmark` returns, their covariance divided by the
+/// benchmark's variance.
+pub fn beta(returns: &[f64], benchmark: &[f64]) -> Option<f64> {
+    let length = returns.len().min(benchmark.len());
+    let variance = stdev(&benchmark[..length])?.powi(2);
+    if variance == 0.0 {
+        return None;
+    }
+
+    Some(covariance(returns, benchmark)? / variance)
+}
+
+/// correlation is the Pearson correlation coefficient between `returns` and `benchmark` returns
+pub fn correlation(returns: &[f64], benchmark: &[f64]) -> Option<f64> {
+    let length = returns.len().min(benchmark.len());
+    let deviations = stdev(&returns[..length])? * stdev(&benchmark[..length])?;
+    if deviations == 0.0 {
+        return None;
+    }
+
+    Some(covariance(returns, benchmark)? / deviations)
+}
+
+/// historical_var is the one period value-at-risk at `confidence`, eg: 0.95, taken from the
+/// empirical distribution of `returns`. It is reported as a positive fraction lost, linearly
+/// interpolating between the two returns either side of the quantile.
+pub fn historical_var(returns: &[f64], confidence: f64) -> Option<f64> {
+    if returns.is_empty() || !(0.0..1.0).contains(&confidence) {
+        return None;
+    }
+
+    let mut sorted = returns.to_vec();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+
+    let position = (1.0 - confidence) * (sorted.len() - 1) as f64;
+    let (lower, upper) = (position.floor() as usize, position.ceil() as usize);
+    let quantile = sorted[lower] + (sorted[upper] - sorted[lower]) * (position - lower as f64);
+
+    Some(-quantile)
+}
+
+/// parametric_var is the one period value-at-risk at `confidence`, assuming `returns` are normally
+/// distributed, reported as a positive fraction lost.
+pub fn parametric_var(returns: &[f64], confidence: f64) -> Option<f64> {
+    if !(0.0..1.0).contains(&confidence) || confidence == 0.0 {
+        return None;
+    }
+
+    Some(normal_quantile(confidence) * stdev(returns)? - mean(returns)?)
+}
+
+/// normal_quantile is the inverse of the standard normal CDF for `p` in (0, 1), using Acklam's
+/// rational approximation, which has a relative error under 1.15e-9.
+/// <https://web.archive.org/web/20151030215612/http://home.online.no/~pjacklam/notes/invnorm/>
+pub fn normal_quantile(p: f64) -> f64 {
+    const A: [f64; 6] = [
+        -3.969683028665376e+01,
+        2.209460984245205e+02,
+        -2.759285104469687e+02,
+        1.38357751867269e+02,
+        -3.066479806614716e+01,
+        2.506628277459239e+00,
+    ];
+    const B: [f64; 5] = [
+        -5.447609879822406e+01,
+        1.615858368580409e+02,
+        -1.556989798598866e+02,
+        6.680131188771972e+01,
+        -1.328068155288572e+01,
+    ];
+    const C: [f64; 6] = [
+        -7.784894002430293e-03,
+        -3.223964580411365e-01,
+        -2.400758277161838e+00,
+        -2.549732539343734e+00,
+        4.374664141464968e+00,
+        2.938163982698783e+00,
+    ];
+    const D: [f64; 4] = [
+        7.784695709041462e-03,
+        3.224671290700398e-01,
+        2.445134137142996e+00,
+        3.754408661907416e+00,
+    ];
+    const LOW: f64 = 0.02425;
+
+    let tail = |q: f64| {
+        (((((C[0] * q + C[1]) * q + C[2]) * q + C[3]) * q + C[4]) * q + C[5])
+            / ((((D[0] * q + D[1]) * q + D[2]) * q + D[3]) * q + 1.0)
+    };
+
+    if p <= 0.0 {
+        f64::NEG_INFINITY
+    } else if p >= 1.0 {
+        f64::INFINITY
+    } else if p < LOW {
+        tail((-2.0 * p.ln()).sqrt())
+    } else if p > 1.0 - LOW {
+        -tail((-2.0 * (1.0 - p).ln()).sqrt())
+    } else {
+        let q = p - 0.5;
+        let r = q * q;
+        (((((A[0] * r + A[1]) * r + A[2]) * r + A[3]) * r + A[4]) * r + A[5]) * q
+            / (((((B[0] * r + B[1]) * r + B[2]) * r + B[3]) * r + B[4]) * r + 1.0)
+    }
+}
+
+/// period_closes keeps the last value recorded in each period of `timeframe`, turning irregularly
+/// sampled points into evenly spaced ones. Points are `(epoch millis, value)` pairs sorted by time,
+/// eg: the balances the collector records in `account_histories` on every poll.
+pub fn period_closes(points: &[(usize, f64)], timeframe: Timeframe) -> Vec<(usize, f64)> {
+    let mut closes: Vec<(usize, f64)> = Vec::new();
+
+    for &(datetime, value) in points {
+        let start = timeframe.start(datetime);
+        match closes.last_mut() {
+            Some(last) if last.0 == start => last.1 = value,
+            _ => closes.push((start, value)),
+        }
+    }
+
+    closes
+}
+
+/// Summary collects the performance statistics of a series of values, such as candle closes or an
+/// account's balance history.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Summary {
+    pub total_return: f64,
+    pub cagr: Option<f64>,
+    pub volatility: Option<f64>,
+    pub sharpe: Option<f64>,
+    pub sortino: Option<f64>,
+    pub calmar: Option<f64>,
+    pub max_drawdown: f64,
+    pub max_drawdown_duration: usize,
+    pub var_95: Option<f64>,
+}
+
+/// summarize computes a `Summary` of evenly spaced `values`, with a risk free rate of 0, eg:
+/// `summarize(&series.closes(), TRADING_DAYS)`. It is an error with fewer than 2 values, or when any
+/// value is NaN, infinite, or not positive.
+pub fn summarize(values: &[f64], periods_per_year: f64) -> Result<Summary, TaError> {
+    check_length(values, 2)?;
+    let returns = simple_returns(values)?;
+    let drawdown = max_drawdown(values);
+
+    Ok(Summary {
+        total_return: values[values.len() - 1] / values[0] - 1.0,
+        cagr: cagr(values, periods_per_year),
+        volatility: stdev(&returns).map(|s| s * periods_per_year.sqrt()),
+        sharpe: sharpe(&returns, 0.0, periods_per_year),
+        sortino: sortino(&returns, 0.0, periods_per_year),
+        calmar: calmar(values, periods_per_year),
+        max_drawdown: drawdown.max,
+        max_drawdown_duration: drawdown.duration,
+        var_95: historical_var(&returns, 0.95),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::*;
+
+    const VALUES: [f64; 10] = [
+        100.0, 110.0, 99.0, 105.0, 120.0, 90.0, 95.0, 130.0, 125.0, 128.0,
+    ];
+    const BENCHMARK: [f64; 10] = [50.0, 52.0, 51.0, 53.0, 55.0, 50.0, 51.0, 56.0, 57.0, 56.0];
+
+    fn close(actual: Option<f64>, expected: f64) {
+        let actual = actual.unwrap();
+        assert!(
+            (actual - expected).abs() < 1e-4,
+            "{} != {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn returns_test() {
+        let simple = simple_returns(&[100.0, 110.0, 99.0]).unwrap();
+        assert_series(
+            &simple.iter().map(|&r| Some(r)).collect::<Vec<_>>(),
+            &[Some(0.1), Some(-0.1)],
+        );
+
+        let log = log_returns(&[100.0, 110.0, 99.0]).unwrap();
+        close(Some(log[0]), 0.09531);
+        close(Some(log[1]), -0.10536);
+
+        assert_eq!(
+            log_returns(&[100.0, -5.0]),
+            Err(TaError::InvalidValue { index: 1 })
+        );
+    }
+
+    #[test]
+    fn rolling_volatility_test() {
+        let returns = simple_returns(&VALUES).unwrap();
+        let volatility = rolling_volatility(&returns, 3, TRADING_DAYS);
+
+        assert_series(
+            &volatility,
+            &warmup(2, &[1.6818, 1.9608, 3.2891, 3.2747, 4.9087, 3.3817, 3.4784]),
+        );
+    }
+
+    #[test]
+    fn max_drawdown_test() {
+        let drawdown = max_drawdown(&VALUES);
+
+        assert_eq!(
+            drawdown,
+            Drawdown {
+                max: 0.25,
+                peak: 4,
+                trough: 5,
+                recovery: Some(7),
+                duration: 2,
+            }
+        );
+        assert_eq!(max_drawdown(&[1.0, 2.0, 3.0]).max, 0.0);
+
+        let drawdowns = drawdown_series(&VALUES);
+        close(Some(drawdowns[2]), 0.1);
+        close(Some(drawdowns[6]), 0.208333);
+    }
+
+    #[test]
+    fn ratios_test() {
+        let returns = simple_returns(&VALUES).unwrap();
+
+        close(cagr(&VALUES, 9.0), 0.28);
+        close(sharpe(&returns, 0.0, TRADING_DAYS), 3.753);
+        close(sortino(&returns, 0.0, TRADING_DAYS), 7.0616);
+        close(calmar(&VALUES, 9.0), 1.12);
+        assert_eq!(sharpe(&[0.01, 0.01], 0.0, TRADING_DAYS), None);
+    }
+
+    #[test]
+    fn benchmark_test() {
+        let returns = simple_returns(&VALUES).unwrap();
+        let benchmark = simple_returns(&BENCHMARK).unwrap();
+
+        close(beta(&returns, &benchmark), 3.0548);
+        close(correlation(&returns, &benchmark), 0.9411);
+        close(correlation(&returns, &returns), 1.0);
+    }
+
+    #[test]
+    fn var_test() {
+        let returns = simple_returns(&VALUES).unwrap();
+
+        close(historical_var(&returns, 0.95), 0.19);
+        close(parametric_var(&returns, 0.95), 0.2403);
+        close(Some(normal_quantile(0.95)), 1.644854);
+        close(Some(normal_quantile(0.01)), -2.326348);
+        assert_eq!(historical_var(&[], 0.95), None);
+    }
+
+    #[test]
+    fn period_closes_test() {
+        let hour = 3_600_000;
+        let day = 24 * hour;
+        let balances = [
+            (hour, 100.0),
+            (2 * hour, 101.0),
+            (day + hour, 99.0),
+            (day + 5 * hour, 98.0),
+            (3 * day, 103.0),
+        ];
+
+        assert_eq!(
+            period_closes(&balances, Timeframe::Days(1)),
+            vec![(0, 101.0), (day, 98.0), (3 * day, 103.0)]
+        );
+    }
+
+    #[test]
+    fn summarize_test() {
+        let summary = summarize(&VALUES, 9.0).unwrap();
+
+        close(Some(summary.total_return), 0.28);
+        assert_eq!(summary.max_drawdown, 0.25);
+        assert_eq!(summary.max_drawdown_duration, 2);
+        assert_eq!(
+            summarize(&[100.0], TRADING_DAYS),
+            Err(TaError::InsufficientData {
+                required: 2,
+                available: 1
+            })
+        );
+        assert_eq!(
+            summarize(&[100.0, 0.0, 50.0], TRADING_DAYS),
+            Err(TaError::InvalidValue { index: 1 })
+        );
+    }
+}
@@ -0,0 +1,779 @@
+use crate::average::{Ema, Rma};
+use crate::error::{check_parameter, check_period};
+use crate::number::{max, min, Number};
+use crate::patterns::Direction;
+use crate::window::Extremes;
+use crate::{Candle, Indicator, TaError};
+use std::collections::VecDeque;
+
+/// macd_series returns the MACD line, signal and histogram for every candle, see `Macd`.
+pub fn macd_series<T: Number>(
+    candles: &[Candle<T>],
+    fast: usize,
+    slow: usize,
+    signal: usize,
+) -> Result<Vec<Option<MacdValue<T>>>, TaError> {
+    check_period(fast)?;
+    check_period(slow)?;
+    check_period(signal)?;
+
+    Macd::new(fast, slow, signal).try_calculate(candles)
+}
+
+/// adx_series returns the ADX, +DI and -DI for every candle, see `Adx`.
+pub fn adx_series<T: Number>(
+    candles: &[Candle<T>],
+    period: usize,
+) -> Result<Vec<Option<AdxValue<T>>>, TaError> {
+    check_period(period)?;
+
+    Adx::new(period).try_calculate(candles)
+}
+
+/// aroon_series returns Aroon up and down for every candle, see `Aroon`.
+pub fn aroon_series<T: Number>(
+    candles: &[Candle<T>],
+    period: usize,
+) -> Result<Vec<Option<AroonValue<T>>>, TaError> {
+    check_period(period)?;
+
+    Aroon::new(period).try_calculate(candles)
+}
+
+/// parabolic_sar_series returns the stop and reverse level for every candle, see `ParabolicSar`.
+pub fn parabolic_sar_series<T: Number>(
+    candles: &[Candle<T>],
+    step: f64,
+    maximum: f64,
+) -> Result<Vec<Option<ParabolicSarValue<T>>>, TaError> {
+    check_parameter("step", step)?;
+    check_parameter("maximum", maximum)?;
+
+    ParabolicSar::new(step, maximum).try_calculate(candles)
+}
+
+/// ichimoku_series returns the Ichimoku lines for every candle, see `Ichimoku`. Use
+/// `Ichimoku::forecast` for the cloud projected past the last candle.
+pub fn ichimoku_series<T: Number>(
+    candles: &[Candle<T>],
+    tenkan: usize,
+    kijun: usize,
+    senkou_b: usize,
+    displacement: usize,
+) -> Result<Vec<Option<IchimokuValue<T>>>, TaError> {
+    check_period(tenkan)?;
+    check_period(kijun)?;
+    check_period(senkou_b)?;
+    check_period(displacement)?;
+
+    Ichimoku::new(tenkan, kijun, senkou_b, displacement).try_calculate(candles)
+}
+
+/// MacdValue holds the MACD line, and once enough MACD values exist, its signal line and histogram.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MacdValue<T = f64> {
+    pub macd: T,
+    pub signal: Option<T>,
+    pub histogram: Option<T>,
+}
+
+/// Macd is the moving average convergence divergence indicator.
+///     MACD = EMA(fast) - EMA(slow)
+///     signal = EMA(MACD, signal)
+///     histogram = MACD - signal
+/// The common parameters are 12, 26 and 9.
+/// <https://school.stockcharts.com/doku.php?id=technical_indicators:moving_average_convergence_divergence_macd>
+#[derive(Clone, Debug)]
+pub struct Macd<T = f64> {
+    fast: Ema<T>,
+    slow: Ema<T>,
+    signal: Ema<T>,
+}
+
+impl<T: Number> Macd<T> {
+    pub fn new(fast: usize, slow: usize, signal: usize) -> Macd<T> {
+        Macd {
+            fast: Ema::new(fast),
+            slow: Ema::new(slow),
+            signal: Ema::new(signal),
+        }
+    }
+}
+
+impl<T: Number> Indicator<T> for Macd<T> {
+    type Output = MacdValue<T>;
+
+    fn next(&mut self, candle: &Candle<T>) -> Option<MacdValue<T>> {
+        let fast = self.fast.update(candle.close);
+        let slow = self.slow.update(candle.close)?;
+
+        let macd = fast? - slow;
+        let signal = self.signal.update(macd);
+
+        Some(MacdValue {
+            macd,
+            signal,
+            histogram: signal.map(|s| macd - s),
+        })
+    }
+
+    fn reset(&mut self) {
+        self.fast.reset();
+        self.slow.reset();
+        self.signal.reset();
+    }
+}
+
+/// AdxValue holds the directional indicators, and once enough DX values exist, the ADX itself.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AdxValue<T = f64> {
+    pub adx: Option<T>,
+    pub plus_di: T,
+    pub minus_di: T,
+}
+
+/// Adx is Wilder's average directional index, along with the +DI and -DI lines.
+///
+/// True range and directional movement are smoothed with Wilder's average over `period`, giving
+/// the first +DI/-DI on index `period`. The ADX is Wilder's average of DX, so it first appears on
+/// index `2 x period - 1`. An ADX above 25 is commonly read as a trending market.
+/// <https://school.stockcharts.com/doku.php?id=technical_indicators:average_directional_index_adx>
+#[derive(Clone, Debug)]
+pub struct Adx<T = f64> {
+    previous: Option<Candle<T>>,
+    true_range: Rma<T>,
+    plus_dm: Rma<T>,
+    minus_dm: Rma<T>,
+    adx: Rma<T>,
+}
+
+impl<T: Number> Adx<T> {
+    pub fn new(period: usize) -> Adx<T> {
+        Adx {
+            previous: None,
+            true_range: Rma::new(period),
+            plus_dm: Rma::new(period),
+            minus_dm: Rma::new(period),
+            adx: Rma::new(period),
+        }
+    }
+}
+
+impl<T: Number> Indicator<T> for Adx<T> {
+    type Output = AdxValue<T>;
+
+    fn next(&mut self, candle: &Candle<T>) -> Option<AdxValue<T>> {
+        let previous = self.previous.replace(*candle)?;
+
+        let up = candle.high - previous.high;
+        let down = previous.low - candle.low;
+        let zero = T::zero();
+        let plus_dm = if up > down && up > zero { up } else { zero };
+        let minus_dm = if down > up && down > zero { down } else { zero };
+
+        let true_range = self
+            .true_range
+            .update(candle.true_range(Some(previous.close)));
+        let plus_dm = self.plus_dm.update(plus_dm);
+        let minus_dm = self.minus_dm.update(minus_dm);
+        let true_range = true_range?;
+
+        let hundred = T::from_count(100);
+        let (plus_di, minus_di) = if true_range > zero {
+            (
+                hundred * plus_dm? / true_range,
+                hundred * minus_dm? / true_range,
+            )
+        } else {
+            (zero, zero)
+        };
+
+        let di_sum = plus_di + minus_di;
+        let dx = if di_sum > zero {
+            hundred * (plus_di - minus_di).abs() / di_sum
+        } else {
+            zero
+        };
+
+        Some(AdxValue {
+            adx: self.adx.update(dx),
+            plus_di,
+            minus_di,
+        })
+    }
+
+    fn reset(&mut self) {
+        self.previous = None;
+        self.true_range.reset();
+        self.plus_dm.reset();
+        self.minus_dm.reset();
+        self.adx.reset();
+    }
+}
+
+/// AroonValue holds Aroon up and down, both between 0 and 100.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AroonValue<T = f64> {
+    pub up: T,
+    pub down: T,
+}
+
+impl<T: Number> AroonValue<T> {
+    /// oscillator is Aroon up minus Aroon down
+    pub fn oscillator(&self) -> T {
+        self.up - self.down
+    }
+}
+
+/// Aroon measures how recently the highest high and lowest low occurred over the last `period`
+/// candles, plus the current one.
+///     up = (period - candles since highest high) / period x 100
+///     down = (period - candles since lowest low) / period x 100
+/// <https://school.stockcharts.com/doku.php?id=technical_indicators:aroon>
+#[derive(Clone, Debug)]
+pub struct Aroon<T = f64> {
+    period: usize,
+    extremes: Extremes<T>,
+}
+
+impl<T: Number> Aroon<T> {
+    pub fn new(period: usize) -> Aroon<T> {
+        Aroon {
+            period,
+            extremes: Extremes::new(period + 1),
+        }
+    }
+}
+
+impl<T: Number> Indicator<T> for Aroon<T> {
+    type Output = AroonValue<T>;
+
+    fn next(&mut self, candle: &Candle<T>) -> Option<AroonValue<T>> {
+        self.extremes.push(candle.high, candle.low);
+        if self.period == 0 || !self.extremes.is_full() {
+            return None;
+        }
+
+        let period = T::from_count(self.period);
+        let since_high = T::from_count(self.extremes.bars_since_highest());
+        let since_low = T::from_count(self.extremes.bars_since_lowest());
+        let hundred = T::from_count(100);
+
+        Some(AroonValue {
+            up: (period - since_high) / period * hundred,
+            down: (period - since_low) / period * hundred,
+        })
+    }
+
+    fn reset(&mut self) {
+        self.extremes.reset();
+    }
+}
+
+/// ParabolicSarValue is the stop and reverse level for a candle, and whether it is trailing a long
+/// (below price) or short (above price) trend.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ParabolicSarValue<T = f64> {
+    pub sar: T,
+    pub long: bool,
+}
+
+/// ParabolicSar is Wilder's parabolic stop and reverse.
+///
+/// The initial trend is taken from the direction of the first two closes. Each candle the SAR
+/// moves towards the extreme point by the acceleration factor, which starts at `step` and grows by
+/// `step` every new extreme, up to `maximum`. The common parameters are 0.02 and 0.2.
+/// <https://school.stockcharts.com/doku.php?id=technical_indicators:parabolic_sar>
+#[derive(Clone, Debug)]
+pub struct ParabolicSar<T = f64> {
+    step: T,
+    maximum: T,
+    candles: Vec<Candle<T>>,
+    state: Option<ParabolicSarState<T>>,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct ParabolicSarState<T> {
+    sar: T,
+    extreme: T,
+    acceleration: T,
+    long: bool,
+}
+
+impl<T: Number> ParabolicSar<T> {
+    pub fn new(step: f64, maximum: f64) -> ParabolicSar<T> {
+        ParabolicSar {
+            step: T::from_parameter(step),
+            maximum: T::from_parameter(maximum),
+            candles: Vec::with_capacity(2),
+            state: None,
+        }
+    }
+}
+
+impl<T: Number> Indicator<T> for ParabolicSar<T> {
+    type Output = ParabolicSarValue<T>;
+
+    fn next(&mut self, candle: &Candle<T>) -> Option<ParabolicSarValue<T>> {
+        let mut state = match self.state {
+            Some(state) => state,
+            None => {
+                self.candles.push(*candle);
+                if self.candles.len() < 2 {
+                    return None;
+                }
+
+                let first = self.candles[0];
+                let long = candle.close >= first.close;
+                let state = ParabolicSarState {
+                    sar: if long { first.low } else { first.high },
+                    extreme: if long { candle.high } else { candle.low },
+                    acceleration: self.step,
+                    long,
+                };
+                self.state = Some(state);
+
+                return Some(ParabolicSarValue {
+                    sar: state.sar,
+                    long,
+                });
+            }
+        };
+
+        // the two previous candles bound the SAR, so it never moves into their range
+        let (before, previous) = (self.candles[0], self.candles[1]);
+        let mut sar = state.sar + state.acceleration * (state.extreme - state.sar);
+
+        if state.long {
+            sar = min(min(sar, previous.low), before.low);
+            if candle.low < sar {
+                sar = state.extreme;
+                state.long = false;
+                state.extreme = candle.low;
+                state.acceleration = self.step;
+            } else if candle.high > state.extreme {
+                state.extreme = candle.high;
+                state.acceleration = min(state.acceleration + self.step, self.maximum);
+            }
+        } else {
+            sar = max(max(sar, previous.high), before.high);
+            if candle.high > sar {
+                sar = state.extreme;
+                state.long = true;
+                state.extreme = candle.high;
+                state.acceleration = self.step;
+            } else if candle.low < state.extreme {
+                state.extreme = candle.low;
+                state.acceleration = min(state.acceleration + self.step, self.maximum);
+            }
+        }
+
+        state.sar = sar;
+        self.state = Some(state);
+        self.candles = vec![previous, *candle];
+
+        Some(ParabolicSarValue {
+            sar,
+            long: state.long,
+        })
+    }
+
+    fn reset(&mut self) {
+        self.candles.clear();
+        self.state = None;
+    }
+}
+
+/// IchimokuValue holds the Ichimoku lines as they stand at a candle. The Senkou spans are the ones
+/// plotted at this candle, which were calculated `displacement` candles earlier, so comparing them
+/// with the close needs no shifting.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct IchimokuValue<T = f64> {
+    pub close: T,
+    /// Tenkan-sen, the conversion line
+    pub tenkan: T,
+    /// Kijun-sen, the base line
+    pub kijun: T,
+    /// Senkou span A, the leading edge of the cloud, missing until `displacement` candles in
+    pub senkou_a: Option<T>,
+    /// Senkou span B, the slower edge of the cloud
+    pub senkou_b: Option<T>,
+    /// Chikou span, this close, which is plotted `displacement` candles back
+    pub chikou: T,
+    /// the close `displacement` candles ago, which the Chikou span is plotted against
+    pub chikou_price: Option<T>,
+}
+
+impl<T: Number> IchimokuValue<T> {
+    /// cloud_top is the higher of the two Senkou spans
+    pub fn cloud_top(&self) -> Option<T> {
+        Some(max(self.senkou_a?, self.senkou_b?))
+    }
+
+    /// cloud_bottom is the lower of the two Senkou spans
+    pub fn cloud_bottom(&self) -> Option<T> {
+        Some(min(self.senkou_a?, self.senkou_b?))
+    }
+
+    /// is_above_cloud is true when the close is above both Senkou spans
+    pub fn is_above_cloud(&self) -> bool {
+        self.cloud_top().is_some_and(|top| self.close > top)
+    }
+
+    /// is_below_cloud is true when the close is below both Senkou spans
+    pub fn is_below_cloud(&self) -> bool {
+        self.cloud_bottom()
+            .is_some_and(|bottom| self.close < bottom)
+    }
+
+    /// is_in_cloud is true when the close is between the Senkou spans, where the trend is unclear
+    pub fn is_in_cloud(&self) -> bool {
+        match (self.cloud_bottom(), self.cloud_top()) {
+            (Some(bottom), Some(top)) => self.close >= bottom && self.close <= top,
+            _ => false,
+        }
+    }
+
+    /// cloud_direction is bullish when Senkou span A is above span B, and bearish when below
+    pub fn cloud_direction(&self) -> Option<Direction> {
+        Some(direction(self.senkou_a? - self.senkou_b?))
+    }
+
+    /// is_chikou_above_price is true when the Chikou span is above the price it's plotted against
+    pub fn is_chikou_above_price(&self) -> bool {
+        self.chikou_price.is_some_and(|price| self.chikou > price)
+    }
+
+    /// tk_cross returns the direction Tenkan-sen crossed Kijun-sen between `previous` and this
+    /// value, or `None` if it didn't cross.
+    pub fn tk_cross(&self, previous: &IchimokuValue<T>) -> Option<Direction> {
+        let before = previous.tenkan - previous.kijun;
+        let after = self.tenkan - self.kijun;
+
+        let zero = T::zero();
+        if before <= zero && after > zero {
+            Some(Direction::Bullish)
+        } else if before >= zero && after < zero {
+            Some(Direction::Bearish)
+        } else {
+            None
+        }
+    }
+}
+
+fn direction<T: Number>(difference: T) -> Direction {
+    if difference > T::zero() {
+        Direction::Bullish
+    } else if difference < T::zero() {
+        Direction::Bearish
+    } else {
+        Direction::Neutral
+    }
+}
+
+/// CloudProjection is the Ichimoku cloud at a candle that hasn't happened yet
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CloudProjection<T = f64> {
+    pub datetime: usize,
+    pub senkou_a: Option<T>,
+    pub senkou_b: Option<T>,
+}
+
+/// Ichimoku Kinko Hyo, each line being the midpoint of the highest high and lowest low over its
+/// period. The standard periods are 9, 26 and 52, with a displacement of 26.
+///     tenkan = midpoint over `tenkan` candles
+///     kijun = midpoint over `kijun` candles
+///     senkou A = (tenkan + kijun) / 2, plotted `displacement` candles ahead
+///     senkou B = midpoint over `senkou_b` candles, plotted `displacement` candles ahead
+///     chikou = close, plotted `displacement` candles behind
+/// <https://school.stockcharts.com/doku.php?id=technical_indicators:ichimoku_cloud>
+#[derive(Clone, Debug)]
+pub struct Ichimoku<T = f64> {
+    displacement: usize,
+    tenkan: Extremes<T>,
+    kijun: Extremes<T>,
+    senkou_b: Extremes<T>,
+    /// the clouds calculated over the last `displacement` candles, to be plotted on the next ones
+    clouds: VecDeque<(Option<T>, Option<T>)>,
+    closes: VecDeque<T>,
+    last_datetime: Option<usize>,
+}
+
+impl<T: Number> Ichimoku<T> {
+    pub fn new(tenkan: usize, kijun: usize, senkou_b: usize, displacement: usize) -> Ichimoku<T> {
+        Ichimoku {
+            displacement,
+            tenkan: Extremes::new(tenkan),
+            kijun: Extremes::new(kijun),
+            senkou_b: Extremes::new(senkou_b),
+            clouds: VecDeque::with_capacity(displacement + 1),
+            closes: VecDeque::with_capacity(displacement + 1),
+            last_datetime: None,
+        }
+    }
+
+    /// forecast returns the cloud for the `displacement` candles after the last one, which is
+    /// already known as the Senkou spans are plotted ahead. Datetimes step forward by `interval`
+    /// milliseconds, eg: `resample::HOUR_MILLIS` for hourly bars.
+    pub fn forecast(&self, interval: usize) -> Vec<CloudProjection<T>> {
+        let last = match self.last_datetime {
+            Some(last) => last,
+            None => return Vec::new(),
+        };
+
+        self.clouds
+            .iter()
+            .enumerate()
+            .map(|(i, (senkou_a, senkou_b))| CloudProjection {
+                datetime: last.saturating_add((i + 1).saturating_mul(interval)),
+                senkou_a: *senkou_a,
+                senkou_b: *senkou_b,
+            })
+            .collect()
+    }
+}
+
+impl<T: Number> Default for Ichimoku<T> {
+    fn default() -> Ichimoku<T> {
+        Ichimoku::new(9, 26, 52, 26)
+    }
+}
+
+fn midpoint<T: Number>(extremes: &Extremes<T>) -> Option<T> {
+    if !extremes.is_full() {
+        return None;
+    }
+    Some((extremes.highest()? + extremes.lowest()?) / T::from_count(2))
+}
+
+impl<T: Number> Indicator<T> for Ichimoku<T> {
+    type Output = IchimokuValue<T>;
+
+    fn next(&mut self, candle: &Candle<T>) -> Option<IchimokuValue<T>> {
+        self.last_datetime = Some(candle.datetime);
+        for extremes in [&mut self.tenkan, &mut self.kijun, &mut self.senkou_b] {
+            extremes.push(candle.high, candle.low);
+        }
+
+        let tenkan = midpoint(&self.tenkan);
+        let kijun = midpoint(&self.kijun);
+        let senkou_a = match (tenkan, kijun) {
+            (Some(tenkan), Some(kijun)) => Some((tenkan + kijun) / T::from_count(2)),
+            _ => None,
+        };
+
+        self.clouds.push_back((senkou_a, midpoint(&self.senkou_b)));
+        self.closes.push_back(candle.close);
+        let (senkou_a, senkou_b) = if self.clouds.len() > self.displacement {
+            self.clouds.pop_front().unwrap_or_default()
+        } else {
+            (None, None)
+        };
+        let chikou_price = if self.closes.len() > self.displacement {
+            self.closes.pop_front()
+        } else {
+            None
+        };
+
+        Some(IchimokuValue {
+            close: candle.close,
+            tenkan: tenkan?,
+            kijun: kijun?,
+            senkou_a,
+            senkou_b,
+            chikou: candle.close,
+            chikou_price,
+        })
+    }
+
+    fn reset(&mut self) {
+        self.tenkan.reset();
+        self.kijun.reset();
+        self.senkou_b.reset();
+        self.clouds.clear();
+        self.closes.clear();
+        self.last_datetime = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::*;
+
+    #[test]
+    fn macd_series_test() {
+        let candles = from_closes(&[1.0, 2.0, 3.0, 4.0, 6.0, 5.0]);
+        let macd = macd_series(&candles, 2, 3, 2).unwrap();
+
+        let line: Vec<Option<f64>> = macd.iter().map(|m| m.map(|m| m.macd)).collect();
+        let signal: Vec<Option<f64>> = macd.iter().map(|m| m.and_then(|m| m.signal)).collect();
+        let histogram: Vec<Option<f64>> =
+            macd.iter().map(|m| m.and_then(|m| m.histogram)).collect();
+
+        // EMA(2) runs 1.5, 2.5, 3.5, 31/6, 91/18 and EMA(3) runs 2, 3, 4.5, 4.75, both seeded with
+        // an SMA, so MACD is 0.5, 0.5, 2/3 and 11/36
+        assert_series(&line, &warmup(2, &[0.5, 0.5, 0.6667, 0.3056]));
+        // the signal is an EMA(2) of MACD, seeded with 0.5, then 11/18 and 11/27
+        assert_series(&signal, &warmup(3, &[0.5, 0.6111, 0.4074]));
+        assert_series(&histogram, &warmup(3, &[0.0, 0.0556, -0.1019]));
+    }
+
+    #[test]
+    fn adx_series_test() {
+        let candles = from_hlcv(
+            &[10.0, 12.0, 13.0, 12.0, 9.0],
+            &[8.0, 9.0, 10.0, 7.0, 6.0],
+            &[9.0, 11.0, 12.0, 8.0, 7.0],
+            &[0.0; 5],
+        );
+        let adx = adx_series(&candles, 2).unwrap();
+
+        let plus_di: Vec<Option<f64>> = adx.iter().map(|a| a.map(|a| a.plus_di)).collect();
+        let minus_di: Vec<Option<f64>> = adx.iter().map(|a| a.map(|a| a.minus_di)).collect();
+        let adx: Vec<Option<f64>> = adx.iter().map(|a| a.and_then(|a| a.adx)).collect();
+
+        // true ranges of 3, 3, 5 and 3, +DM of 2, 1, 0, 0 and -DM of 0, 0, 3, 1 smoothed over 2
+        // candles give TR 3, 4, 3.5, +DM 1.5, 0.75, 0.375 and -DM 0, 1.5, 1.25
+        assert_series(&plus_di, &warmup(2, &[50.0, 18.75, 10.7143]));
+        assert_series(&minus_di, &warmup(2, &[0.0, 37.5, 35.7143]));
+        // DX runs 100, 100/3 and 700/13, and the ADX is their Wilder average
+        assert_series(&adx, &warmup(3, &[66.6667, 60.2564]));
+    }
+
+    #[test]
+    fn aroon_series_test() {
+        let candles = from_hlcv(
+            &[5.0, 7.0, 6.0, 4.0, 8.0],
+            &[3.0, 4.0, 2.0, 3.0, 5.0],
+            &[4.0, 5.0, 3.0, 3.5, 7.0],
+            &[0.0; 5],
+        );
+        let aroon = aroon_series(&candles, 3).unwrap();
+
+        let up: Vec<Option<f64>> = aroon.iter().map(|a| a.map(|a| a.up)).collect();
+        let down: Vec<Option<f64>> = aroon.iter().map(|a| a.map(|a| a.down)).collect();
+
+        // the high of 7 was 2 candles ago, then the new high of 8 is the latest, while the low of
+        // 2 was 1 and then 2 candles ago
+        assert_series(&up, &warmup(3, &[33.3333, 100.0]));
+        assert_series(&down, &warmup(3, &[66.6667, 33.3333]));
+        assert!((aroon[4].unwrap().oscillator() - 200.0 / 3.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn parabolic_sar_series_test() {
+        let candles = from_hlcv(
+            &[10.0, 11.0, 12.0, 12.5, 12.0, 11.0, 9.0, 8.0],
+            &[9.0, 10.0, 10.5, 11.5, 9.0, 8.5, 7.0, 6.0],
+            &[9.5, 10.5, 11.5, 12.0, 9.5, 9.0, 7.5, 6.5],
+            &[0.0; 8],
+        );
+        let sar = parabolic_sar_series(&candles, 0.1, 0.2).unwrap();
+
+        // rising closes start a long trend from the first low of 9. The SAR moves 0.1 of the way
+        // to the high of 11 but is held at or below the last two lows, then 0.2 of the way to 12.
+        // The low of 9 breaks the SAR of 10.18, reversing it to the extreme high of 12.5, where
+        // the two prior highs hold it before it falls 0.2 of the way to the low of 7.
+        let levels: Vec<Option<f64>> = sar.iter().map(|s| s.map(|s| s.sar)).collect();
+        assert_series(
+            &levels,
+            &warmup(1, &[9.0, 9.0, 9.6, 12.5, 12.5, 12.0, 11.0]),
+        );
+
+        let long: Vec<bool> = sar[1..].iter().map(|s| s.unwrap().long).collect();
+        assert_eq!(long, [true, true, true, false, false, false, false]);
+    }
+
+    #[test]
+    fn ichimoku_series_test() {
+        let highs = [10.0, 11.0, 12.0, 11.0, 13.0, 14.0, 13.0, 15.0];
+        let lows = [8.0, 9.0, 10.0, 9.0, 10.0, 12.0, 11.0, 12.0];
+        let closes = [9.0, 10.0, 11.0, 10.0, 12.0, 13.0, 12.0, 14.0];
+        let candles: Vec<Candle> = (0..8)
+            .map(|i| Candle::new(i * 3_600_000, closes[i], highs[i], lows[i], closes[i], 1.0))
+            .collect();
+
+        let mut ichimoku = Ichimoku::new(2, 3, 4, 2);
+        let values = ichimoku.try_calculate(&candles).unwrap();
+        let line = |f: fn(&IchimokuValue) -> f64| -> Vec<Option<f64>> {
+            values.iter().map(|v| v.as_ref().map(f)).collect()
+        };
+
+        assert_series(
+            &line(|v| v.tenkan),
+            &warmup(2, &[10.5, 10.5, 11.0, 12.0, 12.5, 13.0]),
+        );
+        assert_series(
+            &line(|v| v.kijun),
+            &warmup(2, &[10.0, 10.5, 11.0, 11.5, 12.0, 13.0]),
+        );
+
+        let senkou_a: Vec<Option<f64>> =
+            values.iter().map(|v| v.and_then(|v| v.senkou_a)).collect();
+        let senkou_b: Vec<Option<f64>> =
+            values.iter().map(|v| v.and_then(|v| v.senkou_b)).collect();
+        assert_series(&senkou_a, &warmup(4, &[10.25, 10.5, 11.0, 11.75]));
+        assert_series(&senkou_b, &warmup(5, &[10.0, 11.0, 11.5]));
+        assert_eq!(values[5].unwrap().chikou_price, Some(10.0));
+
+        assert_eq!(
+            ichimoku.forecast(3_600_000),
+            vec![
+                CloudProjection {
+                    datetime: 8 * 3_600_000,
+                    senkou_a: Some(12.25),
+                    senkou_b: Some(11.5)
+                },
+                CloudProjection {
+                    datetime: 9 * 3_600_000,
+                    senkou_a: Some(13.0),
+                    senkou_b: Some(12.5)
+                },
+            ]
+        );
+
+        let standard = ichimoku_series(&stockcharts_candles(), 9, 26, 52, 26).unwrap();
+        assert!(standard
+            .iter()
+            .all(|v| v.is_none_or(|v| v.senkou_b.is_none())));
+        assert_eq!(
+            ichimoku_series(&candles, 9, 0, 52, 26),
+            Err(TaError::ZeroPeriod)
+        );
+    }
+
+    #[test]
+    fn ichimoku_predicates_test() {
+        let value = IchimokuValue {
+            close: 14.0,
+            tenkan: 13.0,
+            kijun: 12.0,
+            senkou_a: Some(11.75),
+            senkou_b: Some(11.5),
+            chikou: 14.0,
+            chikou_price: Some(12.0),
+        };
+        assert!(value.is_above_cloud());
+        assert!(!value.is_below_cloud());
+        assert!(!value.is_in_cloud());
+        assert!(value.is_chikou_above_price());
+        assert_eq!(value.cloud_direction(), Some(Direction::Bullish));
+
+        let previous = IchimokuValue {
+            tenkan: 11.0,
+            kijun: 11.5,
+            ..value
+        };
+        assert_eq!(value.tk_cross(&previous), Some(Direction::Bullish));
+        assert_eq!(previous.tk_cross(&value), Some(Direction::Bearish));
+        assert_eq!(value.tk_cross(&value), None);
+
+        let warming = IchimokuValue {
+            senkou_b: None,
+            ..value
+        };
+        assert!(!warming.is_above_cloud() && !warming.is_below_cloud() && !warming.is_in_cloud());
+        assert_eq!(warming.cloud_direction(), None);
+    }
+}
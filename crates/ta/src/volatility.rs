@@ -0,0 +1,512 @@
+use crate::average::{Ema, Rma, Sma};
+use crate::error::{check_candles, check_parameter, check_period, check_values};
+use crate::number::Number;
+use crate::window::Extremes;
+use crate::{Candle, Indicator, TaError};
+use std::collections::VecDeque;
+
+/// atr_series returns the average true range for every candle, see `Atr`.
+pub fn atr_series<T: Number>(
+    candles: &[Candle<T>],
+    period: usize,
+) -> Result<Vec<Option<T>>, TaError> {
+    check_period(period)?;
+
+    Atr::new(period).try_calculate(candles)
+}
+
+/// bollinger_series returns Bollinger Bands for every candle, see `BollingerBands`.
+pub fn bollinger_series<T: Number>(
+    candles: &[Candle<T>],
+    period: usize,
+    deviations: f64,
+) -> Result<Vec<Option<BollingerValue<T>>>, TaError> {
+    check_period(period)?;
+    check_parameter("deviations", deviations)?;
+
+    BollingerBands::new(period, deviations).try_calculate(candles)
+}
+
+/// keltner_series returns Keltner Channels for every candle, see `KeltnerChannels`.
+pub fn keltner_series<T: Number>(
+    candles: &[Candle<T>],
+    ema_period: usize,
+    atr_period: usize,
+    multiplier: f64,
+) -> Result<Vec<Option<Bands<T>>>, TaError> {
+    check_period(ema_period)?;
+    check_period(atr_period)?;
+    check_parameter("multiplier", multiplier)?;
+
+    KeltnerChannels::new(ema_period, atr_period, multiplier).try_calculate(candles)
+}
+
+/// donchian_series returns Donchian Channels for every candle, see `DonchianChannels`.
+pub fn donchian_series<T: Number>(
+    candles: &[Candle<T>],
+    period: usize,
+) -> Result<Vec<Option<Bands<T>>>, TaError> {
+    check_period(period)?;
+
+    DonchianChannels::new(period).try_calculate(candles)
+}
+
+/// historical_volatility_series returns annualized close to close volatility for every candle,
+/// see `HistoricalVolatility`. It is an error when any close isn't positive, as it has no log
+/// return.
+pub fn historical_volatility_series<T: Number>(
+    candles: &[Candle<T>],
+    period: usize,
+    periods_per_year: f64,
+) -> Result<Vec<Option<T>>, TaError> {
+    check_period(period)?;
+    check_parameter("periods_per_year", periods_per_year)?;
+    check_candles(candles)?;
+    let closes: Vec<T> = candles.iter().map(|candle| candle.close).collect();
+    check_values(&closes)?;
+
+    HistoricalVolatility::new(period, periods_per_year).try_calculate(candles)
+}
+
+/// Bands narrower than this fraction of their middle line are flat, as a window of equal prices
+/// rarely averages back to exactly that price
+const FLAT_WIDTH: f64 = 1e-9;
+
+/// Bands are an upper and lower level around a middle line, as produced by channel indicators.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Bands<T = f64> {
+    pub upper: T,
+    pub middle: T,
+    pub lower: T,
+}
+
+impl<T: Number> Bands<T> {
+    /// width is the distance between the upper and lower band
+    pub fn width(&self) -> T {
+        self.upper - self.lower
+    }
+
+    /// bandwidth is the width of the bands relative to the middle line. A middle line of 0 has
+    /// nothing to be relative to, so gives 0 rather than dividing by 0.
+    pub fn bandwidth(&self) -> T {
+        if self.middle == T::zero() {
+            return T::zero();
+        }
+        self.width() / self.middle
+    }
+
+    /// percent_b is where `price` sits within the bands, where 0 is the lower band and 1 the upper.
+    /// Prices outside the bands give values below 0 or above 1. Flat bands, which have no width
+    /// once rounding is allowed for, give 0.5 rather than dividing by 0.
+    pub fn percent_b(&self, price: T) -> T {
+        if self.width() <= T::from_parameter(FLAT_WIDTH) * self.middle.abs() {
+            return T::one() / T::from_count(2);
+        }
+        (price - self.lower) / self.width()
+    }
+}
+
+/// TrueRange is the streaming true range of each candle, see `Candle::true_range`.
+/// The first candle has no previous close, so its true range is just its high minus its low.
+#[derive(Clone, Debug, Default)]
+pub struct TrueRange<T = f64> {
+    previous_close: Option<T>,
+}
+
+impl<T: Number> TrueRange<T> {
+    pub fn new() -> TrueRange<T> {
+        TrueRange::default()
+    }
+}
+
+impl<T: Number> Indicator<T> for TrueRange<T> {
+    type Output = T;
+
+    fn next(&mut self, candle: &Candle<T>) -> Option<T> {
+        let previous_close = self.previous_close.replace(candle.close);
+        Some(candle.true_range(previous_close))
+    }
+
+    fn reset(&mut self) {
+        self.previous_close = None;
+    }
+}
+
+/// Atr is Wilder's average true range, the first value being the simple average of the first
+/// `period` true ranges.
+/// <https://school.stockcharts.com/doku.php?id=technical_indicators:average_true_range_atr>
+#[derive(Clone, Debug)]
+pub struct Atr<T = f64> {
+    true_range: TrueRange<T>,
+    average: Rma<T>,
+}
+
+impl<T: Number> Atr<T> {
+    pub fn new(period: usize) -> Atr<T> {
+        Atr {
+            true_range: TrueRange::new(),
+            average: Rma::new(period),
+        }
+    }
+}
+
+impl<T: Number> Indicator<T> for Atr<T> {
+    type Output = T;
+
+    fn next(&mut self, candle: &Candle<T>) -> Option<T> {
+        let true_range = self.true_range.next(candle)?;
+        self.average.update(true_range)
+    }
+
+    fn reset(&mut self) {
+        self.true_range.reset();
+        self.average.reset();
+    }
+}
+
+/// BollingerValue holds the Bollinger Bands for a candle, along with where the close sits within
+/// them (%B) and how wide they are relative to the middle band (bandwidth).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BollingerValue<T = f64> {
+    pub bands: Bands<T>,
+    pub percent_b: T,
+    pub bandwidth: T,
+}
+
+/// BollingerBands are an SMA of closes, with bands `deviations` population standard deviations
+/// above and below it. The common parameters are 20 and 2.
+/// <https://school.stockcharts.com/doku.php?id=technical_indicators:bollinger_bands>
+#[derive(Clone, Debug)]
+pub struct BollingerBands<T = f64> {
+    period: usize,
+    deviations: T,
+    window: VecDeque<T>,
+    average: Sma<T>,
+}
+
+impl<T: Number> BollingerBands<T> {
+    pub fn new(period: usize, deviations: f64) -> BollingerBands<T> {
+        BollingerBands {
+            period,
+            deviations: T::from_parameter(deviations),
+            window: VecDeque::with_capacity(period + 1),
+            average: Sma::new(period),
+        }
+    }
+}
+
+impl<T: Number> Indicator<T> for BollingerBands<T> {
+    type Output = BollingerValue<T>;
+
+    fn next(&mut self, candle: &Candle<T>) -> Option<BollingerValue<T>> {
+        self.window.push_back(candle.close);
+        if self.window.len() > self.period {
+            self.window.pop_front();
+        }
+        let middle = self.average.update(candle.close)?;
+
+        // summing squared deviations from the current mean avoids the cancellation error of a
+        // running sum of squares
+        let variance = self
+            .window
+            .iter()
+            .map(|v| (*v - middle) * (*v - middle))
+            .sum::<T>()
+            / T::from_count(self.period);
+        let offset = self.deviations * variance.sqrt();
+
+        let bands = Bands {
+            upper: middle + offset,
+            middle,
+            lower: middle - offset,
+        };
+
+        Some(BollingerValue {
+            bands,
+            percent_b: bands.percent_b(candle.close),
+            bandwidth: bands.bandwidth(),
+        })
+    }
+
+    fn reset(&mut self) {
+        self.window.clear();
+        self.average.reset();
+    }
+}
+
+/// KeltnerChannels are an EMA of closes, with bands `multiplier` ATRs above and below it.
+/// The common parameters are 20, 10 and 2.
+/// <https://school.stockcharts.com/doku.php?id=technical_indicators:keltner_channels>
+#[derive(Clone, Debug)]
+pub struct KeltnerChannels<T = f64> {
+    multiplier: T,
+    average: Ema<T>,
+    atr: Atr<T>,
+}
+
+impl<T: Number> KeltnerChannels<T> {
+    pub fn new(ema_period: usize, atr_period: usize, multiplier: f64) -> KeltnerChannels<T> {
+        KeltnerChannels {
+            multiplier: T::from_parameter(multiplier),
+            average: Ema::new(ema_period),
+            atr: Atr::new(atr_period),
+        }
+    }
+}
+
+impl<T: Number> Indicator<T> for KeltnerChannels<T> {
+    type Output = Bands<T>;
+
+    fn next(&mut self, candle: &Candle<T>) -> Option<Bands<T>> {
+        let atr = self.atr.next(candle);
+        let middle = self.average.update(candle.close)?;
+        let offset = self.multiplier * atr?;
+
+        Some(Bands {
+            upper: middle + offset,
+            middle,
+            lower: middle - offset,
+        })
+    }
+
+    fn reset(&mut self) {
+        self.average.reset();
+        self.atr.reset();
+    }
+}
+
+/// DonchianChannels are the highest high and lowest low of the last `period` candles, with the
+/// middle line halfway between them.
+/// <https://school.stockcharts.com/doku.php?id=technical_indicators:price_channels>
+#[derive(Clone, Debug)]
+pub struct DonchianChannels<T = f64> {
+    extremes: Extremes<T>,
+}
+
+impl<T: Number> DonchianChannels<T> {
+    pub fn new(period: usize) -> DonchianChannels<T> {
+        DonchianChannels {
+            extremes: Extremes::new(period),
+        }
+    }
+}
+
+impl<T: Number> Indicator<T> for DonchianChannels<T> {
+    type Output = Bands<T>;
+
+    fn next(&mut self, candle: &Candle<T>) -> Option<Bands<T>> {
+        self.extremes.push(candle.high, candle.low);
+        if !self.extremes.is_full() {
+            return None;
+        }
+
+        let (upper, lower) = (self.extremes.highest()?, self.extremes.lowest()?);
+        Some(Bands {
+            upper,
+            middle: (upper + lower) / T::from_count(2),
+            lower,
+        })
+    }
+
+    fn reset(&mut self) {
+        self.extremes.reset();
+    }
+}
+
+/// HistoricalVolatility is the sample standard deviation of the last `period` log returns of
+/// closes, annualized by `sqrt(periods_per_year)`, eg: 252 for daily equity candles or 365 for
+/// daily crypto candles. A close that isn't positive has no log return, so that candle is skipped.
+#[derive(Clone, Debug)]
+pub struct HistoricalVolatility<T = f64> {
+    period: usize,
+    annualization: T,
+    previous: Option<T>,
+    returns: VecDeque<T>,
+}
+
+impl<T: Number> HistoricalVolatility<T> {
+    pub fn new(period: usize, periods_per_year: f64) -> HistoricalVolatility<T> {
+        HistoricalVolatility {
+            period,
+            annualization: T::from_parameter(periods_per_year).sqrt(),
+            previous: None,
+            returns: VecDeque::with_capacity(period + 1),
+        }
+    }
+}
+
+impl<T: Number> Indicator<T> for HistoricalVolatility<T> {
+    type Output = T;
+
+    fn next(&mut self, candle: &Candle<T>) -> Option<T> {
+        if candle.close <= T::zero() {
+            return None;
+        }
+        let previous = self.previous.replace(candle.close)?;
+
+        self.returns.push_back((candle.close / previous).ln()?);
+        if self.returns.len() > self.period {
+            self.returns.pop_front();
+        }
+        if self.period < 2 || self.returns.len() < self.period {
+            return None;
+        }
+
+        let mean = self.returns.iter().copied().sum::<T>() / T::from_count(self.period);
+        let variance = self
+            .returns
+            .iter()
+            .map(|r| (*r - mean) * (*r - mean))
+            .sum::<T>()
+            / T::from_count(self.period - 1);
+
+        Some(variance.sqrt() * self.annualization)
+    }
+
+    fn reset(&mut self) {
+        self.previous = None;
+        self.returns.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::*;
+    use rust_decimal_macros::dec;
+
+    /// the candles the volatility examples are worked through by hand with, including a gap down
+    /// and a gap up
+    fn gap_candles() -> Vec<Candle> {
+        from_hlcv(
+            &[10.0, 12.0, 11.0, 9.0, 13.0],
+            &[8.0, 10.0, 9.0, 7.0, 11.0],
+            &[9.0, 11.0, 10.0, 8.0, 12.0],
+            &[0.0; 5],
+        )
+    }
+
+    #[test]
+    fn true_range_test() {
+        let true_range: Vec<Option<f64>> = TrueRange::new().calculate(&gap_candles());
+
+        // the high minus the low, unless the gap from the previous close is wider: 12 - 9 on the
+        // second candle, 10 - 7 on the gap down and 13 - 8 on the gap up
+        assert_series(&true_range, &warmup(0, &[2.0, 3.0, 2.0, 3.0, 5.0]));
+    }
+
+    #[test]
+    fn atr_series_test() {
+        // the average of the first 3 true ranges, 7/3, then Wilder smoothed to 23/9 and 91/27
+        assert_series(
+            &atr_series(&gap_candles(), 3).unwrap(),
+            &warmup(2, &[2.3333, 2.5556, 3.3704]),
+        );
+    }
+
+    #[test]
+    fn bollinger_series_test() {
+        let bollinger = bollinger_series(&gap_candles(), 3, 2.0).unwrap();
+
+        let upper: Vec<Option<f64>> = bollinger.iter().map(|b| b.map(|b| b.bands.upper)).collect();
+        let middle: Vec<Option<f64>> = bollinger
+            .iter()
+            .map(|b| b.map(|b| b.bands.middle))
+            .collect();
+        let lower: Vec<Option<f64>> = bollinger.iter().map(|b| b.map(|b| b.bands.lower)).collect();
+        let percent_b: Vec<Option<f64>> =
+            bollinger.iter().map(|b| b.map(|b| b.percent_b)).collect();
+        let bandwidth: Vec<Option<f64>> =
+            bollinger.iter().map(|b| b.map(|b| b.bandwidth)).collect();
+
+        // closes of 9, 11, 10, 8 and 12 have population variances of 2/3, 14/9 and 8/3 over 3
+        // candles, so the bands sit 2 x sqrt(variance) either side of the SMA
+        assert_series(&middle, &warmup(2, &[10.0, 9.6667, 10.0]));
+        assert_series(&upper, &warmup(2, &[11.633, 12.1611, 13.266]));
+        assert_series(&lower, &warmup(2, &[8.367, 7.1722, 6.734]));
+        assert_series(&percent_b, &warmup(2, &[0.5, 0.1659, 0.8062]));
+        assert_series(&bandwidth, &warmup(2, &[0.3266, 0.5161, 0.6532]));
+
+        // flat closes collapse the bands, which puts the close in the middle of them
+        let flat = from_closes(&[0.1, 0.1, 0.1, 0.1]);
+        let bollinger = bollinger_series(&flat, 3, 2.0).unwrap();
+        assert_eq!(bollinger[3].unwrap().percent_b, 0.5);
+        let bands = Bands {
+            upper: 10.0,
+            middle: 10.0,
+            lower: 10.0,
+        };
+        assert_eq!(bands.percent_b(10.0), 0.5);
+
+        // zero closes have no middle line to measure the width against
+        let zero = bollinger_series(&from_closes(&[0.0; 3]), 3, 2.0).unwrap();
+        assert_eq!(zero[2].unwrap().bandwidth, 0.0);
+        assert_eq!(zero[2].unwrap().percent_b, 0.5);
+    }
+
+    #[test]
+    fn bollinger_decimal_test() {
+        let closes = [0.1, 0.2, 0.3, 0.3, 0.3, 0.3];
+        let bollinger = bollinger_series(&to_decimal(&from_closes(&closes)), 3, 2.0).unwrap();
+
+        // 0.1 + 0.2 + 0.3 isn't exactly 0.6 in f64, so only Decimal averages it to exactly 0.2
+        assert_eq!(bollinger[2].unwrap().bands.middle, dec!(0.2));
+        assert_eq!(bollinger[5].unwrap().bands.width(), dec!(0));
+        assert_eq!(bollinger[5].unwrap().percent_b, dec!(0.5));
+        assert!(
+            bollinger_series(&from_closes(&closes), 3, 2.0).unwrap()[2]
+                .unwrap()
+                .bands
+                .middle
+                != 0.2
+        );
+    }
+
+    #[test]
+    fn keltner_series_test() {
+        let keltner = keltner_series(&gap_candles(), 3, 3, 2.0).unwrap();
+
+        let upper: Vec<Option<f64>> = keltner.iter().map(|b| b.map(|b| b.upper)).collect();
+        let middle: Vec<Option<f64>> = keltner.iter().map(|b| b.map(|b| b.middle)).collect();
+        let lower: Vec<Option<f64>> = keltner.iter().map(|b| b.map(|b| b.lower)).collect();
+
+        // an EMA(3) of 10, 9 and 10.5, with bands 2 x the ATR(3) of 7/3, 23/9 and 91/27 either side
+        assert_series(&middle, &warmup(2, &[10.0, 9.0, 10.5]));
+        assert_series(&upper, &warmup(2, &[14.6667, 14.1111, 17.2407]));
+        assert_series(&lower, &warmup(2, &[5.3333, 3.8889, 3.7593]));
+    }
+
+    #[test]
+    fn donchian_series_test() {
+        let donchian = donchian_series(&gap_candles(), 3).unwrap();
+
+        let upper: Vec<Option<f64>> = donchian.iter().map(|b| b.map(|b| b.upper)).collect();
+        let middle: Vec<Option<f64>> = donchian.iter().map(|b| b.map(|b| b.middle)).collect();
+        let lower: Vec<Option<f64>> = donchian.iter().map(|b| b.map(|b| b.lower)).collect();
+
+        assert_series(&upper, &warmup(2, &[12.0, 12.0, 13.0]));
+        assert_series(&middle, &warmup(2, &[10.0, 9.5, 10.0]));
+        assert_series(&lower, &warmup(2, &[8.0, 7.0, 7.0]));
+    }
+
+    #[test]
+    fn historical_volatility_series_test() {
+        // log returns of ln(11/9), ln(10/11), ln(8/10) and ln(12/8), so each pair has a sample
+        // standard deviation of |r1 - r2| / sqrt(2), annualized by sqrt(252)
+        assert_series(
+            &historical_volatility_series(&gap_candles(), 2, 252.0).unwrap(),
+            &warmup(2, &[3.3224, 1.4349, 7.0561]),
+        );
+
+        // a close of 0 has no log return
+        let zero = from_closes(&[9.0, 11.0, 0.0, 8.0]);
+        assert_eq!(
+            historical_volatility_series(&zero, 2, 252.0),
+            Err(TaError::InvalidValue { index: 2 })
+        );
+        let mut volatility = HistoricalVolatility::new(2, 252.0);
+        let streamed = volatility.calculate(&zero);
+        assert_eq!(streamed[2], None);
+        assert!(streamed[3].unwrap().is_finite());
+    }
+}
@@ -0,0 +1,385 @@
+use crate::average::Sma;
+use crate::error::check_period;
+use crate::momentum::typical_price;
+use crate::number::Number;
+use crate::{Candle, Indicator, TaError};
+use std::collections::VecDeque;
+
+/// The length of a trading session used by `Vwap::new`, one UTC day in milliseconds
+pub const DAY_MILLIS: usize = 86_400_000;
+
+/// vwap_series returns the session VWAP for every candle, with sessions starting each UTC day, see `Vwap`.
+pub fn vwap_series<T: Number>(candles: &[Candle<T>]) -> Result<Vec<Option<T>>, TaError> {
+    Vwap::new().try_calculate(candles)
+}
+
+/// obv_series returns on-balance volume for every candle, see `Obv`.
+pub fn obv_series<T: Number>(candles: &[Candle<T>]) -> Result<Vec<Option<T>>, TaError> {
+    Obv::new().try_calculate(candles)
+}
+
+/// ad_series returns the accumulation/distribution line for every candle, see `AccumulationDistribution`.
+pub fn ad_series<T: Number>(candles: &[Candle<T>]) -> Result<Vec<Option<T>>, TaError> {
+    AccumulationDistribution::new().try_calculate(candles)
+}
+
+/// cmf_series returns Chaikin money flow for every candle, see `ChaikinMoneyFlow`.
+pub fn cmf_series<T: Number>(
+    candles: &[Candle<T>],
+    period: usize,
+) -> Result<Vec<Option<T>>, TaError> {
+    check_period(period)?;
+
+    ChaikinMoneyFlow::new(period).try_calculate(candles)
+}
+
+/// volume_sma_series returns the simple moving average of volume for every candle, see `VolumeSma`.
+pub fn volume_sma_series<T: Number>(
+    candles: &[Candle<T>],
+    period: usize,
+) -> Result<Vec<Option<T>>, TaError> {
+    check_period(period)?;
+
+    VolumeSma::new(period).try_calculate(candles)
+}
+
+/// money_flow_multiplier is where the close sits within the candle's range, from -1 at the low to
+/// 1 at the high. A candle without a range has a multiplier of 0.
+pub fn money_flow_multiplier<T: Number>(candle: &Candle<T>) -> T {
+    let range = candle.high - candle.low;
+    if range <= T::zero() {
+        return T::zero();
+    }
+
+    ((candle.close - candle.low) - (candle.high - candle.close)) / range
+}
+
+/// Vwap is the volume weighted average typical price, accumulated from the start of each session.
+///
+/// Sessions are fixed length windows of epoch time, by default a UTC day. A regular US equity
+/// session (13:30 to 20:00 UTC) always falls within a single UTC day.
+#[derive(Clone, Debug)]
+pub struct Vwap<T = f64> {
+    session_millis: usize,
+    session: Option<usize>,
+    price_volume: T,
+    volume: T,
+}
+
+impl<T: Number> Vwap<T> {
+    pub fn new() -> Vwap<T> {
+        Vwap::with_session(DAY_MILLIS)
+    }
+
+    /// with_session builds a VWAP that restarts every `session_millis` milliseconds of epoch time
+    pub fn with_session(session_millis: usize) -> Vwap<T> {
+        Vwap {
+            session_millis,
+            session: None,
+            price_volume: T::zero(),
+            volume: T::zero(),
+        }
+    }
+}
+
+impl<T: Number> Default for Vwap<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Number> Indicator<T> for Vwap<T> {
+    type Output = T;
+
+    fn next(&mut self, candle: &Candle<T>) -> Option<T> {
+        let session = candle.datetime / self.session_millis.max(1);
+        if self.session != Some(session) {
+            self.session = Some(session);
+            self.price_volume = T::zero();
+            self.volume = T::zero();
+        }
+
+        self.price_volume += typical_price(candle) * candle.volume;
+        self.volume += candle.volume;
+
+        if self.volume <= T::zero() {
+            return None;
+        }
+        Some(self.price_volume / self.volume)
+    }
+
+    fn reset(&mut self) {
+        self.session = None;
+        self.price_volume = T::zero();
+        self.volume = T::zero();
+    }
+}
+
+/// AnchoredVwap is the volume weighted average typical price of every candle since `anchor`, an
+/// epoch timestamp in milliseconds. Candles before the anchor produce no value.
+#[derive(Clone, Debug)]
+pub struct AnchoredVwap<T = f64> {
+    anchor: usize,
+    price_volume: T,
+    volume: T,
+}
+
+impl<T: Number> AnchoredVwap<T> {
+    pub fn new(anchor: usize) -> AnchoredVwap<T> {
+        AnchoredVwap {
+            anchor,
+            price_volume: T::zero(),
+            volume: T::zero(),
+        }
+    }
+}
+
+impl<T: Number> Indicator<T> for AnchoredVwap<T> {
+    type Output = T;
+
+    fn next(&mut self, candle: &Candle<T>) -> Option<T> {
+        if candle.datetime < self.anchor {
+            return None;
+        }
+
+        self.price_volume += typical_price(candle) * candle.volume;
+        self.volume += candle.volume;
+
+        if self.volume <= T::zero() {
+            return None;
+        }
+        Some(self.price_volume / self.volume)
+    }
+
+    fn reset(&mut self) {
+        self.price_volume = T::zero();
+        self.volume = T::zero();
+    }
+}
+
+/// Obv is on-balance volume, a running total that adds volume on up closes and subtracts it on
+/// down closes. It starts at 0 on the first candle.
+/// <https://school.stockcharts.com/doku.php?id=technical_indicators:on_balance_volume_obv>
+#[derive(Clone, Debug, Default)]
+pub struct Obv<T = f64> {
+    previous_close: Option<T>,
+    total: T,
+}
+
+impl<T: Number> Obv<T> {
+    pub fn new() -> Obv<T> {
+        Obv::default()
+    }
+}
+
+impl<T: Number> Indicator<T> for Obv<T> {
+    type Output = T;
+
+    fn next(&mut self, candle: &Candle<T>) -> Option<T> {
+        if let Some(previous) = self.previous_close.replace(candle.close) {
+            if candle.close > previous {
+                self.total += candle.volume;
+            } else if candle.close < previous {
+                self.total -= candle.volume;
+            }
+        }
+
+        Some(self.total)
+    }
+
+    fn reset(&mut self) {
+        self.previous_close = None;
+        self.total = T::zero();
+    }
+}
+
+/// AccumulationDistribution is the running total of money flow volume, the candle's volume
+/// weighted by its `money_flow_multiplier`.
+/// <https://school.stockcharts.com/doku.php?id=technical_indicators:accumulation_distribution_line>
+#[derive(Clone, Debug, Default)]
+pub struct AccumulationDistribution<T = f64> {
+    total: T,
+}
+
+impl<T: Number> AccumulationDistribution<T> {
+    pub fn new() -> AccumulationDistribution<T> {
+        AccumulationDistribution::default()
+    }
+}
+
+impl<T: Number> Indicator<T> for AccumulationDistribution<T> {
+    type Output = T;
+
+    fn next(&mut self, candle: &Candle<T>) -> Option<T> {
+        self.total += money_flow_multiplier(candle) * candle.volume;
+        Some(self.total)
+    }
+
+    fn reset(&mut self) {
+        self.total = T::zero();
+    }
+}
+
+/// ChaikinMoneyFlow is the sum of money flow volume over `period` candles, divided by the sum of
+/// volume, giving a value between -1 and 1. The common period is 20.
+/// <https://school.stockcharts.com/doku.php?id=technical_indicators:chaikin_money_flow_cmf>
+#[derive(Clone, Debug)]
+pub struct ChaikinMoneyFlow<T = f64> {
+    period: usize,
+    window: VecDeque<(T, T)>,
+    flow_volume: T,
+    volume: T,
+}
+
+impl<T: Number> ChaikinMoneyFlow<T> {
+    pub fn new(period: usize) -> ChaikinMoneyFlow<T> {
+        ChaikinMoneyFlow {
+            period,
+            window: VecDeque::with_capacity(period + 1),
+            flow_volume: T::zero(),
+            volume: T::zero(),
+        }
+    }
+}
+
+impl<T: Number> Indicator<T> for ChaikinMoneyFlow<T> {
+    type Output = T;
+
+    fn next(&mut self, candle: &Candle<T>) -> Option<T> {
+        if self.period == 0 {
+            return None;
+        }
+
+        let flow_volume = money_flow_multiplier(candle) * candle.volume;
+        self.window.push_back((flow_volume, candle.volume));
+        self.flow_volume += flow_volume;
+        self.volume += candle.volume;
+        if self.window.len() > self.period {
+            let (flow_volume, volume) = self.window.pop_front().unwrap_or_default();
+            self.flow_volume -= flow_volume;
+            self.volume -= volume;
+        }
+
+        if self.window.len() < self.period || self.volume <= T::zero() {
+            return None;
+        }
+        Some(self.flow_volume / self.volume)
+    }
+
+    fn reset(&mut self) {
+        self.window.clear();
+        self.flow_volume = T::zero();
+        self.volume = T::zero();
+    }
+}
+
+/// VolumeSma is the simple moving average of volume, typically used to confirm that a breakout
+/// candle trades on above average volume.
+#[derive(Clone, Debug)]
+pub struct VolumeSma<T = f64> {
+    average: Sma<T>,
+}
+
+impl<T: Number> VolumeSma<T> {
+    pub fn new(period: usize) -> VolumeSma<T> {
+        VolumeSma {
+            average: Sma::new(period),
+        }
+    }
+}
+
+impl<T: Number> Indicator<T> for VolumeSma<T> {
+    type Output = T;
+
+    fn next(&mut self, candle: &Candle<T>) -> Option<T> {
+        self.average.update(candle.volume)
+    }
+
+    fn reset(&mut self) {
+        self.average.reset();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::*;
+
+    fn intraday_candles() -> Vec<Candle> {
+        let hour = 3_600_000;
+        vec![
+            Candle::new(14 * hour, 10.0, 11.0, 9.0, 10.0, 100.0),
+            Candle::new(15 * hour, 10.0, 12.0, 10.0, 11.0, 300.0),
+            Candle::new(16 * hour, 11.0, 13.0, 11.0, 12.0, 100.0),
+            Candle::new(DAY_MILLIS + 14 * hour, 20.0, 21.0, 19.0, 20.0, 50.0),
+            Candle::new(DAY_MILLIS + 15 * hour, 20.0, 23.0, 20.0, 22.0, 150.0),
+        ]
+    }
+
+    #[test]
+    fn vwap_series_test() {
+        let vwap = vwap_series(&intraday_candles()).unwrap();
+
+        // typical prices are 10, 11, 12 and then 20, 21.67 after the session restarts on the next day
+        assert_series(&vwap, &warmup(0, &[10.0, 10.75, 11.0, 20.0, 21.25]));
+    }
+
+    #[test]
+    fn anchored_vwap_test() {
+        let candles = intraday_candles();
+        let vwap = AnchoredVwap::new(candles[2].datetime).calculate(&candles);
+
+        assert_series(
+            &vwap,
+            &[None, None, Some(12.0), Some(14.6667), Some(18.1667)],
+        );
+    }
+
+    /// daily candles closing at the high, in the lower half, mid range and upper half of their range
+    fn flow_candles() -> Vec<Candle> {
+        from_hlcv(
+            &[10.0, 12.0, 11.0, 9.0, 13.0],
+            &[8.0, 10.0, 9.0, 7.0, 11.0],
+            &[10.0, 10.5, 10.0, 7.5, 12.5],
+            &[100.0, 200.0, 150.0, 300.0, 250.0],
+        )
+    }
+
+    #[test]
+    fn obv_series_test() {
+        // volume is added on up closes and subtracted on down closes
+        assert_series(
+            &obv_series(&flow_candles()).unwrap(),
+            &warmup(0, &[0.0, 200.0, 50.0, -250.0, 0.0]),
+        );
+    }
+
+    #[test]
+    fn ad_series_test() {
+        // money flow multipliers of 1, -0.5, 0, -0.5 and 0.5 give money flow volumes of 100, -100,
+        // 0, -150 and 125
+        assert_series(
+            &ad_series(&flow_candles()).unwrap(),
+            &warmup(0, &[100.0, 0.0, 0.0, -150.0, -25.0]),
+        );
+    }
+
+    #[test]
+    fn cmf_series_test() {
+        // money flow volume over 3 candles divided by their volume: 0 / 450, -250 / 650 and
+        // -25 / 700
+        assert_series(
+            &cmf_series(&flow_candles(), 3).unwrap(),
+            &warmup(2, &[0.0, -0.3846, -0.0357]),
+        );
+    }
+
+    #[test]
+    fn volume_sma_series_test() {
+        let candles = intraday_candles();
+        assert_series(
+            &volume_sma_series(&candles, 2).unwrap(),
+            &warmup(1, &[200.0, 200.0, 75.0, 100.0]),
+        );
+    }
+}
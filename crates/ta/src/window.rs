@@ -0,0 +1,86 @@
+use crate::number::Number;
+use std::collections::VecDeque;
+
+/// Extremes tracks the highest and lowest values over a rolling window in amortized O(1), using
+/// monotonic queues. Positions are kept so indicators like Aroon can tell how long ago an extreme was.
+#[derive(Clone, Debug)]
+pub(crate) struct Extremes<T = f64> {
+    period: usize,
+    count: usize,
+    highs: VecDeque<(usize, T)>,
+    lows: VecDeque<(usize, T)>,
+}
+
+impl<T: Number> Extremes<T> {
+    pub(crate) fn new(period: usize) -> Extremes<T> {
+        Extremes {
+            period,
+            count: 0,
+            highs: VecDeque::new(),
+            lows: VecDeque::new(),
+        }
+    }
+
+    pub(crate) fn push(&mut self, high: T, low: T) {
+        let index = self.count;
+        self.count += 1;
+
+        while self.highs.back().is_some_and(|(_, h)| *h <= high) {
+            self.highs.pop_back();
+        }
+        self.highs.push_back((index, high));
+
+        while self.lows.back().is_some_and(|(_, l)| *l >= low) {
+            self.lows.pop_back();
+        }
+        self.lows.push_back((index, low));
+
+        while self
+            .highs
+            .front()
+            .is_some_and(|(i, _)| i + self.period <= index)
+        {
+            self.highs.pop_front();
+        }
+        while self
+            .lows
+            .front()
+            .is_some_and(|(i, _)| i + self.period <= index)
+        {
+            self.lows.pop_front();
+        }
+    }
+
+    /// is_full is true once `period` values have been pushed
+    pub(crate) fn is_full(&self) -> bool {
+        self.period > 0 && self.count >= self.period
+    }
+
+    /// highest is the highest value in the window, or `None` until one has been pushed
+    pub(crate) fn highest(&self) -> Option<T> {
+        self.highs.front().map(|(_, h)| *h)
+    }
+
+    /// lowest is the lowest value in the window, or `None` until one has been pushed
+    pub(crate) fn lowest(&self) -> Option<T> {
+        self.lows.front().map(|(_, l)| *l)
+    }
+
+    /// bars_since_highest is the number of values pushed since the window's highest value,
+    /// preferring the most recent value when there is a tie
+    pub(crate) fn bars_since_highest(&self) -> usize {
+        self.highs.front().map_or(0, |(i, _)| self.count - 1 - i)
+    }
+
+    /// bars_since_lowest is the number of values pushed since the window's lowest value,
+    /// preferring the most recent value when there is a tie
+    pub(crate) fn bars_since_lowest(&self) -> usize {
+        self.lows.front().map_or(0, |(i, _)| self.count - 1 - i)
+    }
+
+    pub(crate) fn reset(&mut self) {
+        self.count = 0;
+        self.highs.clear();
+        self.lows.clear();
+    }
+}
@@ -0,0 +1,580 @@
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fmt, fs, path::Path, str::FromStr, time::Duration};
+
+use backtest::{Execution, Metric, MonteCarlo, PortfolioBacktest, Search, Values, WalkForward};
+use strategy::{RiskLimits, StrategyConfig};
+use ta::resample::{Timeframe, HOUR_MILLIS, MINUTE_MILLIS};
+use ta::volume::DAY_MILLIS;
+use ta::Rounding;
+
+/// Config is everything the mate binary needs to trade, read from a TOML or YAML file, eg:
+///
+/// ```toml
+/// interval = "15m"
+/// capital = 25000.0
+///
+/// [[accounts]]
+/// name = "tdameritrade"
+/// vendor = "tdameritrade"
+///
+/// [watchlists]
+/// tech = ["MSFT", "AAPL"]
+///
+/// [[strategies]]
+/// name = "ema_crossover"
+/// watchlist = "tech"
+/// fast = 10
+/// slow = 30
+///
+/// [risk]
+/// max_position = 0.25
+///
+/// [execution]
+/// commission = { percentage = 0.001 }
+/// ```
+///
+/// Account keys and secrets may be left out, in which case they're read from the vendor's
+/// environment variables, eg: `TDA_CLIENT_ID` and `TDA_REFRESH_TOKEN`.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(default)]
+pub struct Config {
+    pub accounts: Vec<AccountConfig>,
+    /// named lists of symbols, which strategies are run on
+    pub watchlists: BTreeMap<String, Vec<String>>,
+    pub strategies: Vec<StrategyEntry>,
+    /// how often to poll for new candles, eg: "15m", "1h" or "1d"
+    pub interval: String,
+    /// notional capital strategies size their positions against
+    pub capital: f64,
+    pub risk: RiskLimits,
+    /// how simulated orders are filled and charged for, when backtesting
+    pub execution: Execution,
+    /// how `mate backtest` resamples each backtest's trades to show how much was luck, if at all
+    pub monte_carlo: Option<MonteCarlo>,
+    /// the parameters `mate optimize` searches over
+    pub optimize: OptimizeConfig,
+    /// the basket `mate portfolio` backtests
+    pub portfolio: PortfolioConfig,
+    pub rounding: Rounding,
+}
+
+impl Default for Config {
+    /// default runs the EMA crossover on MSFT through TD Ameritrade every hour
+    fn default() -> Config {
+        let mut watchlists = BTreeMap::new();
+        watchlists.insert(DEFAULT_WATCHLIST.to_string(), vec!["MSFT".to_string()]);
+
+        Config {
+            accounts: vec![AccountConfig::new("tdameritrade")],
+            watchlists,
+            strategies: vec![StrategyEntry {
+                watchlist: DEFAULT_WATCHLIST.to_string(),
+                strategy: StrategyConfig::EmaCrossover(Default::default()),
+            }],
+            interval: "1h".to_string(),
+            capital: 10_000.0,
+            risk: RiskLimits::default(),
+            execution: Execution::default(),
+            monte_carlo: None,
+            optimize: OptimizeConfig::default(),
+            portfolio: PortfolioConfig::default(),
+            rounding: Rounding::CENTS,
+        }
+    }
+}
+
+/// The watchlist strategies run on when they don't name one
+pub const DEFAULT_WATCHLIST: &str = "default";
+
+/// AccountConfig is a brokerage or exchange account to trade through, see `accounts::new_account`
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct AccountConfig {
+    pub name: String,
+    pub vendor: String,
+    #[serde(default)]
+    pub id: String,
+    #[serde(default)]
+    pub key: String,
+    #[serde(default)]
+    pub secret: String,
+}
+
+impl AccountConfig {
+    /// new configures an account for `vendor`, with credentials read from the environment
+    pub fn new(vendor: &str) -> AccountConfig {
+        AccountConfig {
+            name: vendor.to_string(),
+            vendor: vendor.to_string(),
+            id: String::new(),
+            key: String::new(),
+            secret: String::new(),
+        }
+    }
+}
+
+/// StrategyEntry runs a strategy on every symbol in a watchlist
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct StrategyEntry {
+    #[serde(default = "default_watchlist")]
+    pub watchlist: String,
+    #[serde(flatten)]
+    pub strategy: StrategyConfig,
+}
+
+fn default_watchlist() -> String {
+    DEFAULT_WATCHLIST.to_string()
+}
+
+/// OptimizeConfig is the search `mate optimize` runs each strategy's parameters through, by the
+/// names they're configured with, eg:
+///
+/// ```toml
+/// [optimize]
+/// metric = "sortino"
+/// search = { random = { samples = 50, seed = 1 } }
+/// walk_forward = { in_sample = 500, out_of_sample = 100 }
+///
+/// [optimize.parameters]
+/// fast = { start = 5, end = 30, step = 5 }
+/// slow = [50, 100, 200]
+/// ```
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(default)]
+pub struct OptimizeConfig {
+    pub parameters: BTreeMap<String, Values>,
+    pub search: Search,
+    pub metric: Metric,
+    /// candles to optimize on and then test over, repeatedly, after optimizing over all history
+    pub walk_forward: Option<WalkForward>,
+    /// how many of the best trials to log
+    pub top: usize,
+}
+
+impl Default for OptimizeConfig {
+    /// default searches every combination of the given parameters for the best Sharpe ratio
+    fn default() -> OptimizeConfig {
+        OptimizeConfig {
+            parameters: BTreeMap::new(),
+            search: Search::Grid,
+            metric: Metric::Sharpe,
+            walk_forward: None,
+            top: 5,
+        }
+    }
+}
+
+/// PortfolioConfig is a basket of symbols and crypto pairs `mate portfolio` backtests as one pool
+/// of cash, traded back to target weights every `rebalance` period, eg:
+///
+/// ```toml
+/// [portfolio]
+/// rebalance = "1M"
+/// threshold = 0.02
+/// benchmark = "QQQ"
+///
+/// [portfolio.weights]
+/// MSFT = 0.25
+/// AAPL = 0.25
+/// QQQ = 0.3
+/// XXBTZUSD = 0.1
+/// ```
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(default)]
+pub struct PortfolioConfig {
+    /// the fraction of equity to hold in each symbol, with the rest kept as cash
+    pub weights: BTreeMap<String, f64>,
+    /// how often to trade back to the weights, eg: "1w" or "1M"
+    pub rebalance: String,
+    /// how far a symbol's weight may drift from its target before it's traded back
+    pub threshold: f64,
+    /// a symbol to compare the portfolio against buying and holding
+    pub benchmark: Option<String>,
+}
+
+impl Default for PortfolioConfig {
+    /// default rebalances monthly, without a benchmark
+    fn default() -> PortfolioConfig {
+        PortfolioConfig {
+            weights: BTreeMap::new(),
+            rebalance: "1M".to_string(),
+            threshold: 0.0,
+            benchmark: None,
+        }
+    }
+}
+
+/// ConfigError describes why a config file couldn't be loaded
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(std::io::Error),
+    Parse(String),
+    /// the file parsed, but a value in it can't be used
+    Invalid(String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io(e) => write!(f, "couldn't read config: {}", e),
+            ConfigError::Parse(e) => write!(f, "couldn't parse config: {}", e),
+            ConfigError::Invalid(e) => write!(f, "invalid config: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl Config {
+    /// from_file reads a config, parsing it as YAML if the extension is `.yaml` or `.yml`, and as
+    /// TOML otherwise
+    pub fn from_file(path: &Path) -> Result<Config, ConfigError> {
+        let contents = fs::read_to_string(path).map_err(ConfigError::Io)?;
+
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("yaml") | Some("yml") => Config::from_yaml(&contents),
+            _ => Config::from_toml(&contents),
+        }
+    }
+
+    pub fn from_toml(contents: &str) -> Result<Config, ConfigError> {
+        let config: Config =
+            toml::from_str(contents).map_err(|e| ConfigError::Parse(e.to_string()))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn from_yaml(contents: &str) -> Result<Config, ConfigError> {
+        let config: Config =
+            serde_yaml::from_str(contents).map_err(|e| ConfigError::Parse(e.to_string()))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// validate checks the values serde can't, such as every strategy naming a known watchlist
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        self.poll_interval()?;
+
+        for entry in &self.strategies {
+            if !self.watchlists.contains_key(&entry.watchlist) {
+                return Err(ConfigError::Invalid(format!(
+                    "strategy uses unknown watchlist {}",
+                    entry.watchlist
+                )));
+            }
+            entry
+                .strategy
+                .build()
+                .map_err(|e| ConfigError::Invalid(e.to_string()))?;
+        }
+
+        self.execution
+            .check()
+            .map_err(|e| ConfigError::Invalid(e.to_string()))?;
+
+        if let Some(monte_carlo) = &self.monte_carlo {
+            monte_carlo
+                .check()
+                .map_err(|e| ConfigError::Invalid(format!("monte_carlo: {}", e)))?;
+        }
+
+        let portfolio = self.portfolio_backtest()?;
+        if !portfolio.weights.is_empty() {
+            portfolio
+                .check()
+                .map_err(|e| ConfigError::Invalid(format!("portfolio: {}", e)))?;
+        }
+
+        for (name, values) in &self.optimize.parameters {
+            values.values().map_err(|e| {
+                ConfigError::Invalid(format!("optimize.parameters.{}: {}", name, e))
+            })?;
+        }
+        if let Some(walk_forward) = self.optimize.walk_forward {
+            if walk_forward.in_sample == 0 || walk_forward.out_of_sample == 0 {
+                return Err(ConfigError::Invalid(
+                    "optimize.walk_forward needs at least one candle in each sample".to_string(),
+                ));
+            }
+        }
+
+        // signals are booked against one notional portfolio, which a single paper account mirrors
+        let paper = self
+            .accounts
+            .iter()
+            .filter(|account| account.vendor.eq_ignore_ascii_case("paper"))
+            .count();
+        if paper > 1 {
+            return Err(ConfigError::Invalid(
+                "only one paper account can be traded at a time".to_string(),
+            ));
+        }
+
+        if !self.capital.is_finite() || self.capital < 0.0 {
+            return Err(ConfigError::Invalid(
+                "capital must be a positive number".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// poll_interval parses `interval` as a `ta::resample::Timeframe` of minutes, hours, days or
+    /// weeks. Months vary in length, so can't be polled on.
+    pub fn poll_interval(&self) -> Result<Duration, ConfigError> {
+        let millis = match Timeframe::from_str(&self.interval) {
+            Ok(Timeframe::Minutes(n)) => n.checked_mul(MINUTE_MILLIS),
+            Ok(Timeframe::Hours(n)) => n.checked_mul(HOUR_MILLIS),
+            Ok(Timeframe::Days(n)) => n.checked_mul(DAY_MILLIS),
+            Ok(Timeframe::Weeks(n)) => n.checked_mul(7 * DAY_MILLIS),
+            Ok(Timeframe::Months(_)) => None,
+            Err(e) => return Err(ConfigError::Invalid(format!("interval: {}", e))),
+        };
+
+        millis
+            .map(|millis| Duration::from_millis(millis as u64))
+            .ok_or_else(|| ConfigError::Invalid(format!("interval {}", self.interval)))
+    }
+
+    /// portfolio_backtest sets up a backtest of the `portfolio` basket, with candles of the config's
+    /// `interval` and its capital, risk limits and execution
+    pub fn portfolio_backtest(&self) -> Result<PortfolioBacktest, ConfigError> {
+        let timeframe = |name: &str, value: &str| {
+            Timeframe::from_str(value).map_err(|e| ConfigError::Invalid(format!("{}: {}", name, e)))
+        };
+
+        Ok(PortfolioBacktest {
+            capital: self.capital,
+            risk: self.risk,
+            execution: self.execution.clone(),
+            weights: self.portfolio.weights.clone(),
+            timeframe: timeframe("interval", &self.interval)?,
+            rebalance: timeframe("portfolio.rebalance", &self.portfolio.rebalance)?,
+            threshold: self.portfolio.threshold,
+        })
+    }
+
+    /// symbols returns every symbol a strategy runs on, sorted and without duplicates
+    pub fn symbols(&self) -> Vec<String> {
+        let mut symbols: Vec<String> = self
+            .strategies
+            .iter()
+            .filter_map(|entry| self.watchlists.get(&entry.watchlist))
+            .flatten()
+            .cloned()
+            .collect();
+        symbols.sort();
+        symbols.dedup();
+        symbols
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use backtest::{OrderType, Slippage};
+    use strategy::{EmaCrossoverConfig, Size};
+
+    const TOML: &str = r#"
+interval = "15m"
+capital = 25000.0
+rounding = { DecimalPlaces = 8 }
+
+[[accounts]]
+name = "crypto"
+vendor = "kraken"
+
+[watchlists]
+tech = ["MSFT", "AAPL"]
+crypto = ["XXBTZUSD", "MSFT"]
+
+[[strategies]]
+name = "ema_crossover"
+watchlist = "tech"
+fast = 10
+slow = 30
+size = { units = 2.0 }
+
+[[strategies]]
+name = "ema_crossover"
+watchlist = "crypto"
+
+[risk]
+max_position = 0.25
+max_open_positions = 3
+
+[execution]
+orders = { stop = { offset = 0.01 } }
+commission = { maker_taker = [{ volume = 0.0, maker = 0.0016, taker = 0.0026 }] }
+slippage = { spread = { fallback = 5.0 } }
+
+[monte_carlo]
+method = "resample"
+perturbation = 0.001
+
+[portfolio]
+rebalance = "1w"
+benchmark = "QQQ"
+weights = { MSFT = 0.5, XXBTZUSD = 0.25 }
+
+[optimize]
+metric = "max_drawdown"
+search = { random = { samples = 20, seed = 7 } }
+walk_forward = { in_sample = 200, out_of_sample = 50 }
+
+[optimize.parameters]
+fast = [5, 10, 20]
+slow = { start = 30, end = 100, step = 10 }
+"#;
+
+    #[test]
+    fn from_toml_test() {
+        let config = Config::from_toml(TOML).unwrap();
+
+        assert_eq!(config.accounts[0].vendor, "kraken");
+        assert!(config.accounts[0].key.is_empty());
+        assert_eq!(
+            config.poll_interval().unwrap(),
+            Duration::from_secs(15 * 60)
+        );
+        assert_eq!(config.rounding, Rounding::DecimalPlaces(8));
+        assert_eq!(config.symbols(), vec!["AAPL", "MSFT", "XXBTZUSD"]);
+        assert_eq!(
+            config.strategies[0].strategy,
+            StrategyConfig::EmaCrossover(EmaCrossoverConfig {
+                fast: 10,
+                slow: 30,
+                size: Size::Units(2.0),
+                stop_loss: Some(0.95),
+            })
+        );
+        assert_eq!(config.risk.max_position, Some(0.25));
+        assert_eq!(config.risk.max_drawdown, None);
+        assert_eq!(config.execution.orders, OrderType::Stop { offset: 0.01 });
+        assert_eq!(
+            config.execution.slippage,
+            Slippage::Spread { fallback: 5.0 }
+        );
+        assert_eq!(
+            config.monte_carlo,
+            Some(MonteCarlo {
+                method: backtest::monte_carlo::Method::Resample,
+                perturbation: 0.001,
+                ..Default::default()
+            })
+        );
+        let portfolio = config.portfolio_backtest().unwrap();
+        assert_eq!(portfolio.rebalance, Timeframe::Weeks(1));
+        assert_eq!(portfolio.timeframe, Timeframe::Minutes(15));
+        assert_eq!(portfolio.weights["XXBTZUSD"], 0.25);
+        assert_eq!(config.portfolio.benchmark, Some("QQQ".to_string()));
+        assert_eq!(config.optimize.metric, Metric::MaxDrawdown);
+        assert_eq!(
+            config.optimize.search,
+            Search::Random {
+                samples: 20,
+                seed: 7
+            }
+        );
+        assert_eq!(
+            config.optimize.parameters["slow"].values().unwrap().len(),
+            8
+        );
+        assert_eq!(config.optimize.top, 5);
+    }
+
+    #[test]
+    fn from_yaml_test() {
+        let yaml = r#"
+interval: 1d
+watchlists:
+  default: [SPY]
+strategies:
+  - name: ema_crossover
+    stop_loss: ~
+"#;
+        let config = Config::from_yaml(yaml).unwrap();
+
+        assert_eq!(config.accounts, Config::default().accounts);
+        assert_eq!(config.poll_interval().unwrap(), Duration::from_secs(86_400));
+        assert_eq!(config.symbols(), vec!["SPY"]);
+        assert_eq!(
+            config.strategies[0].strategy,
+            StrategyConfig::EmaCrossover(EmaCrossoverConfig {
+                stop_loss: None,
+                ..Default::default()
+            })
+        );
+    }
+
+    #[test]
+    fn default_test() {
+        let config = Config::default();
+
+        assert_eq!(config.validate().unwrap(), ());
+        assert_eq!(config.symbols(), vec!["MSFT"]);
+        assert_eq!(config.poll_interval().unwrap(), Duration::from_secs(3600));
+        assert_eq!(Config::from_toml("").unwrap(), config);
+    }
+
+    #[test]
+    fn invalid_test() {
+        let unknown_watchlist = "[[strategies]]\nname = \"ema_crossover\"\nwatchlist = \"nope\"";
+        assert!(matches!(
+            Config::from_toml(unknown_watchlist),
+            Err(ConfigError::Invalid(_))
+        ));
+
+        let bad_parameters = "[[strategies]]\nname = \"ema_crossover\"\nfast = 90";
+        assert!(matches!(
+            Config::from_toml(bad_parameters),
+            Err(ConfigError::Invalid(_))
+        ));
+
+        assert!(matches!(
+            Config::from_toml("[execution]\nslippage = { fixed = -1.0 }"),
+            Err(ConfigError::Invalid(_))
+        ));
+        assert!(matches!(
+            Config::from_toml("[portfolio.weights]\nMSFT = 0.6\nAAPL = 0.6"),
+            Err(ConfigError::Invalid(_))
+        ));
+        assert!(matches!(
+            Config::from_toml("[portfolio]\nrebalance = \"often\""),
+            Err(ConfigError::Invalid(_))
+        ));
+        assert!(matches!(
+            Config::from_toml("[portfolio]\nrebalance = \"99999999999999999m\""),
+            Err(ConfigError::Invalid(_))
+        ));
+        assert!(matches!(
+            Config::from_toml("[monte_carlo]\nconfidence = 1.5"),
+            Err(ConfigError::Invalid(_))
+        ));
+        assert!(matches!(
+            Config::from_toml("[optimize.parameters]\nfast = { start = 10, end = 5, step = 1 }"),
+            Err(ConfigError::Invalid(_))
+        ));
+        assert!(matches!(
+            Config::from_toml("[optimize]\nwalk_forward = { in_sample = 0, out_of_sample = 5 }"),
+            Err(ConfigError::Invalid(_))
+        ));
+        assert!(matches!(
+            Config::from_toml(
+                "[[accounts]]\nname = \"a\"\nvendor = \"paper\"\n\n[[accounts]]\nname = \"b\"\nvendor = \"Paper\""
+            ),
+            Err(ConfigError::Invalid(_))
+        ));
+        assert!(matches!(
+            Config::from_toml("interval = \"1M\""),
+            Err(ConfigError::Invalid(_))
+        ));
+        assert!(matches!(
+            Config::from_toml("[[strategies]]\nname = \"martingale\""),
+            Err(ConfigError::Parse(_))
+        ));
+        assert!(matches!(
+            Config::from_file(Path::new("/nonexistent/mate.toml")),
+            Err(ConfigError::Io(_))
+        ));
+    }
+}
@@ -1,15 +1,32 @@
-use clap::Parser;
-use log::info;
-use std::{collections::HashMap, thread, time::Duration};
-use tda_sdk::responses::Candle;
+use clap::{Parser, Subcommand};
+use log::{info, warn};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    str::FromStr,
+    thread,
+};
 
 use accounts::kraken::KrakenAccount;
+use accounts::paper::{OrderKind, PaperAccount, PaperState};
 use accounts::tdameritrade::TDAmeritradeAccount;
 use accounts::types::AccountType;
 
+use backtest::{
+    data, optimize, report::report_dir, Backtest, Optimization, Optimizer, Report, Timeline,
+};
+
 use matelog::init_logging;
 
-use ta::average::{ema, sma};
+use strategy::{Engine, Portfolio, Position, RiskLimits};
+
+mod config;
+use config::{AccountConfig, Config};
+mod market;
+
+use ta::average::{Ema, Sma};
+use ta::resample::Timeframe;
+use ta::{patterns, Candle, Indicator, Rounding, Series};
 
 /// You can see the spec for clap's arg attributes here:
 ///      <https://github.com/clap-rs/clap/blob/v3.0.0-rc.11/examples/derive_ref/README.md#arg-attributes>
@@ -21,44 +38,214 @@ use ta::average::{ema, sma};
     author
 )]
 struct Args {
-    #[clap(short, long, default_value = "tdameritrade")]
+    /// TOML or YAML config file, see `Config`, which defaults to trading MSFT hourly
+    #[clap(short, long, env = "MATE_CONFIG")]
+    config: Option<PathBuf>,
+
+    /// account vendors to trade through, replacing those in the config
+    #[clap(short, long)]
     accounts: Vec<String>,
 
+    /// polling interval, eg: 15m, replacing the one in the config
+    #[clap(short, long, env = "MATE_INTERVAL")]
+    interval: Option<String>,
+
     #[clap(short, long, parse(from_occurrences))]
     verbose: usize,
+
+    #[clap(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// replay the history the collector has stored through the configured strategies, instead of
+    /// trading
+    Backtest {
+        /// the collector's --filepath, holding equity/daily and crypto/tick
+        #[clap(short, long)]
+        filepath: PathBuf,
+    },
+    /// backtest the config's [portfolio] basket and strategies as one pool of cash, rebalancing
+    /// the basket to its weights
+    Portfolio {
+        /// the collector's --filepath, holding equity/daily and crypto/tick
+        #[clap(short, long)]
+        filepath: PathBuf,
+    },
+    /// search for the parameters each configured strategy would have done best with over the
+    /// collected history, as set in the config's [optimize] section
+    Optimize {
+        /// the collector's --filepath, holding equity/daily and crypto/tick
+        #[clap(short, long)]
+        filepath: PathBuf,
+    },
+    /// trade the config's paper accounts over the collected history, as if it were arriving live,
+    /// carrying on from the last candle they were marked with
+    Paper {
+        /// the collector's --filepath, holding equity/daily and crypto/tick
+        #[clap(short, long)]
+        filepath: PathBuf,
+    },
+}
+
+/// AverageValues are the most recent moving average values computed for a symbol
+#[derive(Clone, Copy, Debug, Default)]
+pub struct AverageValues {
+    sma20: Option<f64>,
+    sma50: Option<f64>,
+    sma100: Option<f64>,
+    ema20: Option<f64>,
+    ema50: Option<f64>,
+}
+
+/// Averages holds the streaming moving averages for a symbol, along with the datetime of the last
+/// candle they were fed, so that each update only processes new candles.
+#[derive(Clone, Debug)]
+pub struct Averages {
+    sma20: Sma,
+    sma50: Sma,
+    sma100: Sma,
+    ema20: Ema,
+    ema50: Ema,
+    last_datetime: Option<usize>,
+    latest: AverageValues,
+}
+
+impl Averages {
+    pub fn new() -> Averages {
+        Averages {
+            sma20: Sma::new(20),
+            sma50: Sma::new(50),
+            sma100: Sma::new(100),
+            ema20: Ema::new(20),
+            ema50: Ema::new(50),
+            last_datetime: None,
+            latest: AverageValues::default(),
+        }
+    }
+
+    /// update feeds any candles newer than the last one seen, and returns the latest values
+    pub fn update(&mut self, candles: &[Candle]) -> AverageValues {
+        for candle in candles {
+            if let Some(last) = self.last_datetime {
+                if candle.datetime <= last {
+                    continue;
+                }
+            }
+
+            // a candle with a NaN or infinite value is skipped, rather than poisoning every average
+            self.last_datetime = Some(candle.datetime);
+            if !candle.is_finite() {
+                warn!(
+                    "skipping candle at {} with a non-finite value",
+                    candle.datetime
+                );
+                continue;
+            }
+
+            self.latest = AverageValues {
+                sma20: self.sma20.next(candle),
+                sma50: self.sma50.next(candle),
+                sma100: self.sma100.next(candle),
+                ema20: self.ema20.next(candle),
+                ema50: self.ema50.next(candle),
+            };
+        }
+
+        self.latest
+    }
+}
+
+impl Default for Averages {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 // mate makes use of the tda-sdk crate for access to a brokerage API
 // https://github.com/rideron89/tda-sdk-rs
 pub struct Mate {
     accounts: Vec<AccountType>,
-    candles: HashMap<String, Vec<Candle>>,
+    averages: HashMap<String, Averages>,
+    candles: HashMap<String, Series>,
+    engine: Engine,
+    // positions are only read back from the paper account, so signals are booked against a notional
+    // portfolio that it mirrors, sized from its cash and positions. It's rebuilt from the account
+    // after every mark, so orders the account couldn't fill aren't counted as held
+    portfolio: Portfolio,
+    peak_equity: f64,
+    risk: RiskLimits,
+    rounding: Rounding,
     symbols: Vec<String>,
+    // the length of the candles strategies run on, and that market data is fetched in
+    timeframe: Timeframe,
 }
 
 impl Mate {
-    pub fn new(accounts: Vec<String>) -> Mate {
+    pub fn new(config: &Config) -> Mate {
         let mut mate = Mate {
             accounts: Vec::new(),
+            averages: HashMap::new(),
             candles: HashMap::new(),
-            symbols: Vec::new(),
+            engine: Engine::new(),
+            portfolio: Portfolio::new(config.capital),
+            peak_equity: config.capital,
+            risk: config.risk,
+            rounding: config.rounding,
+            symbols: config.symbols(),
+            // the interval was checked when the config was validated
+            timeframe: Timeframe::from_str(&config.interval).unwrap(),
         };
 
-        for account in accounts {
-            let new_account =
-                accounts::new_account(account.as_str(), account.as_str(), "", None, "", "")
-                    .unwrap();
+        // keys and secrets left out of the config are read from the vendor's environment variables
+        // paper accounts not given a starting balance start with the config's capital
+        for account in &config.accounts {
+            let mut secret = account.secret.clone();
+            if account.vendor.eq_ignore_ascii_case("paper") && secret.is_empty() {
+                secret = config.capital.to_string();
+            }
+
+            let new_account = accounts::new_account(
+                &account.name,
+                &account.vendor,
+                &account.id,
+                None,
+                &account.key,
+                &secret,
+            )
+            .unwrap();
             mate.accounts.push(new_account);
         }
 
+        // a paper account remembers what it holds across restarts, so trading carries on from there
+        if let Some(equity) = mate.sync_paper() {
+            mate.peak_equity = mate.peak_equity.max(equity);
+        }
+
+        for entry in &config.strategies {
+            for symbol in &config.watchlists[&entry.watchlist] {
+                // the config was validated when loaded, so every strategy builds
+                let strategy = entry.strategy.build().unwrap();
+                mate.engine.add(symbol, strategy);
+            }
+        }
+
         mate
     }
 
     pub fn default() -> Self {
         Mate {
             accounts: Vec::new(),
+            averages: HashMap::new(),
             candles: HashMap::new(),
+            engine: Engine::new(),
+            portfolio: Portfolio::default(),
+            peak_equity: 0.0,
+            risk: RiskLimits::default(),
+            rounding: Rounding::CENTS,
             symbols: Vec::new(),
+            timeframe: Timeframe::Hours(1),
         }
     }
 
@@ -71,52 +258,594 @@ impl Mate {
                 AccountType::KrakenAccount(account) => {
                     info!("Found Kraken Accounts: {}", account.get_account_balance());
                 }
+                AccountType::PaperAccount(account) => {
+                    info!(
+                        "Found Paper Account {}: {}",
+                        account.account.name,
+                        account.get_account_balance()
+                    );
+                }
             }
         }
     }
 
+    /// paper_accounts returns the paper account being traded, which signals are mirrored to. Configs
+    /// are validated to have at most one
+    fn paper_accounts(&self) -> Vec<&PaperAccount> {
+        self.accounts
+            .iter()
+            .filter_map(|account| match account {
+                AccountType::PaperAccount(account) => Some(account),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// sync_paper rebuilds the notional portfolio from the paper account's cash and positions,
+    /// returning the account's equity, or None without a paper account. Configs are validated to
+    /// have at most one. A state that can't be read is logged, and the account starts over from its
+    /// starting cash.
+    fn sync_paper(&mut self) -> Option<f64> {
+        let state = self.paper_accounts().first().map(|account| {
+            account.state().unwrap_or_else(|e| {
+                warn!("{}: {}, starting over", account.account.name, e);
+                PaperState::new(account.starting_cash)
+            })
+        })?;
+
+        self.portfolio = Portfolio {
+            cash: state.cash,
+            positions: state
+                .positions
+                .iter()
+                .map(|(symbol, p)| {
+                    let position = Position {
+                        quantity: p.quantity,
+                        entry_price: p.entry_price,
+                    };
+                    (symbol.to_string(), position)
+                })
+                .collect(),
+        };
+        Some(state.equity())
+    }
+
     pub fn update_td(&mut self, account: TDAmeritradeAccount) {
         let symbols = self.symbols.clone();
         for symbol in symbols {
-            self.candles
-                .insert(symbol.to_string(), account.get_candles(symbol.to_string()));
+            let candles = account
+                .get_candles(symbol.to_string())
+                .into_iter()
+                .map(Candle::from)
+                .collect();
+
+            let series = Series::new(&symbol, candles);
+            if let Some(last) = series.len().checked_sub(1) {
+                match patterns::detect_at(&series, last) {
+                    Ok(found) => {
+                        for pattern in found {
+                            info!(
+                                "{}: {:?} {:?} pattern",
+                                symbol, pattern.direction, pattern.kind
+                            );
+                        }
+                    }
+                    Err(e) => warn!("{}: couldn't detect patterns: {}", symbol, e),
+                }
+            }
+
+            self.candles.insert(symbol.to_string(), series);
+        }
+
+        for symbol in &self.symbols {
+            let series = match self.candles.get(symbol) {
+                Some(series) => series,
+                None => continue,
+            };
+            let averages = self.averages.entry(symbol.to_string()).or_default();
+
+            // only candles we haven't seen yet are fed in, keeping each update O(1) per new bar
+            let values = averages.update(series);
+
+            // averages are kept at full precision, and only rounded for display
+            let rounding = self.rounding;
+
+            let sma20 = values.sma20.map(|v| rounding.apply(v)).unwrap_or(f64::NAN);
+            let sma50 = values.sma50.map(|v| rounding.apply(v)).unwrap_or(f64::NAN);
+            let sma100 = values.sma100.map(|v| rounding.apply(v)).unwrap_or(f64::NAN);
+
+            info!(
+                "{}: SMA20: {}\tSMA50: {}\tSMA100: {}",
+                symbol, sma20, sma50, sma100
+            );
+
+            let ema20 = values.ema20.map(|v| rounding.apply(v)).unwrap_or(f64::NAN);
+            let ema50 = values.ema50.map(|v| rounding.apply(v)).unwrap_or(f64::NAN);
+
+            info!("{}: EMA20: {}\tEMA50: {}", symbol, ema20, ema50);
         }
 
-        let msft_candles = self.candles.get("MSFT").unwrap();
+        for symbol in self.symbols.clone() {
+            self.decide(&symbol);
+        }
+    }
 
-        let sma20 = sma(msft_candles, 0, 20);
-        let sma50 = sma(msft_candles, 0, 50);
-        let sma100 = sma(msft_candles, 0, 100);
+    /// decide runs the strategies for `symbol` over its latest candles, booking their signals
+    /// against the notional portfolio and placing the same orders with the paper account, if any
+    fn decide(&mut self, symbol: &str) {
+        let series = match self.candles.get(symbol) {
+            Some(series) => series,
+            None => return,
+        };
+        let close = match series.last() {
+            Some(candle) => candle.close,
+            None => return,
+        };
 
-        info!("SMA20: {}\tSMA50: {}\tSMA100: {}", sma20, sma50, sma100);
+        for decision in self.engine.update(series, &self.portfolio) {
+            if decision.signal.is_hold() {
+                continue;
+            }
 
-        let ema20 = ema(msft_candles, 20);
-        let ema50 = ema(msft_candles, 50);
+            let candles = &self.candles;
+            let equity = self
+                .portfolio
+                .equity(|symbol| candles.get(symbol)?.last().map(|c| c.close));
+            self.peak_equity = self.peak_equity.max(equity);
 
-        // TODO Check for NaN values to ensure we don't submit a faulty order
-        info!("EMA20: {}\tEMA50: {}", ema20, ema50);
-        if ema20 > 0.0 && ema50 > 0.0 {
-            if ema20 > ema50 {
-                info!("buy");
-                info!("set stop loss at 95%");
-            } else {
-                info!("sell");
+            let signal = self.risk.limit(
+                &self.portfolio,
+                symbol,
+                decision.signal,
+                close,
+                equity,
+                self.peak_equity,
+            );
+            if signal != decision.signal {
+                info!(
+                    "{}: {} signalled {:?}, limited to {:?}",
+                    symbol, decision.strategy, decision.signal, signal
+                );
+            }
+
+            let quantity = self.portfolio.apply(symbol, &signal, close);
+            info!(
+                "{}: {} signalled {:?}, booked {} at {}",
+                symbol, decision.strategy, signal, quantity, close
+            );
+
+            if quantity == 0.0 {
+                continue;
+            }
+            for account in self.paper_accounts() {
+                if let Err(e) = account.place_order(symbol, quantity, OrderKind::Market) {
+                    warn!("{}: {}", account.account.name, e);
+                }
             }
         }
     }
 
+    /// update_paper trades the paper account, if any. Symbols a TD Ameritrade account hasn't
+    /// fetched candles for this poll are fetched from Kraken's public market data and decided on,
+    /// so a paper account can trade crypto pairs without a brokerage account. The account is then
+    /// marked with the latest close of every symbol, filling any of its orders those prices reach,
+    /// and the notional portfolio is rebuilt from what it holds.
+    pub fn update_paper(&mut self) {
+        let account = match self.paper_accounts().first() {
+            Some(account) => (*account).clone(),
+            None => return,
+        };
+
+        let td = self
+            .accounts
+            .iter()
+            .any(|account| matches!(account, AccountType::TDAmeritradeAccount(_)));
+        for symbol in self.symbols.clone() {
+            if td && self.candles.get(&symbol).is_some_and(|s| !s.is_empty()) {
+                continue;
+            }
+
+            match market::kraken_candles(&symbol, self.timeframe) {
+                Ok(candles) => {
+                    let series = Series::new(&symbol, candles);
+                    self.candles.insert(symbol.to_string(), series);
+                    self.decide(&symbol);
+                }
+                Err(e) => warn!("{}: couldn't fetch candles from Kraken, {}", symbol, e),
+            }
+        }
+
+        for symbol in &self.symbols {
+            if let Some(candle) = self.candles.get(symbol).and_then(|s| s.last()) {
+                if let Err(e) = account.mark(symbol, candle.close, candle.datetime) {
+                    warn!("{}: {}", account.account.name, e);
+                }
+            }
+        }
+        self.sync_paper();
+    }
+
     pub fn update_kraken(&self, account: KrakenAccount) {
         info!("BTC: {}", account.get_pairs("XXBTZUSD").as_str());
         info!("ETH: {}", account.get_pairs("XETHZUSD").as_str());
     }
 }
 
+/// backtest runs each configured strategy over the history collected for every symbol in its
+/// watchlist, writing a report on how it would have done to the `backtest` directory alongside the
+/// history. Crypto ticks are resampled to the config's interval, and each result is run through a
+/// Monte Carlo analysis if the config asks for one.
+fn backtest(config: &Config, filepath: &Path) {
+    // the interval was checked when the config was validated
+    let timeframe = Timeframe::from_str(&config.interval).unwrap();
+    let backtest = Backtest {
+        capital: config.capital,
+        risk: config.risk,
+        execution: config.execution.clone(),
+    };
+
+    for entry in &config.strategies {
+        for symbol in &config.watchlists[&entry.watchlist] {
+            let series = match data::load(filepath, symbol, timeframe) {
+                Ok(series) => series,
+                Err(e) => {
+                    warn!("{}: skipping backtest, {}", symbol, e);
+                    continue;
+                }
+            };
+
+            let spreads = data::load_spreads(filepath, symbol, timeframe).unwrap_or_else(|e| {
+                warn!("{}: backtesting without spreads, {}", symbol, e);
+                Default::default()
+            });
+
+            let mut strategy = entry.strategy.build().unwrap();
+            let result = backtest.run_quoted(&series, &spreads, strategy.as_mut());
+            let mut report = Report::new(result);
+            monte_carlo(config, symbol, &mut report);
+            let summary = &report.summary;
+            info!(
+                "{}: {} made {} trades over {} candles, paying {} in fees, ending with {} ({}%)",
+                symbol,
+                report.result.strategy,
+                summary.trades,
+                series.len(),
+                config.rounding.apply(summary.fees),
+                config.rounding.apply(summary.final_equity),
+                Rounding::CENTS.apply(summary.total_return * 100.0)
+            );
+
+            write_report(&report, symbol, filepath);
+        }
+    }
+}
+
+/// optimize searches the parameters of each configured strategy for those that did best over the
+/// history collected for every symbol in its watchlist, then walks forward through the history if
+/// the config asks to. Every trial is written, ranked, to the `backtest` directory.
+fn optimize(config: &Config, filepath: &Path) {
+    // the interval was checked when the config was validated
+    let timeframe = Timeframe::from_str(&config.interval).unwrap();
+    let settings = &config.optimize;
+
+    for entry in &config.strategies {
+        let optimizer = Optimizer {
+            backtest: Backtest {
+                capital: config.capital,
+                risk: config.risk,
+                execution: config.execution.clone(),
+            },
+            strategy: entry.strategy.clone(),
+            parameters: settings.parameters.clone(),
+            search: settings.search,
+            metric: settings.metric,
+        };
+
+        for symbol in &config.watchlists[&entry.watchlist] {
+            let series = match data::load(filepath, symbol, timeframe) {
+                Ok(series) => series,
+                Err(e) => {
+                    warn!("{}: skipping optimization, {}", symbol, e);
+                    continue;
+                }
+            };
+
+            let spreads = data::load_spreads(filepath, symbol, timeframe).unwrap_or_else(|e| {
+                warn!("{}: optimizing without spreads, {}", symbol, e);
+                Default::default()
+            });
+
+            let trials = match optimizer.optimize(&series, &spreads) {
+                Ok(trials) => trials,
+                Err(e) => {
+                    warn!("{}: skipping optimization, {}", symbol, e);
+                    continue;
+                }
+            };
+            for (rank, trial) in trials.iter().take(settings.top).enumerate() {
+                info!(
+                    "{}: #{} {:?} scored {} on {:?}, ending with {} ({}%)",
+                    symbol,
+                    rank + 1,
+                    trial.parameters,
+                    trial.score,
+                    settings.metric,
+                    config.rounding.apply(trial.summary.final_equity),
+                    Rounding::CENTS.apply(trial.summary.total_return * 100.0)
+                );
+            }
+
+            let mut folds = Vec::new();
+            if let Some(walk_forward) = settings.walk_forward {
+                // the walk forward was checked when the config was validated
+                folds = optimizer
+                    .walk_forward(&series, &spreads, walk_forward)
+                    .unwrap_or_default();
+                for fold in &folds {
+                    info!(
+                        "{}: walk forward {:?} from {} returned {}% out of sample",
+                        symbol,
+                        fold.in_sample.parameters,
+                        fold.split,
+                        Rounding::CENTS.apply(fold.out_of_sample.total_return * 100.0)
+                    );
+                }
+                info!(
+                    "{}: walk forward returned {}% out of sample over {} folds",
+                    symbol,
+                    Rounding::CENTS.apply(optimize::out_of_sample_return(&folds) * 100.0),
+                    folds.len()
+                );
+            }
+
+            let optimization = Optimization::new(symbol, &optimizer, trials, folds);
+            match optimization.write(&report_dir(filepath)) {
+                Ok(paths) => {
+                    for path in paths {
+                        info!("{}: wrote {}", symbol, path.display());
+                    }
+                }
+                Err(e) => warn!("{}: couldn't write optimization, {}", symbol, e),
+            }
+        }
+    }
+}
+
+/// monte_carlo runs the backtest in `report` through the Monte Carlo analysis in the config, if
+/// it has one, logging what it found
+fn monte_carlo(config: &Config, name: &str, report: &mut Report) {
+    if let Some(monte_carlo) = &config.monte_carlo {
+        // the settings were checked when the config was validated
+        let analysis = monte_carlo.run(&report.result).unwrap();
+        info!(
+            "{}: {} Monte Carlo simulations ended with {} to {} with {}% confidence, a max drawdown of up to {}%, and a {}% risk of ruin",
+            name,
+            analysis.simulations,
+            config.rounding.apply(analysis.final_equity.lower),
+            config.rounding.apply(analysis.final_equity.upper),
+            Rounding::CENTS.apply(analysis.confidence * 100.0),
+            Rounding::CENTS.apply(analysis.max_drawdown.upper * 100.0),
+            Rounding::CENTS.apply(analysis.risk_of_ruin.estimate * 100.0)
+        );
+        report.monte_carlo = Some(analysis);
+    }
+}
+
+/// write_report writes `report` to the `backtest` directory alongside the collected history
+fn write_report(report: &Report, name: &str, filepath: &Path) {
+    match report.write(&report_dir(filepath)) {
+        Ok(paths) => {
+            for path in paths {
+                info!("{}: wrote {}", name, path.display());
+            }
+        }
+        Err(e) => warn!("{}: couldn't write backtest report, {}", name, e),
+    }
+}
+
+/// portfolio backtests the config's `portfolio` basket and strategies as one pool of cash,
+/// comparing it against buying and holding its benchmark, and writes the report to the `backtest`
+/// directory. Strategies on symbols without collected history are left out.
+fn portfolio(config: &Config, filepath: &Path) {
+    // the portfolio was checked when the config was validated
+    let backtest = config.portfolio_backtest().unwrap();
+    if backtest.weights.is_empty() {
+        warn!("portfolio: no [portfolio.weights] are configured to backtest");
+        return;
+    }
+
+    let mut engine = Engine::new();
+    for entry in &config.strategies {
+        for symbol in &config.watchlists[&entry.watchlist] {
+            // the config was validated when loaded, so every strategy builds
+            engine.add(symbol, entry.strategy.build().unwrap());
+        }
+    }
+
+    // weighted symbols are loaded first, then any more the strategies run on, then a benchmark
+    // that isn't traded, which is kept off the portfolio's calendar
+    let mut symbols: Vec<String> = backtest.weights.keys().cloned().collect();
+    for symbol in engine.symbols() {
+        if !symbols.contains(&symbol) {
+            symbols.push(symbol);
+        }
+    }
+    if let Some(benchmark) = &config.portfolio.benchmark {
+        if !symbols.contains(benchmark) {
+            symbols.push(benchmark.to_string());
+        }
+    }
+
+    let mut series = Vec::new();
+    let mut spreads = HashMap::new();
+    let mut traded = 0;
+    for symbol in &symbols {
+        let weighted = backtest.weights.contains_key(symbol);
+        let strategies = !engine.strategies(symbol).is_empty();
+        match data::load(filepath, symbol, backtest.timeframe) {
+            Ok(loaded) => series.push(loaded),
+            Err(e) if strategies && !weighted => {
+                warn!(
+                    "{}: leaving its strategies out of the portfolio, {}",
+                    symbol, e
+                );
+                continue;
+            }
+            Err(e) => {
+                warn!("portfolio: skipping backtest, {}", e);
+                return;
+            }
+        }
+        if weighted || strategies {
+            traded += 1;
+        }
+        let quoted = data::load_spreads(filepath, symbol, backtest.timeframe).unwrap_or_else(|e| {
+            warn!("{}: backtesting without spreads, {}", symbol, e);
+            Default::default()
+        });
+        spreads.insert(symbol.to_string(), quoted);
+    }
+
+    let result = backtest
+        .run(&series[..traded], &spreads, &mut engine)
+        .unwrap();
+    let mut report = Report::new(result);
+    if let Some(symbol) = &config.portfolio.benchmark {
+        let held = series.iter().find(|s| &s.symbol == symbol).unwrap();
+        let benchmark = backtest.benchmark(&report.result, held, &spreads[symbol]);
+        report = report.with_benchmark(benchmark);
+    }
+    monte_carlo(config, "portfolio", &mut report);
+
+    let summary = &report.summary;
+    info!(
+        "portfolio: {} symbols made {} trades over {} periods, paying {} in fees, ending with {} ({}%)",
+        backtest.weights.len(),
+        summary.trades,
+        report.result.equity.len(),
+        config.rounding.apply(summary.fees),
+        config.rounding.apply(summary.final_equity),
+        Rounding::CENTS.apply(summary.total_return * 100.0)
+    );
+    if let Some(benchmark) = &report.benchmark {
+        info!(
+            "portfolio: buying and holding {} ended with {} ({}%), a max drawdown of {}% against {}%",
+            benchmark.symbol,
+            config.rounding.apply(benchmark.summary.final_equity),
+            Rounding::CENTS.apply(benchmark.summary.total_return * 100.0),
+            Rounding::CENTS.apply(benchmark.summary.max_drawdown * 100.0),
+            Rounding::CENTS.apply(summary.max_drawdown * 100.0)
+        );
+    }
+
+    write_report(&report, "portfolio", filepath);
+}
+
+/// paper trades the config's paper account over the history collected for every symbol, feeding
+/// the strategies one candle at a time as if it were arriving live, and marking the account with
+/// each close. Candles the account has already been marked with only warm the strategies up, so
+/// each replay carries on where the last one stopped.
+fn paper(config: &Config, filepath: &Path) {
+    // the interval was checked when the config was validated
+    let timeframe = Timeframe::from_str(&config.interval).unwrap();
+
+    // only paper accounts are traded, so brokerage credentials aren't needed
+    let mut config = config.clone();
+    config
+        .accounts
+        .retain(|account| account.vendor.eq_ignore_ascii_case("paper"));
+    if config.accounts.is_empty() {
+        warn!("no paper accounts are configured, so there's nothing to trade");
+        return;
+    }
+    let mut mate = Mate::new(&config);
+
+    let mut history = Vec::new();
+    for symbol in &mate.symbols {
+        match data::load(filepath, symbol, timeframe) {
+            Ok(series) => history.push(series),
+            Err(e) => warn!("{}: skipping paper trading, {}", symbol, e),
+        }
+    }
+
+    // the replay picks up after the last candle every paper account has been marked with
+    let resume: Vec<Option<usize>> = history
+        .iter()
+        .map(|series| {
+            let accounts = mate.paper_accounts();
+            accounts
+                .iter()
+                .map(|a| a.marked(&series.symbol))
+                .min()
+                .flatten()
+        })
+        .collect();
+
+    let timeline = Timeline::new(&history, timeframe);
+    for period in 0..timeline.datetimes.len() {
+        for (i, series) in history.iter().enumerate() {
+            let candle = match timeline.candles[i][period] {
+                Some(index) => series.candles[index],
+                None => continue,
+            };
+            let symbol = &series.symbol;
+            mate.candles
+                .entry(symbol.to_string())
+                .or_insert_with(|| Series::new(symbol, Vec::new()))
+                .push(candle);
+
+            if resume[i].is_some_and(|last| candle.datetime <= last) {
+                mate.engine.update(&mate.candles[symbol], &mate.portfolio);
+                continue;
+            }
+
+            mate.decide(symbol);
+            for account in mate.paper_accounts() {
+                if let Err(e) = account.mark(symbol, candle.close, candle.datetime) {
+                    warn!("{}: {}", account.account.name, e);
+                }
+            }
+            mate.sync_paper();
+        }
+    }
+
+    mate.status();
+}
+
 fn main() {
     let args = Args::parse();
     init_logging(args.verbose);
 
-    let mut mate = Mate::new(args.accounts);
-    mate.symbols = vec!["MSFT".to_string()];
+    let mut config = match &args.config {
+        Some(path) => Config::from_file(path).unwrap_or_else(|e| panic!("{}", e)),
+        None => Config::default(),
+    };
+    if !args.accounts.is_empty() {
+        config.accounts = args
+            .accounts
+            .iter()
+            .map(|a| AccountConfig::new(a))
+            .collect();
+    }
+    if let Some(interval) = args.interval {
+        config.interval = interval;
+    }
+    // accounts given on the command line haven't been validated with the rest of the config
+    config.validate().unwrap_or_else(|e| panic!("{}", e));
+    let interval = config.poll_interval().unwrap_or_else(|e| panic!("{}", e));
+
+    match &args.command {
+        Some(Command::Backtest { filepath }) => return backtest(&config, filepath),
+        Some(Command::Optimize { filepath }) => return optimize(&config, filepath),
+        Some(Command::Portfolio { filepath }) => return portfolio(&config, filepath),
+        Some(Command::Paper { filepath }) => return paper(&config, filepath),
+        None => {}
+    }
+
+    let mut mate = Mate::new(&config);
+    for symbol in &mate.symbols {
+        info!("{}: running {:?}", symbol, mate.engine.strategies(symbol));
+    }
 
     loop {
         mate.status();
@@ -129,12 +858,13 @@ fn main() {
                 AccountType::KrakenAccount(account) => {
                     mate.update_kraken(account);
                 }
+                // paper accounts are traded once every other account has fetched its candles
+                AccountType::PaperAccount(_) => (),
             }
         }
+        mate.update_paper();
 
-        // sleep for an hour, as not to miss any trading window
-        let hour = Duration::from_secs(60 * 60);
-        // let day = Duration::from_secs(60 * 60 * 24);
-        thread::sleep(hour);
+        // sleep until the next poll, as not to miss any trading window
+        thread::sleep(interval);
     }
 }
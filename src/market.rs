@@ -0,0 +1,144 @@
+use serde_json::Value;
+use std::{
+    convert::TryFrom,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use ta::resample::{is_complete, resample, Timeframe};
+use ta::{Candle, KrakenOhlc};
+
+/// Kraken's public OHLC endpoint, which needs no account or keys
+/// <https://docs.kraken.com/rest/#operation/getOHLCData>
+const KRAKEN_OHLC_URL: &str = "https://api.kraken.com/0/public/OHLC";
+
+/// The candle lengths, in minutes, that Kraken's OHLC endpoint serves
+const KRAKEN_INTERVALS: [usize; 9] = [1, 5, 15, 30, 60, 240, 1440, 10080, 21600];
+
+/// kraken_candles fetches the latest completed candles of `timeframe` for a Kraken pair, eg:
+/// `XXBTZUSD`. It's public market data, so paper accounts can trade crypto pairs live without a
+/// brokerage account.
+///
+/// Kraken returns up to 720 candles of the longest length it serves that `timeframe` is a whole
+/// number of, which are resampled to `timeframe`. The last candle is dropped while it's forming.
+pub fn kraken_candles(pair: &str, timeframe: Timeframe) -> Result<Vec<Candle>, String> {
+    let url = format!(
+        "{}?pair={}&interval={}",
+        KRAKEN_OHLC_URL,
+        pair,
+        kraken_interval(timeframe)?
+    );
+    let body = reqwest::blocking::get(url)
+        .and_then(|response| response.text())
+        .map_err(|e| e.to_string())?;
+    let body: Value = serde_json::from_str(&body).map_err(|e| e.to_string())?;
+
+    let candles = parse_kraken_ohlc(&body)?;
+    let mut bars = resample(&candles, timeframe).map_err(|e| e.to_string())?;
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|e| e.to_string())?
+        .as_millis() as usize;
+    if bars
+        .last()
+        .is_some_and(|bar| !is_complete(bar, timeframe, now))
+    {
+        bars.pop();
+    }
+
+    Ok(bars)
+}
+
+/// kraken_interval returns the longest candle length Kraken serves, in minutes, that `timeframe`
+/// is a whole number of
+fn kraken_interval(timeframe: Timeframe) -> Result<usize, String> {
+    let minutes = match timeframe {
+        Timeframe::Minutes(n) => Some(n),
+        Timeframe::Hours(n) => n.checked_mul(60),
+        Timeframe::Days(n) => n.checked_mul(1440),
+        Timeframe::Weeks(n) => n.checked_mul(10080),
+        Timeframe::Months(_) => None,
+    }
+    .filter(|&minutes| minutes > 0)
+    .ok_or_else(|| format!("Kraken candles can't be resampled to {:?}", timeframe))?;
+
+    Ok(KRAKEN_INTERVALS
+        .iter()
+        .rev()
+        .copied()
+        .find(|interval| minutes % interval == 0)
+        .unwrap_or(1))
+}
+
+/// parse_kraken_ohlc reads the candles out of an OHLC response, which keeps them under the pair's
+/// name alongside a `last` cursor. Kraken reports failures, such as an unknown pair, in `error`.
+fn parse_kraken_ohlc(body: &Value) -> Result<Vec<Candle>, String> {
+    if let Some(error) = body
+        .get("error")
+        .and_then(Value::as_array)
+        .and_then(|errors| errors.first())
+    {
+        return Err(error
+            .as_str()
+            .unwrap_or("Kraken OHLC request failed")
+            .to_string());
+    }
+
+    let rows = body
+        .get("result")
+        .and_then(Value::as_object)
+        .and_then(|result| result.iter().find(|(key, _)| key.as_str() != "last"))
+        .map(|(_, rows)| rows.clone())
+        .ok_or("Kraken OHLC response has no candles")?;
+    let rows: Vec<KrakenOhlc> = serde_json::from_value(rows).map_err(|e| e.to_string())?;
+
+    rows.into_iter()
+        .map(|row| Candle::try_from(row).map_err(str::to_string))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn parse_kraken_ohlc_test() {
+        let body = json!({
+            "error": [],
+            "result": {
+                "XXBTZUSD": [
+                    [1641470400, "43000.0", "43500.5", "42800.0", "43200.1", "43150.2", "12.5", 800],
+                    [1641474000, "43200.1", "43300.0", "43000.0", "43100.0", "43150.0", "8.25", 500]
+                ],
+                "last": 1641474000
+            }
+        });
+
+        let candles = parse_kraken_ohlc(&body).unwrap();
+        assert_eq!(
+            candles,
+            vec![
+                Candle::new(1641470400000, 43000.0, 43500.5, 42800.0, 43200.1, 12.5),
+                Candle::new(1641474000000, 43200.1, 43300.0, 43000.0, 43100.0, 8.25),
+            ]
+        );
+
+        let unknown = json!({"error": ["EQuery:Unknown asset pair"]});
+        assert_eq!(
+            parse_kraken_ohlc(&unknown),
+            Err("EQuery:Unknown asset pair".to_string())
+        );
+        assert!(parse_kraken_ohlc(&json!({"error": [], "result": {"last": 0}})).is_err());
+    }
+
+    #[test]
+    fn kraken_interval_test() {
+        assert_eq!(kraken_interval(Timeframe::Minutes(15)), Ok(15));
+        assert_eq!(kraken_interval(Timeframe::Hours(2)), Ok(60));
+        assert_eq!(kraken_interval(Timeframe::Minutes(7)), Ok(1));
+        assert_eq!(kraken_interval(Timeframe::Days(1)), Ok(1440));
+        assert_eq!(kraken_interval(Timeframe::Weeks(1)), Ok(10080));
+        assert!(kraken_interval(Timeframe::Months(1)).is_err());
+    }
+}